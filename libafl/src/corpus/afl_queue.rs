@@ -0,0 +1,642 @@
+//! Import and export of AFL++ output directories, to move campaigns between AFL++ and `LibAFL`.
+//!
+//! An AFL++ output directory of a single fuzzer instance, e.g. `out/default`, holds the corpus in `queue/`,
+//! and the solutions in `crashes/` and `hangs/`. AFL++ encodes the lineage of each entry in its file name,
+//! e.g. `id:000123,src:000045,time:1234,execs:5678,op:havoc,rep:4,+cov`, see [`AflEntryMetadata`].
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt::Write as _, time::Duration};
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use hashbrown::HashMap;
+use libafl_bolts::{current_time, impl_serdeany};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{Corpus, CorpusId, Testcase},
+    events::EventFirer,
+    executors::HasObservers,
+    feedbacks::MapFeedbackMetadata,
+    fuzzer::Evaluator,
+    inputs::{HasTargetBytes, Input, UsesInput},
+    schedulers::SchedulerMetadata,
+    state::{HasCorpus, HasExecutions, HasSolutions, HasStartTime, UsesState},
+    Error, HasMetadata, HasNamedMetadata,
+};
+
+/// The subdirectory AFL++ keeps its corpus in
+pub const AFL_QUEUE_DIR: &str = "queue";
+/// The subdirectory AFL++ keeps crashing inputs in
+pub const AFL_CRASHES_DIR: &str = "crashes";
+/// The subdirectory AFL++ keeps hanging inputs in
+pub const AFL_HANGS_DIR: &str = "hangs";
+/// The file `afl-plot` reads the progress of a campaign from
+pub const AFL_PLOT_DATA_FILE: &str = "plot_data";
+/// The columns of an AFL++ `plot_data` file
+pub(crate) const AFL_PLOT_DATA_HEADER: &str = "# relative_time, cycles_done, cur_item, corpus_count, pending_total, pending_favs, map_size, saved_crashes, saved_hangs, max_depth, execs_per_sec, total_execs, edges_found";
+
+/// The name [`crate::feedbacks::TimeoutFeedback`] reports as hit objective
+#[cfg(feature = "track_hit_feedbacks")]
+const TIMEOUT_FEEDBACK_NAME: &str = "TimeoutFeedback";
+
+/// The subdirectory of an AFL++ output directory an entry belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AflEntryKind {
+    /// An entry of the corpus, in `queue/`
+    Queue,
+    /// A crashing input, in `crashes/`
+    Crash,
+    /// A hanging input, in `hangs/`
+    Hang,
+}
+
+impl AflEntryKind {
+    /// The name of the subdirectory
+    #[must_use]
+    pub fn dir_name(self) -> &'static str {
+        match self {
+            AflEntryKind::Queue => AFL_QUEUE_DIR,
+            AflEntryKind::Crash => AFL_CRASHES_DIR,
+            AflEntryKind::Hang => AFL_HANGS_DIR,
+        }
+    }
+}
+
+/// A testcase metadata holding what AFL++ encodes in the file name of an entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct AflEntryMetadata {
+    /// The subdirectory of the entry
+    pub kind: AflEntryKind,
+    /// The id of the entry, unique in its subdirectory (`id:`)
+    pub id: usize,
+    /// The queue entries this entry was derived from, two for splicing (`src:`)
+    pub src: Vec<usize>,
+    /// The signal that killed the target, for crashes (`sig:`)
+    pub sig: Option<u32>,
+    /// Milliseconds since the start of the campaign (`time:`)
+    pub time: Option<u64>,
+    /// Executions since the start of the campaign (`execs:`)
+    pub execs: Option<u64>,
+    /// The mutation operator (`op:`)
+    pub op: Option<String>,
+    /// The number of stacked mutations (`rep:`)
+    pub rep: Option<u32>,
+    /// The original file name of a seed (`orig:`)
+    pub orig: Option<String>,
+    /// Whether this entry found new coverage, not just new hit counts (`+cov`)
+    pub new_cov: bool,
+    /// Other parts of the name, such as `pos:` or `sync:`, kept as they are
+    pub extra: Vec<String>,
+}
+
+impl_serdeany!(AflEntryMetadata);
+
+impl AflEntryMetadata {
+    /// Creates metadata for an entry without any lineage information
+    #[must_use]
+    pub fn new(kind: AflEntryKind, id: usize) -> Self {
+        Self {
+            kind,
+            id,
+            src: Vec::new(),
+            sig: None,
+            time: None,
+            execs: None,
+            op: None,
+            rep: None,
+            orig: None,
+            new_cov: false,
+            extra: Vec::new(),
+        }
+    }
+
+    /// Parses an AFL++ file name. Returns `None` if it is not one, i.e., it has no valid `id:`.
+    #[must_use]
+    pub fn parse(kind: AflEntryKind, file_name: &str) -> Option<Self> {
+        let mut meta = Self::new(kind, 0);
+        let mut has_id = false;
+        let mut rest = file_name;
+        while !rest.is_empty() {
+            // The original name of a seed comes last and may contain anything, including commas
+            if let Some(orig) = rest.strip_prefix("orig:") {
+                meta.orig = Some(orig.to_string());
+                break;
+            }
+            let (part, tail) = rest.split_once(',').unwrap_or((rest, ""));
+            rest = tail;
+
+            if part == "+cov" {
+                meta.new_cov = true;
+                continue;
+            }
+            let Some((key, value)) = part.split_once(':') else {
+                meta.extra.push(part.to_string());
+                continue;
+            };
+            match key {
+                "id" => {
+                    meta.id = value.parse().ok()?;
+                    has_id = true;
+                }
+                "src" => {
+                    meta.src = value
+                        .split('+')
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()
+                        .ok()?;
+                }
+                "sig" => meta.sig = Some(value.parse().ok()?),
+                "time" => meta.time = Some(value.parse().ok()?),
+                "execs" => meta.execs = Some(value.parse().ok()?),
+                "op" => meta.op = Some(value.to_string()),
+                "rep" => meta.rep = Some(value.parse().ok()?),
+                _ => meta.extra.push(part.to_string()),
+            }
+        }
+        has_id.then_some(meta)
+    }
+
+    /// The file name AFL++ would give this entry
+    #[must_use]
+    pub fn file_name(&self) -> String {
+        let mut name = format!("id:{:06}", self.id);
+        if let Some(sig) = self.sig {
+            write!(name, ",sig:{sig:02}").unwrap();
+        }
+        if !self.src.is_empty() {
+            let src: Vec<_> = self.src.iter().map(|src| format!("{src:06}")).collect();
+            write!(name, ",src:{}", src.join("+")).unwrap();
+        }
+        if let Some(time) = self.time {
+            write!(name, ",time:{time}").unwrap();
+        }
+        if let Some(execs) = self.execs {
+            write!(name, ",execs:{execs}").unwrap();
+        }
+        if let Some(op) = &self.op {
+            write!(name, ",op:{op}").unwrap();
+        }
+        for extra in &self.extra {
+            write!(name, ",{extra}").unwrap();
+        }
+        if let Some(rep) = self.rep {
+            write!(name, ",rep:{rep}").unwrap();
+        }
+        if let Some(orig) = &self.orig {
+            write!(name, ",orig:{orig}").unwrap();
+        }
+        if self.new_cov {
+            name.push_str(",+cov");
+        }
+        name
+    }
+}
+
+/// What [`import_afl_output`] imported
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AflImportStats {
+    /// Entries added to the corpus
+    pub queue: usize,
+    /// Crashes added to the solutions
+    pub crashes: usize,
+    /// Hangs added to the solutions
+    pub hangs: usize,
+}
+
+/// Lists the entries of a subdirectory: those with AFL++ names ordered by id, then all others by name
+fn afl_entries(
+    dir: &Path,
+    kind: AflEntryKind,
+) -> Result<Vec<(PathBuf, Option<AflEntryMetadata>)>, Error> {
+    let dir = dir.join(kind.dir_name());
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        // Skip `.state/`, `README.txt`, and the like
+        if file_name.starts_with('.') || file_name == "README.txt" || !entry.file_type()?.is_file()
+        {
+            continue;
+        }
+        let meta = AflEntryMetadata::parse(kind, &file_name);
+        entries.push((entry.path(), meta));
+    }
+    entries.sort_by(
+        |(path_a, meta_a), (path_b, meta_b)| match (meta_a, meta_b) {
+            (Some(a), Some(b)) => a.id.cmp(&b.id),
+            (Some(_), None) => core::cmp::Ordering::Less,
+            (None, Some(_)) => core::cmp::Ordering::Greater,
+            (None, None) => path_a.cmp(path_b),
+        },
+    );
+    Ok(entries)
+}
+
+/// Imports the output directory of an AFL++ instance, e.g. `out/default`.
+///
+/// The entries of `queue/` are executed and added to the corpus in the order of their ids, like with
+/// [`Evaluator::add_input`], so feedbacks and the scheduler learn about them.
+/// The entries of `crashes/` and `hangs/` are added to the solutions without running them.
+/// Each testcase gets the parent named by `src:`, and an [`AflEntryMetadata`] with the rest of its name.
+pub fn import_afl_output<E, EM, Z>(
+    fuzzer: &mut Z,
+    executor: &mut E,
+    manager: &mut EM,
+    state: &mut E::State,
+    afl_dir: &Path,
+) -> Result<AflImportStats, Error>
+where
+    E: UsesState + HasObservers,
+    E::State: HasCorpus + HasSolutions,
+    EM: EventFirer<State = E::State>,
+    Z: Evaluator<E, EM, State = E::State>,
+{
+    let mut stats = AflImportStats::default();
+    let mut ids: HashMap<usize, CorpusId> = HashMap::new();
+    let parent_of = |meta: &Option<AflEntryMetadata>, ids: &HashMap<usize, CorpusId>| {
+        meta.as_ref()
+            .and_then(|meta| meta.src.first())
+            .and_then(|src| ids.get(src).copied())
+    };
+
+    for (path, meta) in afl_entries(afl_dir, AflEntryKind::Queue)? {
+        let input = <E::State as UsesInput>::Input::from_file(&path)?;
+        let parent = parent_of(&meta, &ids);
+
+        // Make the parent the current entry, so the scheduler sees the lineage as if we had found this entry
+        let current = *state.corpus().current();
+        *state.corpus_mut().current_mut() = parent;
+        let result = fuzzer.add_input(state, executor, manager, input);
+        *state.corpus_mut().current_mut() = current;
+        let idx = result?;
+
+        let mut testcase = state.corpus().get(idx)?.borrow_mut();
+        testcase.set_parent_id_optional(parent);
+        if let Some(meta) = meta {
+            ids.insert(meta.id, idx);
+            testcase.add_metadata(meta);
+        }
+        stats.queue += 1;
+    }
+
+    for kind in [AflEntryKind::Crash, AflEntryKind::Hang] {
+        for (path, meta) in afl_entries(afl_dir, kind)? {
+            let input = <E::State as UsesInput>::Input::from_file(&path)?;
+            let mut testcase = Testcase::new(input);
+            testcase.set_parent_id_optional(parent_of(&meta, &ids));
+            if let Some(meta) = meta {
+                testcase.add_metadata(meta);
+            }
+            state.solutions_mut().add(testcase)?;
+            match kind {
+                AflEntryKind::Crash => stats.crashes += 1,
+                _ => stats.hangs += 1,
+            }
+        }
+    }
+
+    log::info!("Imported {stats:?} from {}", afl_dir.display());
+    Ok(stats)
+}
+
+/// An exported entry, for reconstructing the `plot_data`
+struct ExportedEntry {
+    kind: AflEntryKind,
+    time: Option<u64>,
+    execs: u64,
+    depth: u32,
+}
+
+/// Writes one entry, returns its metadata with the exported id and lineage
+fn export_entry<C>(
+    corpus: &C,
+    idx: CorpusId,
+    kind: AflEntryKind,
+    afl_id: usize,
+    ids: &HashMap<CorpusId, usize>,
+    dir: &Path,
+) -> Result<(AflEntryMetadata, u64, Option<CorpusId>), Error>
+where
+    C: Corpus,
+    C::Input: HasTargetBytes,
+{
+    let mut testcase = corpus.get(idx)?.borrow_mut();
+    let bytes = testcase.load_input(corpus)?.target_bytes().to_vec();
+
+    let parent = testcase.parent_id();
+    let mut meta = if let Ok(meta) = testcase.metadata::<AflEntryMetadata>() {
+        meta.clone()
+    } else {
+        let mut meta = AflEntryMetadata::new(kind, 0);
+        meta.execs = Some(*testcase.executions());
+        if parent.is_none() {
+            meta.orig.clone_from(testcase.filename());
+        }
+        meta
+    };
+    meta.kind = kind;
+    meta.id = afl_id;
+    if let Some(src) = parent.and_then(|parent| ids.get(&parent)) {
+        meta.src = alloc::vec![*src];
+    } else if parent.is_some() {
+        // The parent was not exported
+        meta.src.clear();
+    }
+
+    fs::write(dir.join(meta.file_name()), bytes)?;
+    let execs = meta.execs.unwrap_or(*testcase.executions());
+    Ok((meta, execs, parent))
+}
+
+/// Exports the corpus and the solutions as an AFL++ output directory,
+/// so that tools like `afl-cmin`, `afl-showmap`, and `afl-plot` work on it.
+///
+/// The enabled corpus entries go to `queue/`, with AFL++ names encoding their parents.
+/// Solutions go to `hangs/` if they were imported from there or a [`crate::feedbacks::TimeoutFeedback`] reported them
+/// (with the `track_hit_feedbacks` feature), else to `crashes/`.
+/// `plot_data` is reconstructed from the entries; times not known from an import are estimated from the executions.
+/// Its last row is the state at the time of the export, with the queue cycles of the [`SchedulerMetadata`],
+/// and the coverage of the `u8` map feedback named `map_feedback_name`.
+pub fn export_afl_output<S>(state: &S, afl_dir: &Path, map_feedback_name: &str) -> Result<(), Error>
+where
+    S: HasCorpus + HasSolutions + HasExecutions + HasStartTime + HasMetadata + HasNamedMetadata,
+    S::Input: HasTargetBytes,
+{
+    for kind in [AflEntryKind::Queue, AflEntryKind::Crash, AflEntryKind::Hang] {
+        fs::create_dir_all(afl_dir.join(kind.dir_name()))?;
+    }
+
+    let mut ids: HashMap<CorpusId, usize> = HashMap::new();
+    let mut depths: HashMap<CorpusId, u32> = HashMap::new();
+    let mut entries = Vec::new();
+
+    let queue_dir = afl_dir.join(AFL_QUEUE_DIR);
+    for (afl_id, idx) in state.corpus().ids().enumerate() {
+        let (meta, execs, parent) = export_entry(
+            state.corpus(),
+            idx,
+            AflEntryKind::Queue,
+            afl_id,
+            &ids,
+            &queue_dir,
+        )?;
+        let depth = parent.and_then(|parent| depths.get(&parent)).unwrap_or(&0) + 1;
+        ids.insert(idx, afl_id);
+        depths.insert(idx, depth);
+        entries.push(ExportedEntry {
+            kind: AflEntryKind::Queue,
+            time: meta.time,
+            execs,
+            depth,
+        });
+    }
+
+    let (mut crashes, mut hangs) = (0, 0);
+    for idx in state.solutions().ids() {
+        let kind = solution_kind(&state.solutions().get(idx)?.borrow());
+        let counter = match kind {
+            AflEntryKind::Hang => &mut hangs,
+            _ => &mut crashes,
+        };
+        let (meta, execs, _) = export_entry(
+            state.solutions(),
+            idx,
+            kind,
+            *counter,
+            &ids,
+            &afl_dir.join(kind.dir_name()),
+        )?;
+        *counter += 1;
+        entries.push(ExportedEntry {
+            kind,
+            time: meta.time,
+            execs,
+            depth: 0,
+        });
+    }
+
+    write_plot_data(
+        state,
+        &mut entries,
+        map_feedback_name,
+        &afl_dir.join(AFL_PLOT_DATA_FILE),
+    )?;
+    log::info!(
+        "Exported {} queue entries, {crashes} crashes and {hangs} hangs to {}",
+        ids.len(),
+        afl_dir.display()
+    );
+    Ok(())
+}
+
+/// Whether a solution is a hang or a crash
+fn solution_kind<I>(testcase: &Testcase<I>) -> AflEntryKind
+where
+    I: Input,
+{
+    if let Ok(meta) = testcase.metadata::<AflEntryMetadata>() {
+        if meta.kind != AflEntryKind::Queue {
+            return meta.kind;
+        }
+    }
+    #[cfg(feature = "track_hit_feedbacks")]
+    if testcase
+        .hit_objectives()
+        .iter()
+        .any(|name| name == TIMEOUT_FEEDBACK_NAME)
+    {
+        return AflEntryKind::Hang;
+    }
+    AflEntryKind::Crash
+}
+
+/// Writes a `plot_data` with one line per entry, in the order they were found, and one for now.
+///
+/// The queue cycles and the coverage are only known for now, the lines of the entries leave them at 0.
+#[allow(clippy::cast_precision_loss)]
+fn write_plot_data<S>(
+    state: &S,
+    entries: &mut [ExportedEntry],
+    map_feedback_name: &str,
+    path: &Path,
+) -> Result<(), Error>
+where
+    S: HasExecutions + HasStartTime + HasMetadata + HasNamedMetadata,
+{
+    let total_execs = *state.executions();
+    let elapsed = current_time().saturating_sub(*state.start_time());
+    let millis_per_exec = if total_execs == 0 {
+        0.0
+    } else {
+        elapsed.as_millis() as f64 / total_execs as f64
+    };
+    let execs_per_sec = if elapsed.is_zero() {
+        0.0
+    } else {
+        total_execs as f64 / elapsed.as_secs_f64()
+    };
+    for entry in entries.iter_mut() {
+        if entry.time.is_none() {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let time = (entry.execs as f64 * millis_per_exec) as u64;
+            entry.time = Some(time);
+        }
+    }
+    entries.sort_by_key(|entry| (entry.time, entry.execs));
+
+    let mut plot = String::from(AFL_PLOT_DATA_HEADER);
+    plot.push('\n');
+    let (mut corpus_count, mut crashes, mut hangs, mut max_depth) = (0, 0, 0, 0);
+    for entry in entries.iter() {
+        match entry.kind {
+            AflEntryKind::Queue => corpus_count += 1,
+            AflEntryKind::Crash => crashes += 1,
+            AflEntryKind::Hang => hangs += 1,
+        }
+        max_depth = max_depth.max(entry.depth);
+        let seconds = Duration::from_millis(entry.time.unwrap_or(0)).as_secs();
+        writeln!(
+            plot,
+            "{seconds}, 0, 0, {corpus_count}, 0, 0, 0.00%, {crashes}, {hangs}, {max_depth}, {execs_per_sec:.2}, {}, 0",
+            entry.execs
+        )
+        .unwrap();
+    }
+
+    let cycles_done = state
+        .metadata::<SchedulerMetadata>()
+        .map_or(0, SchedulerMetadata::queue_cycles);
+    let (map_size, edges_found) = state
+        .named_metadata_map()
+        .get::<MapFeedbackMetadata<u8>>(map_feedback_name)
+        .filter(|meta| !meta.history_map.is_empty())
+        .map_or((0.0, 0), |meta| {
+            (
+                meta.num_covered_map_indexes as f64 * 100.0 / meta.history_map.len() as f64,
+                meta.num_covered_map_indexes,
+            )
+        });
+    writeln!(
+        plot,
+        "{}, {cycles_done}, 0, {corpus_count}, 0, 0, {map_size:.2}%, {crashes}, {hangs}, {max_depth}, {execs_per_sec:.2}, {total_execs}, {edges_found}",
+        elapsed.as_secs()
+    )
+    .unwrap();
+
+    let mut file = fs::File::create(path)?;
+    file.write_all(plot.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, vec, vec::Vec};
+
+    use libafl_bolts::rands::StdRand;
+
+    use super::{export_afl_output, AflEntryKind, AflEntryMetadata};
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::{ConstFeedback, MapFeedbackMetadata},
+        inputs::BytesInput,
+        schedulers::SchedulerMetadata,
+        state::{HasCorpus, StdState},
+        HasMetadata, HasNamedMetadata,
+    };
+
+    #[test]
+    fn test_afl_entry_names() {
+        let name = "id:000123,src:000045+000067,time:1234,execs:5678,op:splice,rep:4,+cov";
+        let meta = AflEntryMetadata::parse(AflEntryKind::Queue, name).unwrap();
+        assert_eq!(meta.id, 123);
+        assert_eq!(meta.src, [45, 67]);
+        assert_eq!(meta.time, Some(1234));
+        assert_eq!(meta.op.as_deref(), Some("splice"));
+        assert!(meta.new_cov);
+        assert_eq!(meta.file_name(), name);
+
+        let crash = "id:000001,sig:11,src:000002,time:9,execs:10,op:havoc,rep:2";
+        let meta = AflEntryMetadata::parse(AflEntryKind::Crash, crash).unwrap();
+        assert_eq!(meta.sig, Some(11));
+        assert_eq!(meta.file_name(), crash);
+
+        let seed = "id:000000,time:0,execs:0,orig:a,b.png";
+        let meta = AflEntryMetadata::parse(AflEntryKind::Queue, seed).unwrap();
+        assert_eq!(meta.orig.as_deref(), Some("a,b.png"));
+        assert_eq!(meta.file_name(), seed);
+
+        assert!(AflEntryMetadata::parse(AflEntryKind::Queue, "README.txt").is_none());
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_afl_export() {
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let seed = state
+            .corpus_mut()
+            .add(Testcase::with_filename(
+                BytesInput::new(b"seed".to_vec()),
+                "seed.txt".into(),
+            ))
+            .unwrap();
+        let mut child = Testcase::with_parent_id(BytesInput::new(b"child".to_vec()), seed);
+        let mut imported = AflEntryMetadata::new(AflEntryKind::Queue, 7);
+        imported.op = Some("havoc".into());
+        child.add_metadata(imported);
+        state.corpus_mut().add(child).unwrap();
+        let mut scheduler_meta = SchedulerMetadata::new(None);
+        scheduler_meta.set_queue_cycles(3);
+        state.add_metadata(scheduler_meta);
+        state.add_named_metadata(
+            "edges",
+            MapFeedbackMetadata::with_history_map(vec![1_u8, 0, 0, 0], 0),
+        );
+
+        let dir = env::temp_dir().join(format!("libafl_afl_export_{}", std::process::id()));
+        export_afl_output(&state, &dir, "edges").unwrap();
+
+        let mut names: Vec<_> = fs::read_dir(dir.join("queue"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert!(names[0].starts_with("id:000000,execs:0,orig:seed.txt"));
+        assert_eq!(names[1], "id:000001,src:000000,op:havoc");
+        assert_eq!(
+            fs::read(dir.join("queue").join(&names[1])).unwrap(),
+            b"child"
+        );
+        let plot_data = fs::read_to_string(dir.join("plot_data")).unwrap();
+        assert_eq!(plot_data.lines().count(), 4);
+        // Only the last line has the queue cycles and the coverage
+        let now: Vec<&str> = plot_data.lines().last().unwrap().split(", ").collect();
+        assert_eq!(now[1], "3");
+        assert_eq!(now[3], "2");
+        assert_eq!(now[6], "25.00%");
+        assert_eq!(now[12], "1");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
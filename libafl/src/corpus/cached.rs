@@ -107,6 +107,12 @@ where
         self.inner.replace(idx, testcase)
     }
 
+    /// Disables the enabled testcase with the given id, keeping its id
+    #[inline]
+    fn disable(&mut self, idx: CorpusId) -> Result<(), Error> {
+        self.inner.disable(idx)
+    }
+
     /// Removes an entry from the corpus, returning it if it was present; considers both enabled and disabled testcases.
     fn remove(&mut self, idx: CorpusId) -> Result<Testcase<Self::Input>, Error> {
         let testcase = self.inner.remove(idx)?;
@@ -274,11 +274,28 @@ where
     pub fn insert_disabled(&mut self, testcase: RefCell<Testcase<I>>) -> CorpusId {
         self._insert(testcase, true)
     }
+
+    /// Moves an enabled testcase to the disabled testcases, keeping its `CorpusId`.
+    /// Returns `false` if there is no enabled testcase with this id.
+    pub fn disable(&mut self, idx: CorpusId) -> bool {
+        let Some(testcase) = self.enabled.remove(idx) else {
+            return false;
+        };
+        self.insert_with_id(testcase, idx, true);
+        true
+    }
+
     /// Insert a testcase assigning a `CorpusId` to it
-    #[cfg(not(feature = "corpus_btreemap"))]
     fn _insert(&mut self, testcase: RefCell<Testcase<I>>, is_disabled: bool) -> CorpusId {
         let idx = CorpusId::from(self.progressive_idx);
         self.progressive_idx += 1;
+        self.insert_with_id(testcase, idx, is_disabled);
+        idx
+    }
+
+    /// Insert a testcase with the given `CorpusId`, after the last inserted one
+    #[cfg(not(feature = "corpus_btreemap"))]
+    fn insert_with_id(&mut self, testcase: RefCell<Testcase<I>>, idx: CorpusId, is_disabled: bool) {
         let corpus = if is_disabled {
             &mut self.disabled
         } else {
@@ -303,14 +320,11 @@ where
                 next: None,
             },
         );
-        idx
     }
 
-    /// Insert a testcase assigning a `CorpusId` to it
+    /// Insert a testcase with the given `CorpusId`
     #[cfg(feature = "corpus_btreemap")]
-    fn _insert(&mut self, testcase: RefCell<Testcase<I>>, is_disabled: bool) -> CorpusId {
-        let idx = CorpusId::from(self.progressive_idx);
-        self.progressive_idx += 1;
+    fn insert_with_id(&mut self, testcase: RefCell<Testcase<I>>, idx: CorpusId, is_disabled: bool) {
         let corpus = if is_disabled {
             &mut self.disabled
         } else {
@@ -318,7 +332,6 @@ where
         };
         corpus.insert_key(idx);
         corpus.map.insert(idx, testcase);
-        idx
     }
 
     /// Create new `TestcaseStorage`
@@ -396,6 +409,16 @@ where
             .ok_or_else(|| Error::key_not_found(format!("Index {idx} not found")))
     }
 
+    /// Disables the enabled testcase with the given id, keeping its id
+    #[inline]
+    fn disable(&mut self, idx: CorpusId) -> Result<(), Error> {
+        if self.storage.disable(idx) {
+            Ok(())
+        } else {
+            Err(Error::key_not_found(format!("Index {idx} not found")))
+        }
+    }
+
     /// Removes an entry from the corpus, returning it if it was present; considers both enabled and disabled testcases
     #[inline]
     fn remove(&mut self, idx: CorpusId) -> Result<Testcase<Self::Input>, Error> {
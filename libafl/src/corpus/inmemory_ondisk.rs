@@ -112,6 +112,12 @@ where
         Ok(entry)
     }
 
+    /// Disables the enabled testcase with the given id, keeping its id
+    #[inline]
+    fn disable(&mut self, idx: CorpusId) -> Result<(), Error> {
+        self.inner.disable(idx)
+    }
+
     /// Removes an entry from the corpus, returning it if it was present; considers both enabled and disabled corpus
     #[inline]
     fn remove(&mut self, idx: CorpusId) -> Result<Testcase<I>, Error> {
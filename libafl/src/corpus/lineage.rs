@@ -0,0 +1,184 @@
+//! Export the lineage of a corpus, i.e., which entry was derived from which by which mutations, as a graph.
+
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Write as _;
+#[cfg(feature = "std")]
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{Corpus, CorpusId},
+    mutators::lineage::LineageMetadata,
+    stages::StageId,
+    Error, HasMetadata,
+};
+
+/// A corpus entry in a [`LineageGraph`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineageNode {
+    /// The id of the entry
+    pub id: CorpusId,
+    /// The entry it was derived from, if any
+    pub parent: Option<CorpusId>,
+    /// The names of the mutations applied to the parent, if known
+    pub mutations: Vec<Cow<'static, str>>,
+    /// The stage that found the entry, if known
+    pub stage: Option<StageId>,
+    /// The file name of the entry, if any
+    pub filename: Option<String>,
+    /// Whether the entry is disabled
+    pub disabled: bool,
+}
+
+/// The lineage of all entries of a corpus, built from their [`LineageMetadata`] and parent ids
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineageGraph {
+    /// The entries, in the order of their ids
+    pub nodes: Vec<LineageNode>,
+}
+
+impl LineageGraph {
+    /// Builds the lineage graph of all entries of `corpus`, including disabled ones
+    pub fn from_corpus<C>(corpus: &C) -> Result<Self, Error>
+    where
+        C: Corpus,
+    {
+        let mut nodes = Vec::with_capacity(corpus.count_all());
+        for nth in 0..corpus.count_all() {
+            let id = corpus.nth_from_all(nth);
+            let testcase = corpus.get_from_all(id)?.borrow();
+            let (parent, mutations, stage) = match testcase.metadata::<LineageMetadata>() {
+                Ok(lineage) => (
+                    lineage.parent,
+                    lineage.mutation_names.clone(),
+                    lineage.stage,
+                ),
+                Err(_) => (testcase.parent_id(), Vec::new(), None),
+            };
+            nodes.push(LineageNode {
+                id,
+                parent,
+                mutations,
+                stage,
+                filename: testcase.filename().clone(),
+                disabled: corpus.get(id).is_err(),
+            });
+        }
+        nodes.sort_by_key(|node| node.id);
+        Ok(Self { nodes })
+    }
+
+    /// Renders the graph in the DOT language of graphviz, with one edge from each parent to its children
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph lineage {\n    node [shape=box];\n");
+        for node in &self.nodes {
+            let mut label = node.id.to_string();
+            if let Some(filename) = &node.filename {
+                write!(label, "\\n{}", escape_dot(filename)).unwrap();
+            }
+            if let Some(stage) = node.stage {
+                write!(label, "\\nstage {}", stage.0).unwrap();
+            }
+            let style = if node.disabled { ", style=dashed" } else { "" };
+            writeln!(dot, "    n{} [label=\"{label}\"{style}];", node.id).unwrap();
+        }
+        for node in &self.nodes {
+            let Some(parent) = node.parent else {
+                continue;
+            };
+            let mutations: Vec<String> =
+                node.mutations.iter().map(|name| escape_dot(name)).collect();
+            writeln!(
+                dot,
+                "    n{parent} -> n{} [label=\"{}\"];",
+                node.id,
+                mutations.join("\\n")
+            )
+            .unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Writes the graph to `path` in the DOT language, see [`LineageGraph::to_dot`]
+    #[cfg(feature = "std")]
+    pub fn write_dot<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        fs::write(path, self.to_dot())?;
+        Ok(())
+    }
+
+    /// Writes the graph to `path` as JSON
+    #[cfg(feature = "std")]
+    pub fn write_json<P>(&self, path: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::borrow::Cow;
+
+    use super::LineageGraph;
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        inputs::BytesInput,
+        mutators::lineage::LineageMetadata,
+        HasMetadata,
+    };
+
+    #[test]
+    fn test_lineage_graph() {
+        let mut corpus = InMemoryCorpus::<BytesInput>::new();
+        let seed = corpus
+            .add(Testcase::with_filename(
+                BytesInput::new(b"seed".to_vec()),
+                "seed \"1\"".into(),
+            ))
+            .unwrap();
+        let mut child = Testcase::new(BytesInput::new(b"child".to_vec()));
+        child.add_metadata(LineageMetadata {
+            parent: Some(seed),
+            mutations: vec![0.into(), 3.into()],
+            mutation_names: vec![
+                Cow::Borrowed("BitFlipMutator"),
+                Cow::Borrowed("ByteAddMutator"),
+            ],
+            stage: None,
+        });
+        let child = corpus.add(child).unwrap();
+        corpus
+            .add_disabled(Testcase::with_parent_id(
+                BytesInput::new(b"other".to_vec()),
+                child,
+            ))
+            .unwrap();
+
+        let graph = LineageGraph::from_corpus(&corpus).unwrap();
+        assert_eq!(graph.nodes.len(), 3);
+        assert_eq!(graph.nodes[1].parent, Some(seed));
+        assert_eq!(graph.nodes[2].parent, Some(child));
+        assert!(graph.nodes[2].disabled);
+
+        let dot = graph.to_dot();
+        assert!(dot.contains("n0 [label=\"0\\nseed \\\"1\\\"\"];"));
+        assert!(dot.contains("n0 -> n1 [label=\"BitFlipMutator\\nByteAddMutator\"];"));
+        assert!(dot.contains("n1 -> n2 [label=\"\"];"));
+    }
+}
@@ -2,7 +2,9 @@
 //! of your corpus.
 
 use alloc::{borrow::Cow, string::ToString, vec::Vec};
-use core::{hash::Hash, marker::PhantomData};
+#[cfg(feature = "cmin")]
+use core::hash::Hash;
+use core::{cmp::Ordering, marker::PhantomData};
 
 use hashbrown::{HashMap, HashSet};
 use libafl_bolts::{
@@ -11,10 +13,11 @@ use libafl_bolts::{
     AsIter, Named,
 };
 use num_traits::ToPrimitive;
+#[cfg(feature = "cmin")]
 use z3::{ast::Bool, Config, Context, Optimize};
 
 use crate::{
-    corpus::Corpus,
+    corpus::{Corpus, CorpusId},
     events::{Event, EventFirer, LogSeverity},
     executors::{Executor, HasObservers},
     monitors::{AggregatorOps, UserStats, UserStatsValue},
@@ -49,6 +52,7 @@ where
 /// Minimizes a corpus according to coverage maps, weighting by the specified `TestcaseScore`.
 ///
 /// Algorithm based on WMOPT: <https://hexhive.epfl.ch/publications/files/21ISSTA2.pdf>
+#[cfg(feature = "cmin")]
 #[derive(Debug)]
 pub struct MapCorpusMinimizer<C, E, O, T, TS> {
     observer_handle: Handle<C>,
@@ -56,9 +60,11 @@ pub struct MapCorpusMinimizer<C, E, O, T, TS> {
 }
 
 /// Standard corpus minimizer, which weights inputs by length and time.
+#[cfg(feature = "cmin")]
 pub type StdCorpusMinimizer<C, E, O, T> =
     MapCorpusMinimizer<C, E, O, T, LenTimeMulTestcaseScore<<E as UsesState>::State>>;
 
+#[cfg(feature = "cmin")]
 impl<C, E, O, T, TS> MapCorpusMinimizer<C, E, O, T, TS>
 where
     E: UsesState,
@@ -76,6 +82,7 @@ where
     }
 }
 
+#[cfg(feature = "cmin")]
 impl<C, E, O, T, TS> CorpusMinimizer<E> for MapCorpusMinimizer<C, E, O, T, TS>
 where
     E: UsesState,
@@ -239,3 +246,555 @@ where
         res
     }
 }
+
+/// How the hit counts of a map entry are taken into account by the [`GreedyCorpusMinimizer`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HitcountMode {
+    /// Only care whether an entry was hit at all
+    Ignore,
+    /// Group hit counts into the AFL buckets (1, 2, 3, 4-7, 8-15, 16-31, 32-127, 128+).
+    /// Maps that were already classified, e.g. by a [`crate::observers::HitcountsMapObserver`], are left as they are:
+    /// if all hit counts in the corpus are AFL classes (powers of two up to 128), they are not bucketed again.
+    #[default]
+    Buckets,
+    /// Every distinct hit count of an entry has to be kept
+    Exact,
+}
+
+/// Maps a hit count to the AFL bucket it belongs to, identified by the lowest count in the bucket.
+/// Bucketing a bucket again leaves it as it is.
+#[must_use]
+pub fn hitcount_bucket(count: u64) -> u64 {
+    match count {
+        0..=3 => count,
+        4..=7 => 4,
+        8..=15 => 8,
+        16..=31 => 16,
+        32..=127 => 32,
+        _ => 128,
+    }
+}
+
+/// Returns `true` if `count` is a hit count class of AFL's hit count classification
+fn is_afl_class(count: u64) -> bool {
+    count.is_power_of_two() && count <= 128
+}
+
+/// Replaces the hit counts of the `seeds` (id, weight, features) by their class, see [`HitcountMode`]
+fn classify_hitcounts(seeds: &mut [(CorpusId, f64, Vec<Feature>)], hitcount_mode: HitcountMode) {
+    let classify: fn(u64) -> u64 = match hitcount_mode {
+        HitcountMode::Exact => return,
+        HitcountMode::Ignore => |_| 0,
+        HitcountMode::Buckets => {
+            // Bucketing the classes of an already classified map again would merge some of them
+            let classified = seeds
+                .iter()
+                .flat_map(|seed| &seed.2)
+                .all(|(_, count)| is_afl_class(*count));
+            if classified {
+                return;
+            }
+            hitcount_bucket
+        }
+    };
+    for feature in seeds.iter_mut().flat_map(|seed| &mut seed.2) {
+        feature.1 = classify(feature.1);
+    }
+}
+
+/// Minimizes a corpus according to coverage maps, weighting by the specified `TestcaseScore`,
+/// with a greedy weighted set cover instead of a `MaxSAT` solver.
+///
+/// This is the approach of `afl-cmin`: for each coverage entry (and hit count class, see [`HitcountMode`]),
+/// the input with the lowest weight is the candidate to cover it. The entries are then visited from the rarest
+/// to the most common one, and for each entry not yet covered its candidate is kept, covering all of its entries.
+/// The result is not guaranteed to be minimal, but it does not require `z3` and scales to large corpora.
+#[derive(Debug)]
+pub struct GreedyCorpusMinimizer<C, E, O, T, TS> {
+    observer_handle: Handle<C>,
+    hitcount_mode: HitcountMode,
+    phantom: PhantomData<(E, O, T, TS)>,
+}
+
+/// Standard greedy corpus minimizer, which weights inputs by length and time.
+pub type StdGreedyCorpusMinimizer<C, E, O, T> =
+    GreedyCorpusMinimizer<C, E, O, T, LenTimeMulTestcaseScore<<E as UsesState>::State>>;
+
+impl<C, E, O, T, TS> GreedyCorpusMinimizer<C, E, O, T, TS>
+where
+    E: UsesState,
+    E::State: HasCorpus + HasMetadata,
+    TS: TestcaseScore<E::State>,
+    C: Named,
+{
+    /// Constructs a new `GreedyCorpusMinimizer` from a provided observer, bucketing hit counts.
+    /// This observer will be used in the future to get observed maps from an executed input.
+    pub fn new(obs: &C) -> Self {
+        Self {
+            observer_handle: obs.handle(),
+            hitcount_mode: HitcountMode::default(),
+            phantom: PhantomData,
+        }
+    }
+
+    /// Sets how hit counts are distinguished
+    #[must_use]
+    pub fn with_hitcount_mode(mut self, hitcount_mode: HitcountMode) -> Self {
+        self.hitcount_mode = hitcount_mode;
+        self
+    }
+
+    /// The way hit counts are distinguished
+    #[must_use]
+    pub fn hitcount_mode(&self) -> HitcountMode {
+        self.hitcount_mode
+    }
+}
+
+impl<C, E, O, T, TS> GreedyCorpusMinimizer<C, E, O, T, TS>
+where
+    E: UsesState,
+    for<'a> O: MapObserver<Entry = T> + AsIter<'a, Item = T>,
+    C: AsRef<O>,
+    E::State: HasMetadata + HasCorpus + HasExecutions,
+    T: Copy + PartialEq + ToPrimitive,
+    TS: TestcaseScore<E::State>,
+{
+    /// Executes each corpus entry and returns the ids of the entries not needed to keep the coverage,
+    /// from back to front. The corpus itself is left untouched.
+    pub fn find_redundant<EM, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        manager: &mut EM,
+        state: &mut E::State,
+    ) -> Result<Vec<CorpusId>, Error>
+    where
+        E: Executor<EM, Z> + HasObservers,
+        EM: EventFirer<State = E::State>,
+        Z: UsesState<State = E::State>,
+    {
+        let seeds = self.execute_corpus(fuzzer, executor, manager, state)?;
+
+        manager.log(
+            state,
+            LogSeverity::Info,
+            "Computing greedy set cover...".to_string(),
+        )?;
+
+        Ok(greedy_set_cover(&seeds))
+    }
+
+    /// Executes each corpus entry and returns the ids of the `candidates` that can be left out
+    /// together, keeping the coverage of the corpus, from back to front.
+    /// All corpus entries that are not candidates are kept. The corpus itself is left untouched.
+    pub fn find_redundant_among<EM, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        manager: &mut EM,
+        state: &mut E::State,
+        candidates: &[CorpusId],
+    ) -> Result<Vec<CorpusId>, Error>
+    where
+        E: Executor<EM, Z> + HasObservers,
+        EM: EventFirer<State = E::State>,
+        Z: UsesState<State = E::State>,
+    {
+        let seeds = self.execute_corpus(fuzzer, executor, manager, state)?;
+        Ok(redundant_candidates(&seeds, candidates))
+    }
+
+    /// Executes each corpus entry, returning its id, weight and features, with the hit counts classified
+    #[allow(clippy::type_complexity)]
+    fn execute_corpus<EM, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        manager: &mut EM,
+        state: &mut E::State,
+    ) -> Result<Vec<(CorpusId, f64, Vec<Feature>)>, Error>
+    where
+        E: Executor<EM, Z> + HasObservers,
+        EM: EventFirer<State = E::State>,
+        Z: UsesState<State = E::State>,
+    {
+        let mut seeds = Vec::with_capacity(state.corpus().count());
+        let mut cur_id = state.corpus().first();
+
+        manager.log(
+            state,
+            LogSeverity::Info,
+            "Executing each input...".to_string(),
+        )?;
+
+        let total = state.corpus().count() as u64;
+        let mut curr = 0;
+        while let Some(idx) = cur_id {
+            let (weight, input) = {
+                let mut testcase = state.corpus().get(idx)?.borrow_mut();
+                let weight = TS::compute(state, &mut *testcase)?;
+                let input = testcase.load_input(state.corpus())?.clone();
+                (weight, input)
+            };
+
+            // Execute the input; we cannot rely on the metadata already being present.
+            executor.observers_mut().pre_exec_all(state, &input)?;
+            let kind = executor.run_target(fuzzer, state, manager, &input)?;
+            executor
+                .observers_mut()
+                .post_exec_all(state, &input, &kind)?;
+
+            let executions = *state.executions();
+
+            curr += 1;
+
+            manager.fire(
+                state,
+                Event::UpdateUserStats {
+                    name: Cow::from("minimisation exec pass"),
+                    value: UserStats::new(UserStatsValue::Ratio(curr, total), AggregatorOps::None),
+                    phantom: PhantomData,
+                },
+            )?;
+
+            manager.fire(
+                state,
+                Event::UpdateExecStats {
+                    time: current_time(),
+                    phantom: PhantomData,
+                    executions,
+                },
+            )?;
+
+            let observers = executor.observers();
+            let obs = observers[&self.observer_handle].as_ref();
+            let initial = obs.initial();
+
+            // Each map index, together with the class of its hit count, is one feature to cover
+            let features = obs
+                .as_iter()
+                .map(|x| *x)
+                .enumerate()
+                .filter(|(_, e)| *e != initial)
+                .map(|(i, e)| (i, e.to_u64().unwrap_or(u64::MAX)))
+                .collect();
+            seeds.push((idx, weight, features));
+
+            cur_id = state.corpus().next(idx);
+        }
+
+        classify_hitcounts(&mut seeds, self.hitcount_mode);
+        Ok(seeds)
+    }
+}
+
+impl<C, E, O, T, TS> CorpusMinimizer<E> for GreedyCorpusMinimizer<C, E, O, T, TS>
+where
+    E: UsesState,
+    for<'a> O: MapObserver<Entry = T> + AsIter<'a, Item = T>,
+    C: AsRef<O>,
+    E::State: HasMetadata + HasCorpus + HasExecutions,
+    T: Copy + PartialEq + ToPrimitive,
+    TS: TestcaseScore<E::State>,
+{
+    fn minimize<CS, EM, Z>(
+        &self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        manager: &mut EM,
+        state: &mut E::State,
+    ) -> Result<(), Error>
+    where
+        E: Executor<EM, Z> + HasObservers,
+        CS: Scheduler<State = E::State> + RemovableScheduler,
+        EM: EventFirer<State = E::State>,
+        Z: HasScheduler<Scheduler = CS, State = E::State>,
+    {
+        for idx in self.find_redundant(fuzzer, executor, manager, state)? {
+            let removed = state.corpus_mut().remove(idx)?;
+            // scheduler needs to know we've removed the input, or it will continue to try
+            // to use now-missing inputs
+            fuzzer
+                .scheduler_mut()
+                .on_remove(state, idx, &Some(removed))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A map index, together with the class of its hit count
+type Feature = (usize, u64);
+
+/// Greedily selects a subset of the `seeds` (id, weight, features) covering all features, preferring low weights.
+/// Returns the ids of the seeds that are not needed, from back to front.
+fn greedy_set_cover(seeds: &[(CorpusId, f64, Vec<Feature>)]) -> Vec<CorpusId> {
+    // cheapest seeds first, so the first seed seen for a feature is the best one to cover it
+    let mut order: Vec<usize> = (0..seeds.len()).collect();
+    order.sort_by(|&a, &b| {
+        seeds[a]
+            .1
+            .partial_cmp(&seeds[b].1)
+            .unwrap_or(Ordering::Equal)
+            .then(seeds[a].0.cmp(&seeds[b].0))
+    });
+
+    // feature -> (number of seeds hitting it, best seed)
+    let mut best: HashMap<Feature, (usize, usize)> = HashMap::new();
+    for &seed in &order {
+        for feature in &seeds[seed].2 {
+            best.entry(*feature)
+                .and_modify(|(count, _)| *count += 1)
+                .or_insert((1, seed));
+        }
+    }
+
+    // visit rare features first: their few candidates are hard to replace
+    let mut features: Vec<_> = best.iter().map(|(f, (c, s))| (*c, *f, *s)).collect();
+    features.sort_unstable();
+
+    let mut covered = HashSet::with_capacity(features.len());
+    let mut kept = vec![false; seeds.len()];
+    for (_, feature, seed) in features {
+        if covered.contains(&feature) {
+            continue;
+        }
+        kept[seed] = true;
+        covered.extend(seeds[seed].2.iter().copied());
+    }
+
+    let mut removed: Vec<CorpusId> = seeds
+        .iter()
+        .zip(kept)
+        .filter(|(_, kept)| !kept)
+        .map(|(seed, _)| seed.0)
+        .collect();
+    // reverse order; if indexes are stored in a vec, we need to remove from back to front
+    removed.sort_unstable_by(|idx1, idx2| idx2.cmp(idx1));
+    removed
+}
+
+/// Returns the ids of the `candidates` among the `seeds` (id, weight, features) that are not needed to cover
+/// all features, keeping all other seeds, from back to front. The most expensive candidates are left out first.
+fn redundant_candidates(
+    seeds: &[(CorpusId, f64, Vec<Feature>)],
+    candidates: &[CorpusId],
+) -> Vec<CorpusId> {
+    // feature -> number of seeds still hitting it
+    let mut hits: HashMap<Feature, usize> = HashMap::new();
+    for feature in seeds.iter().flat_map(|seed| &seed.2) {
+        *hits.entry(*feature).or_default() += 1;
+    }
+
+    let candidates: HashSet<CorpusId> = candidates.iter().copied().collect();
+    let mut order: Vec<&(CorpusId, f64, Vec<Feature>)> = seeds
+        .iter()
+        .filter(|seed| candidates.contains(&seed.0))
+        .collect();
+    order.sort_by(|a, b| {
+        b.1.partial_cmp(&a.1)
+            .unwrap_or(Ordering::Equal)
+            .then(a.0.cmp(&b.0))
+    });
+
+    let mut removed = Vec::new();
+    for seed in order {
+        // a candidate can go if each of its features is still hit by another seed
+        if seed.2.iter().all(|feature| hits[feature] > 1) {
+            for feature in &seed.2 {
+                *hits.get_mut(feature).unwrap() -= 1;
+            }
+            removed.push(seed.0);
+        }
+    }
+    removed.sort_unstable_by(|idx1, idx2| idx2.cmp(idx1));
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use core::ptr::addr_of_mut;
+
+    use libafl_bolts::{rands::StdRand, tuples::tuple_list};
+
+    use super::{
+        classify_hitcounts, greedy_set_cover, hitcount_bucket, redundant_candidates,
+        CorpusMinimizer, HitcountMode, StdGreedyCorpusMinimizer,
+    };
+    use crate::{
+        corpus::{Corpus, CorpusId, InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::{ExitKind, InProcessExecutor},
+        feedbacks::ConstFeedback,
+        inputs::{BytesInput, HasMutatorBytes},
+        observers::StdMapObserver,
+        schedulers::QueueScheduler,
+        state::{HasCorpus, StdState},
+        StdFuzzer,
+    };
+
+    #[test]
+    fn test_hitcount_bucket() {
+        let buckets: Vec<u64> = [0, 1, 2, 3, 4, 7, 8, 15, 16, 31, 32, 127, 128, 255, 4096]
+            .into_iter()
+            .map(hitcount_bucket)
+            .collect();
+        assert_eq!(
+            buckets,
+            vec![0, 1, 2, 3, 4, 4, 8, 8, 16, 16, 32, 32, 128, 128, 128]
+        );
+        for count in 0..300 {
+            assert_eq!(
+                hitcount_bucket(hitcount_bucket(count)),
+                hitcount_bucket(count)
+            );
+        }
+    }
+
+    #[test]
+    fn test_classify_hitcounts() {
+        // Raw hit counts are bucketed, 33 and 64 both are in the bucket 32-127
+        let mut raw = vec![
+            (CorpusId(0), 1.0, vec![(0, 33)]),
+            (CorpusId(1), 1.0, vec![(0, 64)]),
+        ];
+        classify_hitcounts(&mut raw, HitcountMode::Buckets);
+        assert_eq!(raw[0].2, raw[1].2);
+
+        // Classified hit counts stay distinct, 32 stands for 16-31, 64 for 32-127
+        let mut classified = vec![
+            (CorpusId(0), 1.0, vec![(0, 32)]),
+            (CorpusId(1), 1.0, vec![(0, 64)]),
+        ];
+        classify_hitcounts(&mut classified, HitcountMode::Buckets);
+        assert_eq!(classified[0].2, [(0, 32)]);
+        assert_eq!(classified[1].2, [(0, 64)]);
+
+        classify_hitcounts(&mut classified, HitcountMode::Ignore);
+        assert_eq!(classified[0].2, classified[1].2);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_greedy_minimize() {
+        static mut MAP: [u8; 4] = [0; 4];
+
+        let mut corpus = InMemoryCorpus::<BytesInput>::new();
+        for input in [
+            &[0][..],
+            // covers what the cheaper inputs 0 and 2 cover
+            &[0, 1],
+            &[1],
+            // 3 and 4 hits are in different buckets
+            &[2, 2, 2],
+            &[2, 2, 2, 2],
+            // 5 and 6 hits are in the same bucket, the cheaper input is kept
+            &[3, 3, 3, 3, 3],
+            &[3, 3, 3, 3, 3, 3],
+        ] {
+            corpus
+                .add(Testcase::new(BytesInput::new(input.to_vec())))
+                .unwrap();
+        }
+
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut manager = NopEventManager::new();
+        let mut fuzzer: StdFuzzer<_, _, _, ()> =
+            StdFuzzer::new(QueueScheduler::new(), feedback, objective);
+
+        // # Safety
+        // The map is only used by this test
+        let observer =
+            unsafe { StdMapObserver::from_mut_ptr("map", addr_of_mut!(MAP).cast::<u8>(), 4) };
+        let minimizer = StdGreedyCorpusMinimizer::new(&observer);
+        let mut harness = |input: &BytesInput| {
+            for byte in input.bytes() {
+                // # Safety
+                // The map is only used by this test
+                unsafe {
+                    MAP[usize::from(*byte)] += 1;
+                }
+            }
+            ExitKind::Ok
+        };
+        let mut executor = InProcessExecutor::new(
+            &mut harness,
+            tuple_list!(observer),
+            &mut fuzzer,
+            &mut state,
+            &mut manager,
+        )
+        .unwrap();
+
+        minimizer
+            .minimize(&mut fuzzer, &mut executor, &mut manager, &mut state)
+            .unwrap();
+
+        let kept: Vec<CorpusId> = state.corpus().ids().collect();
+        assert_eq!(
+            kept,
+            [
+                CorpusId(0),
+                CorpusId(2),
+                CorpusId(3),
+                CorpusId(4),
+                CorpusId(5)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_greedy_set_cover() {
+        let seeds = vec![
+            // covers everything, but is expensive
+            (CorpusId(0), 100.0, vec![(0, 1), (1, 1), (2, 1), (3, 1)]),
+            (CorpusId(1), 1.0, vec![(0, 1), (1, 1)]),
+            (CorpusId(2), 1.0, vec![(2, 1), (3, 1)]),
+            // subsumed by 1
+            (CorpusId(3), 2.0, vec![(0, 1)]),
+            // the only one hitting index 1 twice
+            (CorpusId(4), 50.0, vec![(1, 2)]),
+            // no coverage at all
+            (CorpusId(5), 1.0, vec![]),
+        ];
+        assert_eq!(
+            greedy_set_cover(&seeds),
+            vec![CorpusId(5), CorpusId(3), CorpusId(0)]
+        );
+
+        assert!(greedy_set_cover(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_redundant_candidates() {
+        let seeds = vec![
+            (CorpusId(0), 1.0, vec![(0, 1), (1, 1)]),
+            // only this one hits index 2
+            (CorpusId(1), 1.0, vec![(1, 1), (2, 1)]),
+            (CorpusId(2), 5.0, vec![(0, 1), (3, 1)]),
+            (CorpusId(3), 9.0, vec![(0, 1), (3, 1)]),
+        ];
+        // 1 is needed, and only one of the two seeds hitting index 3 can go: the more expensive one
+        assert_eq!(
+            redundant_candidates(&seeds, &[CorpusId(1), CorpusId(2), CorpusId(3)]),
+            vec![CorpusId(3)]
+        );
+        assert_eq!(
+            redundant_candidates(&seeds, &[CorpusId(0), CorpusId(3)]),
+            vec![CorpusId(3), CorpusId(0)]
+        );
+        assert!(redundant_candidates(&seeds, &[]).is_empty());
+    }
+}
@@ -21,12 +21,23 @@ pub mod cached;
 #[cfg(feature = "std")]
 pub use cached::CachedOnDiskCorpus;
 
-#[cfg(feature = "cmin")]
+pub mod lineage;
+pub use lineage::{LineageGraph, LineageNode};
+
+#[cfg(feature = "std")]
+pub mod afl_queue;
+#[cfg(feature = "std")]
+pub use afl_queue::{export_afl_output, import_afl_output, AflEntryKind, AflEntryMetadata};
+
+#[cfg(feature = "sqlite_corpus")]
+pub mod sqlite;
+#[cfg(feature = "sqlite_corpus")]
+pub use sqlite::SqliteCorpus;
+
 pub mod minimizer;
 use core::{cell::RefCell, fmt};
 
 pub mod nop;
-#[cfg(feature = "cmin")]
 pub use minimizer::*;
 pub use nop::NopCorpus;
 use serde::{Deserialize, Serialize};
@@ -113,6 +124,14 @@ pub trait Corpus: UsesInput + Serialize + for<'de> Deserialize<'de> {
         testcase: Testcase<Self::Input>,
     ) -> Result<Testcase<Self::Input>, Error>;
 
+    /// Disables the enabled testcase with the given id. It is no longer scheduled,
+    /// but keeps its id, and can still be found with [`Corpus::get_from_all`].
+    fn disable(&mut self, id: CorpusId) -> Result<(), Error> {
+        Err(Error::unsupported(format!(
+            "This corpus can't disable the testcase {id}"
+        )))
+    }
+
     /// Removes an entry from the corpus, returning it if it was present; considers both enabled and disabled testcases
     fn remove(&mut self, id: CorpusId) -> Result<Testcase<Self::Input>, Error>;
 
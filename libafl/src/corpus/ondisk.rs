@@ -112,6 +112,12 @@ where
         self.inner.peek_free_id()
     }
 
+    /// Disables the enabled testcase with the given id, keeping its id
+    #[inline]
+    fn disable(&mut self, idx: CorpusId) -> Result<(), Error> {
+        self.inner.disable(idx)
+    }
+
     /// Removes an entry from the corpus, returning it if it was present; considers both enabled and disabled testcases
     #[inline]
     fn remove(&mut self, idx: CorpusId) -> Result<Testcase<I>, Error> {
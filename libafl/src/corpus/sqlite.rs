@@ -0,0 +1,712 @@
+//! The [`SqliteCorpus`] stores all [`Testcase`]s, together with their metadata, in a single embedded `SQLite` database.
+//! Like the [`crate::corpus::CachedOnDiskCorpus`], only a limited number of inputs are kept in memory,
+//! the rest is loaded from the database on demand and evicted in a FIFO manner.
+//!
+//! In contrast to the file-based corpora, the metadata of each entry is indexed,
+//! so that it can be queried efficiently, even for millions of entries.
+//! See [`SqliteCorpus::ids_covering_index`] and [`SqliteCorpus::ids_added_after`].
+
+use alloc::{collections::vec_deque::VecDeque, string::String, vec::Vec};
+use core::{
+    cell::{Ref, RefCell},
+    time::Duration,
+};
+use std::path::{Path, PathBuf};
+
+use hashbrown::HashMap;
+use libafl_bolts::{current_time, serdeany::SerdeAnyMap};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{Corpus, CorpusId, HasTestcase, InMemoryCorpus, Testcase},
+    feedbacks::MapIndexesMetadata,
+    inputs::{Input, UsesInput},
+    Error, HasMetadata,
+};
+
+/// The database schema used by the [`SqliteCorpus`]
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS testcases (
+    id INTEGER PRIMARY KEY,
+    filename TEXT NOT NULL UNIQUE,
+    input BLOB NOT NULL,
+    metadata BLOB NOT NULL,
+    exec_time_ns INTEGER,
+    executions INTEGER NOT NULL,
+    parent_id INTEGER,
+    disabled INTEGER NOT NULL,
+    added_at_ms INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS testcases_added_at ON testcases(added_at_ms);
+CREATE INDEX IF NOT EXISTS testcases_parent ON testcases(parent_id);
+CREATE TABLE IF NOT EXISTS map_indexes (
+    map_idx INTEGER NOT NULL,
+    id INTEGER NOT NULL,
+    PRIMARY KEY (map_idx, id)
+) WITHOUT ROWID;
+CREATE INDEX IF NOT EXISTS map_indexes_by_id ON map_indexes(id);
+";
+
+/// Converts a [`rusqlite::Error`] into a `LibAFL` [`Error`]
+#[allow(clippy::needless_pass_by_value)]
+fn sqlite_error(err: rusqlite::Error) -> Error {
+    Error::illegal_state(format!("SQLite corpus error: {err}"))
+}
+
+/// A corpus storing all [`Testcase`]s and their metadata in a single `SQLite` database file.
+///
+/// At most `cache_max_len` inputs are kept in memory, the others get loaded from the database when needed.
+/// The metadata of each [`Testcase`] is written to the database when it is added,
+/// and again each time its input gets evicted from the cache, or when [`SqliteCorpus::sync`] is called.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(bound = "I: serde::de::DeserializeOwned")]
+pub struct SqliteCorpus<I>
+where
+    I: Input,
+{
+    inner: InMemoryCorpus<I>,
+    db_path: PathBuf,
+    cached_indexes: RefCell<VecDeque<CorpusId>>,
+    cache_max_len: usize,
+    /// The connection is opened lazily, so it will be re-opened after deserialization.
+    #[serde(skip)]
+    conn: RefCell<Option<Connection>>,
+}
+
+impl<I> UsesInput for SqliteCorpus<I>
+where
+    I: Input,
+{
+    type Input = I;
+}
+
+impl<I> SqliteCorpus<I>
+where
+    I: Input,
+{
+    /// Creates a new [`SqliteCorpus`], backed by the database at `db_path`.
+    ///
+    /// If the database already contains testcases (for example, from a previous run),
+    /// they will be loaded into this corpus. Their ids will be renumbered, if there are gaps.
+    ///
+    /// Will error, if the database could not be opened, or if `cache_max_len` is `0`.
+    pub fn new<P>(db_path: P, cache_max_len: usize) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        if cache_max_len == 0 {
+            return Err(Error::illegal_argument(
+                "The max cache len in SqliteCorpus cannot be 0",
+            ));
+        }
+        let mut corpus = Self {
+            inner: InMemoryCorpus::new(),
+            db_path: db_path.as_ref().into(),
+            cached_indexes: RefCell::new(VecDeque::new()),
+            cache_max_len,
+            conn: RefCell::new(None),
+        };
+        corpus.load_existing()?;
+        Ok(corpus)
+    }
+
+    /// Path to the database backing this corpus
+    #[must_use]
+    pub fn db_path(&self) -> &PathBuf {
+        &self.db_path
+    }
+
+    /// Fetch the inner corpus
+    pub fn inner(&self) -> &InMemoryCorpus<I> {
+        &self.inner
+    }
+
+    /// Returns all [`CorpusId`]s whose [`MapIndexesMetadata`] contains the given map index (i.e., edge),
+    /// considering both enabled and disabled testcases.
+    pub fn ids_covering_index(&self, map_idx: usize) -> Result<Vec<CorpusId>, Error> {
+        self.query_ids(
+            "SELECT id FROM map_indexes WHERE map_idx = ?1 ORDER BY id",
+            params![map_idx],
+        )
+    }
+
+    /// Returns all [`CorpusId`]s added to the corpus after the given time, as duration since the UNIX epoch
+    /// (the same format returned by [`libafl_bolts::current_time`]).
+    pub fn ids_added_after(&self, time: Duration) -> Result<Vec<CorpusId>, Error> {
+        let millis = u64::try_from(time.as_millis())?;
+        self.query_ids(
+            "SELECT id FROM testcases WHERE added_at_ms > ?1 ORDER BY id",
+            params![millis],
+        )
+    }
+
+    /// Returns all [`CorpusId`]s that were derived from the given parent.
+    pub fn ids_with_parent(&self, parent_id: CorpusId) -> Result<Vec<CorpusId>, Error> {
+        self.query_ids(
+            "SELECT id FROM testcases WHERE parent_id = ?1 ORDER BY id",
+            params![parent_id.0],
+        )
+    }
+
+    /// Writes the metadata of all testcases that are not currently borrowed back to the database.
+    pub fn sync(&self) -> Result<(), Error> {
+        for id in (0..self.inner.count_all()).map(|nth| self.inner.nth_from_all(nth)) {
+            if let Ok(testcase) = self.inner.get_from_all(id)?.try_borrow() {
+                self.update_metadata(id, &testcase)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs a query returning a single column of ids
+    fn query_ids<P>(&self, sql: &str, params: P) -> Result<Vec<CorpusId>, Error>
+    where
+        P: rusqlite::Params,
+    {
+        let conn = self.connection()?;
+        let mut stmt = conn.prepare_cached(sql).map_err(sqlite_error)?;
+        let ids = stmt
+            .query_map(params, |row| row.get::<_, usize>(0))
+            .map_err(sqlite_error)?
+            .map(|id| id.map(CorpusId).map_err(sqlite_error))
+            .collect();
+        ids
+    }
+
+    /// Returns the connection to the database, opening it first, if needed
+    fn connection(&self) -> Result<Ref<'_, Connection>, Error> {
+        if self.conn.borrow().is_none() {
+            let conn = Connection::open(&self.db_path).map_err(sqlite_error)?;
+            conn.pragma_update(None, "journal_mode", "WAL")
+                .map_err(sqlite_error)?;
+            conn.pragma_update(None, "synchronous", "NORMAL")
+                .map_err(sqlite_error)?;
+            conn.execute_batch(SCHEMA).map_err(sqlite_error)?;
+            *self.conn.borrow_mut() = Some(conn);
+        }
+        Ok(Ref::map(self.conn.borrow(), |conn| conn.as_ref().unwrap()))
+    }
+
+    /// Loads all testcases already present in the database into memory, without their inputs
+    fn load_existing(&mut self) -> Result<(), Error> {
+        let mut rows = Vec::new();
+        {
+            let conn = self.connection()?;
+            let mut stmt = conn
+                .prepare(
+                    "SELECT id, filename, metadata, exec_time_ns, executions, parent_id, disabled
+                     FROM testcases ORDER BY id",
+                )
+                .map_err(sqlite_error)?;
+            let mut query = stmt.query([]).map_err(sqlite_error)?;
+            while let Some(row) = query.next().map_err(sqlite_error)? {
+                let id: usize = row.get(0).map_err(sqlite_error)?;
+                let filename: String = row.get(1).map_err(sqlite_error)?;
+                let metadata: Vec<u8> = row.get(2).map_err(sqlite_error)?;
+                let exec_time_ns: Option<u64> = row.get(3).map_err(sqlite_error)?;
+                let executions: u64 = row.get(4).map_err(sqlite_error)?;
+                let parent_id: Option<usize> = row.get(5).map_err(sqlite_error)?;
+                let disabled: bool = row.get(6).map_err(sqlite_error)?;
+
+                let mut testcase = Testcase::default();
+                *testcase.filename_mut() = Some(filename);
+                *testcase.metadata_map_mut() = postcard::from_bytes::<SerdeAnyMap>(&metadata)?;
+                *testcase.exec_time_mut() = exec_time_ns.map(Duration::from_nanos);
+                *testcase.executions_mut() = executions;
+                testcase.set_parent_id_optional(parent_id.map(CorpusId));
+                testcase.set_disabled(disabled);
+                rows.push((CorpusId(id), testcase, disabled));
+            }
+        }
+
+        // The inner corpus assigns fresh ids, so we may need to renumber rows (and parents) in the database.
+        let mut renumbered = HashMap::new();
+        for (old_id, testcase, disabled) in rows {
+            let new_id = if disabled {
+                self.inner.add_disabled(testcase)?
+            } else {
+                self.inner.add(testcase)?
+            };
+            if new_id != old_id {
+                renumbered.insert(old_id, new_id);
+            }
+        }
+        if renumbered.is_empty() {
+            return Ok(());
+        }
+
+        let conn = self.connection()?;
+        let tx = conn.unchecked_transaction().map_err(sqlite_error)?;
+        let mut sorted: Vec<_> = renumbered.iter().collect();
+        // New ids are always smaller than the old ones, so renumbering in ascending order never collides.
+        sorted.sort_unstable();
+        for (old_id, new_id) in sorted {
+            tx.execute(
+                "UPDATE testcases SET id = ?2 WHERE id = ?1",
+                params![old_id.0, new_id.0],
+            )
+            .map_err(sqlite_error)?;
+            tx.execute(
+                "UPDATE testcases SET parent_id = ?2 WHERE parent_id = ?1",
+                params![old_id.0, new_id.0],
+            )
+            .map_err(sqlite_error)?;
+            tx.execute(
+                "UPDATE map_indexes SET id = ?2 WHERE id = ?1",
+                params![old_id.0, new_id.0],
+            )
+            .map_err(sqlite_error)?;
+        }
+        tx.commit().map_err(sqlite_error)?;
+        drop(conn);
+
+        for idx in 0..self.inner.count_all() {
+            let id = self.inner.nth_from_all(idx);
+            let mut testcase = self.inner.get_from_all(id)?.borrow_mut();
+            if let Some(parent_id) = testcase.parent_id() {
+                if let Some(new_parent) = renumbered.get(&parent_id) {
+                    testcase.set_parent_id(*new_parent);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Inserts the database row for the given testcase, including its input.
+    /// If `replace` is set, the existing row of this id is updated instead, keeping the time it was added.
+    fn save_testcase(
+        &self,
+        testcase: &mut Testcase<I>,
+        id: CorpusId,
+        disabled: bool,
+        replace: bool,
+    ) -> Result<(), Error> {
+        let Some(input) = testcase.input() else {
+            return Err(Error::illegal_argument(
+                "No input available for testcase. Could not store anything.",
+            ));
+        };
+        let serialized_input = postcard::to_allocvec(input)?;
+
+        let conn = self.connection()?;
+        let filename = match testcase.filename() {
+            Some(filename) => filename.clone(),
+            None => input.generate_name(id.0),
+        };
+        let taken: Option<usize> = conn
+            .query_row(
+                "SELECT id FROM testcases WHERE filename = ?1",
+                params![filename],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(sqlite_error)?;
+        let filename = match taken {
+            Some(other) if other != id.0 => format!("{filename}-{id}"),
+            _ => filename,
+        };
+
+        let tx = conn.unchecked_transaction().map_err(sqlite_error)?;
+        let metadata = postcard::to_allocvec(testcase.metadata_map())?;
+        let exec_time_ns = Self::exec_time_ns(testcase)?;
+        let parent_id = testcase.parent_id().map(|parent| parent.0);
+        if replace {
+            let updated = tx
+                .execute(
+                    "UPDATE testcases SET filename = ?2, input = ?3, metadata = ?4, exec_time_ns = ?5,
+                     executions = ?6, parent_id = ?7, disabled = ?8
+                     WHERE id = ?1",
+                    params![
+                        id.0,
+                        filename,
+                        serialized_input,
+                        metadata,
+                        exec_time_ns,
+                        testcase.executions(),
+                        parent_id,
+                        disabled,
+                    ],
+                )
+                .map_err(sqlite_error)?;
+            if updated == 0 {
+                return Err(Error::key_not_found(format!(
+                    "No testcase with id {id} in the database"
+                )));
+            }
+        } else {
+            tx.execute(
+                "INSERT OR REPLACE INTO testcases
+                 (id, filename, input, metadata, exec_time_ns, executions, parent_id, disabled, added_at_ms)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    id.0,
+                    filename,
+                    serialized_input,
+                    metadata,
+                    exec_time_ns,
+                    testcase.executions(),
+                    parent_id,
+                    disabled,
+                    u64::try_from(current_time().as_millis())?,
+                ],
+            )
+            .map_err(sqlite_error)?;
+        }
+        Self::store_map_indexes(&tx, id, testcase)?;
+        tx.commit().map_err(sqlite_error)?;
+
+        *testcase.filename_mut() = Some(filename);
+        Ok(())
+    }
+
+    /// Writes the (possibly changed) metadata of a testcase back to the database
+    fn update_metadata(&self, id: CorpusId, testcase: &Testcase<I>) -> Result<(), Error> {
+        let conn = self.connection()?;
+        let tx = conn.unchecked_transaction().map_err(sqlite_error)?;
+        tx.execute(
+            "UPDATE testcases SET metadata = ?2, exec_time_ns = ?3, executions = ?4, parent_id = ?5
+             WHERE id = ?1",
+            params![
+                id.0,
+                postcard::to_allocvec(testcase.metadata_map())?,
+                Self::exec_time_ns(testcase)?,
+                testcase.executions(),
+                testcase.parent_id().map(|parent| parent.0),
+            ],
+        )
+        .map_err(sqlite_error)?;
+        Self::store_map_indexes(&tx, id, testcase)?;
+        tx.commit().map_err(sqlite_error)
+    }
+
+    /// Replaces the indexed map entries of this testcase with the ones in its [`MapIndexesMetadata`]
+    fn store_map_indexes(
+        conn: &Connection,
+        id: CorpusId,
+        testcase: &Testcase<I>,
+    ) -> Result<(), Error> {
+        conn.execute("DELETE FROM map_indexes WHERE id = ?1", params![id.0])
+            .map_err(sqlite_error)?;
+        if let Ok(meta) = testcase.metadata::<MapIndexesMetadata>() {
+            let mut stmt = conn
+                .prepare_cached("INSERT OR IGNORE INTO map_indexes (map_idx, id) VALUES (?1, ?2)")
+                .map_err(sqlite_error)?;
+            for map_idx in &meta.list {
+                stmt.execute(params![map_idx, id.0]).map_err(sqlite_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes the database rows of the given testcase
+    fn remove_testcase(&self, id: CorpusId) -> Result<(), Error> {
+        let conn = self.connection()?;
+        let tx = conn.unchecked_transaction().map_err(sqlite_error)?;
+        tx.execute("DELETE FROM testcases WHERE id = ?1", params![id.0])
+            .map_err(sqlite_error)?;
+        tx.execute("DELETE FROM map_indexes WHERE id = ?1", params![id.0])
+            .map_err(sqlite_error)?;
+        tx.commit().map_err(sqlite_error)
+    }
+
+    /// The exec time of a testcase in nanoseconds, if set
+    fn exec_time_ns(testcase: &Testcase<I>) -> Result<Option<u64>, Error> {
+        Ok(match testcase.exec_time() {
+            Some(exec_time) => Some(u64::try_from(exec_time.as_nanos())?),
+            None => None,
+        })
+    }
+
+    /// Loads the input of the testcase (if needed) and evicts old inputs from memory
+    fn cache_testcase<'a>(
+        &'a self,
+        testcase: &'a RefCell<Testcase<I>>,
+        idx: CorpusId,
+    ) -> Result<(), Error> {
+        if testcase.borrow().input().is_none() {
+            self.load_input_into(&mut testcase.borrow_mut())?;
+            let mut borrowed_num = 0;
+            while self.cached_indexes.borrow().len() >= self.cache_max_len {
+                let removed = self.cached_indexes.borrow_mut().pop_front().unwrap();
+
+                if let Ok(mut borrowed) = self.inner.get_from_all(removed)?.try_borrow_mut() {
+                    self.update_metadata(removed, &borrowed)?;
+                    *borrowed.input_mut() = None;
+                } else {
+                    self.cached_indexes.borrow_mut().push_back(removed);
+                    borrowed_num += 1;
+                    if self.cache_max_len == borrowed_num {
+                        break;
+                    }
+                }
+            }
+            self.cached_indexes.borrow_mut().push_back(idx);
+        }
+        Ok(())
+    }
+}
+
+impl<I> Corpus for SqliteCorpus<I>
+where
+    I: Input,
+{
+    /// Returns the number of all enabled entries
+    #[inline]
+    fn count(&self) -> usize {
+        self.inner.count()
+    }
+
+    /// Returns the number of all disabled entries
+    fn count_disabled(&self) -> usize {
+        self.inner.count_disabled()
+    }
+
+    /// Returns the number of elements including disabled entries
+    #[inline]
+    fn count_all(&self) -> usize {
+        self.inner.count_all()
+    }
+
+    /// Add an enabled testcase to the corpus and return its index
+    fn add(&mut self, testcase: Testcase<I>) -> Result<CorpusId, Error> {
+        let idx = self.inner.add(testcase)?;
+        let testcase = &mut self.inner.get(idx)?.borrow_mut();
+        self.save_testcase(testcase, idx, false, false)?;
+        *testcase.input_mut() = None;
+        Ok(idx)
+    }
+
+    /// Add a disabled testcase to the corpus and return its index
+    fn add_disabled(&mut self, testcase: Testcase<I>) -> Result<CorpusId, Error> {
+        let idx = self.inner.add_disabled(testcase)?;
+        let testcase = &mut self.inner.get_from_all(idx)?.borrow_mut();
+        self.save_testcase(testcase, idx, true, false)?;
+        *testcase.input_mut() = None;
+        Ok(idx)
+    }
+
+    /// Replaces the testcase at the given idx
+    fn replace(&mut self, idx: CorpusId, testcase: Testcase<I>) -> Result<Testcase<I>, Error> {
+        let entry = self.inner.replace(idx, testcase)?;
+        self.cached_indexes.borrow_mut().retain(|e| *e != idx);
+        let testcase = &mut self.inner.get(idx)?.borrow_mut();
+        self.save_testcase(testcase, idx, false, true)?;
+        *testcase.input_mut() = None;
+        Ok(entry)
+    }
+
+    /// Disables the enabled testcase with the given id, keeping its id
+    fn disable(&mut self, idx: CorpusId) -> Result<(), Error> {
+        self.inner.disable(idx)?;
+        self.connection()?
+            .execute(
+                "UPDATE testcases SET disabled = 1 WHERE id = ?1",
+                params![idx.0],
+            )
+            .map_err(sqlite_error)?;
+        Ok(())
+    }
+
+    /// Removes an entry from the corpus, returning it if it was present; considers both enabled and disabled testcases
+    fn remove(&mut self, idx: CorpusId) -> Result<Testcase<I>, Error> {
+        let entry = self.inner.remove(idx)?;
+        self.cached_indexes.borrow_mut().retain(|e| *e != idx);
+        self.remove_testcase(idx)?;
+        Ok(entry)
+    }
+
+    /// Get by id; considers only enabled testcases
+    #[inline]
+    fn get(&self, idx: CorpusId) -> Result<&RefCell<Testcase<I>>, Error> {
+        let testcase = { self.inner.get(idx)? };
+        self.cache_testcase(testcase, idx)?;
+        Ok(testcase)
+    }
+
+    /// Get by id; considers both enabled and disabled testcases
+    #[inline]
+    fn get_from_all(&self, idx: CorpusId) -> Result<&RefCell<Testcase<I>>, Error> {
+        let testcase = { self.inner.get_from_all(idx)? };
+        self.cache_testcase(testcase, idx)?;
+        Ok(testcase)
+    }
+
+    /// Current testcase scheduled
+    #[inline]
+    fn current(&self) -> &Option<CorpusId> {
+        self.inner.current()
+    }
+
+    /// Current testcase scheduled (mutable)
+    #[inline]
+    fn current_mut(&mut self) -> &mut Option<CorpusId> {
+        self.inner.current_mut()
+    }
+
+    #[inline]
+    fn next(&self, idx: CorpusId) -> Option<CorpusId> {
+        self.inner.next(idx)
+    }
+
+    /// Peek the next free corpus id
+    #[inline]
+    fn peek_free_id(&self) -> CorpusId {
+        self.inner.peek_free_id()
+    }
+
+    #[inline]
+    fn prev(&self, idx: CorpusId) -> Option<CorpusId> {
+        self.inner.prev(idx)
+    }
+
+    #[inline]
+    fn first(&self) -> Option<CorpusId> {
+        self.inner.first()
+    }
+
+    #[inline]
+    fn last(&self) -> Option<CorpusId> {
+        self.inner.last()
+    }
+
+    /// Get the nth corpus id; considers only enabled testcases
+    #[inline]
+    fn nth(&self, nth: usize) -> CorpusId {
+        self.inner.nth(nth)
+    }
+
+    /// Get the nth corpus id; considers both enabled and disabled testcases
+    #[inline]
+    fn nth_from_all(&self, nth: usize) -> CorpusId {
+        self.inner.nth_from_all(nth)
+    }
+
+    fn load_input_into(&self, testcase: &mut Testcase<Self::Input>) -> Result<(), Error> {
+        if testcase.input().is_none() {
+            let Some(filename) = testcase.filename() else {
+                return Err(Error::illegal_argument(
+                    "No filename set for testcase. Could not load inputs.",
+                ));
+            };
+            let serialized: Vec<u8> = self
+                .connection()?
+                .query_row(
+                    "SELECT input FROM testcases WHERE filename = ?1",
+                    params![filename],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(sqlite_error)?
+                .ok_or_else(|| {
+                    Error::key_not_found(format!("Testcase {filename} not found in the database"))
+                })?;
+            testcase.set_input(postcard::from_bytes(&serialized)?);
+        }
+        Ok(())
+    }
+
+    fn store_input_from(&self, testcase: &Testcase<Self::Input>) -> Result<(), Error> {
+        let Some(filename) = testcase.filename() else {
+            return Err(Error::illegal_argument(
+                "No filename set for testcase. Could not store input to the database.",
+            ));
+        };
+        let Some(input) = testcase.input() else {
+            return Err(Error::illegal_argument(
+                "No input available for testcase. Could not store anything.",
+            ));
+        };
+        self.connection()?
+            .execute(
+                "UPDATE testcases SET input = ?2 WHERE filename = ?1",
+                params![filename, postcard::to_allocvec(input)?],
+            )
+            .map_err(sqlite_error)?;
+        Ok(())
+    }
+}
+
+impl<I> HasTestcase for SqliteCorpus<I>
+where
+    I: Input,
+{
+    fn testcase(&self, id: CorpusId) -> Result<Ref<Testcase<Self::Input>>, Error> {
+        Ok(self.get(id)?.borrow())
+    }
+
+    fn testcase_mut(
+        &self,
+        id: CorpusId,
+    ) -> Result<core::cell::RefMut<Testcase<Self::Input>>, Error> {
+        Ok(self.get(id)?.borrow_mut())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::{env, fs, thread};
+
+    use libafl_bolts::current_time;
+
+    use crate::{
+        corpus::{Corpus, CorpusId, SqliteCorpus, Testcase},
+        feedbacks::MapIndexesMetadata,
+        inputs::{BytesInput, HasMutatorBytes},
+        HasMetadata,
+    };
+
+    #[test]
+    fn test_sqlite_corpus() {
+        let db_path =
+            env::temp_dir().join(format!("libafl_sqlite_corpus_{}.db", std::process::id()));
+        drop(fs::remove_file(&db_path));
+        let start = current_time().saturating_sub(Duration::from_secs(1));
+
+        let mut corpus = SqliteCorpus::<BytesInput>::new(&db_path, 1).unwrap();
+        let mut first = Testcase::new(BytesInput::new(vec![1, 2, 3]));
+        first.add_metadata(MapIndexesMetadata::new(vec![4, 7]));
+        let first = corpus.add(first).unwrap();
+        let mut second = Testcase::with_parent_id(BytesInput::new(vec![4, 5]), first);
+        second.add_metadata(MapIndexesMetadata::new(vec![7]));
+        let second = corpus.add(second).unwrap();
+
+        // Only one input fits into the cache, so this forces a reload from the database
+        for id in [first, second, first] {
+            let input = corpus.cloned_input_for_id(id).unwrap();
+            assert_eq!(input.bytes().len(), if id == first { 3 } else { 2 });
+        }
+
+        assert_eq!(corpus.ids_covering_index(7).unwrap(), vec![first, second]);
+        assert_eq!(corpus.ids_covering_index(4).unwrap(), vec![first]);
+        assert_eq!(corpus.ids_with_parent(first).unwrap(), vec![second]);
+        assert_eq!(corpus.ids_added_after(start).unwrap().len(), 2);
+
+        // Replacing keeps the time the entry was added
+        let added = current_time();
+        thread::sleep(Duration::from_millis(5));
+        let mut replacement = Testcase::with_parent_id(BytesInput::new(vec![6]), first);
+        replacement.add_metadata(MapIndexesMetadata::new(vec![9]));
+        corpus.replace(second, replacement).unwrap();
+        assert!(corpus.ids_added_after(added).unwrap().is_empty());
+        assert_eq!(corpus.ids_covering_index(9).unwrap(), vec![second]);
+        assert_eq!(corpus.ids_covering_index(7).unwrap(), vec![first]);
+        assert_eq!(corpus.cloned_input_for_id(second).unwrap().bytes(), &[6]);
+
+        corpus.remove(first).unwrap();
+        drop(corpus);
+
+        // Re-opening renumbers the remaining entry
+        let corpus = SqliteCorpus::<BytesInput>::new(&db_path, 1).unwrap();
+        assert_eq!(corpus.count(), 1);
+        assert_eq!(corpus.ids_covering_index(9).unwrap(), vec![CorpusId(0)]);
+        assert_eq!(
+            corpus.cloned_input_for_id(CorpusId(0)).unwrap().bytes(),
+            &[6]
+        );
+
+        drop(corpus);
+        drop(fs::remove_file(&db_path));
+    }
+}
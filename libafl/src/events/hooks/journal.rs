@@ -0,0 +1,52 @@
+//! An [`EventManagerHook`] journaling the testcases imported from other clients to a [`SessionJournal`].
+
+use libafl_bolts::ClientId;
+
+use crate::{
+    events::{hooks::EventManagerHook, Event},
+    fuzzer::replay::{JournalEntry, SessionJournal},
+    state::State,
+    Error, HasMetadata,
+};
+
+/// Journals each [`Event::NewTestcase`] to the [`SessionJournal`] of the state, if it records.
+///
+/// When replaying, imported testcases come from the journal, so incoming ones are dropped.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JournalEventHook;
+
+impl JournalEventHook {
+    /// Creates a new [`JournalEventHook`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<S> EventManagerHook<S> for JournalEventHook
+where
+    S: State + HasMetadata,
+{
+    fn pre_exec(
+        &mut self,
+        state: &mut S,
+        _client_id: ClientId,
+        event: &Event<S::Input>,
+    ) -> Result<bool, Error> {
+        let Event::NewTestcase { input, .. } = event else {
+            return Ok(true);
+        };
+        let Ok(journal) = state.metadata_mut::<SessionJournal>() else {
+            return Ok(true);
+        };
+        if journal.is_replaying() {
+            return Ok(false);
+        }
+        journal.record(&JournalEntry::Imported(postcard::to_allocvec(input)?))?;
+        Ok(true)
+    }
+
+    fn post_exec(&mut self, _state: &mut S, _client_id: ClientId) -> Result<bool, Error> {
+        Ok(true)
+    }
+}
@@ -3,6 +3,11 @@
 //! other clients
 use libafl_bolts::ClientId;
 
+#[cfg(feature = "std")]
+pub mod journal;
+#[cfg(feature = "std")]
+pub use journal::JournalEventHook;
+
 use crate::{events::Event, state::State, Error};
 
 /// The hooks that are run before and after the event manager calls `handle_in_client`
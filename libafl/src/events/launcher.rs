@@ -6,6 +6,9 @@
 //! To use multiple [`Launcher`]`s` for individual configurations,
 //! we can set `spawn_broker` to `false` on all but one.
 //!
+//! To run differently configured fuzzers on different cores, sharing their finds through the same `broker`,
+//! describe them in an [`Ensemble`] and pass it to the [`Launcher`].
+//!
 //! To connect multiple nodes together via TCP, we can use the `remote_broker_addr`.
 //! (this requires the `llmp_bind_public` compile-time feature for `LibAFL`).
 //!
@@ -14,6 +17,8 @@
 
 use alloc::string::ToString;
 #[cfg(feature = "std")]
+use alloc::{borrow::Cow, string::String, vec::Vec};
+#[cfg(feature = "std")]
 use core::marker::PhantomData;
 #[cfg(feature = "std")]
 use core::time::Duration;
@@ -59,9 +64,9 @@ use crate::{
 use crate::{
     events::{
         llmp::{LlmpRestartingEventManager, LlmpShouldSaveState, ManagerKind, RestartingMgr},
-        EventConfig,
+        Event, EventConfig, EventFirer,
     },
-    monitors::Monitor,
+    monitors::{ensemble::ENSEMBLE_MEMBER_STAT, AggregatorOps, Monitor, UserStats, UserStatsValue},
     state::{HasExecutions, State},
     Error,
 };
@@ -73,6 +78,138 @@ const _AFL_LAUNCHER_CLIENT: &str = "AFL_LAUNCHER_CLIENT";
 #[cfg(all(feature = "fork", unix))]
 const LIBAFL_DEBUG_OUTPUT: &str = "LIBAFL_DEBUG_OUTPUT";
 
+/// One configuration of an [`Ensemble`], run on its own set of cores
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct EnsembleMember {
+    name: String,
+    cores: Cores,
+    configuration: EventConfig,
+}
+
+#[cfg(feature = "std")]
+impl EnsembleMember {
+    /// The name of this configuration
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The cores running this configuration
+    #[must_use]
+    pub fn cores(&self) -> &Cores {
+        &self.cores
+    }
+
+    /// The [`EventConfig`] of the clients running this configuration.
+    ///
+    /// It is derived from the name, so testcases from other configurations get re-executed,
+    /// as their observers may not match.
+    #[must_use]
+    pub fn configuration(&self) -> EventConfig {
+        self.configuration
+    }
+
+    /// Tells the monitor which configuration this client runs, so that an
+    /// [`EnsembleMonitor`](crate::monitors::EnsembleMonitor) can show its statistics.
+    ///
+    /// Call it once the state is available in the `run_client` closure.
+    pub fn announce<EM>(&self, state: &mut EM::State, mgr: &mut EM) -> Result<(), Error>
+    where
+        EM: EventFirer,
+    {
+        mgr.fire(
+            state,
+            Event::UpdateUserStats {
+                name: Cow::Borrowed(ENSEMBLE_MEMBER_STAT),
+                value: UserStats::new(
+                    UserStatsValue::String(Cow::Owned(self.name.clone())),
+                    AggregatorOps::None,
+                ),
+                phantom: PhantomData,
+            },
+        )
+    }
+}
+
+/// An ensemble of differently configured fuzzers, e.g., with different schedulers and mutators,
+/// or with and without cmplog, each running on its own set of cores.
+///
+/// Pass it to the [`Launcher`] and use [`Ensemble::member_for`] in `run_client` to decide what to run on each core.
+/// All clients share their finds through the same `broker`.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct Ensemble {
+    members: Vec<EnsembleMember>,
+    cores: Cores,
+}
+
+#[cfg(feature = "std")]
+impl Default for Ensemble {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Ensemble {
+    /// Creates an empty [`Ensemble`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            members: Vec::new(),
+            cores: Cores::from(Vec::new()),
+        }
+    }
+
+    /// Adds a configuration called `name`, running on `cores`.
+    ///
+    /// Fails if the name is taken, or if any of the cores already runs another configuration.
+    pub fn with_member(mut self, name: &str, cores: Cores) -> Result<Self, Error> {
+        if self.members.iter().any(|member| member.name == name) {
+            return Err(Error::illegal_argument(format!(
+                "Ensemble configuration {name} was added twice"
+            )));
+        }
+        if let Some(core) = cores.ids.iter().find(|core| self.cores.contains(**core)) {
+            return Err(Error::illegal_argument(format!(
+                "Core {} of ensemble configuration {name} already runs another configuration",
+                core.0
+            )));
+        }
+        let mut ids: Vec<usize> = self.cores.ids.iter().map(|core| core.0).collect();
+        ids.extend(cores.ids.iter().map(|core| core.0));
+        self.cores = Cores::from(ids);
+        self.members.push(EnsembleMember {
+            name: name.to_string(),
+            cores,
+            configuration: EventConfig::from_name(name),
+        });
+        Ok(self)
+    }
+
+    /// The configurations of this ensemble
+    #[must_use]
+    pub fn members(&self) -> &[EnsembleMember] {
+        &self.members
+    }
+
+    /// All cores of this ensemble, to pass to the [`Launcher`]
+    #[must_use]
+    pub fn cores(&self) -> &Cores {
+        &self.cores
+    }
+
+    /// The configuration running on `core`, and its index in [`Ensemble::members`]
+    #[must_use]
+    pub fn member_for(&self, core: CoreId) -> Option<(usize, &EnsembleMember)> {
+        self.members
+            .iter()
+            .enumerate()
+            .find(|(_, member)| member.cores.contains(core))
+    }
+}
+
 /// Provides a [`Launcher`], which can be used to launch a fuzzing run on a specified list of cores
 ///
 /// Will hide child output, unless the settings indicate otherwise, or the `LIBAFL_DEBUG_OUTPUT` env variable is set.
@@ -98,6 +235,9 @@ pub struct Launcher<'a, CF, EMH, MT, S, SP> {
     broker_port: u16,
     /// The list of cores to run on
     cores: &'a Cores,
+    /// Run a different configuration on each group of cores, instead of [`Self::configuration`] on all of them
+    #[builder(default, setter(strip_option))]
+    ensemble: Option<&'a Ensemble>,
     /// A file name to write all client output to
     #[cfg(all(unix, feature = "std"))]
     #[builder(default = None)]
@@ -151,6 +291,7 @@ where
             .field("configuration", &self.configuration)
             .field("broker_port", &self.broker_port)
             .field("core", &self.cores)
+            .field("ensemble", &self.ensemble)
             .field("spawn_broker", &self.spawn_broker)
             .field("remote_broker_addr", &self.remote_broker_addr);
         #[cfg(all(unix, feature = "std"))]
@@ -194,6 +335,32 @@ where
     S: State + HasExecutions,
     SP: ShMemProvider + 'static,
 {
+    /// The [`EventConfig`] of the client on `core`, which depends on its configuration in the [`Ensemble`], if any
+    fn client_configuration(&self, core: CoreId) -> EventConfig {
+        self.ensemble
+            .and_then(|ensemble| ensemble.member_for(core))
+            .map_or(self.configuration, |(_, member)| member.configuration())
+    }
+
+    /// Makes sure every core runs one of the configurations of the [`Ensemble`], if any
+    fn check_ensemble(&self) -> Result<(), Error> {
+        let Some(ensemble) = self.ensemble else {
+            return Ok(());
+        };
+        if let Some(core) = self
+            .cores
+            .ids
+            .iter()
+            .find(|core| ensemble.member_for(**core).is_none())
+        {
+            return Err(Error::illegal_argument(format!(
+                "Core {} is not part of any ensemble configuration",
+                core.0
+            )));
+        }
+        Ok(())
+    }
+
     /// Launch the broker and the clients and fuzz with a user-supplied hook
     #[cfg(all(unix, feature = "std", feature = "fork"))]
     #[allow(clippy::similar_names)]
@@ -211,6 +378,8 @@ where
             ));
         }
 
+        self.check_ensemble()?;
+
         let core_ids = get_core_ids().unwrap();
         let num_cores = core_ids.len();
         let mut handles = vec![];
@@ -270,7 +439,7 @@ where
                             .kind(ManagerKind::Client {
                                 cpu_core: Some(*bind_to),
                             })
-                            .configuration(self.configuration)
+                            .configuration(self.client_configuration(*bind_to))
                             .serialize_state(self.serialize_state)
                             .hooks(hooks);
                         #[cfg(feature = "adaptive_serialization")]
@@ -346,7 +515,7 @@ where
                     .kind(ManagerKind::Client {
                         cpu_core: Some(CoreId(core_id)),
                     })
-                    .configuration(self.configuration)
+                    .configuration(self.client_configuration(CoreId(core_id)))
                     .serialize_state(self.serialize_state)
                     .hooks(hooks)
                     .build()
@@ -357,6 +526,7 @@ where
             Err(std::env::VarError::NotPresent) => {
                 // I am a broker
                 // before going to the broker loop, spawn n clients
+                self.check_ensemble()?;
 
                 let core_ids = core_affinity::get_core_ids().unwrap();
                 let num_cores = core_ids.len();
@@ -313,6 +313,8 @@ where
     Objective {
         /// Objective corpus size
         objective_size: usize,
+        /// The [`ExitKind`] of the run that produced this objective
+        exit_kind: ExitKind,
         /// The total number of executions when this objective is found
         executions: u64,
         /// The time when this event was created
@@ -268,6 +268,7 @@ where
             }
             Event::Objective {
                 objective_size,
+                exit_kind,
                 executions,
                 time,
             } => {
@@ -275,6 +276,9 @@ where
                 monitor
                     .client_stats_mut_for(ClientId(0))
                     .update_objective_size(*objective_size as u64);
+                monitor
+                    .client_stats_mut_for(ClientId(0))
+                    .update_objective_exit_kind(exit_kind);
                 monitor
                     .client_stats_mut_for(ClientId(0))
                     .update_executions(*executions, *time);
@@ -595,3 +599,64 @@ where
         Ok((state, mgr))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::{rc::Rc, string::String, vec::Vec};
+    use core::{cell::RefCell, time::Duration};
+
+    use libafl_bolts::rands::StdRand;
+
+    use super::SimpleEventManager;
+    use crate::{
+        corpus::InMemoryCorpus,
+        events::{Event, EventFirer},
+        executors::{CustomExitKind, ExitKind},
+        feedbacks::ConstFeedback,
+        inputs::BytesInput,
+        monitors::SimpleMonitor,
+        state::StdState,
+    };
+
+    #[test]
+    fn test_objective_exit_kinds() {
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let lines = Rc::new(RefCell::new(Vec::<String>::new()));
+        let printed = lines.clone();
+        let mut mgr = SimpleEventManager::new(SimpleMonitor::with_user_monitor(move |s: &str| {
+            printed.borrow_mut().push(s.into());
+        }));
+
+        for (objective_size, exit_kind) in [
+            (1, ExitKind::Crash),
+            (2, ExitKind::Custom(CustomExitKind::new(3))),
+        ] {
+            mgr.fire(
+                &mut state,
+                Event::Objective {
+                    objective_size,
+                    exit_kind,
+                    executions: 10,
+                    time: Duration::from_secs(1),
+                },
+            )
+            .unwrap();
+        }
+
+        let lines = lines.borrow();
+        let last = lines.last().unwrap();
+        assert!(last.starts_with("[Objective #0]"));
+        assert!(last.contains("objectives: 2"));
+        assert!(last.contains("objectives (crash): 1"));
+        assert!(last.contains("objectives (custom_3): 1"));
+    }
+}
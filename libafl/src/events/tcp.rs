@@ -388,12 +388,14 @@ where
             }
             Event::Objective {
                 objective_size,
+                exit_kind,
                 executions,
                 time,
             } => {
                 monitor.client_stats_insert(client_id);
                 let client = monitor.client_stats_mut_for(client_id);
                 client.update_objective_size(*objective_size as u64);
+                client.update_objective_exit_kind(exit_kind);
                 client.update_executions(*executions, *time);
                 monitor.display(event.name(), client_id);
                 Ok(BrokerEventResult::Handled)
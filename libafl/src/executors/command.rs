@@ -18,16 +18,19 @@ use std::{
 
 use libafl_bolts::{
     fs::{get_unique_std_input_file, InputFile},
-    tuples::{MatchName, RefIndexable},
+    tuples::{Handle, Handled, MatchName, MatchNameRef, RefIndexable},
     AsSlice,
 };
 
 #[cfg(all(feature = "std", unix))]
 use crate::executors::{Executor, ExitKind};
 use crate::{
-    executors::HasObservers,
+    executors::{socket::SocketInput, HasObservers},
     inputs::{HasTargetBytes, UsesInput},
-    observers::{ObserversTuple, StdErrObserver, StdOutObserver, UsesObservers},
+    observers::{
+        ObserversTuple, ProtocolStateObserver, SocketResponseObserver, StdErrObserver,
+        StdOutObserver, UsesObservers,
+    },
     state::{HasExecutions, State, UsesState},
     std::borrow::ToOwned,
 };
@@ -37,6 +40,7 @@ use crate::{inputs::Input, Error};
 /// How to deliver input to an external program
 /// `StdIn`: The target reads from stdin
 /// `File`: The target reads from the specified [`InputFile`]
+/// `Socket`: The target reads from a socket, see [`SocketInput`]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum InputLocation {
     /// Mutate a commandline argument to deliver an input
@@ -52,6 +56,8 @@ pub enum InputLocation {
         /// The file to write input to. The target should read input from this location.
         out_file: InputFile,
     },
+    /// Deliver the input over a socket, once the target is ready to accept it
+    Socket(SocketInput),
 }
 
 /// A simple Configurator that takes the most common parameters
@@ -68,6 +74,12 @@ pub struct StdCommandConfigurator {
     timeout: Duration,
     /// true: input gets delivered via stdink
     input_location: InputLocation,
+    /// The observer for responses to inputs delivered over a socket
+    socket_response_observer: Option<Handle<SocketResponseObserver>>,
+    /// The observer for protocol states, parsed from responses to inputs delivered over a socket
+    protocol_state_observer: Option<Handle<ProtocolStateObserver>>,
+    /// The responses to each message of the last run, if the input was delivered over a socket
+    socket_responses: Option<Vec<Vec<u8>>>,
     /// The Command to execute
     command: Command,
 }
@@ -152,12 +164,54 @@ where
                 out_file.write_buf(input.target_bytes().as_slice())?;
                 Ok(self.command.spawn()?)
             }
+            InputLocation::Socket(socket) => {
+                let mut child = self.command.spawn()?;
+                match socket.deliver_messages(&input.target_messages()) {
+                    Ok(responses) => {
+                        self.socket_responses = responses;
+                        Ok(child)
+                    }
+                    Err(err) => {
+                        drop(child.kill());
+                        drop(child.wait());
+                        Err(err)
+                    }
+                }
+            }
         }
     }
 
     fn exec_timeout(&self) -> Duration {
         self.timeout
     }
+
+    fn server_grace_period(&self) -> Option<Duration> {
+        match &self.input_location {
+            InputLocation::Socket(socket) => socket.grace_period(),
+            _ => None,
+        }
+    }
+
+    fn observe_socket_response<OT>(&mut self, observers: &mut OT)
+    where
+        OT: MatchName,
+    {
+        let Some(responses) = self.socket_responses.take() else {
+            return;
+        };
+        if let Some(o_ref) = &self.socket_response_observer {
+            if let Some(observer) = observers.get_mut(o_ref) {
+                observer.observe_response(&responses.concat());
+            }
+        }
+        if let Some(o_ref) = &self.protocol_state_observer {
+            if let Some(observer) = observers.get_mut(o_ref) {
+                for response in &responses {
+                    observer.observe_response(response);
+                }
+            }
+        }
+    }
 }
 
 /// A `CommandExecutor` is a wrapper around [`std::process::Command`] to execute a target as a child process.
@@ -239,8 +293,11 @@ where
 
         let mut child = self.configurer.spawn_child(input)?;
 
+        // A server keeps running after handling the input, so only wait for a crash to show up
+        let grace_period = self.configurer.server_grace_period();
+
         let res = match child
-            .wait_timeout(self.configurer.exec_timeout())
+            .wait_timeout(grace_period.unwrap_or_else(|| self.configurer.exec_timeout()))
             .expect("waiting on child failed")
             .map(|status| status.signal())
         {
@@ -254,7 +311,11 @@ where
                 drop(child.kill());
                 // finally, try to wait to properly clean up system resources.
                 drop(child.wait());
-                Ok(ExitKind::Timeout)
+                if grace_period.is_some() {
+                    Ok(ExitKind::Ok)
+                } else {
+                    Ok(ExitKind::Timeout)
+                }
             }
         };
 
@@ -263,6 +324,8 @@ where
                 .post_exec_child_all(state, input, &exit_kind)?;
         }
 
+        self.configurer.observe_socket_response(&mut self.observers);
+
         if let Some(ref mut ob) = &mut self.configurer.stdout_observer_mut() {
             let mut stdout = Vec::new();
             child.stdout.as_mut().ok_or_else(|| {
@@ -324,6 +387,8 @@ pub struct CommandExecutorBuilder {
     program: Option<OsString>,
     args: Vec<OsString>,
     input_location: InputLocation,
+    socket_response_observer: Option<Handle<SocketResponseObserver>>,
+    protocol_state_observer: Option<Handle<ProtocolStateObserver>>,
     cwd: Option<PathBuf>,
     envs: Vec<(OsString, OsString)>,
     timeout: Duration,
@@ -345,6 +410,8 @@ impl CommandExecutorBuilder {
             program: None,
             args: vec![],
             input_location: InputLocation::StdIn,
+            socket_response_observer: None,
+            protocol_state_observer: None,
             cwd: None,
             envs: vec![],
             timeout: Duration::from_secs(5),
@@ -417,6 +484,27 @@ impl CommandExecutorBuilder {
         self
     }
 
+    /// Sets the input mode to [`InputLocation::Socket`]:
+    /// the target is started, and the input is sent to it over the socket once it is ready.
+    pub fn socket_input(&mut self, socket: SocketInput) -> &mut Self {
+        self.input(InputLocation::Socket(socket));
+        self
+    }
+
+    /// Sets the observer receiving the target's response to inputs delivered over a socket.
+    /// The [`SocketInput`] needs to capture responses, see [`SocketInput::capture_response`].
+    pub fn socket_response_observer(&mut self, observer: &SocketResponseObserver) -> &mut Self {
+        self.socket_response_observer = Some(observer.handle());
+        self
+    }
+
+    /// Sets the observer recording the protocol states the target reports in its responses to inputs delivered over a socket.
+    /// The [`SocketInput`] needs to capture responses, see [`SocketInput::capture_response`].
+    pub fn protocol_state_observer(&mut self, observer: &ProtocolStateObserver) -> &mut Self {
+        self.protocol_state_observer = Some(observer.handle());
+        self
+    }
+
     /// Adds an argument to the program's commandline.
     pub fn arg<O: AsRef<OsStr>>(&mut self, arg: O) -> &mut CommandExecutorBuilder {
         self.args.push(arg.as_ref().to_owned());
@@ -499,7 +587,7 @@ impl CommandExecutorBuilder {
             InputLocation::StdIn => {
                 command.stdin(Stdio::piped());
             }
-            InputLocation::File { .. } | InputLocation::Arg { .. } => {
+            InputLocation::File { .. } | InputLocation::Arg { .. } | InputLocation::Socket(_) => {
                 command.stdin(Stdio::null());
             }
         }
@@ -530,6 +618,9 @@ impl CommandExecutorBuilder {
             stdout_observer: self.stdout.clone(),
             stderr_observer: self.stderr.clone(),
             input_location: self.input_location.clone(),
+            socket_response_observer: self.socket_response_observer.clone(),
+            protocol_state_observer: self.protocol_state_observer.clone(),
+            socket_responses: None,
             timeout: self.timeout,
             command,
         };
@@ -610,6 +701,19 @@ pub trait CommandConfigurator<I>: Sized {
     /// Provides timeout duration for execution of the child process.
     fn exec_timeout(&self) -> Duration;
 
+    /// For targets that keep serving after they handled the input:
+    /// how long to wait for a crash before killing the child and considering the run [`ExitKind::Ok`].
+    fn server_grace_period(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Hands the response the target sent over its socket during the last run to the observers, if one was captured.
+    fn observe_socket_response<OT>(&mut self, _observers: &mut OT)
+    where
+        OT: MatchName,
+    {
+    }
+
     /// Create an `Executor` from this `CommandConfigurator`.
     fn into_executor<OT, S>(self, observers: OT) -> CommandExecutor<OT, S, Self>
     where
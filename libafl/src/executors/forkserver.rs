@@ -39,10 +39,13 @@ use nix::{
 #[cfg(feature = "regex")]
 use crate::observers::{get_asan_runtime_flags_with_log_path, AsanBacktraceObserver};
 use crate::{
-    executors::{Executor, ExitKind, HasObservers},
+    executors::{socket::SocketInput, Executor, ExitKind, HasObservers},
     inputs::{HasTargetBytes, Input, UsesInput},
     mutators::Tokens,
-    observers::{MapObserver, Observer, ObserversTuple, UsesObservers},
+    observers::{
+        MapObserver, Observer, ObserversTuple, ProtocolStateObserver, SocketResponseObserver,
+        UsesObservers,
+    },
     state::{HasExecutions, State, UsesState},
     Error,
 };
@@ -60,6 +63,21 @@ const FS_NEW_OPT_SHDMEM_FUZZ: i32 = 2_u32 as i32;
 #[allow(clippy::cast_possible_wrap)]
 const FS_NEW_OPT_AUTODICT: i32 = 0x00000800_u32 as i32;
 
+#[allow(clippy::cast_possible_wrap)]
+const FS_OPT_ENABLED: i32 = 0x80000001_u32 as i32;
+#[allow(clippy::cast_possible_wrap)]
+const FS_OPT_MAPSIZE: i32 = 0x40000000_u32 as i32;
+#[allow(clippy::cast_possible_wrap)]
+const FS_OPT_SNAPSHOT: i32 = 0x20000000_u32 as i32;
+#[allow(clippy::cast_possible_wrap)]
+const FS_OPT_AUTODICT: i32 = 0x10000000_u32 as i32;
+#[allow(clippy::cast_possible_wrap)]
+const FS_OPT_SHDMEM_FUZZ: i32 = 0x01000000_u32 as i32;
+#[allow(clippy::cast_possible_wrap)]
+const FS_OPT_OLD_AFLPP_WORKAROUND: i32 = 0x0f000000_u32 as i32;
+#[allow(clippy::cast_possible_wrap)]
+const FS_OPT_ERROR: i32 = 0xf800008f_u32 as i32;
+
 #[allow(clippy::cast_possible_wrap)]
 const FS_ERROR_MAP_SIZE: i32 = 1_u32 as i32;
 #[allow(clippy::cast_possible_wrap)]
@@ -516,7 +534,8 @@ impl Forkserver {
 }
 
 /// This [`Executor`] can run binaries compiled for AFL/AFL++ that make use of a forkserver.
-/// Shared memory feature is also available, but you have to set things up in your code.
+/// If a `shmem_provider` is given to the builder, targets using AFL++'s shared memory testcase delivery
+/// (`__AFL_FUZZ_TESTCASE_BUF`) get their inputs over shared memory, negotiated during the forkserver handshake.
 /// Please refer to AFL++'s docs. <https://github.com/AFLplusplus/AFLplusplus/blob/stable/instrumentation/README.persistent_mode.md>
 pub struct ForkserverExecutor<OT, S, SP>
 where
@@ -537,6 +556,9 @@ where
     asan_obs: Handle<AsanBacktraceObserver>,
     timeout: TimeSpec,
     crash_exitcode: Option<i8>,
+    socket_input: Option<SocketInput>,
+    socket_response_observer: Option<Handle<SocketResponseObserver>>,
+    protocol_state_observer: Option<Handle<ProtocolStateObserver>>,
 }
 
 impl<OT, S, SP> Debug for ForkserverExecutor<OT, S, SP>
@@ -600,6 +622,11 @@ where
     pub fn coverage_map_size(&self) -> Option<usize> {
         self.map_size
     }
+
+    /// If the target negotiated to read its testcases from shared memory (`__AFL_FUZZ_TESTCASE_BUF`)
+    pub fn uses_shmem_testcase(&self) -> bool {
+        self.uses_shmem_testcase
+    }
 }
 
 /// The builder for `ForkserverExecutor`
@@ -625,6 +652,9 @@ pub struct ForkserverExecutorBuilder<'a, SP> {
     #[cfg(feature = "regex")]
     asan_obs: Option<Handle<AsanBacktraceObserver>>,
     crash_exitcode: Option<i8>,
+    socket_input: Option<SocketInput>,
+    socket_response_observer: Option<Handle<SocketResponseObserver>>,
+    protocol_state_observer: Option<Handle<ProtocolStateObserver>>,
 }
 
 impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
@@ -689,6 +719,9 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
                 .clone()
                 .unwrap_or(AsanBacktraceObserver::default().handle()),
             crash_exitcode: self.crash_exitcode,
+            socket_input: self.socket_input.clone(),
+            socket_response_observer: self.socket_response_observer.clone(),
+            protocol_state_observer: self.protocol_state_observer.clone(),
         })
     }
 
@@ -753,6 +786,9 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
                 .clone()
                 .unwrap_or(AsanBacktraceObserver::default().handle()),
             crash_exitcode: self.crash_exitcode,
+            socket_input: self.socket_input.clone(),
+            socket_response_observer: self.socket_response_observer.clone(),
+            protocol_state_observer: self.protocol_state_observer.clone(),
         })
     }
 
@@ -771,6 +807,7 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
 
         let input_file = InputFile::create(input_filename)?;
 
+        // the target decides during the handshake whether it uses the shared memory
         let map = match &mut self.shmem_provider {
             None => None,
             Some(provider) => {
@@ -814,21 +851,43 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
             report_error_and_exit(version_status & 0x0000ffff)?;
         }
 
+        if (0x41464c00..=0x41464cff).contains(&version_status) {
+            self.handshake_versioned(&mut forkserver, version_status)?;
+        } else if (version_status & FS_OPT_ERROR) == FS_OPT_ERROR {
+            report_error_and_exit((version_status & 0x00ffff00) >> 8)?;
+        } else if (version_status & FS_OPT_ENABLED) == FS_OPT_ENABLED {
+            self.handshake_option_flags(&mut forkserver, version_status)?;
+        } else {
+            log::info!("All right - old fork server is up, no options negotiated");
+        }
+
+        // The target did not ask for the testcase in shared memory, so it will read from the input file.
+        let map = if self.uses_shmem_testcase { map } else { None };
+
+        Ok((forkserver, input_file, map))
+    }
+
+    /// The handshake of the versioned forkserver protocol, as spoken by targets built with AFL++ 4.10 and later.
+    /// The target announces its options, then sends map size and autodict, and finally repeats its hello.
+    #[allow(clippy::pedantic)]
+    fn handshake_versioned(
+        &mut self,
+        forkserver: &mut Forkserver,
+        version_status: i32,
+    ) -> Result<(), Error> {
         let keep = version_status;
         let version: u32 = version_status as u32 - 0x41464c00_u32;
-        if (0x41464c00..=0x41464cff).contains(&version_status) {
-            match version {
-                0 => {
-                    return Err(Error::unknown("Fork server version is not assigned, this should not happen. Recompile target."));
-                }
-                FS_NEW_VERSION_MIN..=FS_NEW_VERSION_MAX => {
-                    // good, do nothing
-                }
-                _ => {
-                    return Err(Error::unknown(
-                        "Fork server version is not supported. Recompile the target.",
-                    ));
-                }
+        match version {
+            0 => {
+                return Err(Error::unknown("Fork server version is not assigned, this should not happen. Recompile target."));
+            }
+            FS_NEW_VERSION_MIN..=FS_NEW_VERSION_MAX => {
+                // good, do nothing
+            }
+            _ => {
+                return Err(Error::unknown(
+                    "Fork server version is not supported. Recompile the target.",
+                ));
             }
         }
 
@@ -859,26 +918,18 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
                 self.map_size = Some(map_size as usize);
             }
             */
-            let (read_len, mut map_size) = forkserver.read_st()?;
+            let (read_len, map_size) = forkserver.read_st()?;
             if read_len != 4 {
                 return Err(Error::unknown(
                     "Failed to read map size from forkserver".to_string(),
                 ));
             }
-
-            if map_size % 64 != 0 {
-                map_size = ((map_size + 63) >> 6) << 6;
-            }
-
-            // TODO set AFL_MAP_SIZE
-            assert!(self.map_size.is_none() || map_size as usize <= self.map_size.unwrap());
-
-            // we'll use this later when we truncate the observer
-            self.map_size = Some(map_size as usize);
+            self.set_target_map_size(map_size as usize)?;
         }
 
         if status & FS_NEW_OPT_SHDMEM_FUZZ != 0 {
-            if map.is_some() {
+            // this version of the protocol gives us no way to decline
+            if self.shmem_provider.is_some() {
                 log::info!("Using SHARED MEMORY FUZZING feature.");
                 self.uses_shmem_testcase = true;
             } else {
@@ -897,21 +948,7 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
                     "Failed to read dictionary size from forkserver".to_string(),
                 ));
             }
-
-            if !(2..=0xffffff).contains(&dict_size) {
-                return Err(Error::illegal_state(
-                    "Dictionary has an illegal size".to_string(),
-                ));
-            }
-            log::info!("Autodict size {dict_size:x}");
-            let (rlen, buf) = forkserver.read_st_size(dict_size as usize)?;
-
-            if rlen != dict_size as usize {
-                return Err(Error::unknown("Failed to load autodictionary".to_string()));
-            }
-            if let Some(t) = &mut self.autotokens {
-                t.parse_autodict(&buf, dict_size as usize);
-            }
+            self.read_autodict(forkserver, dict_size)?;
         }
 
         let (read_len, aflx) = forkserver.read_st()?;
@@ -926,7 +963,102 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
             )));
         }
 
-        Ok((forkserver, input_file, map))
+        Ok(())
+    }
+
+    /// The handshake of the option flag protocol, as spoken by targets built with AFL++ before 4.10.
+    /// The hello already carries the options and the map size, and we answer with the options we accept.
+    #[allow(clippy::cast_sign_loss)]
+    fn handshake_option_flags(
+        &mut self,
+        forkserver: &mut Forkserver,
+        mut status: i32,
+    ) -> Result<(), Error> {
+        // Some AFL++ versions in between set all of these bits, they carry no meaning
+        if (status & FS_OPT_OLD_AFLPP_WORKAROUND) == FS_OPT_OLD_AFLPP_WORKAROUND {
+            status &= !FS_OPT_OLD_AFLPP_WORKAROUND;
+        }
+
+        log::info!("All right - fork server with option flags {status:#x} is up");
+
+        if (status & FS_OPT_SNAPSHOT) == FS_OPT_SNAPSHOT {
+            log::info!("Target supports snapshot mode, which is not used by this executor.");
+        }
+
+        if (status & FS_OPT_MAPSIZE) == FS_OPT_MAPSIZE {
+            self.set_target_map_size((((status & 0x00fffffe) >> 1) + 1) as usize)?;
+        }
+
+        // If we decline shared memory fuzzing, the target reads its testcases from stdin or the input file
+        let mut reply = FS_OPT_ENABLED;
+        let wants_shmem = (status & FS_OPT_SHDMEM_FUZZ) == FS_OPT_SHDMEM_FUZZ;
+        if wants_shmem && self.shmem_provider.is_some() {
+            log::info!("Using SHARED MEMORY FUZZING feature.");
+            self.uses_shmem_testcase = true;
+            reply |= FS_OPT_SHDMEM_FUZZ;
+        }
+
+        // The target waits for our answer if it asked for shared memory fuzzing or autodict,
+        // and only sends the dictionary if we ask for it.
+        let wants_autodict = (status & FS_OPT_AUTODICT) == FS_OPT_AUTODICT;
+        if wants_autodict && self.autotokens.is_some() {
+            reply |= FS_OPT_AUTODICT;
+        }
+        if (wants_autodict || wants_shmem) && forkserver.write_ctl(reply)? != 4 {
+            return Err(Error::unknown("Writing to forkserver failed.".to_string()));
+        }
+
+        if (reply & FS_OPT_AUTODICT) == FS_OPT_AUTODICT {
+            let (read_len, dict_size) = forkserver.read_st()?;
+            if read_len != 4 {
+                return Err(Error::unknown(
+                    "Failed to read dictionary size from forkserver".to_string(),
+                ));
+            }
+            self.read_autodict(forkserver, dict_size)?;
+        }
+
+        Ok(())
+    }
+
+    /// Takes over the map size announced by the target, rounded up to a multiple of 64 like AFL++ does
+    fn set_target_map_size(&mut self, mut map_size: usize) -> Result<(), Error> {
+        if map_size % 64 != 0 {
+            map_size = ((map_size + 63) >> 6) << 6;
+        }
+
+        // TODO set AFL_MAP_SIZE
+        if let Some(max_size) = self.map_size {
+            if map_size > max_size {
+                return Err(Error::illegal_state(format!(
+                    "The target uses a coverage map of {map_size} bytes, larger than the map size of {max_size} bytes set for the executor"
+                )));
+            }
+        }
+
+        // we'll use this later when we truncate the observer
+        self.map_size = Some(map_size);
+        Ok(())
+    }
+
+    /// Reads the autodict of `dict_size` bytes the target sends and adds it to the autotokens, if any
+    #[allow(clippy::cast_sign_loss)]
+    fn read_autodict(&mut self, forkserver: &mut Forkserver, dict_size: i32) -> Result<(), Error> {
+        if !(2..=0xffffff).contains(&dict_size) {
+            return Err(Error::illegal_state(
+                "Dictionary has an illegal size".to_string(),
+            ));
+        }
+        log::info!("Autodict size {dict_size:x}");
+        let (rlen, buf) = forkserver.read_st_size(dict_size as usize)?;
+
+        if rlen != dict_size as usize {
+            return Err(Error::unknown("Failed to load autodictionary".to_string()));
+        }
+        if let Some(t) = &mut self.autotokens {
+            t.parse_autodict(&buf, dict_size as usize);
+        }
+        Ok(())
     }
 
     /// Use autodict?
@@ -1118,6 +1250,30 @@ impl<'a, SP> ForkserverExecutorBuilder<'a, SP> {
         self
     }
 
+    /// Delivers the input over a socket instead of a file, stdin or shared memory,
+    /// once the forked target is ready to accept it.
+    #[must_use]
+    pub fn socket_input(mut self, socket: SocketInput) -> Self {
+        self.socket_input = Some(socket);
+        self
+    }
+
+    /// Sets the observer receiving the target's response to inputs delivered over a socket.
+    /// The [`SocketInput`] needs to capture responses, see [`SocketInput::capture_response`].
+    #[must_use]
+    pub fn socket_response_observer(mut self, observer: &SocketResponseObserver) -> Self {
+        self.socket_response_observer = Some(observer.handle());
+        self
+    }
+
+    /// Sets the observer recording the protocol states the target reports in its responses to inputs delivered over a socket.
+    /// The [`SocketInput`] needs to capture responses, see [`SocketInput::capture_response`].
+    #[must_use]
+    pub fn protocol_state_observer(mut self, observer: &ProtocolStateObserver) -> Self {
+        self.protocol_state_observer = Some(observer.handle());
+        self
+    }
+
     /// Call this if the harness uses deferred forkserver mode; default is false
     #[must_use]
     pub fn is_deferred_frksrv(mut self, is_deferred_frksrv: bool) -> Self {
@@ -1168,6 +1324,9 @@ impl<'a> ForkserverExecutorBuilder<'a, UnixShMemProvider> {
             timeout: None,
             asan_obs: None,
             crash_exitcode: None,
+            socket_input: None,
+            socket_response_observer: None,
+            protocol_state_observer: None,
         }
     }
 
@@ -1195,6 +1354,9 @@ impl<'a> ForkserverExecutorBuilder<'a, UnixShMemProvider> {
             timeout: None,
             asan_obs: None,
             crash_exitcode: None,
+            socket_input: self.socket_input,
+            socket_response_observer: self.socket_response_observer,
+            protocol_state_observer: self.protocol_state_observer,
         }
     }
 }
@@ -1243,7 +1405,9 @@ where
             input_bytes = OwnedSlice::from(input_bytes_copy);
         }
         let input_size_in_bytes = input_size.to_ne_bytes();
-        if self.uses_shmem_testcase {
+        if self.socket_input.is_some() {
+            // The input is sent once the child is up
+        } else if self.uses_shmem_testcase {
             debug_assert!(
                 self.map.is_some(),
                 "The uses_shmem_testcase() bool can only exist when a map is set"
@@ -1286,7 +1450,46 @@ where
 
         self.forkserver.set_child_pid(Pid::from_raw(pid));
 
-        if let Some(status) = self.forkserver.read_st_timed(&self.timeout)? {
+        let mut timeout = self.timeout;
+        if let Some(socket) = &self.socket_input {
+            let messages = input.target_messages();
+            // A single message is the input bytes, truncated or extended like for the other deliveries
+            let delivered = if messages.len() == 1 {
+                socket.deliver_messages(&[&input_bytes.as_slice()[..input_size]])
+            } else {
+                socket.deliver_messages(&messages)
+            };
+            let responses = match delivered {
+                Ok(responses) => responses,
+                Err(err) => {
+                    // Don't leave the child behind, the forkserver expects us to reap it
+                    self.forkserver.set_last_run_timed_out(true);
+                    let _ = kill(self.forkserver().child_pid(), self.forkserver.kill_signal);
+                    self.forkserver.read_st()?;
+                    self.forkserver.reset_child_pid();
+                    return Err(err);
+                }
+            };
+            if let Some(responses) = responses {
+                if let Some(o_ref) = &self.socket_response_observer {
+                    if let Some(observer) = self.observers.get_mut(o_ref) {
+                        observer.observe_response(&responses.concat());
+                    }
+                }
+                if let Some(o_ref) = &self.protocol_state_observer {
+                    if let Some(observer) = self.observers.get_mut(o_ref) {
+                        for response in &responses {
+                            observer.observe_response(response);
+                        }
+                    }
+                }
+            }
+            if let Some(grace_period) = socket.grace_period() {
+                timeout = grace_period.into();
+            }
+        }
+
+        if let Some(status) = self.forkserver.read_st_timed(&timeout)? {
             self.forkserver.set_status(status);
             let exitcode_is_crash = if let Some(crash_exitcode) = self.crash_exitcode {
                 (libc::WEXITSTATUS(self.forkserver().status()) as i8) == crash_exitcode
@@ -1309,7 +1512,14 @@ where
             if recv_status_len != 4 {
                 return Err(Error::unknown("Could not kill timed-out child".to_string()));
             }
-            exit_kind = ExitKind::Timeout;
+            // A server still running after its grace period simply handled the input
+            let is_server = self
+                .socket_input
+                .as_ref()
+                .is_some_and(|socket| socket.grace_period().is_some());
+            if !is_server {
+                exit_kind = ExitKind::Timeout;
+            }
         }
 
         if !libc::WIFSTOPPED(self.forkserver().status()) {
@@ -1356,7 +1566,9 @@ where
 
 #[cfg(test)]
 mod tests {
-    use std::ffi::OsString;
+    use alloc::{string::String, vec::Vec};
+    use core::{fmt::Write, time::Duration};
+    use std::{env, ffi::OsString, fs, path::Path, process, thread};
 
     use libafl_bolts::{
         shmem::{ShMem, ShMemProvider, UnixShMemProvider},
@@ -1366,11 +1578,47 @@ mod tests {
     use serial_test::serial;
 
     use crate::{
-        executors::forkserver::ForkserverExecutor,
+        executors::forkserver::{
+            ForkserverExecutor, FS_NEW_OPT_AUTODICT, FS_NEW_OPT_MAPSIZE, FS_NEW_OPT_SHDMEM_FUZZ,
+            FS_OPT_ENABLED, FS_OPT_MAPSIZE, FS_OPT_SHDMEM_FUZZ,
+        },
+        mutators::Tokens,
         observers::{ConstMapObserver, HitcountsMapObserver},
         Error,
     };
 
+    /// A shell script playing the forkserver of a target: it sends the `messages` on the status pipe,
+    /// then stores the answer of the fuzzer to `reply`, and sends the `after_reply` messages.
+    fn fake_forkserver(messages: &[i32], reply: &Path, after_reply: &[u8]) -> OsString {
+        let escape = |bytes: &[u8]| {
+            bytes.iter().fold(String::new(), |mut escaped, b| {
+                write!(escaped, "\\{b:03o}").unwrap();
+                escaped
+            })
+        };
+        let messages: Vec<u8> = messages.iter().flat_map(|m| m.to_ne_bytes()).collect();
+        OsString::from(format!(
+            "printf '{}' > /dev/fd/199; head -c 4 /dev/fd/198 > '{}'; printf '{}' > /dev/fd/199",
+            escape(&messages),
+            reply.display(),
+            escape(after_reply)
+        ))
+    }
+
+    /// Waits for the fake forkserver to store the 4 bytes of the answer of the fuzzer
+    fn read_reply(reply: &Path) -> i32 {
+        for _ in 0..500 {
+            if let Ok(bytes) = fs::read(reply) {
+                if bytes.len() == 4 {
+                    fs::remove_file(reply).unwrap();
+                    return i32::from_ne_bytes(bytes.try_into().unwrap());
+                }
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("The fake forkserver stored no answer");
+    }
+
     #[test]
     #[serial]
     #[cfg_attr(miri, ignore)]
@@ -1407,4 +1655,87 @@ mod tests {
         };
         assert!(result);
     }
+
+    #[test]
+    #[serial]
+    #[cfg(target_os = "linux")]
+    #[cfg_attr(miri, ignore)]
+    fn test_option_flags_handshake() {
+        let reply = env::temp_dir().join(format!("libafl_fs_reply_{}", process::id()));
+        // a map of 0x20000 bytes, encoded as in AFL++'s `FS_OPT_SET_MAPSIZE`
+        let hello = FS_OPT_ENABLED | FS_OPT_MAPSIZE | FS_OPT_SHDMEM_FUZZ | ((0x20000 - 1) << 1);
+        let script = fake_forkserver(&[hello], &reply, &[]);
+
+        let mut shmem_provider = UnixShMemProvider::new().unwrap();
+        let executor = ForkserverExecutor::builder()
+            .program("sh")
+            .args([OsString::from("-c"), script.clone()])
+            .shmem_provider(&mut shmem_provider)
+            .build::<_, ()>(tuple_list!())
+            .unwrap();
+        assert_eq!(read_reply(&reply), FS_OPT_ENABLED | FS_OPT_SHDMEM_FUZZ);
+        assert!(executor.uses_shmem_testcase());
+        assert_eq!(executor.coverage_map_size(), Some(0x20000));
+        drop(executor);
+
+        // without a shmem provider, shared memory fuzzing is declined
+        let executor = ForkserverExecutor::builder()
+            .program("sh")
+            .args([OsString::from("-c"), script])
+            .build::<_, ()>(tuple_list!())
+            .unwrap();
+        assert_eq!(read_reply(&reply), FS_OPT_ENABLED);
+        assert!(!executor.uses_shmem_testcase());
+        drop(executor);
+
+        // a map larger than the configured one is an error, not a panic
+        let script = fake_forkserver(&[hello], Path::new("/dev/null"), &[]);
+        let result = ForkserverExecutor::builder()
+            .program("sh")
+            .args([OsString::from("-c"), script])
+            .coverage_map_size(0x10000)
+            .build::<_, ()>(tuple_list!());
+        assert!(matches!(result, Err(Error::IllegalState(..))));
+    }
+
+    #[test]
+    #[serial]
+    #[cfg(target_os = "linux")]
+    #[cfg_attr(miri, ignore)]
+    fn test_versioned_handshake() {
+        let reply = env::temp_dir().join(format!("libafl_fs_reply_{}", process::id()));
+        let hello: i32 = 0x41464c01;
+        let options = FS_NEW_OPT_MAPSIZE | FS_NEW_OPT_SHDMEM_FUZZ | FS_NEW_OPT_AUTODICT;
+        let dict = b"\x03abc\x02de";
+        let mut after_reply: Vec<u8> = [options, 1000, i32::try_from(dict.len()).unwrap()]
+            .iter()
+            .flat_map(|m| m.to_ne_bytes())
+            .collect();
+        after_reply.extend_from_slice(dict);
+        after_reply.extend_from_slice(&hello.to_ne_bytes());
+        let script = fake_forkserver(&[hello], &reply, &after_reply);
+
+        let mut shmem_provider = UnixShMemProvider::new().unwrap();
+        let mut tokens = Tokens::new();
+        let executor = ForkserverExecutor::builder()
+            .program("sh")
+            .args([OsString::from("-c"), script])
+            .shmem_provider(&mut shmem_provider)
+            .autotokens(&mut tokens)
+            .build::<_, ()>(tuple_list!())
+            .unwrap();
+        assert_eq!(read_reply(&reply), !hello);
+        assert!(executor.uses_shmem_testcase());
+        assert_eq!(executor.coverage_map_size(), Some(1024));
+        drop(executor);
+        assert_eq!(tokens.tokens(), &[b"abc".to_vec(), b"de".to_vec()]);
+
+        // this protocol can't decline shared memory fuzzing
+        let script = fake_forkserver(&[hello], Path::new("/dev/null"), &after_reply);
+        let result = ForkserverExecutor::builder()
+            .program("sh")
+            .args([OsString::from("-c"), script])
+            .build::<_, ()>(tuple_list!());
+        assert!(result.is_err());
+    }
 }
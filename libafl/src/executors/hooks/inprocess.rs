@@ -368,6 +368,9 @@ pub struct InProcessExecutorHandlerData {
     /// The timeout handler
     #[cfg(feature = "std")]
     pub(crate) timeout_handler: *const c_void,
+    /// The program counter of the last crash, if the crash handler could read it
+    #[cfg(all(unix, feature = "std"))]
+    pub(crate) crash_pc: Option<u64>,
 
     #[cfg(all(windows, feature = "std"))]
     pub(crate) ptp_timer: Option<PTP_TIMER>,
@@ -442,6 +445,9 @@ pub(crate) static mut GLOBAL_STATE: InProcessExecutorHandlerData = InProcessExec
     // The timeout handler fn
     #[cfg(feature = "std")]
     timeout_handler: ptr::null(),
+    // The pc of the last crash
+    #[cfg(all(unix, feature = "std"))]
+    crash_pc: None,
     #[cfg(all(windows, feature = "std"))]
     ptp_timer: None,
     #[cfg(all(windows, feature = "std"))]
@@ -480,6 +486,13 @@ pub fn inprocess_get_input<'a, I>() -> Option<&'a I> {
     unsafe { (GLOBAL_STATE.current_input_ptr as *const I).as_ref() }
 }
 
+/// Gets the program counter at which the current input crashed, as read by the crash handler
+#[cfg(all(unix, feature = "std"))]
+#[must_use]
+pub fn inprocess_crash_pc() -> Option<u64> {
+    unsafe { GLOBAL_STATE.crash_pc }
+}
+
 /// Know if we ar eexecuting in a crash/timeout handler
 #[must_use]
 pub fn inprocess_in_handler() -> bool {
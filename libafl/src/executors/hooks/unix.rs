@@ -221,6 +221,10 @@ pub mod unix_signal_handler {
                 }
             }
 
+            data.crash_pc = _context
+                .as_deref()
+                .and_then(libafl_bolts::minibsod::fault_pc);
+
             run_observers_and_save_state::<E, EM, OF, Z>(
                 executor,
                 state,
@@ -474,6 +474,7 @@ pub fn run_observers_and_save_state<E, EM, OF, Z>(
                 state,
                 Event::Objective {
                     objective_size: state.solutions().count(),
+                    exit_kind: exitkind,
                     executions,
                     time: libafl_bolts::current_time(),
                 },
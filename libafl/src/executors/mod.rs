@@ -2,7 +2,7 @@
 
 #[cfg(unix)]
 use alloc::vec::Vec;
-use core::fmt::Debug;
+use core::{fmt, fmt::Debug};
 
 pub use combined::CombinedExecutor;
 #[cfg(all(feature = "std", any(unix, doc)))]
@@ -40,13 +40,16 @@ pub mod inprocess_fork;
 
 pub mod shadow;
 
+#[cfg(feature = "std")]
+pub mod socket;
+
 pub mod with_observers;
 
 /// The module for all the hooks
 pub mod hooks;
 
 /// How an execution finished.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[cfg_attr(
     any(not(feature = "serdeany_autoreg"), miri),
     allow(clippy::unsafe_derive_deserialize)
@@ -67,12 +70,59 @@ pub enum ExitKind {
         /// The exitkind of the secondary executor
         secondary: DiffExitKind,
     },
-    // The run resulted in a custom `ExitKind`.
-    // Custom(Box<dyn SerdeAny>),
+    /// The run resulted in a target-specific [`CustomExitKind`].
+    Custom(CustomExitKind),
+}
+
+impl fmt::Display for ExitKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExitKind::Ok => write!(f, "ok"),
+            ExitKind::Crash => write!(f, "crash"),
+            ExitKind::Oom => write!(f, "oom"),
+            ExitKind::Timeout => write!(f, "timeout"),
+            ExitKind::Diff { .. } => write!(f, "diff"),
+            ExitKind::Custom(custom) => write!(f, "{custom}"),
+        }
+    }
+}
+
+/// A target-specific outcome of an execution, such as an assertion class,
+/// a protocol state violation, or a detected leak.
+///
+/// The meaning of the id is up to the target and the feedbacks matching on it.
+/// Only the id is stored, so that [`ExitKind`] stays `Copy` and cheap to pass around.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CustomExitKind(pub u32);
+
+impl CustomExitKind {
+    /// Creates a new [`CustomExitKind`] with the given id
+    #[must_use]
+    pub const fn new(id: u32) -> Self {
+        Self(id)
+    }
+
+    /// The id of this [`CustomExitKind`]
+    #[must_use]
+    pub const fn id(&self) -> u32 {
+        self.0
+    }
+}
+
+impl fmt::Display for CustomExitKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "custom_{}", self.0)
+    }
+}
+
+impl From<CustomExitKind> for ExitKind {
+    fn from(custom: CustomExitKind) -> Self {
+        ExitKind::Custom(custom)
+    }
 }
 
 /// How one of the diffing executions finished.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[cfg_attr(
     any(not(feature = "serdeany_autoreg"), miri),
     allow(clippy::unsafe_derive_deserialize)
@@ -88,8 +138,8 @@ pub enum DiffExitKind {
     Timeout,
     /// One of the executors itelf repots a differential, we can't go into further details.
     Diff,
-    // The run resulted in a custom `ExitKind`.
-    // Custom(Box<dyn SerdeAny>),
+    /// The run resulted in a target-specific [`CustomExitKind`].
+    Custom(CustomExitKind),
 }
 
 libafl_bolts::impl_serdeany!(ExitKind);
@@ -102,6 +152,7 @@ impl From<ExitKind> for DiffExitKind {
             ExitKind::Oom => DiffExitKind::Oom,
             ExitKind::Timeout => DiffExitKind::Timeout,
             ExitKind::Diff { .. } => DiffExitKind::Diff,
+            ExitKind::Custom(custom) => DiffExitKind::Custom(custom),
         }
     }
 }
@@ -0,0 +1,405 @@
+//! Delivers inputs to targets that read them from a network socket, such as daemons listening on a TCP port.
+//! Used by the [`crate::executors::CommandExecutor`] and the [`crate::executors::ForkserverExecutor`].
+
+use alloc::vec::Vec;
+use core::ops::Deref;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::{
+    io::{ErrorKind, Read, Write},
+    net::{Shutdown, SocketAddr, TcpStream, UdpSocket},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use crate::Error;
+
+/// The default time to wait for the target to become ready
+const STARTUP_TIMEOUT_DEFAULT: Duration = Duration::from_secs(1);
+/// The default time between two connection attempts
+const PROBE_INTERVAL_DEFAULT: Duration = Duration::from_millis(2);
+/// The maximum size of a UDP datagram
+const MAX_DATAGRAM_SIZE: usize = 65507;
+
+/// The socket the target reads its input from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SocketAddress {
+    /// Connect to a TCP port and send the input as stream
+    Tcp(SocketAddr),
+    /// Send the input as a single datagram to a UDP port
+    Udp(SocketAddr),
+    /// Connect to a unix domain socket and send the input as stream
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+/// How to find out that the freshly started target is ready to receive input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadinessProbe {
+    /// Try to connect until the target accepts the connection.
+    /// UDP is connectionless, so for [`SocketAddress::Udp`] this sends right away.
+    Connect {
+        /// The time between two connection attempts
+        interval: Duration,
+    },
+    /// Wait for a fixed time after startup, then connect once
+    Delay(Duration),
+}
+
+impl Default for ReadinessProbe {
+    fn default() -> Self {
+        Self::Connect {
+            interval: PROBE_INTERVAL_DEFAULT,
+        }
+    }
+}
+
+/// Delivers each input over a socket, after the target started up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocketInput {
+    address: SocketAddress,
+    probe: ReadinessProbe,
+    startup_timeout: Duration,
+    response_timeout: Option<Duration>,
+    server_grace_period: Option<Duration>,
+}
+
+/// An open connection to the target
+enum Connection {
+    Tcp(TcpStream),
+    Udp(UdpSocket),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl SocketInput {
+    /// Creates a new [`SocketInput`] delivering to the given address.
+    /// By default, it probes the target by connecting, and does not capture responses.
+    #[must_use]
+    pub fn new(address: SocketAddress) -> Self {
+        Self {
+            address,
+            probe: ReadinessProbe::default(),
+            startup_timeout: STARTUP_TIMEOUT_DEFAULT,
+            response_timeout: None,
+            server_grace_period: None,
+        }
+    }
+
+    /// Delivers to the given TCP address
+    #[must_use]
+    pub fn tcp(address: SocketAddr) -> Self {
+        Self::new(SocketAddress::Tcp(address))
+    }
+
+    /// Delivers to the given UDP address
+    #[must_use]
+    pub fn udp(address: SocketAddr) -> Self {
+        Self::new(SocketAddress::Udp(address))
+    }
+
+    /// Delivers to the unix domain socket at the given path
+    #[cfg(unix)]
+    #[must_use]
+    pub fn unix<P>(path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self::new(SocketAddress::Unix(path.into()))
+    }
+
+    /// Sets how to find out that the target is ready
+    #[must_use]
+    pub fn probe(mut self, probe: ReadinessProbe) -> Self {
+        self.probe = probe;
+        self
+    }
+
+    /// Sets how long the target may take to become ready, before the run fails
+    #[must_use]
+    pub fn startup_timeout(mut self, timeout: Duration) -> Self {
+        self.startup_timeout = timeout;
+        self
+    }
+
+    /// Reads the response of the target after sending the input, until it closes the connection or `timeout` passed.
+    /// The response is handed to a [`crate::observers::SocketResponseObserver`], if the executor has one.
+    ///
+    /// For inputs made of several messages, see [`Self::deliver_messages`], the reply to each message but the last
+    /// is read until the target did not send anything for `timeout`, and handed to a
+    /// [`crate::observers::ProtocolStateObserver`] on its own.
+    #[must_use]
+    pub fn capture_response(mut self, timeout: Duration) -> Self {
+        self.response_timeout = Some(timeout);
+        self
+    }
+
+    /// Marks the target as a server that keeps running after it handled the input.
+    /// The executor then only waits `grace_period` for a crash after delivery,
+    /// kills the target if it is still running, and counts the run as [`crate::executors::ExitKind::Ok`].
+    #[must_use]
+    pub fn server_grace_period(mut self, grace_period: Duration) -> Self {
+        self.server_grace_period = Some(grace_period);
+        self
+    }
+
+    /// The address inputs are delivered to
+    #[must_use]
+    pub fn address(&self) -> &SocketAddress {
+        &self.address
+    }
+
+    /// The grace period for targets that keep running, see [`Self::server_grace_period`]
+    #[must_use]
+    pub fn grace_period(&self) -> Option<Duration> {
+        self.server_grace_period
+    }
+
+    /// Waits for the target to become ready, sends the input, and reads the response if configured.
+    ///
+    /// A target closing the connection early, e.g. because it crashed, is not an error.
+    /// Returns the response, if any was captured.
+    pub fn deliver(&self, input: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self
+            .deliver_messages(&[input])?
+            .map(|responses| responses.concat()))
+    }
+
+    /// Waits for the target to become ready, then sends the messages one after the other over the same connection.
+    /// Over UDP, each message is a datagram of its own.
+    ///
+    /// If responses are captured, the reply to each message is read before the next message is sent.
+    /// A target closing the connection early, e.g. because it crashed, is not an error, the remaining messages are dropped.
+    /// Returns the responses, one per message sent, if they were captured.
+    pub fn deliver_messages<M>(&self, messages: &[M]) -> Result<Option<Vec<Vec<u8>>>, Error>
+    where
+        M: Deref<Target = [u8]>,
+    {
+        let Some(mut connection) = self.connect()? else {
+            return Ok(None);
+        };
+
+        let mut responses = Vec::with_capacity(messages.len());
+        if messages.is_empty() {
+            connection.finish()?;
+        }
+        for (i, message) in messages.iter().enumerate() {
+            let is_last = i + 1 == messages.len();
+            let sent = if is_last {
+                connection
+                    .send(message)
+                    .and_then(|()| connection.finish())
+            } else {
+                connection.send(message)
+            };
+            if let Err(err) = sent {
+                return match err.kind() {
+                    ErrorKind::BrokenPipe
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionRefused => Ok(self.response_timeout.map(|_| responses)),
+                    _ => Err(err.into()),
+                };
+            }
+            if let Some(timeout) = self.response_timeout {
+                // Only the last reply ends with the connection, earlier ones end when the target goes quiet
+                responses.push(connection.receive(timeout, !is_last)?);
+            }
+        }
+
+        Ok(self.response_timeout.map(|_| responses))
+    }
+
+    /// Connects to the target, following the [`ReadinessProbe`].
+    /// Returns `None` if the target refused the connection, e.g. because it crashed during startup.
+    fn connect(&self) -> Result<Option<Connection>, Error> {
+        let start = Instant::now();
+        let interval = match self.probe {
+            ReadinessProbe::Connect { interval } => interval,
+            ReadinessProbe::Delay(delay) => {
+                sleep(delay);
+                return self.try_connect();
+            }
+        };
+
+        loop {
+            match self.try_connect()? {
+                Some(connection) => return Ok(Some(connection)),
+                None if start.elapsed() >= self.startup_timeout => {
+                    return Err(Error::unknown(format!(
+                        "Target did not accept a connection on {:?} within {:?}",
+                        self.address, self.startup_timeout
+                    )));
+                }
+                None => sleep(interval),
+            }
+        }
+    }
+
+    fn try_connect(&self) -> Result<Option<Connection>, Error> {
+        let connection = match &self.address {
+            SocketAddress::Tcp(addr) => TcpStream::connect(addr).map(|stream| {
+                // Each message is sent in one go, don't wait for more
+                let _ = stream.set_nodelay(true);
+                Connection::Tcp(stream)
+            }),
+            SocketAddress::Udp(addr) => {
+                let bind_addr: SocketAddr = if addr.is_ipv4() {
+                    (std::net::Ipv4Addr::UNSPECIFIED, 0).into()
+                } else {
+                    (std::net::Ipv6Addr::UNSPECIFIED, 0).into()
+                };
+                let socket = UdpSocket::bind(bind_addr)?;
+                socket.connect(addr)?;
+                Ok(Connection::Udp(socket))
+            }
+            #[cfg(unix)]
+            SocketAddress::Unix(path) => UnixStream::connect(path).map(Connection::Unix),
+        };
+        match connection {
+            Ok(connection) => Ok(Some(connection)),
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    ErrorKind::ConnectionRefused | ErrorKind::NotFound | ErrorKind::ConnectionReset
+                ) =>
+            {
+                Ok(None)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+impl Connection {
+    fn send(&mut self, input: &[u8]) -> Result<(), std::io::Error> {
+        match self {
+            Connection::Tcp(stream) => stream.write_all(input),
+            Connection::Udp(socket) => socket.send(input).map(|_| ()),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.write_all(input),
+        }
+    }
+
+    /// Tells the target that no more input follows
+    fn finish(&mut self) -> Result<(), std::io::Error> {
+        match self {
+            Connection::Tcp(stream) => stream.shutdown(Shutdown::Write),
+            Connection::Udp(_) => Ok(()),
+            #[cfg(unix)]
+            Connection::Unix(stream) => stream.shutdown(Shutdown::Write),
+        }
+    }
+
+    /// Reads until the target closes the connection or `timeout` passed.
+    /// If `until_quiet`, the `timeout` starts over whenever something was read.
+    fn receive(&mut self, timeout: Duration, until_quiet: bool) -> Result<Vec<u8>, Error> {
+        let mut deadline = Instant::now() + timeout;
+        let mut response = Vec::new();
+        let mut buf = vec![0; MAX_DATAGRAM_SIZE];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            let read = match self {
+                Connection::Tcp(stream) => {
+                    stream.set_read_timeout(Some(remaining))?;
+                    stream.read(&mut buf)
+                }
+                Connection::Udp(socket) => {
+                    socket.set_read_timeout(Some(remaining))?;
+                    socket.recv(&mut buf)
+                }
+                #[cfg(unix)]
+                Connection::Unix(stream) => {
+                    stream.set_read_timeout(Some(remaining))?;
+                    stream.read(&mut buf)
+                }
+            };
+            match read {
+                Ok(0) => break,
+                Ok(len) => {
+                    response.extend_from_slice(&buf[..len]);
+                    if until_quiet {
+                        deadline = Instant::now() + timeout;
+                    }
+                }
+                Err(err)
+                    if matches!(
+                        err.kind(),
+                        ErrorKind::WouldBlock
+                            | ErrorKind::TimedOut
+                            | ErrorKind::ConnectionReset
+                            | ErrorKind::ConnectionRefused
+                    ) =>
+                {
+                    break
+                }
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+    use std::{io::Read, net::TcpListener, thread, time::Duration};
+
+    use super::SocketInput;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_tcp_delivery() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = Vec::new();
+            stream.read_to_end(&mut received).unwrap();
+            std::io::Write::write_all(&mut stream, &received[..2]).unwrap();
+            received
+        });
+
+        let socket_input = SocketInput::tcp(addr).capture_response(Duration::from_secs(5));
+        let response = socket_input.deliver(b"hello").unwrap();
+        assert_eq!(response.as_deref(), Some(&b"he"[..]));
+        assert_eq!(server.join().unwrap(), b"hello");
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_tcp_message_delivery() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 16];
+            // one reply per message, the client waits for it before sending the next one
+            for reply in [&b"220 hi\r\n"[..], b"331 pass?\r\n"] {
+                let len = stream.read(&mut buf).unwrap();
+                assert!(len > 0);
+                std::io::Write::write_all(&mut stream, reply).unwrap();
+            }
+            let mut rest = Vec::new();
+            stream.read_to_end(&mut rest).unwrap();
+            std::io::Write::write_all(&mut stream, b"230 ok\r\n").unwrap();
+            rest
+        });
+
+        let socket_input = SocketInput::tcp(addr).capture_response(Duration::from_millis(500));
+        let responses = socket_input
+            .deliver_messages(&[&b"HELO"[..], b"USER", b"PASS"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            responses,
+            [&b"220 hi\r\n"[..], b"331 pass?\r\n", b"230 ok\r\n"]
+        );
+        assert_eq!(server.join().unwrap(), b"PASS");
+    }
+}
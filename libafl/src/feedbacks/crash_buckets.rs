@@ -0,0 +1,674 @@
+//! Crash triage: the [`CrashBucketFeedback`] groups solutions into buckets by a configurable [`CrashSignature`],
+//! and only keeps the first input of each bucket while counting how often the bucket was hit.
+
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt::Debug, marker::PhantomData, time::Duration};
+
+#[cfg(feature = "regex")]
+use ahash::RandomState;
+use hashbrown::HashMap;
+use libafl_bolts::{
+    current_time,
+    tuples::{Handle, Handled, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "track_hit_feedbacks")]
+use crate::feedbacks::premature_last_result_err;
+#[cfg(feature = "regex")]
+use crate::observers::AsanBacktraceObserver;
+use crate::{
+    corpus::{Corpus, CorpusId, Testcase},
+    events::{Event, EventFirer},
+    executors::ExitKind,
+    feedbacks::Feedback,
+    inputs::UsesInput,
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    observers::{ObserverWithHashField, ObserversTuple},
+    state::{HasSolutions, State},
+    Error, HasMetadata, HasNamedMetadata,
+};
+
+/// The prefix of the metadata names
+pub const CRASHBUCKETFEEDBACK_PREFIX: &str = "crashbucketfeedback_metadata_";
+
+/// The name of the user stat holding the number of crash buckets
+pub const CRASH_BUCKETS_STATS_NAME: &str = "crash_buckets";
+
+/// The name of the user stat holding the summary of the most frequently hit crash buckets
+pub const CRASH_BUCKET_SUMMARY_STATS_NAME: &str = "crash_bucket_summary";
+
+/// The number of buckets listed in the [`CRASH_BUCKET_SUMMARY_STATS_NAME`] user stat
+const SUMMARY_BUCKETS: usize = 8;
+
+/// Computes the signature a crash is bucketed by.
+/// Two crashes with the same signature are considered duplicates.
+pub trait CrashSignature<S>: Named
+where
+    S: State,
+{
+    /// Returns the signature of the last run, or `None` if none could be computed
+    fn signature<OT>(&self, observers: &OT, exit_kind: &ExitKind) -> Result<Option<u64>, Error>
+    where
+        OT: ObserversTuple<S>;
+}
+
+/// Buckets crashes by the hash of an [`ObserverWithHashField`], such as a [`crate::observers::BacktraceObserver`]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HashSignature<O> {
+    o_ref: Handle<O>,
+}
+
+impl<O> HashSignature<O>
+where
+    O: Named,
+{
+    /// Creates a new [`HashSignature`] for the given observer
+    #[must_use]
+    pub fn new(observer: &O) -> Self {
+        Self {
+            o_ref: observer.handle(),
+        }
+    }
+}
+
+impl<O> Named for HashSignature<O> {
+    fn name(&self) -> &Cow<'static, str> {
+        self.o_ref.name()
+    }
+}
+
+impl<O, S> CrashSignature<S> for HashSignature<O>
+where
+    O: ObserverWithHashField + Named,
+    S: State,
+{
+    fn signature<OT>(&self, observers: &OT, _exit_kind: &ExitKind) -> Result<Option<u64>, Error>
+    where
+        OT: ObserversTuple<S>,
+    {
+        let observer = observers
+            .get(&self.o_ref)
+            .ok_or_else(|| Error::key_not_found("HashSignature observer not found".to_string()))?;
+        Ok(observer.hash())
+    }
+}
+
+/// Buckets crashes by the ASAN report of an [`AsanBacktraceObserver`]:
+/// the bug class (e.g. `heap-buffer-overflow`), the innermost `top_frames` frames, or both.
+#[cfg(feature = "regex")]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AsanReportSignature {
+    o_ref: Handle<AsanBacktraceObserver>,
+    top_frames: usize,
+    with_class: bool,
+}
+
+#[cfg(feature = "regex")]
+impl AsanReportSignature {
+    /// Buckets by the bug class and the innermost `top_frames` frames of the report
+    #[must_use]
+    pub fn new(observer: &AsanBacktraceObserver, top_frames: usize) -> Self {
+        Self {
+            o_ref: observer.handle(),
+            top_frames,
+            with_class: true,
+        }
+    }
+
+    /// Buckets by the bug class of the report only
+    #[must_use]
+    pub fn class_only(observer: &AsanBacktraceObserver) -> Self {
+        Self::new(observer, 0)
+    }
+
+    /// Buckets by the innermost `top_frames` frames of the report only
+    #[must_use]
+    pub fn frames_only(observer: &AsanBacktraceObserver, top_frames: usize) -> Self {
+        Self {
+            o_ref: observer.handle(),
+            top_frames,
+            with_class: false,
+        }
+    }
+}
+
+#[cfg(feature = "regex")]
+impl Named for AsanReportSignature {
+    fn name(&self) -> &Cow<'static, str> {
+        self.o_ref.name()
+    }
+}
+
+#[cfg(feature = "regex")]
+impl<S> CrashSignature<S> for AsanReportSignature
+where
+    S: State,
+{
+    fn signature<OT>(&self, observers: &OT, _exit_kind: &ExitKind) -> Result<Option<u64>, Error>
+    where
+        OT: ObserversTuple<S>,
+    {
+        let observer = observers.get(&self.o_ref).ok_or_else(|| {
+            Error::key_not_found("AsanReportSignature observer not found".to_string())
+        })?;
+        let class = if self.with_class {
+            observer.report_class()
+        } else {
+            None
+        };
+        let frames = observer.frames();
+        let frames = &frames[..frames.len().min(self.top_frames)];
+        if class.is_none() && frames.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(
+            RandomState::with_seeds(0, 0, 0, 0).hash_one((class, frames)),
+        ))
+    }
+}
+
+/// Buckets crashes of an [`crate::executors::InProcessExecutor`] by the program counter they faulted at,
+/// as read by [`libafl_bolts::minibsod::fault_pc`] in the crash handler.
+#[cfg(unix)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct FaultPcSignature;
+
+#[cfg(unix)]
+impl FaultPcSignature {
+    /// Creates a new [`FaultPcSignature`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[cfg(unix)]
+impl Named for FaultPcSignature {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("FaultPcSignature");
+        &NAME
+    }
+}
+
+#[cfg(unix)]
+impl<S> CrashSignature<S> for FaultPcSignature
+where
+    S: State,
+{
+    fn signature<OT>(&self, _observers: &OT, exit_kind: &ExitKind) -> Result<Option<u64>, Error>
+    where
+        OT: ObserversTuple<S>,
+    {
+        if *exit_kind != ExitKind::Crash {
+            return Ok(None);
+        }
+        Ok(crate::executors::hooks::inprocess::inprocess_crash_pc())
+    }
+}
+
+/// A bucket of crashes sharing the same signature
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CrashBucket {
+    /// How many crashes fell into this bucket
+    pub count: u64,
+    /// When the first crash of this bucket was seen
+    pub first_seen: Duration,
+    /// When the last crash of this bucket was seen
+    pub last_seen: Duration,
+    /// The id of the solution representing this bucket, once it was looked up in the solutions
+    pub solution: Option<CorpusId>,
+}
+
+/// The per-solution metadata of [`CrashBucketFeedback`], updated whenever a duplicate hits the bucket
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CrashBucketMetadata {
+    /// The signature of the bucket
+    pub signature: u64,
+    /// How many crashes fell into this bucket
+    pub count: u64,
+    /// When the first crash of this bucket was seen
+    pub first_seen: Duration,
+    /// When the last crash of this bucket was seen
+    pub last_seen: Duration,
+}
+
+libafl_bolts::impl_serdeany!(CrashBucketMetadata);
+
+/// The state of [`CrashBucketFeedback`], holding all buckets seen so far
+#[derive(Default, Serialize, Deserialize, Clone, Debug)]
+#[allow(clippy::unsafe_derive_deserialize)]
+pub struct CrashBucketsMetadata {
+    /// The buckets, by signature
+    pub buckets: HashMap<u64, CrashBucket>,
+}
+
+libafl_bolts::impl_serdeany!(CrashBucketsMetadata);
+
+impl CrashBucketsMetadata {
+    /// Create a new [`CrashBucketsMetadata`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of distinct buckets
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Returns `true` if no crash was bucketed yet
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+
+    /// The total number of crashes over all buckets, including duplicates
+    #[must_use]
+    pub fn total_crashes(&self) -> u64 {
+        self.buckets.values().map(|b| b.count).sum()
+    }
+
+    /// All buckets with their signature, the most frequently hit first
+    #[must_use]
+    pub fn summary(&self) -> Vec<(u64, &CrashBucket)> {
+        let mut summary: Vec<_> = self.buckets.iter().map(|(s, b)| (*s, b)).collect();
+        summary.sort_by(|a, b| b.1.count.cmp(&a.1.count).then(a.0.cmp(&b.0)));
+        summary
+    }
+
+    /// A one-line summary of the `max_buckets` most frequently hit buckets, e.g. `0000000000001a2b: 12, ...`
+    #[must_use]
+    pub fn summary_line(&self, max_buckets: usize) -> String {
+        let summary = self.summary();
+        let mut entries: Vec<String> = summary
+            .iter()
+            .take(max_buckets)
+            .map(|(signature, bucket)| format!("{signature:016x}: {}", bucket.count))
+            .collect();
+        if summary.len() > max_buckets {
+            entries.push("...".to_string());
+        }
+        entries.join(", ")
+    }
+
+    /// Records a crash with the given signature at time `now`.
+    /// Returns `true` if this opened a new bucket.
+    pub fn record(&mut self, signature: u64, now: Duration) -> bool {
+        if let Some(bucket) = self.buckets.get_mut(&signature) {
+            bucket.count += 1;
+            bucket.last_seen = now;
+            false
+        } else {
+            self.buckets.insert(
+                signature,
+                CrashBucket {
+                    count: 1,
+                    first_seen: now,
+                    last_seen: now,
+                    solution: None,
+                },
+            );
+            true
+        }
+    }
+}
+
+/// A [`CrashBucketFeedback`] buckets crashes by a [`CrashSignature`] and only considers crashes opening a new bucket interesting.
+///
+/// Duplicates update the [`CrashBucketMetadata`] of the solution representing their bucket instead.
+/// A bucket is only opened once its first crash is actually added to the solutions.
+/// Combine it with a feedback deciding what a crash is, e.g. `feedback_and_fast!(CrashFeedback::new(), CrashBucketFeedback::new(sig))`,
+/// so that only the duplicates of actual crashes are counted.
+///
+/// The number of buckets and the most frequently hit ones are reported to the monitors as the
+/// [`CRASH_BUCKETS_STATS_NAME`] and [`CRASH_BUCKET_SUMMARY_STATS_NAME`] user stats.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CrashBucketFeedback<SIG, S> {
+    name: Cow<'static, str>,
+    signature: SIG,
+    /// The signature of the last run, if it opened a new bucket
+    last_new_signature: Option<u64>,
+    #[cfg(feature = "track_hit_feedbacks")]
+    // The previous run's result of `Self::is_interesting`
+    last_result: Option<bool>,
+    phantom: PhantomData<S>,
+}
+
+impl<SIG, S> Feedback<S> for CrashBucketFeedback<SIG, S>
+where
+    SIG: CrashSignature<S>,
+    S: State + HasNamedMetadata + HasSolutions,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.add_named_metadata(&self.name, CrashBucketsMetadata::new());
+        Ok(())
+    }
+
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        _input: &<S as UsesInput>::Input,
+        observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = S>,
+        OT: ObserversTuple<S>,
+    {
+        self.last_new_signature = None;
+        let signature = if *exit_kind == ExitKind::Ok {
+            None
+        } else {
+            self.signature.signature(observers, exit_kind)?
+        };
+
+        let res = if let Some(signature) = signature {
+            let buckets = state
+                .named_metadata_map_mut()
+                .get_mut::<CrashBucketsMetadata>(&self.name)
+                .unwrap();
+            if buckets.buckets.contains_key(&signature) {
+                buckets.record(signature, current_time());
+                self.update_solution(state, signature)?;
+                self.fire_stats(state, manager)?;
+                false
+            } else {
+                // The bucket is only opened in `append_metadata`, once we know the crash is kept
+                self.last_new_signature = Some(signature);
+                true
+            }
+        } else {
+            false
+        };
+
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+
+    fn append_metadata<EM, OT>(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<<S as UsesInput>::Input>,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<S>,
+        EM: EventFirer<State = S>,
+    {
+        let Some(signature) = self.last_new_signature.take() else {
+            return Ok(());
+        };
+        let buckets = state
+            .named_metadata_map_mut()
+            .get_mut::<CrashBucketsMetadata>(&self.name)
+            .unwrap();
+        buckets.record(signature, current_time());
+        let bucket = &buckets.buckets[&signature];
+        testcase.add_metadata(CrashBucketMetadata {
+            signature,
+            count: bucket.count,
+            first_seen: bucket.first_seen,
+            last_seen: bucket.last_seen,
+        });
+        self.fire_stats(state, manager)
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &S::Input) -> Result<(), Error> {
+        self.last_new_signature = None;
+        Ok(())
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+}
+
+impl<SIG, S> CrashBucketFeedback<SIG, S>
+where
+    S: State + HasNamedMetadata + HasSolutions,
+{
+    /// Updates the [`CrashBucketMetadata`] of the solution representing the bucket of `signature`
+    fn update_solution(&self, state: &mut S, signature: u64) -> Result<(), Error> {
+        let represents = |state: &S, id: CorpusId| {
+            state.solutions().get(id).is_ok_and(|testcase| {
+                testcase
+                    .borrow()
+                    .metadata::<CrashBucketMetadata>()
+                    .is_ok_and(|meta| meta.signature == signature)
+            })
+        };
+
+        let bucket = &state
+            .named_metadata_map()
+            .get::<CrashBucketsMetadata>(&self.name)
+            .unwrap()
+            .buckets[&signature];
+        let (mut solution, count, last_seen) = (bucket.solution, bucket.count, bucket.last_seen);
+        // The id of a solution is only known after it was added, so look it up on the first duplicate
+        if !solution.is_some_and(|id| represents(state, id)) {
+            solution = None;
+            let mut id = state.solutions().last();
+            while let Some(cur) = id {
+                if represents(state, cur) {
+                    solution = Some(cur);
+                    break;
+                }
+                id = state.solutions().prev(cur);
+            }
+            state
+                .named_metadata_map_mut()
+                .get_mut::<CrashBucketsMetadata>(&self.name)
+                .unwrap()
+                .buckets
+                .get_mut(&signature)
+                .unwrap()
+                .solution = solution;
+        }
+
+        if let Some(id) = solution {
+            let mut testcase = state.solutions().get(id)?.borrow_mut();
+            let meta = testcase.metadata_mut::<CrashBucketMetadata>()?;
+            meta.count = count;
+            meta.last_seen = last_seen;
+        }
+        Ok(())
+    }
+
+    /// Reports the number of buckets and the most frequently hit ones to the monitors
+    fn fire_stats<EM>(&self, state: &mut S, manager: &mut EM) -> Result<(), Error>
+    where
+        EM: EventFirer<State = S>,
+    {
+        let buckets = state
+            .named_metadata_map()
+            .get::<CrashBucketsMetadata>(&self.name)
+            .unwrap();
+        let (bucket_count, summary) = (buckets.len(), buckets.summary_line(SUMMARY_BUCKETS));
+        manager.fire(
+            state,
+            Event::UpdateUserStats {
+                name: Cow::from(CRASH_BUCKETS_STATS_NAME),
+                value: UserStats::new(
+                    UserStatsValue::Number(bucket_count as u64),
+                    AggregatorOps::Sum,
+                ),
+                phantom: PhantomData,
+            },
+        )?;
+        manager.fire(
+            state,
+            Event::UpdateUserStats {
+                name: Cow::from(CRASH_BUCKET_SUMMARY_STATS_NAME),
+                value: UserStats::new(UserStatsValue::String(summary.into()), AggregatorOps::None),
+                phantom: PhantomData,
+            },
+        )
+    }
+}
+
+impl<SIG, S> Named for CrashBucketFeedback<SIG, S> {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<SIG, S> CrashBucketFeedback<SIG, S>
+where
+    SIG: Named,
+{
+    /// Returns a new [`CrashBucketFeedback`] bucketing crashes by the given [`CrashSignature`]
+    #[must_use]
+    pub fn new(signature: SIG) -> Self {
+        Self {
+            name: Cow::from(CRASHBUCKETFEEDBACK_PREFIX.to_string() + signature.name()),
+            signature,
+            last_new_signature: None,
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The signature this feedback buckets crashes by
+    pub fn signature(&self) -> &SIG {
+        &self.signature
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{borrow::Cow, vec};
+    use core::time::Duration;
+
+    use libafl_bolts::{rands::StdRand, Named};
+
+    use super::{CrashBucketFeedback, CrashBucketMetadata, CrashBucketsMetadata, CrashSignature};
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::ExitKind,
+        feedback_and,
+        feedbacks::{ConstFeedback, Feedback},
+        inputs::BytesInput,
+        observers::ObserversTuple,
+        state::{HasSolutions, State, StdState},
+        Error, HasMetadata, HasNamedMetadata,
+    };
+
+    struct ConstSignature(u64);
+
+    impl Named for ConstSignature {
+        fn name(&self) -> &Cow<'static, str> {
+            static NAME: Cow<'static, str> = Cow::Borrowed("ConstSignature");
+            &NAME
+        }
+    }
+
+    impl<S> CrashSignature<S> for ConstSignature
+    where
+        S: State,
+    {
+        fn signature<OT>(
+            &self,
+            _observers: &OT,
+            _exit_kind: &ExitKind,
+        ) -> Result<Option<u64>, Error>
+        where
+            OT: ObserversTuple<S>,
+        {
+            Ok(Some(self.0))
+        }
+    }
+
+    #[test]
+    fn test_crash_buckets_record() {
+        let mut buckets = CrashBucketsMetadata::new();
+        assert!(buckets.record(1, Duration::from_secs(1)));
+        assert!(buckets.record(2, Duration::from_secs(2)));
+        assert!(!buckets.record(1, Duration::from_secs(3)));
+        assert!(!buckets.record(1, Duration::from_secs(4)));
+
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets.total_crashes(), 4);
+
+        let summary = buckets.summary();
+        assert_eq!(summary[0].0, 1);
+        assert_eq!(summary[0].1.count, 3);
+        assert_eq!(summary[0].1.first_seen, Duration::from_secs(1));
+        assert_eq!(summary[0].1.last_seen, Duration::from_secs(4));
+        assert_eq!(summary[1].0, 2);
+
+        assert_eq!(buckets.summary_line(1), "0000000000000001: 3, ...");
+    }
+
+    #[test]
+    fn test_rejected_crash_opens_no_bucket() {
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut mgr = NopEventManager::new();
+        let input = BytesInput::new(vec![0]);
+
+        let mut rejecting = feedback_and!(
+            ConstFeedback::new(false),
+            CrashBucketFeedback::new(ConstSignature(7))
+        );
+        rejecting.init_state(&mut state).unwrap();
+        assert!(!rejecting
+            .is_interesting(&mut state, &mut mgr, &input, &(), &ExitKind::Crash)
+            .unwrap());
+        rejecting.discard_metadata(&mut state, &input).unwrap();
+        let name = rejecting.second.name().clone();
+        assert!(state
+            .named_metadata::<CrashBucketsMetadata>(&name)
+            .unwrap()
+            .is_empty());
+
+        // The same crash is kept once another feedback accepts it, and opens the bucket then
+        let mut accepting = feedback_and!(
+            ConstFeedback::new(true),
+            CrashBucketFeedback::new(ConstSignature(7))
+        );
+        assert!(accepting
+            .is_interesting(&mut state, &mut mgr, &input, &(), &ExitKind::Crash)
+            .unwrap());
+        let mut testcase = Testcase::new(input.clone());
+        accepting
+            .append_metadata(&mut state, &mut mgr, &(), &mut testcase)
+            .unwrap();
+        let id = state.solutions_mut().add(testcase).unwrap();
+
+        // A duplicate is counted in the metadata of the solution
+        assert!(!accepting
+            .is_interesting(&mut state, &mut mgr, &input, &(), &ExitKind::Crash)
+            .unwrap());
+        accepting.discard_metadata(&mut state, &input).unwrap();
+        let buckets = state.named_metadata::<CrashBucketsMetadata>(&name).unwrap();
+        assert_eq!(buckets.buckets[&7].count, 2);
+        assert_eq!(buckets.buckets[&7].solution, Some(id));
+        let solution = state.solutions().get(id).unwrap().borrow();
+        assert_eq!(solution.metadata::<CrashBucketMetadata>().unwrap().count, 2);
+    }
+}
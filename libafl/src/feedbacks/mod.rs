@@ -14,6 +14,8 @@ use core::{
 
 #[cfg(feature = "std")]
 pub use concolic::ConcolicFeedback;
+#[cfg(feature = "std")]
+pub use crash_buckets::*;
 pub use differential::DiffFeedback;
 use libafl_bolts::{
     tuples::{Handle, Handled, MatchNameRef},
@@ -32,14 +34,16 @@ use serde::{Deserialize, Serialize};
 use crate::{
     corpus::Testcase,
     events::EventFirer,
-    executors::ExitKind,
+    executors::{CustomExitKind, ExitKind},
     observers::{ObserversTuple, TimeObserver},
     state::State,
-    Error,
+    Error, HasMetadata,
 };
 #[cfg(feature = "std")]
 pub mod concolic;
 #[cfg(feature = "std")]
+pub mod crash_buckets;
+#[cfg(feature = "std")]
 /// The module for `CustomFilenameToTestcaseFeedback`
 pub mod custom_filename;
 pub mod differential;
@@ -1123,6 +1127,123 @@ impl<T> FeedbackFactory<DiffExitKindFeedback, T> for DiffExitKindFeedback {
     }
 }
 
+/// A [`CustomExitKindFeedback`] reports as interesting if the target reported an [`ExitKind::Custom`].
+/// It can either match any [`CustomExitKind`], or only a specific one.
+///
+/// The matched [`ExitKind`] is added to the metadata of the resulting [`Testcase`].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CustomExitKindFeedback {
+    /// The custom kind to match, or `None` to match any custom kind
+    kind: Option<CustomExitKind>,
+    /// The custom kind seen in the last run
+    last_kind: Option<CustomExitKind>,
+    #[cfg(feature = "track_hit_feedbacks")]
+    // The previous run's result of `Self::is_interesting`
+    last_result: Option<bool>,
+}
+
+impl<S> Feedback<S> for CustomExitKindFeedback
+where
+    S: State,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &S::Input,
+        _observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error>
+    where
+        EM: EventFirer<State = S>,
+        OT: ObserversTuple<S>,
+    {
+        self.last_kind = match exit_kind {
+            ExitKind::Custom(custom) if self.kind.map_or(true, |kind| kind == *custom) => {
+                Some(*custom)
+            }
+            _ => None,
+        };
+        let res = self.last_kind.is_some();
+        #[cfg(feature = "track_hit_feedbacks")]
+        {
+            self.last_result = Some(res);
+        }
+        Ok(res)
+    }
+
+    #[cfg(feature = "track_hit_feedbacks")]
+    fn last_result(&self) -> Result<bool, Error> {
+        self.last_result.ok_or(premature_last_result_err())
+    }
+
+    fn append_metadata<EM, OT>(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<S::Input>,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<S>,
+        EM: EventFirer<State = S>,
+    {
+        if let Some(custom) = self.last_kind.take() {
+            testcase.add_metadata(ExitKind::Custom(custom));
+        }
+        Ok(())
+    }
+
+    fn discard_metadata(&mut self, _state: &mut S, _input: &S::Input) -> Result<(), Error> {
+        self.last_kind = None;
+        Ok(())
+    }
+}
+
+impl Named for CustomExitKindFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("CustomExitKindFeedback");
+        &NAME
+    }
+}
+
+impl CustomExitKindFeedback {
+    /// Returns a new [`CustomExitKindFeedback`], matching any [`CustomExitKind`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            kind: None,
+            last_kind: None,
+            #[cfg(feature = "track_hit_feedbacks")]
+            last_result: None,
+        }
+    }
+
+    /// Returns a new [`CustomExitKindFeedback`], matching only the given [`CustomExitKind`].
+    #[must_use]
+    pub fn with_kind(kind: CustomExitKind) -> Self {
+        Self {
+            kind: Some(kind),
+            ..Self::new()
+        }
+    }
+}
+
+impl Default for CustomExitKindFeedback {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A feedback factory for custom exit kind feedbacks
+impl<T> FeedbackFactory<CustomExitKindFeedback, T> for CustomExitKindFeedback {
+    fn create_feedback(&self, _ctx: &T) -> CustomExitKindFeedback {
+        self.clone()
+    }
+}
+
 /// Nop feedback that annotates execution time in the new testcase, if any
 /// for this Feedback, the testcase is never interesting (use with an OR).
 /// It decides, if the given [`TimeObserver`] value of a run is interesting.
@@ -1276,3 +1397,74 @@ impl From<ConstFeedback> for bool {
 pub(crate) fn premature_last_result_err() -> Error {
     Error::illegal_state("last_result called before Feedback was run")
 }
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::{ConstFeedback, CustomExitKindFeedback, Feedback};
+    use crate::{
+        corpus::{InMemoryCorpus, Testcase},
+        events::NopEventManager,
+        executors::{CustomExitKind, ExitKind},
+        inputs::BytesInput,
+        state::StdState,
+        HasMetadata,
+    };
+
+    #[test]
+    fn test_custom_exit_kind_feedback() {
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut mgr = NopEventManager::new();
+        let input = BytesInput::new(vec![]);
+        let leak = ExitKind::Custom(CustomExitKind::new(1));
+        let assertion = ExitKind::Custom(CustomExitKind::new(2));
+
+        let mut any = CustomExitKindFeedback::new();
+        let mut only_leaks = CustomExitKindFeedback::with_kind(CustomExitKind::new(1));
+        for (exit_kind, any_expected, leak_expected) in [
+            (ExitKind::Ok, false, false),
+            (ExitKind::Crash, false, false),
+            (leak, true, true),
+            (assertion, true, false),
+        ] {
+            assert_eq!(
+                any.is_interesting(&mut state, &mut mgr, &input, &(), &exit_kind)
+                    .unwrap(),
+                any_expected
+            );
+            assert_eq!(
+                only_leaks
+                    .is_interesting(&mut state, &mut mgr, &input, &(), &exit_kind)
+                    .unwrap(),
+                leak_expected
+            );
+        }
+
+        // The last matched kind ends up in the metadata of the testcase
+        let mut testcase = Testcase::new(input.clone());
+        any.append_metadata(&mut state, &mut mgr, &(), &mut testcase)
+            .unwrap();
+        assert_eq!(*testcase.metadata::<ExitKind>().unwrap(), assertion);
+
+        // A discarded run does not leave its kind behind for the next testcase
+        assert!(only_leaks
+            .is_interesting(&mut state, &mut mgr, &input, &(), &leak)
+            .unwrap());
+        only_leaks.discard_metadata(&mut state, &input).unwrap();
+        let mut testcase = Testcase::new(input);
+        only_leaks
+            .append_metadata(&mut state, &mut mgr, &(), &mut testcase)
+            .unwrap();
+        assert!(!testcase.has_metadata::<ExitKind>());
+    }
+}
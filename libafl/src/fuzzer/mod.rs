@@ -26,6 +26,9 @@ use crate::{
 #[cfg(feature = "introspection")]
 use crate::{monitors::PerfFeature, state::HasClientPerfMonitor};
 
+#[cfg(feature = "std")]
+pub mod replay;
+
 /// Send a monitor update all 15 (or more) seconds
 const STATS_TIMEOUT_DEFAULT: Duration = Duration::from_secs(15);
 
@@ -143,6 +146,19 @@ pub trait Evaluator<E, EM>: UsesState {
         self.evaluate_input_events(state, executor, manager, input, true)
     }
 
+    /// Runs the input and triggers observers and feedback, like [`Evaluator::evaluate_input`],
+    /// but borrows the input, so that the caller can keep using it, e.g., to revert in-place mutations.
+    /// Implementations should only clone the input if it is added to a corpus.
+    fn evaluate_input_ref(
+        &mut self,
+        state: &mut Self::State,
+        executor: &mut E,
+        manager: &mut EM,
+        input: &<Self::State as UsesInput>::Input,
+    ) -> Result<(ExecuteInputResult, Option<CorpusId>), Error> {
+        self.evaluate_input(state, executor, manager, input.clone())
+    }
+
     /// Runs the input and triggers observers and feedback,
     /// returns if is interesting an (option) the index of the new testcase in the corpus
     /// This version has a boolean to decide if send events to the manager.
@@ -508,6 +524,7 @@ where
                         state,
                         Event::Objective {
                             objective_size: state.solutions().count(),
+                            exit_kind: *exit_kind,
                             executions,
                             time: current_time(),
                         },
@@ -573,6 +590,38 @@ where
     ) -> Result<(ExecuteInputResult, Option<CorpusId>), Error> {
         self.evaluate_input_with_observers(state, executor, manager, input, send_events)
     }
+
+    /// Process one input without taking ownership, cloning it only if it is added to a corpus
+    fn evaluate_input_ref(
+        &mut self,
+        state: &mut Self::State,
+        executor: &mut E,
+        manager: &mut EM,
+        input: &<Self::State as UsesInput>::Input,
+    ) -> Result<(ExecuteInputResult, Option<CorpusId>), Error> {
+        let exit_kind = self.execute_input(state, executor, manager, input)?;
+        let observers = executor.observers();
+
+        self.scheduler.on_evaluation(state, input, &*observers)?;
+
+        let exec_res = self.execute_no_process(state, manager, input, &*observers, &exit_kind)?;
+        if exec_res == ExecuteInputResult::None {
+            self.feedback_mut().discard_metadata(state, input)?;
+            self.objective_mut().discard_metadata(state, input)?;
+            return Ok((exec_res, None));
+        }
+        let corpus_idx = self.process_execution(
+            state,
+            manager,
+            input.clone(),
+            &exec_res,
+            &*observers,
+            &exit_kind,
+            true,
+        )?;
+        Ok((exec_res, corpus_idx))
+    }
+
     fn add_disabled_input(
         &mut self,
         state: &mut Self::State,
@@ -625,6 +674,7 @@ where
                 state,
                 Event::Objective {
                     objective_size: state.solutions().count(),
+                    exit_kind,
                     executions,
                     time: current_time(),
                 },
@@ -715,6 +765,8 @@ where
             idx // we are resuming
         } else {
             let idx = self.scheduler.next(state)?;
+            #[cfg(feature = "std")]
+            let idx = replay::journal_scheduled(&mut self.scheduler, state, idx)?;
             state.set_corpus_idx(idx)?; // set up for resume
             idx
         };
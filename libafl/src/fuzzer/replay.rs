@@ -0,0 +1,328 @@
+//! Record a fuzzing session to a journal, and replay it deterministically.
+//!
+//! Recording starts with [`SessionJournal::start_recording`], which seeds the [`Rand`] and writes the seed to the journal.
+//! From then on, [`StdFuzzer`] journals every corpus id its scheduler picks,
+//! a [`crate::mutators::JournalingScheduledMutator`] journals the mutations it stacks,
+//! and a [`crate::events::hooks::journal::JournalEventHook`] journals the testcases imported from other clients.
+//! [`SessionJournal::start_replay`] loads such a journal, and [`StdFuzzer::replay`] then takes the same decisions again.
+//!
+//! The journal overrides decisions that depend on timing, such as those of power schedules,
+//! everything else is reproduced by the seeded [`Rand`]. Replaying only works for a single client,
+//! a deterministic target, and with the same fuzzer setup and initial corpus as the recording.
+
+use alloc::{collections::VecDeque, string::String, vec::Vec};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use libafl_bolts::{impl_serdeany, rands::Rand};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::CorpusId,
+    events::ProgressReporter,
+    fuzzer::{Evaluator, Fuzzer, StdFuzzer, STATS_TIMEOUT_DEFAULT},
+    inputs::UsesInput,
+    mutators::MutationId,
+    schedulers::Scheduler,
+    stages::StagesTuple,
+    state::{HasCorpus, HasExecutions, HasLastReportTime, HasRand, UsesState},
+    Error, HasMetadata,
+};
+
+/// A single decision taken during a fuzzing session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JournalEntry {
+    /// The seed of the [`Rand`], always the first entry
+    Seed(u64),
+    /// The corpus id the scheduler picked
+    Scheduled(CorpusId),
+    /// The mutations stacked by one call of a scheduled mutator
+    Mutations(Vec<MutationId>),
+    /// A testcase imported from another client, serialized with `postcard`
+    Imported(Vec<u8>),
+}
+
+/// Whether a [`SessionJournal`] is written or read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JournalMode {
+    /// Decisions are appended to the journal
+    Record,
+    /// Decisions are taken from the journal
+    Replay,
+}
+
+/// A state metadata recording or replaying the decisions of a fuzzing session, one JSON line per [`JournalEntry`]
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct SessionJournal {
+    mode: JournalMode,
+    path: PathBuf,
+    /// The entries left to replay
+    pending: VecDeque<JournalEntry>,
+    /// Entries are written right away, so the journal is complete even if the fuzzer crashes.
+    /// Reopened after a restart.
+    #[serde(skip)]
+    file: Option<File>,
+}
+
+impl_serdeany!(SessionJournal);
+
+impl SessionJournal {
+    /// Starts recording the session to a new journal at `path`, seeding the [`Rand`] of the state with `seed`.
+    /// Call this right after creating the state, before the initial inputs are loaded or generated.
+    pub fn start_recording<S, P>(state: &mut S, path: P, seed: u64) -> Result<(), Error>
+    where
+        S: HasRand + HasMetadata,
+        P: AsRef<Path>,
+    {
+        let mut journal = Self {
+            mode: JournalMode::Record,
+            path: path.as_ref().to_path_buf(),
+            pending: VecDeque::new(),
+            file: Some(File::create(path)?),
+        };
+        journal.record(&JournalEntry::Seed(seed))?;
+        state.rand_mut().set_seed(seed);
+        state.add_metadata(journal);
+        Ok(())
+    }
+
+    /// Starts replaying the journal at `path`, seeding the [`Rand`] of the state like the recorded session.
+    /// Call this right after creating the state, before the initial inputs are loaded or generated.
+    pub fn start_replay<S, P>(state: &mut S, path: P) -> Result<(), Error>
+    where
+        S: HasRand + HasMetadata,
+        P: AsRef<Path>,
+    {
+        let mut pending: VecDeque<JournalEntry> = Self::load(path.as_ref())?.into();
+        let Some(JournalEntry::Seed(seed)) = pending.pop_front() else {
+            return Err(Error::illegal_argument(format!(
+                "The journal {} does not start with a seed",
+                path.as_ref().display()
+            )));
+        };
+        state.rand_mut().set_seed(seed);
+        state.add_metadata(Self {
+            mode: JournalMode::Replay,
+            path: path.as_ref().to_path_buf(),
+            pending,
+            file: None,
+        });
+        Ok(())
+    }
+
+    /// Reads all entries of the journal at `path`
+    pub fn load(path: &Path) -> Result<Vec<JournalEntry>, Error> {
+        fs::read_to_string(path)?
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect()
+    }
+
+    /// Whether this journal is recorded or replayed
+    #[must_use]
+    pub fn mode(&self) -> JournalMode {
+        self.mode
+    }
+
+    /// Whether decisions are taken from this journal
+    #[must_use]
+    pub fn is_replaying(&self) -> bool {
+        self.mode == JournalMode::Replay
+    }
+
+    /// The path of the journal
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The number of entries left to replay
+    #[must_use]
+    pub fn remaining(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Appends an entry to the journal. Does nothing when replaying.
+    pub fn record(&mut self, entry: &JournalEntry) -> Result<(), Error> {
+        if self.is_replaying() {
+            return Ok(());
+        }
+        if self.file.is_none() {
+            self.file = Some(OpenOptions::new().append(true).open(&self.path)?);
+        }
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        // A single write per entry, so a crash never leaves half a line behind
+        self.file.as_mut().unwrap().write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    /// The scheduler picked `id`. Returns the id to fuzz: `id` itself when recording, the journaled one when replaying.
+    pub fn on_scheduled(&mut self, id: CorpusId) -> Result<CorpusId, Error> {
+        if !self.is_replaying() {
+            self.record(&JournalEntry::Scheduled(id))?;
+            return Ok(id);
+        }
+        match self.pending.pop_front() {
+            Some(JournalEntry::Scheduled(id)) => Ok(id),
+            entry => Err(Self::out_of_sync("a scheduled corpus id", entry)),
+        }
+    }
+
+    /// Takes the next stacked mutations to replay
+    pub fn replay_mutations(&mut self) -> Result<Vec<MutationId>, Error> {
+        match self.pending.pop_front() {
+            Some(JournalEntry::Mutations(ids)) => Ok(ids),
+            entry => Err(Self::out_of_sync("mutations", entry)),
+        }
+    }
+
+    /// Takes the next imported testcase to replay, if the next entry is one
+    pub fn replay_import(&mut self) -> Option<Vec<u8>> {
+        if !matches!(self.pending.front(), Some(JournalEntry::Imported(_))) {
+            return None;
+        }
+        match self.pending.pop_front() {
+            Some(JournalEntry::Imported(input)) => Some(input),
+            _ => None,
+        }
+    }
+
+    fn out_of_sync(expected: &str, found: Option<JournalEntry>) -> Error {
+        let found: String = match found {
+            Some(entry) => format!("{entry:?}"),
+            None => "the end of the journal".into(),
+        };
+        Error::illegal_state(format!(
+            "Replay diverged from the journal: expected {expected}, found {found}. Is the fuzzer set up like during the recording?"
+        ))
+    }
+}
+
+/// Journals the corpus id `scheduler` picked, or replaces it with the journaled one when replaying
+pub(crate) fn journal_scheduled<CS>(
+    scheduler: &mut CS,
+    state: &mut CS::State,
+    idx: CorpusId,
+) -> Result<CorpusId, Error>
+where
+    CS: Scheduler,
+    CS::State: HasCorpus + HasMetadata,
+{
+    let Ok(journal) = state.metadata_mut::<SessionJournal>() else {
+        return Ok(idx);
+    };
+    let journaled = journal.on_scheduled(idx)?;
+    if journaled != idx {
+        scheduler.set_current_scheduled(state, Some(journaled))?;
+    }
+    Ok(journaled)
+}
+
+impl<CS, F, OF, OT> StdFuzzer<CS, F, OF, OT>
+where
+    CS: Scheduler,
+    CS::State: HasCorpus + HasMetadata + HasExecutions + HasLastReportTime,
+{
+    /// Replays the [`SessionJournal`] started with [`SessionJournal::start_replay`], until it is exhausted.
+    /// Imported testcases are evaluated at the point they were imported during the recording.
+    pub fn replay<E, EM, ST>(
+        &mut self,
+        stages: &mut ST,
+        executor: &mut E,
+        state: &mut CS::State,
+        manager: &mut EM,
+    ) -> Result<(), Error>
+    where
+        Self: Fuzzer<E, EM, ST> + Evaluator<E, EM> + UsesState<State = CS::State>,
+        E: UsesState<State = CS::State>,
+        EM: ProgressReporter<State = CS::State>,
+        ST: StagesTuple<E, EM, CS::State, Self>,
+    {
+        if !matches!(state.metadata::<SessionJournal>(), Ok(journal) if journal.is_replaying()) {
+            return Err(Error::illegal_state(
+                "No journal to replay, call SessionJournal::start_replay first",
+            ));
+        }
+        loop {
+            manager.maybe_report_progress(state, STATS_TIMEOUT_DEFAULT)?;
+
+            while let Some(bytes) = state.metadata_mut::<SessionJournal>()?.replay_import() {
+                let input: <CS::State as UsesInput>::Input = postcard::from_bytes(&bytes)?;
+                self.evaluate_input(state, executor, manager, input)?;
+            }
+            if state.metadata::<SessionJournal>()?.remaining() == 0 {
+                log::info!("Replay finished");
+                return Ok(());
+            }
+            self.fuzz_one(stages, executor, state, manager)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use libafl_bolts::rands::{Rand, StdRand};
+
+    use super::{JournalEntry, SessionJournal};
+    use crate::{
+        corpus::{CorpusId, InMemoryCorpus},
+        feedbacks::ConstFeedback,
+        inputs::BytesInput,
+        state::{HasRand, StdState},
+        HasMetadata,
+    };
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_session_journal() {
+        let path = env::temp_dir().join(format!("libafl_journal_{}", std::process::id()));
+        let new_state = || {
+            let mut feedback = ConstFeedback::new(false);
+            let mut objective = ConstFeedback::new(false);
+            StdState::new(
+                StdRand::with_seed(0),
+                InMemoryCorpus::<BytesInput>::new(),
+                InMemoryCorpus::<BytesInput>::new(),
+                &mut feedback,
+                &mut objective,
+            )
+            .unwrap()
+        };
+
+        let mut recorded = new_state();
+        SessionJournal::start_recording(&mut recorded, &path, 1337).unwrap();
+        let journal = recorded.metadata_mut::<SessionJournal>().unwrap();
+        assert_eq!(journal.on_scheduled(CorpusId(3)).unwrap(), CorpusId(3));
+        journal
+            .record(&JournalEntry::Mutations(vec![1.into(), 4.into()]))
+            .unwrap();
+        journal.record(&JournalEntry::Imported(vec![0xaa])).unwrap();
+        journal.on_scheduled(CorpusId(0)).unwrap();
+
+        let mut replayed = new_state();
+        SessionJournal::start_replay(&mut replayed, &path).unwrap();
+        assert_eq!(replayed.rand_mut().next(), recorded.rand_mut().next());
+        let journal = replayed.metadata_mut::<SessionJournal>().unwrap();
+        assert_eq!(journal.remaining(), 4);
+        assert!(journal.replay_import().is_none());
+        // The replayed decision wins over the live one
+        assert_eq!(journal.on_scheduled(CorpusId(5)).unwrap(), CorpusId(3));
+        assert_eq!(journal.replay_mutations().unwrap(), [1.into(), 4.into()]);
+        assert_eq!(journal.replay_import(), Some(vec![0xaa]));
+        assert!(journal.replay_mutations().is_err());
+        assert_eq!(journal.remaining(), 0);
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,1625 @@
+//! Context-free grammars loaded directly from ANTLR4 (`.g4`) or EBNF grammar files.
+//!
+//! A [`Grammar`] can be turned into a Gramatron [`Automaton`] with [`Grammar::to_automaton`],
+//! or, with the `nautilus` feature, into a `NautilusContext` with `NautilusContext::from_grammar`,
+//! without converting the grammar to JSON or Python by hand, or running the `construct_automata` tool.
+
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+#[cfg(feature = "std")]
+use std::{fs, path::Path};
+
+use hashbrown::HashMap;
+use libafl_bolts::Error;
+
+use crate::generators::gramatron::{Automaton, Trigger};
+
+/// Character sets larger than this are sampled evenly, to keep the number of rules in check
+const MAX_CHARSET_LEN: u32 = 256;
+
+/// Gives up converting to Greibach normal form after this many alternatives
+const MAX_GNF_ALTERNATIVES: usize = 1 << 20;
+
+/// Gives up building an [`Automaton`] with more states than this
+const MAX_AUTOMATON_STATES: usize = 1 << 20;
+
+/// The characters `.` and negated sets like `~[a-z]` pick from: tab, newlines, and printable ASCII
+const PRINTABLE: [(u32, u32); 3] = [(0x09, 0x0a), (0x0d, 0x0d), (0x20, 0x7e)];
+
+/// A symbol on the right hand side of a [`GrammarRule`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum GrammarSymbol {
+    /// Bytes emitted as they are
+    Terminal(Vec<u8>),
+    /// A reference to the rules of the nonterminal with this name
+    NonTerminal(String),
+}
+
+/// One alternative of a nonterminal: the nonterminal expands to the `symbols`, in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrammarRule {
+    /// The name of the nonterminal
+    pub nonterminal: String,
+    /// The symbols it expands to, empty for the empty string
+    pub symbols: Vec<GrammarSymbol>,
+}
+
+/// A context-free grammar in plain BNF: nonterminals with alternatives of terminals and nonterminals.
+///
+/// The EBNF operators of the grammar files (groups, `?`, `*`, `+`, character sets) are rewritten into
+/// helper nonterminals named after the rule they appear in, e.g., `expr_star1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grammar {
+    start: String,
+    rules: Vec<GrammarRule>,
+}
+
+impl Grammar {
+    /// Creates an empty [`Grammar`] deriving from the nonterminal `start`
+    #[must_use]
+    pub fn new(start: &str) -> Self {
+        Self {
+            start: start.to_string(),
+            rules: Vec::new(),
+        }
+    }
+
+    /// Adds an alternative to the nonterminal `nonterminal`
+    pub fn add_rule(&mut self, nonterminal: &str, symbols: Vec<GrammarSymbol>) {
+        self.rules.push(GrammarRule {
+            nonterminal: nonterminal.to_string(),
+            symbols,
+        });
+    }
+
+    /// The start nonterminal
+    #[must_use]
+    pub fn start(&self) -> &str {
+        &self.start
+    }
+
+    /// Sets the start nonterminal, e.g., to fuzz a sub-language of the grammar
+    pub fn set_start(&mut self, start: &str) {
+        self.start = start.to_string();
+    }
+
+    /// All alternatives of all nonterminals, in the order of the grammar file
+    #[must_use]
+    pub fn rules(&self) -> &[GrammarRule] {
+        &self.rules
+    }
+
+    /// Parses an ANTLR4 grammar.
+    ///
+    /// The first parser rule is the start rule. Actions, predicates, labels, options and lexer modes are ignored.
+    /// If the grammar skips tokens, e.g., whitespace with `WS : [ \t\r\n]+ -> skip ;`,
+    /// parser rules put a space between their elements, so that the generated tokens stay apart.
+    /// For grammars split into a lexer and a parser grammar, pass both sources concatenated.
+    pub fn from_antlr4(src: &str) -> Result<Self, Error> {
+        let mut parser = Parser::new(src, Dialect::Antlr);
+        let (rules, declared_tokens) = parser.antlr_rules()?;
+        let spaced = rules.iter().any(|rule| rule.hidden);
+        let start = rules
+            .iter()
+            .find(|rule| !rule.lexer)
+            .or(rules.first())
+            .ok_or_else(|| Error::illegal_argument("The grammar has no rules"))?
+            .name
+            .clone();
+
+        let mut lowering = Lowering::new(&start, &rules);
+        for token in declared_tokens {
+            // tokens synthesized by the lexer, e.g., INDENT, have no rule
+            if lowering.taken.insert(token.clone()) {
+                lowering.grammar.add_rule(&token, Vec::new());
+            }
+        }
+        for rule in rules {
+            lowering.spaced = spaced && !rule.lexer;
+            lowering.alternatives(&rule.name, rule.expr);
+        }
+        lowering.grammar.check()?;
+        Ok(lowering.grammar)
+    }
+
+    /// Parses an EBNF grammar. The first rule is the start rule.
+    ///
+    /// Both common dialects are understood, decided by how each rule is defined:
+    /// - ISO 14977 rules, `name = a, [ b ], { c } | "d" ;`, with `(* comments *)`.
+    /// - W3C rules, `name ::= a b? c* [a-z] #x41 | 'd'`, also for BNF with `<angle brackets>`.
+    ///
+    /// Exceptions, `a - b`, are approximated by `a`.
+    pub fn from_ebnf(src: &str) -> Result<Self, Error> {
+        let mut parser = Parser::new(src, Dialect::Iso);
+        let rules = parser.ebnf_rules()?;
+        let start = rules
+            .first()
+            .ok_or_else(|| Error::illegal_argument("The grammar has no rules"))?
+            .name
+            .clone();
+
+        let mut lowering = Lowering::new(&start, &rules);
+        for rule in rules {
+            lowering.alternatives(&rule.name, rule.expr);
+        }
+        lowering.grammar.check()?;
+        Ok(lowering.grammar)
+    }
+
+    /// Loads a grammar file, parsed by [`Grammar::from_antlr4`] for `.g4` files, else by [`Grammar::from_ebnf`]
+    #[cfg(feature = "std")]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let src = fs::read_to_string(path)?;
+        if path.extension().is_some_and(|ext| ext == "g4") {
+            Self::from_antlr4(&src)
+        } else {
+            Self::from_ebnf(&src)
+        }
+    }
+
+    /// Returns an error if a nonterminal is referenced but has no rules,
+    /// or if a nonterminal reachable from the start never derives a string, e.g., `a : 'x' a ;`
+    pub(crate) fn check(&self) -> Result<(), Error> {
+        let defined: BTreeSet<&str> = self
+            .rules
+            .iter()
+            .map(|rule| rule.nonterminal.as_str())
+            .collect();
+        if !defined.contains(self.start.as_str()) {
+            return Err(Error::illegal_argument(format!(
+                "The start nonterminal {} has no rules",
+                self.start
+            )));
+        }
+        for rule in &self.rules {
+            for symbol in &rule.symbols {
+                if let GrammarSymbol::NonTerminal(name) = symbol {
+                    if !defined.contains(name.as_str()) {
+                        return Err(Error::illegal_argument(format!(
+                            "Nonterminal {name}, used in {}, has no rules",
+                            rule.nonterminal
+                        )));
+                    }
+                }
+            }
+        }
+
+        let reachable = self.reachable();
+        let mut productive: BTreeSet<&str> = BTreeSet::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for rule in &self.rules {
+                if !productive.contains(rule.nonterminal.as_str())
+                    && rule.symbols.iter().all(|symbol| match symbol {
+                        GrammarSymbol::Terminal(_) => true,
+                        GrammarSymbol::NonTerminal(name) => productive.contains(name.as_str()),
+                    })
+                {
+                    productive.insert(rule.nonterminal.as_str());
+                    changed = true;
+                }
+            }
+        }
+        for rule in &self.rules {
+            let name = rule.nonterminal.as_str();
+            if reachable.contains(name) && !productive.contains(name) {
+                return Err(Error::illegal_argument(format!(
+                    "Nonterminal {name} never derives a string"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The nonterminals reachable from the start, the rules of all others are never used
+    pub(crate) fn reachable(&self) -> BTreeSet<&str> {
+        let mut reachable: BTreeSet<&str> = BTreeSet::from([self.start.as_str()]);
+        let mut todo = vec![self.start.as_str()];
+        while let Some(name) = todo.pop() {
+            for rule in self.rules.iter().filter(|rule| rule.nonterminal == name) {
+                for symbol in &rule.symbols {
+                    if let GrammarSymbol::NonTerminal(name) = symbol {
+                        if reachable.insert(name.as_str()) {
+                            todo.push(name.as_str());
+                        }
+                    }
+                }
+            }
+        }
+        reachable
+    }
+
+    /// Builds a Gramatron [`Automaton`] for this grammar, like the `construct_automata` tool.
+    ///
+    /// The grammar is converted to Greibach normal form first. Each state of the automaton stands for a stack of
+    /// nonterminals still to expand. If `stack_limit` is not `0`, deeper stacks are abandoned,
+    /// which is needed for most recursive grammars to get a finite automaton.
+    pub fn to_automaton(&self, stack_limit: usize) -> Result<Automaton, Error> {
+        self.check()?;
+
+        let mut by_name: BTreeMap<&str, Vec<&[GrammarSymbol]>> = BTreeMap::new();
+        for rule in &self.rules {
+            by_name
+                .entry(rule.nonterminal.as_str())
+                .or_default()
+                .push(&rule.symbols);
+        }
+
+        // index the nonterminals reachable from the start
+        let mut names: Vec<&str> = vec![self.start.as_str()];
+        let mut index: BTreeMap<&str, usize> = BTreeMap::new();
+        index.insert(self.start.as_str(), 0);
+        let mut alts: Vec<Vec<Vec<Sym>>> = Vec::new();
+        while alts.len() < names.len() {
+            let name = names[alts.len()];
+            let mut nt_alts = Vec::new();
+            for symbols in &by_name[name] {
+                let mut alt = Vec::with_capacity(symbols.len());
+                for symbol in *symbols {
+                    alt.push(match symbol {
+                        GrammarSymbol::Terminal(bytes) => {
+                            Sym::T(String::from_utf8_lossy(bytes).into_owned())
+                        }
+                        GrammarSymbol::NonTerminal(name) => {
+                            Sym::N(*index.entry(name.as_str()).or_insert_with(|| {
+                                names.push(name.as_str());
+                                names.len() - 1
+                            }))
+                        }
+                    });
+                }
+                nt_alts.push(alt);
+            }
+            alts.push(nt_alts);
+        }
+
+        let mut gnf = Gnf {
+            names: names.iter().map(ToString::to_string).collect(),
+            alts,
+        };
+        gnf.convert()?;
+        gnf.automaton(stack_limit)
+    }
+}
+
+/// A symbol of a grammar in conversion to Greibach normal form
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Sym {
+    T(String),
+    N(usize),
+}
+
+/// A grammar with indexed nonterminals, converted to Greibach normal form:
+/// each alternative is a terminal followed by nonterminals only.
+#[derive(Debug)]
+struct Gnf {
+    names: Vec<String>,
+    alts: Vec<Vec<Vec<Sym>>>,
+}
+
+impl Gnf {
+    fn add_nonterminal(&mut self, name: String, alts: Vec<Vec<Sym>>) -> usize {
+        self.names.push(name);
+        self.alts.push(alts);
+        self.alts.len() - 1
+    }
+
+    fn check_size(&self) -> Result<(), Error> {
+        if self.alts.iter().map(Vec::len).sum::<usize>() > MAX_GNF_ALTERNATIVES {
+            return Err(Error::illegal_argument(format!(
+                "The grammar grew beyond {MAX_GNF_ALTERNATIVES} alternatives in Greibach normal form"
+            )));
+        }
+        Ok(())
+    }
+
+    fn leading_nonterminal(alt: &[Sym]) -> Option<usize> {
+        match alt.first() {
+            Some(Sym::N(nt)) => Some(*nt),
+            _ => None,
+        }
+    }
+
+    fn is_normal(&self, nt: usize) -> bool {
+        self.alts[nt]
+            .iter()
+            .all(|alt| Self::leading_nonterminal(alt).is_none())
+    }
+
+    /// Replaces the leading nonterminals of the alternatives of `nt` selected by `pred` by their alternatives
+    fn substitute<P: Fn(usize) -> bool>(&mut self, nt: usize, pred: P) -> bool {
+        let mut changed = false;
+        let mut new_alts = Vec::with_capacity(self.alts[nt].len());
+        for alt in &self.alts[nt] {
+            match Self::leading_nonterminal(alt) {
+                Some(lead) if lead != nt && pred(lead) => {
+                    changed = true;
+                    for expansion in &self.alts[lead] {
+                        let mut new_alt = expansion.clone();
+                        new_alt.extend_from_slice(&alt[1..]);
+                        new_alts.push(new_alt);
+                    }
+                }
+                _ => new_alts.push(alt.clone()),
+            }
+        }
+        dedup(&mut new_alts);
+        self.alts[nt] = new_alts;
+        changed
+    }
+
+    /// Removes direct left recursion, `A -> A a | b` becomes `A -> b | b Z`, `Z -> a | a Z`
+    fn remove_left_recursion(&mut self, nt: usize) -> Result<(), Error> {
+        let (recursive, others): (Vec<Vec<Sym>>, Vec<Vec<Sym>>) = self.alts[nt]
+            .drain(..)
+            .partition(|alt| Self::leading_nonterminal(alt) == Some(nt));
+        let tails: Vec<Vec<Sym>> = recursive
+            .into_iter()
+            .map(|alt| alt[1..].to_vec())
+            .filter(|tail| !tail.is_empty())
+            .collect();
+        if others.is_empty() {
+            return Err(Error::illegal_argument(format!(
+                "Nonterminal {} never derives a string",
+                self.names[nt]
+            )));
+        }
+        if tails.is_empty() {
+            self.alts[nt] = others;
+            return Ok(());
+        }
+
+        let tail_nt = self.alts.len();
+        let mut alts = others.clone();
+        alts.extend(others.into_iter().map(|mut alt| {
+            alt.push(Sym::N(tail_nt));
+            alt
+        }));
+        self.alts[nt] = alts;
+
+        let mut tail_alts = tails.clone();
+        tail_alts.extend(tails.into_iter().map(|mut tail| {
+            tail.push(Sym::N(tail_nt));
+            tail
+        }));
+        let name = format!("{}_tail", self.names[nt]);
+        self.add_nonterminal(name, tail_alts);
+        Ok(())
+    }
+
+    fn convert(&mut self) -> Result<(), Error> {
+        // merge leading terminals, and turn the empty string into an empty terminal
+        for alts in &mut self.alts {
+            for alt in alts.iter_mut() {
+                alt.retain(|sym| *sym != Sym::T(String::new()));
+                while let [Sym::T(first), Sym::T(second), ..] = alt.as_slice() {
+                    let merged = format!("{first}{second}");
+                    alt.splice(0..2, [Sym::T(merged)]);
+                }
+                if alt.is_empty() {
+                    alt.push(Sym::T(String::new()));
+                }
+            }
+        }
+
+        // only the first symbol of an alternative may be a terminal
+        let mut terminals: BTreeMap<String, usize> = BTreeMap::new();
+        for nt in 0..self.alts.len() {
+            for alt_idx in 0..self.alts[nt].len() {
+                for sym_idx in 1..self.alts[nt][alt_idx].len() {
+                    if let Sym::T(term) = &self.alts[nt][alt_idx][sym_idx] {
+                        let term = term.clone();
+                        let term_nt = if let Some(term_nt) = terminals.get(&term) {
+                            *term_nt
+                        } else {
+                            let name = format!("{}_term{}", self.names[nt], terminals.len());
+                            let term_nt =
+                                self.add_nonterminal(name, vec![vec![Sym::T(term.clone())]]);
+                            terminals.insert(term, term_nt);
+                            term_nt
+                        };
+                        self.alts[nt][alt_idx][sym_idx] = Sym::N(term_nt);
+                    }
+                }
+            }
+        }
+
+        // Paull's algorithm: afterwards, each nonterminal only starts with nonterminals of a higher index
+        let len = self.alts.len();
+        for nt in 0..len {
+            self.substitute(nt, |lead| lead < nt);
+            self.remove_left_recursion(nt)?;
+            self.check_size()?;
+        }
+
+        // substitute leading nonterminals that are in normal form, until all are
+        loop {
+            let mut changed = false;
+            let mut pending = false;
+            for nt in 0..self.alts.len() {
+                let normal: Vec<bool> = (0..self.alts.len()).map(|n| self.is_normal(n)).collect();
+                changed |= self.substitute(nt, |lead| normal[lead]);
+                pending |= !self.is_normal(nt);
+            }
+            self.check_size()?;
+            if !pending {
+                return Ok(());
+            }
+            if !changed {
+                return Err(Error::illegal_argument(
+                    "The grammar could not be converted to Greibach normal form",
+                ));
+            }
+        }
+    }
+
+    /// Builds the automaton, starting with the stack holding the nonterminal `0`
+    fn automaton(&self, stack_limit: usize) -> Result<Automaton, Error> {
+        let mut stacks: Vec<Vec<usize>> = vec![vec![0]];
+        // as in Gramatron, states with the same nonterminals on the stack, in any order, are merged
+        let mut known: HashMap<Vec<usize>, usize> = HashMap::new();
+        known.insert(vec![0], 0);
+        let mut pda: Vec<Vec<Trigger>> = vec![Vec::new()];
+        let mut worklist = VecDeque::from([0]);
+
+        while let Some(state) = worklist.pop_front() {
+            let Some(&top) = stacks[state].first() else {
+                continue;
+            };
+            for alt in &self.alts[top] {
+                let Sym::T(term) = &alt[0] else {
+                    return Err(Error::illegal_state("Not in Greibach normal form"));
+                };
+                let stack: Vec<usize> = alt[1..]
+                    .iter()
+                    .map(|sym| match sym {
+                        Sym::N(nt) => *nt,
+                        Sym::T(_) => unreachable!("Only the first symbol is a terminal"),
+                    })
+                    .chain(stacks[state][1..].iter().copied())
+                    .collect();
+                let mut sorted = stack.clone();
+                sorted.sort_unstable();
+
+                let dest = if let Some(dest) = known.get(&sorted) {
+                    *dest
+                } else {
+                    if stack_limit > 0 && stack.len() > stack_limit {
+                        continue;
+                    }
+                    if stacks.len() >= MAX_AUTOMATON_STATES {
+                        return Err(Error::illegal_argument(format!(
+                            "The automaton grew beyond {MAX_AUTOMATON_STATES} states, set a lower stack limit"
+                        )));
+                    }
+                    let dest = stacks.len();
+                    stacks.push(stack);
+                    known.insert(sorted, dest);
+                    pda.push(Vec::new());
+                    worklist.push_back(dest);
+                    dest
+                };
+                pda[state].push(Trigger {
+                    dest,
+                    term: term.clone(),
+                });
+            }
+        }
+
+        let final_state = *known.get(&Vec::new()).ok_or_else(|| {
+            Error::illegal_argument("No string of the grammar fits into the stack limit")
+        })?;
+
+        // drop the transitions to states that can't reach the final state because of the stack limit
+        let mut sources: Vec<Vec<usize>> = vec![Vec::new(); pda.len()];
+        for (state, triggers) in pda.iter().enumerate() {
+            for trigger in triggers {
+                sources[trigger.dest].push(state);
+            }
+        }
+        let mut alive = vec![false; pda.len()];
+        alive[final_state] = true;
+        let mut worklist = vec![final_state];
+        while let Some(state) = worklist.pop() {
+            for source in &sources[state] {
+                if !alive[*source] {
+                    alive[*source] = true;
+                    worklist.push(*source);
+                }
+            }
+        }
+        for triggers in &mut pda {
+            triggers.retain(|trigger| alive[trigger.dest]);
+        }
+        if !alive[0] {
+            return Err(Error::illegal_argument(
+                "No string of the grammar fits into the stack limit",
+            ));
+        }
+
+        Ok(Automaton {
+            init_state: 0,
+            final_state,
+            pda,
+        })
+    }
+}
+
+/// Removes duplicate alternatives, keeping the first
+fn dedup(alts: &mut Vec<Vec<Sym>>) {
+    let mut unique: Vec<Vec<Sym>> = Vec::with_capacity(alts.len());
+    for alt in alts.drain(..) {
+        if !unique.contains(&alt) {
+            unique.push(alt);
+        }
+    }
+    *alts = unique;
+}
+
+/// The right hand side of a rule in a grammar file
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Lit(String),
+    Ref(String),
+    /// Inclusive ranges of code points
+    Set(Vec<(u32, u32)>),
+    Seq(Vec<Expr>),
+    Alt(Vec<Expr>),
+    Opt(Box<Expr>),
+    Star(Box<Expr>),
+    Plus(Box<Expr>),
+}
+
+/// A rule of a grammar file
+#[derive(Debug)]
+struct ParsedRule {
+    name: String,
+    expr: Expr,
+    /// An ANTLR lexer rule
+    lexer: bool,
+    /// An ANTLR lexer rule whose tokens are skipped or sent to another channel
+    hidden: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Dialect {
+    Antlr,
+    /// ISO 14977 EBNF
+    Iso,
+    /// W3C EBNF, and BNF
+    W3c,
+}
+
+/// A recursive descent parser for grammar files
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    dialect: Dialect,
+    /// Set if the lexer commands of the current rule hide its tokens
+    hidden: bool,
+}
+
+impl Parser {
+    fn new(src: &str, dialect: Dialect) -> Self {
+        Self {
+            chars: src.chars().collect(),
+            pos: 0,
+            dialect,
+            hidden: false,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars()
+            .enumerate()
+            .all(|(idx, c)| self.peek_at(idx) == Some(c))
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        if self.starts_with(s) {
+            self.pos += s.chars().count();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error(&self, msg: &str) -> Error {
+        let line = 1 + self.chars[..self.pos.min(self.chars.len())]
+            .iter()
+            .filter(|c| **c == '\n')
+            .count();
+        Error::illegal_argument(format!("Grammar parse error in line {line}: {msg}"))
+    }
+
+    fn expect(&mut self, s: &str) -> Result<(), Error> {
+        self.skip_trivia();
+        if self.eat(s) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{s}`")))
+        }
+    }
+
+    /// Skips whitespace and comments
+    fn skip_trivia(&mut self) {
+        loop {
+            while self.peek().is_some_and(char::is_whitespace) {
+                self.pos += 1;
+            }
+            if self.starts_with("//") {
+                while self.peek().is_some_and(|c| c != '\n') {
+                    self.pos += 1;
+                }
+            } else if self.eat("/*") {
+                while self.peek().is_some() && !self.eat("*/") {
+                    self.pos += 1;
+                }
+            } else if self.dialect != Dialect::Antlr && self.eat("(*") {
+                while self.peek().is_some() && !self.eat("*)") {
+                    self.pos += 1;
+                }
+            } else {
+                return;
+            }
+        }
+    }
+
+    /// Skips a block delimited by `open` and `close`, e.g., an ANTLR action, including nested blocks and strings
+    fn skip_balanced(&mut self, open: char, close: char) -> Result<(), Error> {
+        self.skip_trivia();
+        if self.bump() != Some(open) {
+            return Err(self.error(&format!("expected `{open}`")));
+        }
+        let mut depth = 1;
+        while depth > 0 {
+            match self.bump() {
+                None => return Err(self.error(&format!("unterminated `{open}`"))),
+                Some(c @ ('"' | '\'')) if open != '[' => {
+                    while let Some(next) = self.bump() {
+                        if next == '\\' {
+                            self.pos += 1;
+                        } else if next == c {
+                            break;
+                        }
+                    }
+                }
+                Some('\\') => self.pos += 1,
+                Some(c) if c == open => depth += 1,
+                Some(c) if c == close => depth -= 1,
+                Some(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn skip_past(&mut self, end: char) {
+        while let Some(c) = self.bump() {
+            if c == end {
+                return;
+            }
+        }
+    }
+
+    fn is_ident_start(c: char) -> bool {
+        c.is_ascii_alphabetic() || c == '_'
+    }
+
+    fn is_ident_char(c: char) -> bool {
+        c.is_ascii_alphanumeric() || c == '_'
+    }
+
+    fn word(&mut self) -> Option<String> {
+        if !self.peek().is_some_and(Self::is_ident_start) {
+            return None;
+        }
+        let start = self.pos;
+        while self.peek().is_some_and(Self::is_ident_char) {
+            self.pos += 1;
+        }
+        Some(self.chars[start..self.pos].iter().collect())
+    }
+
+    /// A nonterminal name: a word, `<a name>` in BNF, or, in ISO EBNF, words separated by spaces
+    fn name(&mut self) -> Option<String> {
+        if self.dialect != Dialect::Antlr && self.peek() == Some('<') {
+            let start = self.pos + 1;
+            let end = start + self.chars[start..].iter().position(|c| *c == '>')?;
+            self.pos = end + 1;
+            let name: String = self.chars[start..end].iter().collect();
+            return Some(name.trim().to_string());
+        }
+        let mut name = self.word()?;
+        if self.dialect == Dialect::Iso {
+            loop {
+                let save = self.pos;
+                while self.peek().is_some_and(|c| c == ' ' || c == '\t') {
+                    self.pos += 1;
+                }
+                let Some(word) = self.word() else {
+                    self.pos = save;
+                    break;
+                };
+                name.push(' ');
+                name.push_str(&word);
+            }
+        }
+        Some(name)
+    }
+
+    /// Reads the operator defining an EBNF rule and switches to its dialect
+    fn ebnf_definer(&mut self) -> Option<Dialect> {
+        self.skip_trivia();
+        if self.eat("::=") || self.eat(":=") {
+            Some(Dialect::W3c)
+        } else if self.starts_with("==") {
+            None
+        } else if self.eat("=") {
+            Some(Dialect::Iso)
+        } else if self.eat(":") {
+            Some(Dialect::W3c)
+        } else {
+            None
+        }
+    }
+
+    /// If the next tokens start a new EBNF rule, for rules without terminator
+    fn at_rule_start(&mut self) -> bool {
+        let save = (self.pos, self.dialect);
+        let res = self.name().is_some() && self.ebnf_definer().is_some();
+        (self.pos, self.dialect) = save;
+        res
+    }
+
+    fn ebnf_rules(&mut self) -> Result<Vec<ParsedRule>, Error> {
+        let mut rules = Vec::new();
+        loop {
+            self.dialect = Dialect::Iso;
+            self.skip_trivia();
+            if self.peek().is_none() {
+                return Ok(rules);
+            }
+            // the name is read as ISO, which allows spaces, and re-read if the rule is W3C
+            let start = self.pos;
+            let name = self
+                .name()
+                .ok_or_else(|| self.error("expected a rule name"))?;
+            let dialect = self
+                .ebnf_definer()
+                .ok_or_else(|| self.error("expected `=` or `::=`"))?;
+            let name = if dialect == Dialect::Iso {
+                name
+            } else {
+                self.pos = start;
+                self.dialect = dialect;
+                let name = self.name().unwrap();
+                self.ebnf_definer();
+                name
+            };
+            self.dialect = dialect;
+
+            let expr = self.alternatives()?;
+            self.skip_trivia();
+            if !self.eat(";") && dialect == Dialect::Iso {
+                self.eat(".");
+            }
+            rules.push(ParsedRule {
+                name,
+                expr,
+                lexer: false,
+                hidden: false,
+            });
+        }
+    }
+
+    /// Parses the rules of an ANTLR grammar, and the tokens declared without rules
+    fn antlr_rules(&mut self) -> Result<(Vec<ParsedRule>, Vec<String>), Error> {
+        let mut rules = Vec::new();
+        let mut tokens = Vec::new();
+        loop {
+            self.skip_trivia();
+            if self.peek().is_none() {
+                return Ok((rules, tokens));
+            }
+            if self.eat("@") {
+                // named actions, e.g., `@header {...}` or `@lexer::members {...}`
+                self.word();
+                if self.eat("::") {
+                    self.word();
+                }
+                self.skip_balanced('{', '}')?;
+                continue;
+            }
+            let mut word = self
+                .word()
+                .ok_or_else(|| self.error("expected a rule name"))?;
+            match word.as_str() {
+                "lexer" | "parser" | "grammar" | "import" | "mode" => {
+                    self.skip_past(';');
+                    continue;
+                }
+                "options" | "channels" => {
+                    self.skip_balanced('{', '}')?;
+                    continue;
+                }
+                "tokens" => {
+                    self.expect("{")?;
+                    loop {
+                        self.skip_trivia();
+                        if self.eat("}") {
+                            break;
+                        }
+                        match self.word() {
+                            Some(token) => tokens.push(token),
+                            None if self.eat(",") => {}
+                            None => return Err(self.error("expected a token name")),
+                        }
+                    }
+                    continue;
+                }
+                "fragment" | "public" | "private" | "protected" => {
+                    self.skip_trivia();
+                    word = self
+                        .word()
+                        .ok_or_else(|| self.error("expected a rule name"))?;
+                }
+                _ => {}
+            }
+
+            // skip arguments, return values, locals, options and actions up to the colon
+            loop {
+                self.skip_trivia();
+                match self.peek() {
+                    Some(':') => {
+                        self.pos += 1;
+                        break;
+                    }
+                    Some('[') => self.skip_balanced('[', ']')?,
+                    Some('{') => self.skip_balanced('{', '}')?,
+                    Some(_) => self.pos += 1,
+                    None => return Err(self.error("expected `:`")),
+                }
+            }
+
+            self.hidden = false;
+            let expr = self.alternatives()?;
+            self.expect(";")?;
+            loop {
+                self.skip_trivia();
+                if self.eat("catch") {
+                    self.skip_balanced('[', ']')?;
+                    self.skip_balanced('{', '}')?;
+                } else if self.eat("finally") {
+                    self.skip_balanced('{', '}')?;
+                } else {
+                    break;
+                }
+            }
+
+            let lexer = word.starts_with(|c: char| c.is_ascii_uppercase());
+            rules.push(ParsedRule {
+                name: word,
+                expr,
+                lexer,
+                hidden: lexer && self.hidden,
+            });
+        }
+    }
+
+    fn alternatives(&mut self) -> Result<Expr, Error> {
+        let mut alts = vec![self.sequence()?];
+        loop {
+            self.skip_trivia();
+            if !self.eat("|") {
+                break;
+            }
+            alts.push(self.sequence()?);
+        }
+        Ok(if alts.len() == 1 {
+            alts.pop().unwrap()
+        } else {
+            Expr::Alt(alts)
+        })
+    }
+
+    fn sequence(&mut self) -> Result<Expr, Error> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_trivia();
+            match self.peek() {
+                None | Some('|' | ')' | ';') => break,
+                Some(']' | '}' | '.') if self.dialect == Dialect::Iso => break,
+                Some(',') if self.dialect == Dialect::Iso => self.pos += 1,
+                Some('-') if self.dialect == Dialect::Antlr && self.peek_at(1) == Some('>') => {
+                    self.pos += 2;
+                    self.lexer_commands()?;
+                }
+                Some('#') if self.dialect == Dialect::Antlr => {
+                    // alternative label
+                    self.pos += 1;
+                    self.skip_trivia();
+                    self.word();
+                }
+                Some('<') if self.dialect == Dialect::Antlr => self.skip_balanced('<', '>')?,
+                Some('-') => {
+                    // an exception, approximated by the left operand
+                    self.pos += 1;
+                    self.element()?;
+                }
+                Some(_) if self.dialect == Dialect::W3c && self.at_rule_start() => break,
+                Some(_) => items.push(self.element()?),
+            }
+        }
+        Ok(match items.len() {
+            0 => Expr::Lit(String::new()),
+            1 => items.pop().unwrap(),
+            _ => Expr::Seq(items),
+        })
+    }
+
+    fn lexer_commands(&mut self) -> Result<(), Error> {
+        loop {
+            self.skip_trivia();
+            match self.peek() {
+                None | Some(';' | '|') => return Ok(()),
+                Some(',') => self.pos += 1,
+                Some('(') => self.skip_balanced('(', ')')?,
+                Some(_) => {
+                    let command = self
+                        .word()
+                        .ok_or_else(|| self.error("expected a lexer command"))?;
+                    if command == "skip" || command == "channel" {
+                        self.hidden = true;
+                    }
+                }
+            }
+        }
+    }
+
+    fn element(&mut self) -> Result<Expr, Error> {
+        if self.dialect == Dialect::Antlr {
+            // labels, `x=atom` or `x+=atom`
+            let save = self.pos;
+            if self.word().is_some() {
+                self.skip_trivia();
+                if self.eat("+=") || (self.peek() == Some('=') && self.peek_at(1) != Some('>')) {
+                    self.eat("=");
+                } else {
+                    self.pos = save;
+                }
+            }
+        }
+
+        let mut expr = self.atom()?;
+        if self.dialect == Dialect::Iso {
+            return Ok(expr);
+        }
+        loop {
+            match self.peek() {
+                Some('?') => expr = Expr::Opt(Box::new(expr)),
+                Some('*') => expr = Expr::Star(Box::new(expr)),
+                Some('+') => expr = Expr::Plus(Box::new(expr)),
+                _ => return Ok(expr),
+            }
+            self.pos += 1;
+            if self.dialect == Dialect::Antlr {
+                // non-greedy
+                self.eat("?");
+            }
+        }
+    }
+
+    fn atom(&mut self) -> Result<Expr, Error> {
+        self.skip_trivia();
+        let Some(c) = self.peek() else {
+            return Err(self.error("unexpected end of grammar"));
+        };
+        match (c, self.dialect) {
+            ('(', _) => {
+                self.pos += 1;
+                let expr = self.alternatives()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            ('[', Dialect::Iso) => {
+                self.pos += 1;
+                let expr = self.alternatives()?;
+                self.expect("]")?;
+                Ok(Expr::Opt(Box::new(expr)))
+            }
+            ('{', Dialect::Iso) => {
+                self.pos += 1;
+                let expr = self.alternatives()?;
+                self.expect("}")?;
+                Ok(Expr::Star(Box::new(expr)))
+            }
+            ('[', _) => self.char_class(),
+            ('{', Dialect::Antlr) => {
+                // actions and semantic predicates generate nothing
+                self.skip_balanced('{', '}')?;
+                self.eat("?");
+                Ok(Expr::Lit(String::new()))
+            }
+            ('\'', Dialect::Antlr) => {
+                let lit = self.antlr_literal()?;
+                self.skip_trivia();
+                if self.eat("..") {
+                    self.skip_trivia();
+                    let end = self.antlr_literal()?;
+                    match (single_char(&lit), single_char(&end)) {
+                        (Some(lo), Some(hi)) if lo <= hi => Ok(Expr::Set(vec![(lo, hi)])),
+                        _ => Err(self.error("invalid character range")),
+                    }
+                } else {
+                    Ok(Expr::Lit(lit))
+                }
+            }
+            ('\'' | '"', _) => {
+                self.pos += 1;
+                let start = self.pos;
+                while self.peek().is_some_and(|next| next != c) {
+                    self.pos += 1;
+                }
+                let lit = self.chars[start..self.pos].iter().collect();
+                if self.bump().is_none() {
+                    return Err(self.error("unterminated string"));
+                }
+                Ok(Expr::Lit(lit))
+            }
+            ('.', Dialect::Antlr) => {
+                self.pos += 1;
+                Ok(Expr::Set(PRINTABLE.to_vec()))
+            }
+            ('~', Dialect::Antlr) => {
+                self.pos += 1;
+                let expr = self.atom()?;
+                let ranges = char_ranges(&expr)
+                    .ok_or_else(|| self.error("only characters can be negated"))?;
+                Ok(Expr::Set(complement(&ranges)))
+            }
+            ('#', Dialect::W3c) => {
+                self.pos += 1;
+                let code = self.hex_char()?;
+                let c = char::from_u32(code).ok_or_else(|| self.error("invalid character"))?;
+                Ok(Expr::Lit(c.to_string()))
+            }
+            ('?', Dialect::Iso) => {
+                // special sequences are up to the reader, we generate nothing
+                self.pos += 1;
+                self.skip_past('?');
+                Ok(Expr::Lit(String::new()))
+            }
+            _ => {
+                let name = self
+                    .name()
+                    .ok_or_else(|| self.error(&format!("unexpected `{c}`")))?;
+                if self.dialect == Dialect::Antlr && name == "EOF" {
+                    Ok(Expr::Lit(String::new()))
+                } else {
+                    Ok(Expr::Ref(name))
+                }
+            }
+        }
+    }
+
+    /// `xHEX`, after the `#` of a W3C character
+    fn hex_char(&mut self) -> Result<u32, Error> {
+        if !self.eat("x") {
+            return Err(self.error("expected `#x`"));
+        }
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+            self.pos += 1;
+        }
+        let hex: String = self.chars[start..self.pos].iter().collect();
+        u32::from_str_radix(&hex, 16).map_err(|_| self.error("invalid hex character"))
+    }
+
+    /// An escaped character in an ANTLR literal or character class, after the backslash
+    fn escape(&mut self) -> Result<u32, Error> {
+        let c = self
+            .bump()
+            .ok_or_else(|| self.error("unterminated escape"))?;
+        Ok(match c {
+            'n' => 0x0a,
+            'r' => 0x0d,
+            't' => 0x09,
+            'b' => 0x08,
+            'f' => 0x0c,
+            'u' => {
+                let braced = self.eat("{");
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_ascii_hexdigit())
+                    && (braced || self.pos < start + 4)
+                {
+                    self.pos += 1;
+                }
+                let hex: String = self.chars[start..self.pos].iter().collect();
+                if braced {
+                    self.eat("}");
+                }
+                u32::from_str_radix(&hex, 16).map_err(|_| self.error("invalid unicode escape"))?
+            }
+            c => c as u32,
+        })
+    }
+
+    fn antlr_literal(&mut self) -> Result<String, Error> {
+        self.pos += 1;
+        let mut lit = String::new();
+        loop {
+            match self.bump() {
+                None => return Err(self.error("unterminated string")),
+                Some('\'') => return Ok(lit),
+                Some('\\') => {
+                    let code = self.escape()?;
+                    lit.push(char::from_u32(code).ok_or_else(|| self.error("invalid escape"))?);
+                }
+                Some(c) => lit.push(c),
+            }
+        }
+    }
+
+    /// A character class, `[a-z_]`, with `[^...]` for negation in W3C EBNF
+    fn char_class(&mut self) -> Result<Expr, Error> {
+        self.pos += 1;
+        let negated = self.dialect == Dialect::W3c && self.eat("^");
+        let mut ranges = Vec::new();
+        loop {
+            if self.eat("]") {
+                break;
+            }
+            if self.dialect == Dialect::Antlr && self.eat("\\p{") {
+                // unicode classes, approximated by ASCII letters
+                self.skip_past('}');
+                ranges.extend([
+                    (u32::from('a'), u32::from('z')),
+                    (u32::from('A'), u32::from('Z')),
+                ]);
+                continue;
+            }
+            let lo = self.class_char()?;
+            let hi = if self.peek() == Some('-') && self.peek_at(1) != Some(']') {
+                self.pos += 1;
+                self.class_char()?
+            } else {
+                lo
+            };
+            if lo > hi {
+                return Err(self.error("invalid character range"));
+            }
+            ranges.push((lo, hi));
+        }
+        Ok(Expr::Set(if negated {
+            complement(&ranges)
+        } else {
+            ranges
+        }))
+    }
+
+    fn class_char(&mut self) -> Result<u32, Error> {
+        match self.bump() {
+            None => Err(self.error("unterminated character class")),
+            Some('\\') if self.dialect == Dialect::Antlr => self.escape(),
+            Some('#') if self.dialect == Dialect::W3c && self.peek() == Some('x') => {
+                self.hex_char()
+            }
+            Some(c) => Ok(c as u32),
+        }
+    }
+}
+
+/// The characters matched by `expr`, if it only matches single characters
+fn char_ranges(expr: &Expr) -> Option<Vec<(u32, u32)>> {
+    match expr {
+        Expr::Set(ranges) => Some(ranges.clone()),
+        Expr::Lit(lit) => single_char(lit).map(|c| vec![(c, c)]),
+        Expr::Alt(alts) => {
+            let mut ranges = Vec::new();
+            for alt in alts {
+                ranges.extend(char_ranges(alt)?);
+            }
+            Some(ranges)
+        }
+        _ => None,
+    }
+}
+
+/// The code point of `lit`, if it is a single character
+fn single_char(lit: &str) -> Option<u32> {
+    let mut chars = lit.chars();
+    let c = chars.next()?;
+    chars.next().is_none().then_some(c as u32)
+}
+
+/// The [`PRINTABLE`] characters not in `ranges`
+fn complement(ranges: &[(u32, u32)]) -> Vec<(u32, u32)> {
+    let mut res: Vec<(u32, u32)> = Vec::new();
+    for (lo, hi) in PRINTABLE {
+        for c in lo..=hi {
+            if ranges.iter().any(|(l, h)| (*l..=*h).contains(&c)) {
+                continue;
+            }
+            match res.last_mut() {
+                Some((_, last)) if *last + 1 == c => *last = c,
+                _ => res.push((c, c)),
+            }
+        }
+    }
+    res
+}
+
+/// The characters of the `ranges`, evenly sampled down to about [`MAX_CHARSET_LEN`] characters
+fn expand_charset(ranges: &[(u32, u32)]) -> Vec<char> {
+    let total: u32 = ranges
+        .iter()
+        .map(|(lo, hi)| hi - lo + 1)
+        .fold(0, u32::saturating_add);
+    let step = total.div_ceil(MAX_CHARSET_LEN).max(1);
+    let mut chars: Vec<char> = Vec::new();
+    for (lo, hi) in ranges {
+        let mut c = *lo;
+        while c <= *hi {
+            chars.extend(char::from_u32(c));
+            c = c.saturating_add(step);
+            if c == u32::MAX {
+                break;
+            }
+        }
+        chars.extend(char::from_u32(*hi));
+    }
+    chars.sort_unstable();
+    chars.dedup();
+    chars
+}
+
+/// Rewrites the EBNF expressions of the parsed rules into a [`Grammar`]
+struct Lowering {
+    grammar: Grammar,
+    /// All nonterminal names, to pick fresh names for helper nonterminals
+    taken: BTreeSet<String>,
+    /// Helper nonterminals of character sets, shared by all rules
+    sets: BTreeMap<Vec<(u32, u32)>, String>,
+    /// Put a space between the symbols of the current rule
+    spaced: bool,
+}
+
+impl Lowering {
+    fn new(start: &str, rules: &[ParsedRule]) -> Self {
+        Self {
+            grammar: Grammar::new(start),
+            taken: rules.iter().map(|rule| rule.name.clone()).collect(),
+            sets: BTreeMap::new(),
+            spaced: false,
+        }
+    }
+
+    fn fresh(&mut self, base: &str, kind: &str) -> String {
+        let mut n = 1;
+        loop {
+            let name = format!("{base}_{kind}{n}");
+            if self.taken.insert(name.clone()) {
+                return name;
+            }
+            n += 1;
+        }
+    }
+
+    fn rule(&mut self, name: &str, symbols: Vec<GrammarSymbol>) {
+        let symbols = if self.spaced && symbols.len() > 1 {
+            let mut spaced = Vec::with_capacity(symbols.len() * 2);
+            for symbol in symbols {
+                if !spaced.is_empty() {
+                    spaced.push(GrammarSymbol::Terminal(vec![b' ']));
+                }
+                spaced.push(symbol);
+            }
+            spaced
+        } else {
+            symbols
+        };
+        self.grammar.add_rule(name, symbols);
+    }
+
+    fn alternatives(&mut self, name: &str, expr: Expr) {
+        match expr {
+            Expr::Alt(alts) => {
+                for alt in alts {
+                    let symbols = self.sequence(name, alt);
+                    self.rule(name, symbols);
+                }
+            }
+            expr => {
+                let symbols = self.sequence(name, expr);
+                self.rule(name, symbols);
+            }
+        }
+    }
+
+    fn sequence(&mut self, base: &str, expr: Expr) -> Vec<GrammarSymbol> {
+        match expr {
+            Expr::Lit(lit) if lit.is_empty() => Vec::new(),
+            Expr::Lit(lit) => vec![GrammarSymbol::Terminal(lit.into_bytes())],
+            Expr::Ref(name) => vec![GrammarSymbol::NonTerminal(name)],
+            Expr::Seq(items) => items
+                .into_iter()
+                .flat_map(|item| self.sequence(base, item))
+                .collect(),
+            Expr::Alt(alts) => {
+                let name = self.fresh(base, "alt");
+                for alt in alts {
+                    let symbols = self.sequence(base, alt);
+                    self.rule(&name, symbols);
+                }
+                vec![GrammarSymbol::NonTerminal(name)]
+            }
+            Expr::Set(ranges) => {
+                if let Some(name) = self.sets.get(&ranges) {
+                    return vec![GrammarSymbol::NonTerminal(name.clone())];
+                }
+                let name = self.fresh(base, "set");
+                for c in expand_charset(&ranges) {
+                    let bytes = c.to_string().into_bytes();
+                    self.grammar
+                        .add_rule(&name, vec![GrammarSymbol::Terminal(bytes)]);
+                }
+                self.sets.insert(ranges, name.clone());
+                vec![GrammarSymbol::NonTerminal(name)]
+            }
+            Expr::Opt(expr) => {
+                let name = self.fresh(base, "opt");
+                let symbols = self.sequence(base, *expr);
+                self.rule(&name, symbols);
+                self.rule(&name, Vec::new());
+                vec![GrammarSymbol::NonTerminal(name)]
+            }
+            Expr::Star(expr) => {
+                let name = self.fresh(base, "star");
+                let mut symbols = self.sequence(base, *expr);
+                self.rule(&name, Vec::new());
+                symbols.push(GrammarSymbol::NonTerminal(name.clone()));
+                self.rule(&name, symbols);
+                vec![GrammarSymbol::NonTerminal(name)]
+            }
+            Expr::Plus(expr) => {
+                let name = self.fresh(base, "plus");
+                let mut symbols = self.sequence(base, *expr);
+                self.rule(&name, symbols.clone());
+                symbols.push(GrammarSymbol::NonTerminal(name.clone()));
+                self.rule(&name, symbols);
+                vec![GrammarSymbol::NonTerminal(name)]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+
+    use libafl_bolts::rands::{Rand, StdRand};
+
+    use super::{Grammar, GrammarSymbol};
+    use crate::generators::gramatron::Automaton;
+
+    const EXPR_G4: &str = r"
+grammar Expr;
+
+// the start rule
+prog : stat+ EOF ;
+stat : expr ';'        # printExpr
+     | ID '=' expr ';' # assign
+     ;
+expr : <assoc=right> expr '^' expr
+     | expr op=('*'|'/') expr
+     | INT
+     | '(' expr ')'
+     | ID
+     ;
+ID  : [a-z]+ ;
+INT : '0' | [1-9] [0-9]* ;
+WS  : [ \t\r\n]+ -> skip ;
+";
+
+    /// Walks the automaton randomly, like the `GramatronGenerator`
+    fn walk(automaton: &Automaton, rand: &mut StdRand) -> String {
+        let mut state = automaton.init_state;
+        let mut out = String::new();
+        while state != automaton.final_state {
+            let triggers = &automaton.pda[state];
+            let trigger = &triggers[rand.below(triggers.len())];
+            out.push_str(&trigger.term);
+            state = trigger.dest;
+        }
+        out
+    }
+
+    /// Checks that `s` is a sequence of balanced parentheses
+    fn balanced(s: &str) -> bool {
+        let mut depth = 0_i32;
+        for c in s.chars() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => return false,
+            }
+            if depth < 0 {
+                return false;
+            }
+        }
+        depth == 0
+    }
+
+    #[test]
+    fn test_antlr4_grammar() {
+        let grammar = Grammar::from_antlr4(EXPR_G4).unwrap();
+        assert_eq!(grammar.start(), "prog");
+        let stat_rules: Vec<&[GrammarSymbol]> = grammar
+            .rules()
+            .iter()
+            .filter(|rule| rule.nonterminal == "stat")
+            .map(|rule| rule.symbols.as_slice())
+            .collect();
+        // the skipped whitespace puts spaces between the tokens of parser rules
+        let space = GrammarSymbol::Terminal(b" ".to_vec());
+        assert_eq!(
+            stat_rules[1],
+            [
+                GrammarSymbol::NonTerminal("ID".into()),
+                space.clone(),
+                GrammarSymbol::Terminal(b"=".to_vec()),
+                space.clone(),
+                GrammarSymbol::NonTerminal("expr".into()),
+                space,
+                GrammarSymbol::Terminal(b";".to_vec()),
+            ]
+        );
+        assert_eq!(
+            grammar
+                .rules()
+                .iter()
+                .filter(|rule| rule.nonterminal == "expr")
+                .count(),
+            5
+        );
+
+        let automaton = grammar.to_automaton(8).unwrap();
+        let mut rand = StdRand::with_seed(1337);
+        for _ in 0..50 {
+            let program = walk(&automaton, &mut rand);
+            assert!(program.ends_with(';'), "{program:?}");
+            assert!(program
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || " =;^*/()".contains(c)));
+        }
+    }
+
+    #[test]
+    fn test_ebnf_grammar() {
+        let iso = r#"
+(* balanced parentheses *)
+parens = { "(", parens, ")" } ;
+"#;
+        let w3c = r"
+parens ::= ( #x28 parens ')' )*
+";
+        let bnf = r#"
+<parens> ::= "(" <parens> ")" <parens> | ""
+"#;
+        let mut rand = StdRand::with_seed(1337);
+        for src in [iso, w3c, bnf] {
+            let grammar = Grammar::from_ebnf(src).unwrap();
+            assert_eq!(grammar.start(), "parens");
+            let automaton = grammar.to_automaton(10).unwrap();
+            let mut longest = 0;
+            for _ in 0..100 {
+                let parens = walk(&automaton, &mut rand);
+                assert!(balanced(&parens), "{parens:?}");
+                longest = longest.max(parens.len());
+            }
+            assert!(longest > 2);
+        }
+
+        let sets = r"
+number ::= [1-9] [0-9]* ('.' [0-9]+)?
+";
+        let automaton = Grammar::from_ebnf(sets).unwrap().to_automaton(0).unwrap();
+        for _ in 0..100 {
+            let number = walk(&automaton, &mut rand);
+            assert!(!number.starts_with(['0', '.']), "{number:?}");
+            assert!(number.parse::<f64>().is_ok(), "{number:?}");
+        }
+
+        assert!(Grammar::from_ebnf("a = b ;").is_err());
+        // unproductive nonterminals, only unreachable ones are accepted
+        assert!(Grammar::from_antlr4("a : 'x' a ;").is_err());
+        assert!(Grammar::from_antlr4("a : b ; b : a ;").is_err());
+        assert!(Grammar::from_antlr4("a : 'x' | b ; b : 'y' b ;").is_err());
+        assert!(Grammar::from_antlr4("a : 'x' ; b : 'y' b ;").is_ok());
+        assert!(Grammar::from_ebnf("a ::= 'x' (").is_err());
+    }
+
+    #[cfg(feature = "nautilus")]
+    #[test]
+    fn test_nautilus_context() {
+        use crate::{
+            generators::{Generator, NautilusContext, NautilusGenerator},
+            inputs::NautilusInput,
+            state::NopState,
+        };
+
+        // braces and backslashes in terminals, and names Nautilus does not accept
+        let src = r#"
+json ::= value
+value ::= '{' (pair (',' pair)*)? '}' | '"\' [a-z]* '"' | <digit-list>
+pair ::= '"k":' value
+<digit-list> ::= [0-9]+
+"#;
+        let grammar = Grammar::from_ebnf(src).unwrap();
+        let context = NautilusContext::from_grammar(10, &grammar).unwrap();
+        let mut generator = NautilusGenerator::new(&context);
+        let mut state = NopState::<NautilusInput>::new();
+        let mut bytes = Vec::new();
+        for _ in 0..50 {
+            generator
+                .generate(&mut state)
+                .unwrap()
+                .unparse(&context, &mut bytes);
+            let json = String::from_utf8(bytes.clone()).unwrap();
+            assert_eq!(json.matches('{').count(), json.matches('}').count());
+        }
+
+        // the unreachable, unproductive `b` is left out, an unproductive start is an error
+        let grammar = Grammar::from_antlr4("a : 'x' ; b : 'y' b ;").unwrap();
+        assert!(NautilusContext::from_grammar(10, &grammar).is_ok());
+        let mut grammar = Grammar::new("a");
+        grammar.add_rule(
+            "a",
+            vec![
+                GrammarSymbol::Terminal(b"x".to_vec()),
+                GrammarSymbol::NonTerminal("a".into()),
+            ],
+        );
+        assert!(NautilusContext::from_grammar(10, &grammar).is_err());
+    }
+
+    #[test]
+    fn test_left_recursion() {
+        // indirect left recursion through a unit cycle
+        let src = r"
+list ::= item | list ',' item
+item ::= 'x' | '[' list ']' | group
+group ::= item
+";
+        let grammar = Grammar::from_ebnf(src).unwrap();
+        let automaton = grammar.to_automaton(6).unwrap();
+        let mut rand = StdRand::with_seed(1337);
+        for _ in 0..100 {
+            let list = walk(&automaton, &mut rand);
+            assert!(list.starts_with(['x', '[']), "{list:?}");
+            assert_eq!(list.matches('[').count(), list.matches(']').count());
+        }
+    }
+}
@@ -14,6 +14,12 @@ use crate::{
 pub mod gramatron;
 pub use gramatron::*;
 
+pub mod grammar;
+pub use grammar::{Grammar, GrammarRule, GrammarSymbol};
+
+pub mod schema;
+pub use schema::SchemaGenerator;
+
 #[cfg(feature = "nautilus")]
 pub mod nautilus;
 #[cfg(feature = "nautilus")]
@@ -4,14 +4,22 @@ use alloc::{
     vec::Vec,
 };
 use core::fmt::Debug;
-use std::{fs, io::BufReader, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::BufReader,
+    path::Path,
+};
 
 use libafl_bolts::rands::Rand;
 
 pub use crate::common::nautilus::grammartec::newtypes::NTermId;
 use crate::{
-    common::nautilus::grammartec::context::Context, generators::Generator,
-    inputs::nautilus::NautilusInput, state::HasRand, Error,
+    common::nautilus::grammartec::context::Context,
+    generators::{Generator, Grammar, GrammarSymbol},
+    inputs::nautilus::NautilusInput,
+    state::HasRand,
+    Error,
 };
 
 /// The nautilus context for a generator
@@ -92,6 +100,89 @@ impl NautilusContext {
             serde_json::from_reader(reader).expect("Cannot parse grammar file");
         Self::new(tree_depth, &rules)
     }
+
+    /// Create a new [`NautilusContext`] from a [`Grammar`], e.g., loaded from an ANTLR4 or EBNF grammar file.
+    ///
+    /// Nonterminal names are renamed to the names Nautilus accepts, e.g., `expr` becomes `Expr`.
+    /// The rules of nonterminals not reachable from the start are left out.
+    /// Returns an error if a reachable nonterminal never derives a string, which Nautilus can't handle.
+    pub fn from_grammar(tree_depth: usize, grammar: &Grammar) -> Result<Self, Error> {
+        grammar.check()?;
+        let reachable = grammar.reachable();
+
+        let mut names: HashMap<String, String> = HashMap::new();
+        let mut taken: HashSet<String> = HashSet::from(["START".to_string()]);
+        let mut nautilus_name = |name: &str| -> String {
+            if let Some(renamed) = names.get(name) {
+                return renamed.clone();
+            }
+            let mut base: String = name
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            if !base.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                base.insert(0, 'N');
+            }
+            base[..1].make_ascii_uppercase();
+            let mut renamed = base.clone();
+            let mut suffix = 1;
+            while !taken.insert(renamed.clone()) {
+                suffix += 1;
+                renamed = format!("{base}_{suffix}");
+            }
+            names.insert(name.to_string(), renamed.clone());
+            renamed
+        };
+
+        let mut ctx = Context::new();
+        let mut term_rules = 0;
+        let root = format!("{{{}}}", nautilus_name(grammar.start()));
+        for rule in grammar.rules() {
+            if !reachable.contains(rule.nonterminal.as_str()) {
+                continue;
+            }
+            let nt = nautilus_name(&rule.nonterminal);
+            let mut format = Vec::new();
+            for symbol in &rule.symbols {
+                match symbol {
+                    GrammarSymbol::Terminal(bytes)
+                        if !bytes.iter().any(|b| matches!(b, b'{' | b'}' | b'\\')) =>
+                    {
+                        format.extend_from_slice(bytes);
+                    }
+                    GrammarSymbol::Terminal(bytes) => {
+                        // the format of a rule can't hold braces or backslashes, so they get a rule of their own
+                        term_rules += 1;
+                        let term_nt = nautilus_name(&format!("Term{term_rules}"));
+                        ctx.add_term_rule(&term_nt, bytes);
+                        format.extend_from_slice(format!("{{{term_nt}}}").as_bytes());
+                    }
+                    GrammarSymbol::NonTerminal(name) => {
+                        format.extend_from_slice(format!("{{{}}}", nautilus_name(name)).as_bytes());
+                    }
+                }
+            }
+            ctx.add_rule(&nt, &format);
+        }
+        ctx.add_rule("START", root.as_bytes());
+        ctx.initialize(tree_depth);
+        Ok(Self { ctx })
+    }
+
+    /// Create a new [`NautilusContext`] from an ANTLR4 (`.g4`) or EBNF grammar file, see [`Grammar::from_file`]
+    pub fn from_grammar_file<P: AsRef<Path>>(
+        tree_depth: usize,
+        grammar_file: P,
+    ) -> Result<Self, Error> {
+        let grammar = Grammar::from_file(grammar_file)?;
+        Self::from_grammar(tree_depth, &grammar)
+    }
 }
 
 #[derive(Clone)]
@@ -0,0 +1,47 @@
+//! Generates random [`SchemaInput`]s from a [`Schema`]
+use alloc::sync::Arc;
+use core::marker::PhantomData;
+
+use crate::{
+    generators::Generator,
+    inputs::{
+        schema::{generate_fields, Schema},
+        SchemaInput,
+    },
+    state::HasRand,
+    Error,
+};
+
+#[derive(Clone, Debug)]
+/// Generates random, well-formed inputs for the format described by a [`Schema`]
+pub struct SchemaGenerator<S>
+where
+    S: HasRand,
+{
+    schema: Arc<Schema>,
+    phantom: PhantomData<S>,
+}
+
+impl<S> Generator<SchemaInput, S> for SchemaGenerator<S>
+where
+    S: HasRand,
+{
+    fn generate(&mut self, state: &mut S) -> Result<SchemaInput, Error> {
+        let values = generate_fields(self.schema.fields(), state.rand_mut());
+        Ok(SchemaInput::new(&self.schema, values))
+    }
+}
+
+impl<S> SchemaGenerator<S>
+where
+    S: HasRand,
+{
+    /// Returns a new [`SchemaGenerator`]
+    #[must_use]
+    pub fn new(schema: Arc<Schema>) -> Self {
+        Self {
+            schema,
+            phantom: PhantomData,
+        }
+    }
+}
@@ -15,6 +15,12 @@ pub use generalized::*;
 pub mod bytessub;
 pub use bytessub::BytesSubInput;
 
+pub mod sequence;
+pub use sequence::MessageSequenceInput;
+
+pub mod schema;
+pub use schema::SchemaInput;
+
 #[cfg(feature = "multipart_inputs")]
 pub mod multi;
 #[cfg(feature = "multipart_inputs")]
@@ -128,6 +134,12 @@ impl HasTargetBytes for NopInput {
 pub trait HasTargetBytes {
     /// Target bytes, that can be written to a target
     fn target_bytes(&self) -> OwnedSlice<u8>;
+
+    /// The target bytes as separate messages, for executors that deliver them one by one, such as over a socket.
+    /// Most inputs are a single message.
+    fn target_messages(&self) -> Vec<OwnedSlice<'_, u8>> {
+        vec![self.target_bytes()]
+    }
 }
 
 /// Contains mutateable and resizable bytes
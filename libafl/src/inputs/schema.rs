@@ -0,0 +1,859 @@
+//! Inputs for binary formats described by a declarative [`Schema`], similar to 010 Editor templates or `FormatFuzzer`.
+//!
+//! A [`SchemaInput`] stores the values of the fields, and their serialization for the target.
+//! Mutators change the fields structurally, see [`crate::mutators::schema`],
+//! and length, count and checksum fields are recomputed whenever the input is serialized,
+//! so formats like PNG stay well-formed beyond the fields the fuzzer touched.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::hash::{BuildHasher, Hasher};
+
+use ahash::RandomState;
+use libafl_bolts::{ownedref::OwnedSlice, rands::Rand, HasLen};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    inputs::{HasTargetBytes, Input},
+    Error,
+};
+
+/// How many bytes a generated [`FieldType::Bytes`] field may exceed its minimum length by
+const GENERATED_BYTES_EXTRA_MAX: usize = 64;
+/// How many elements a generated [`FieldType::Array`] field may exceed its minimum length by
+const GENERATED_ITEMS_EXTRA_MAX: usize = 4;
+
+/// The byte order of a multi-byte field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Endian {
+    /// Least significant byte first
+    Little,
+    /// Most significant byte first
+    Big,
+}
+
+/// The algorithm of a [`FieldType::Checksum`] field. All of them are 4 bytes wide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ChecksumKind {
+    /// The CRC-32 used by zlib, PNG and Ethernet
+    Crc32,
+    /// The Adler-32 checksum used by zlib streams
+    Adler32,
+}
+
+impl ChecksumKind {
+    /// Computes this checksum over `data`
+    #[must_use]
+    pub fn compute(self, data: &[u8]) -> u32 {
+        match self {
+            ChecksumKind::Crc32 => crc32(data),
+            ChecksumKind::Adler32 => adler32(data),
+        }
+    }
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0_u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0_u32, |crc, &b| {
+        CRC32_TABLE[((crc ^ u32::from(b)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    let (mut a, mut b) = (1_u32, 0_u32);
+    // 5552 is the largest number of bytes that cannot overflow `b` before the modulo
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= MOD_ADLER;
+        b %= MOD_ADLER;
+    }
+    (b << 16) | a
+}
+
+/// The type of a field, and how it relates to its sibling fields
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FieldType {
+    /// Constant bytes, such as a file signature. Never mutated.
+    Magic(Vec<u8>),
+    /// An unsigned integer of `width` (1, 2, 4 or 8) bytes
+    Int {
+        /// The width in bytes
+        width: usize,
+        /// The byte order
+        endian: Endian,
+    },
+    /// The serialized length in bytes of the sibling field `of`, recomputed on serialization
+    LengthOf {
+        /// The name of the sibling field
+        of: String,
+        /// The width in bytes
+        width: usize,
+        /// The byte order
+        endian: Endian,
+    },
+    /// The number of elements of the sibling [`FieldType::Array`] `of`, recomputed on serialization
+    CountOf {
+        /// The name of the sibling array
+        of: String,
+        /// The width in bytes
+        width: usize,
+        /// The byte order
+        endian: Endian,
+    },
+    /// A checksum over the serialized sibling fields `over`, recomputed on serialization
+    Checksum {
+        /// The algorithm
+        kind: ChecksumKind,
+        /// The byte order
+        endian: Endian,
+        /// The names of the sibling fields, concatenated in this order
+        over: Vec<String>,
+    },
+    /// Raw bytes, between `min_len` and `max_len` long
+    Bytes {
+        /// The minimum length
+        min_len: usize,
+        /// The maximum length
+        max_len: usize,
+    },
+    /// A group of fields
+    Struct(Vec<Field>),
+    /// Repeated elements, each a group of fields, such as the chunks of a PNG
+    Array {
+        /// The fields of each element
+        element: Vec<Field>,
+        /// The minimum number of elements
+        min_len: usize,
+        /// The maximum number of elements
+        max_len: usize,
+    },
+}
+
+impl FieldType {
+    /// The serialized size, if it is the same for all values
+    fn fixed_size(&self) -> Option<usize> {
+        match self {
+            FieldType::Magic(magic) => Some(magic.len()),
+            FieldType::Int { width, .. }
+            | FieldType::LengthOf { width, .. }
+            | FieldType::CountOf { width, .. } => Some(*width),
+            FieldType::Checksum { .. } => Some(4),
+            FieldType::Bytes { min_len, max_len } => (min_len == max_len).then_some(*min_len),
+            FieldType::Struct(fields) => fields_fixed_size(fields),
+            FieldType::Array { .. } => None,
+        }
+    }
+}
+
+fn fields_fixed_size(fields: &[Field]) -> Option<usize> {
+    fields.iter().map(|field| field.ty.fixed_size()).sum()
+}
+
+/// A named field of a [`Schema`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Field {
+    /// The name, unique among its siblings
+    pub name: String,
+    /// The type
+    pub ty: FieldType,
+}
+
+impl Field {
+    /// Creates a new field
+    #[must_use]
+    pub fn new(name: &str, ty: FieldType) -> Self {
+        Self {
+            name: name.to_string(),
+            ty,
+        }
+    }
+
+    /// Creates a [`FieldType::Magic`] field
+    #[must_use]
+    pub fn magic(name: &str, magic: &[u8]) -> Self {
+        Self::new(name, FieldType::Magic(magic.to_vec()))
+    }
+
+    /// Creates a [`FieldType::Int`] field
+    #[must_use]
+    pub fn int(name: &str, width: usize, endian: Endian) -> Self {
+        Self::new(name, FieldType::Int { width, endian })
+    }
+
+    /// Creates a [`FieldType::LengthOf`] field
+    #[must_use]
+    pub fn length_of(name: &str, of: &str, width: usize, endian: Endian) -> Self {
+        Self::new(
+            name,
+            FieldType::LengthOf {
+                of: of.to_string(),
+                width,
+                endian,
+            },
+        )
+    }
+
+    /// Creates a [`FieldType::CountOf`] field
+    #[must_use]
+    pub fn count_of(name: &str, of: &str, width: usize, endian: Endian) -> Self {
+        Self::new(
+            name,
+            FieldType::CountOf {
+                of: of.to_string(),
+                width,
+                endian,
+            },
+        )
+    }
+
+    /// Creates a [`FieldType::Checksum`] field
+    #[must_use]
+    pub fn checksum(name: &str, kind: ChecksumKind, endian: Endian, over: &[&str]) -> Self {
+        Self::new(
+            name,
+            FieldType::Checksum {
+                kind,
+                endian,
+                over: over.iter().map(ToString::to_string).collect(),
+            },
+        )
+    }
+
+    /// Creates a [`FieldType::Bytes`] field
+    #[must_use]
+    pub fn bytes(name: &str, min_len: usize, max_len: usize) -> Self {
+        Self::new(name, FieldType::Bytes { min_len, max_len })
+    }
+
+    /// Creates a [`FieldType::Struct`] field
+    #[must_use]
+    pub fn structure(name: &str, fields: Vec<Field>) -> Self {
+        Self::new(name, FieldType::Struct(fields))
+    }
+
+    /// Creates a [`FieldType::Array`] field
+    #[must_use]
+    pub fn array(name: &str, element: Vec<Field>, min_len: usize, max_len: usize) -> Self {
+        Self::new(
+            name,
+            FieldType::Array {
+                element,
+                min_len,
+                max_len,
+            },
+        )
+    }
+}
+
+/// The value of a field in a [`SchemaInput`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum FieldValue {
+    /// A field whose bytes come from the schema: magic, length, count, or checksum fields
+    Fixed,
+    /// The value of a [`FieldType::Int`]
+    Int(u64),
+    /// The content of a [`FieldType::Bytes`]
+    Bytes(Vec<u8>),
+    /// The fields of a [`FieldType::Struct`]
+    Struct(Vec<FieldValue>),
+    /// The elements of a [`FieldType::Array`], each a [`FieldValue::Struct`]
+    Array(Vec<FieldValue>),
+}
+
+/// A declarative description of a binary format
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Schema {
+    fields: Vec<Field>,
+}
+
+impl Schema {
+    /// Creates a new [`Schema`] from its top-level fields.
+    ///
+    /// Fails if a relation points to a field that is not a sibling, or widths or bounds are invalid.
+    pub fn new(fields: Vec<Field>) -> Result<Self, Error> {
+        validate_fields(&fields)?;
+        Ok(Self { fields })
+    }
+
+    /// The top-level fields
+    #[must_use]
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+}
+
+fn validate_width(name: &str, width: usize) -> Result<(), Error> {
+    if matches!(width, 1 | 2 | 4 | 8) {
+        Ok(())
+    } else {
+        Err(Error::illegal_argument(format!(
+            "Field {name} has width {width}, but only 1, 2, 4 or 8 bytes are supported"
+        )))
+    }
+}
+
+fn validate_fields(fields: &[Field]) -> Result<(), Error> {
+    let sibling = |name: &str, of: &str| {
+        fields
+            .iter()
+            .find(|field| field.name == of && field.name != name)
+            .ok_or_else(|| {
+                Error::illegal_argument(format!("Field {name} refers to unknown sibling {of}"))
+            })
+    };
+
+    for (i, field) in fields.iter().enumerate() {
+        let name = &field.name;
+        if fields[..i].iter().any(|other| other.name == *name) {
+            return Err(Error::illegal_argument(format!(
+                "Field name {name} is used more than once"
+            )));
+        }
+        match &field.ty {
+            FieldType::Int { width, .. } => validate_width(name, *width)?,
+            FieldType::LengthOf { of, width, .. } => {
+                validate_width(name, *width)?;
+                sibling(name, of)?;
+            }
+            FieldType::CountOf { of, width, .. } => {
+                validate_width(name, *width)?;
+                if !matches!(sibling(name, of)?.ty, FieldType::Array { .. }) {
+                    return Err(Error::illegal_argument(format!(
+                        "Field {name} counts the elements of {of}, which is not an array"
+                    )));
+                }
+            }
+            FieldType::Checksum { over, .. } => {
+                for of in over {
+                    if matches!(sibling(name, of)?.ty, FieldType::Checksum { .. }) {
+                        return Err(Error::illegal_argument(format!(
+                            "Checksum {name} covers checksum {of}, which is not supported"
+                        )));
+                    }
+                }
+            }
+            FieldType::Bytes { min_len, max_len }
+            | FieldType::Array {
+                min_len, max_len, ..
+            } if min_len > max_len => {
+                return Err(Error::illegal_argument(format!(
+                    "Field {name} has a minimum length of {min_len}, above its maximum {max_len}"
+                )));
+            }
+            FieldType::Magic(_) | FieldType::Bytes { .. } => {}
+            FieldType::Struct(fields)
+            | FieldType::Array {
+                element: fields, ..
+            } => {
+                validate_fields(fields)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn index_of(fields: &[Field], name: &str) -> usize {
+    fields
+        .iter()
+        .position(|field| field.name == name)
+        .expect("relations are validated when creating the schema")
+}
+
+fn write_uint(out: &mut Vec<u8>, value: u64, width: usize, endian: Endian) {
+    match endian {
+        Endian::Little => out.extend_from_slice(&value.to_le_bytes()[..width]),
+        Endian::Big => out.extend_from_slice(&value.to_be_bytes()[8 - width..]),
+    }
+}
+
+fn read_uint(bytes: &[u8], endian: Endian) -> u64 {
+    let fold = |value: u64, &b: &u8| (value << 8) | u64::from(b);
+    match endian {
+        Endian::Little => bytes.iter().rev().fold(0, fold),
+        Endian::Big => bytes.iter().fold(0, fold),
+    }
+}
+
+/// Serializes the fields, recomputing lengths, counts, and checksums
+fn serialize_fields(fields: &[Field], values: &[FieldValue], out: &mut Vec<u8>) {
+    let mut parts: Vec<Vec<u8>> = Vec::with_capacity(fields.len());
+    for (field, value) in fields.iter().zip(values) {
+        let mut part = Vec::new();
+        match (&field.ty, value) {
+            (FieldType::Magic(magic), _) => part.extend_from_slice(magic),
+            (FieldType::Int { width, endian }, FieldValue::Int(value)) => {
+                write_uint(&mut part, *value, *width, *endian);
+            }
+            (FieldType::Bytes { .. }, FieldValue::Bytes(data)) => part.extend_from_slice(data),
+            (FieldType::Struct(fields), FieldValue::Struct(values)) => {
+                serialize_fields(fields, values, &mut part);
+            }
+            (FieldType::Array { element, .. }, FieldValue::Array(items)) => {
+                for item in items {
+                    if let FieldValue::Struct(values) = item {
+                        serialize_fields(element, values, &mut part);
+                    }
+                }
+            }
+            _ => {}
+        }
+        parts.push(part);
+    }
+
+    // Lengths and counts first, so checksums can cover them
+    for (i, field) in fields.iter().enumerate() {
+        match &field.ty {
+            FieldType::LengthOf { of, width, endian } => {
+                let len = parts[index_of(fields, of)].len() as u64;
+                write_uint(&mut parts[i], len, *width, *endian);
+            }
+            FieldType::CountOf { of, width, endian } => {
+                let count = match values.get(index_of(fields, of)) {
+                    Some(FieldValue::Array(items)) => items.len() as u64,
+                    _ => 0,
+                };
+                write_uint(&mut parts[i], count, *width, *endian);
+            }
+            _ => {}
+        }
+    }
+    for (i, field) in fields.iter().enumerate() {
+        if let FieldType::Checksum { kind, endian, over } = &field.ty {
+            let mut covered = Vec::new();
+            for of in over {
+                covered.extend_from_slice(&parts[index_of(fields, of)]);
+            }
+            write_uint(&mut parts[i], kind.compute(&covered).into(), 4, *endian);
+        }
+    }
+
+    for part in parts {
+        out.extend_from_slice(&part);
+    }
+}
+
+/// A size announced by an earlier [`FieldType::LengthOf`] or [`FieldType::CountOf`] field
+#[derive(Clone, Copy)]
+enum Announced {
+    Length(usize),
+    Count(usize),
+}
+
+/// Fails if a field with an announced length did not consume all of it
+fn check_announced_length(
+    announced: Option<Announced>,
+    len: usize,
+    name: &str,
+) -> Result<(), Error> {
+    match announced {
+        Some(Announced::Length(announced)) if announced != len => Err(Error::illegal_argument(
+            format!("Field {name} has length {announced}, but its content takes {len} bytes"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Parses the fields from the start of `bytes`, returning the values and the number of bytes consumed
+fn parse_fields(fields: &[Field], bytes: &[u8]) -> Result<(Vec<FieldValue>, usize), Error> {
+    let mut values = Vec::with_capacity(fields.len());
+    let mut announced: Vec<Option<Announced>> = alloc::vec![None; fields.len()];
+    let mut pos = 0;
+
+    let take = |pos: usize, len: usize, name: &str| -> Result<&[u8], Error> {
+        pos.checked_add(len)
+            .and_then(|end| bytes.get(pos..end))
+            .ok_or_else(|| {
+                Error::illegal_argument(format!("Input ends in the middle of field {name}"))
+            })
+    };
+
+    for (i, field) in fields.iter().enumerate() {
+        let name = &field.name;
+        // Variable-sized fields without an announced size may take everything but what the following fields need
+        let available = bytes.len() - pos;
+        let greedy = available.saturating_sub(fields_fixed_size(&fields[i + 1..]).unwrap_or(0));
+
+        let (value, len) = match &field.ty {
+            FieldType::Magic(magic) => {
+                if take(pos, magic.len(), name)? != magic.as_slice() {
+                    return Err(Error::illegal_argument(format!(
+                        "Field {name} does not match the expected magic bytes"
+                    )));
+                }
+                (FieldValue::Fixed, magic.len())
+            }
+            FieldType::Int { width, endian } => (
+                FieldValue::Int(read_uint(take(pos, *width, name)?, *endian)),
+                *width,
+            ),
+            FieldType::LengthOf { of, width, endian }
+            | FieldType::CountOf { of, width, endian } => {
+                let size = usize::try_from(read_uint(take(pos, *width, name)?, *endian))
+                    .map_err(|_| Error::illegal_argument(format!("Field {name} is too large")))?;
+                announced[index_of(fields, of)] = Some(match field.ty {
+                    FieldType::LengthOf { .. } => Announced::Length(size),
+                    _ => Announced::Count(size),
+                });
+                (FieldValue::Fixed, *width)
+            }
+            FieldType::Checksum { .. } => {
+                take(pos, 4, name)?;
+                (FieldValue::Fixed, 4)
+            }
+            FieldType::Bytes { min_len, max_len } => {
+                let len = match announced[i] {
+                    Some(Announced::Length(len)) => len,
+                    _ if min_len == max_len => *min_len,
+                    _ => greedy.min(*max_len),
+                };
+                if len < *min_len || len > *max_len {
+                    return Err(Error::illegal_argument(format!(
+                        "Field {name} has length {len}, outside of {min_len}..={max_len}"
+                    )));
+                }
+                (FieldValue::Bytes(take(pos, len, name)?.to_vec()), len)
+            }
+            FieldType::Struct(fields) => {
+                let scope = match announced[i] {
+                    Some(Announced::Length(len)) => take(pos, len, name)?,
+                    _ => &bytes[pos..],
+                };
+                let (values, len) = parse_fields(fields, scope)?;
+                check_announced_length(announced[i], len, name)?;
+                (FieldValue::Struct(values), len)
+            }
+            FieldType::Array {
+                element,
+                min_len,
+                max_len,
+            } => {
+                let (scope, count) = match announced[i] {
+                    Some(Announced::Length(len)) => (take(pos, len, name)?, None),
+                    Some(Announced::Count(count)) => (&bytes[pos..], Some(count)),
+                    None => (&bytes[pos..pos + greedy], None),
+                };
+                let mut items = Vec::new();
+                let mut len = 0;
+                while count.map_or(len < scope.len(), |count| items.len() < count) {
+                    let (values, item_len) = parse_fields(element, &scope[len..])?;
+                    if item_len == 0 {
+                        break;
+                    }
+                    items.push(FieldValue::Struct(values));
+                    len += item_len;
+                }
+                if items.len() < *min_len || items.len() > *max_len {
+                    return Err(Error::illegal_argument(format!(
+                        "Field {name} has {} elements, outside of {min_len}..={max_len}",
+                        items.len()
+                    )));
+                }
+                check_announced_length(announced[i], len, name)?;
+                (FieldValue::Array(items), len)
+            }
+        };
+        values.push(value);
+        pos += len;
+    }
+    Ok((values, pos))
+}
+
+/// Generates random values for the fields
+pub(crate) fn generate_fields<R>(fields: &[Field], rand: &mut R) -> Vec<FieldValue>
+where
+    R: Rand,
+{
+    fields
+        .iter()
+        .map(|field| match &field.ty {
+            FieldType::Magic(_)
+            | FieldType::LengthOf { .. }
+            | FieldType::CountOf { .. }
+            | FieldType::Checksum { .. } => FieldValue::Fixed,
+            FieldType::Int { width, .. } => FieldValue::Int(truncate(rand.next(), *width)),
+            FieldType::Bytes { min_len, max_len } => {
+                let max_len = (*max_len).min(min_len + GENERATED_BYTES_EXTRA_MAX);
+                let len = rand.between(*min_len, max_len);
+                FieldValue::Bytes((0..len).map(|_| rand.below(256) as u8).collect())
+            }
+            FieldType::Struct(fields) => FieldValue::Struct(generate_fields(fields, rand)),
+            FieldType::Array {
+                element,
+                min_len,
+                max_len,
+            } => {
+                let max_len = (*max_len).min(min_len + GENERATED_ITEMS_EXTRA_MAX);
+                let count = rand.between(*min_len, max_len);
+                FieldValue::Array(
+                    (0..count)
+                        .map(|_| FieldValue::Struct(generate_fields(element, rand)))
+                        .collect(),
+                )
+            }
+        })
+        .collect()
+}
+
+/// Keeps the lowest `width` bytes of `value`
+#[must_use]
+pub(crate) fn truncate(value: u64, width: usize) -> u64 {
+    if width >= 8 {
+        value
+    } else {
+        value & ((1 << (width * 8)) - 1)
+    }
+}
+
+/// Calls `visit` for every field, depth-first, until it returns `true`
+fn visit_fields_mut<F>(fields: &[Field], values: &mut [FieldValue], visit: &mut F) -> bool
+where
+    F: FnMut(&FieldType, &mut FieldValue) -> bool,
+{
+    for (field, value) in fields.iter().zip(values.iter_mut()) {
+        if visit(&field.ty, value) {
+            return true;
+        }
+        let done = match (&field.ty, value) {
+            (FieldType::Struct(fields), FieldValue::Struct(values)) => {
+                visit_fields_mut(fields, values, visit)
+            }
+            (FieldType::Array { element, .. }, FieldValue::Array(items)) => {
+                items.iter_mut().any(|item| match item {
+                    FieldValue::Struct(values) => visit_fields_mut(element, values, visit),
+                    _ => false,
+                })
+            }
+            _ => false,
+        };
+        if done {
+            return true;
+        }
+    }
+    false
+}
+
+/// An input for a binary format described by a [`Schema`].
+/// It does not own the schema: the generator and the mutators share it, and pass it in where it is needed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SchemaInput {
+    values: Vec<FieldValue>,
+    /// The serialized values, refreshed whenever they change
+    bytes: Vec<u8>,
+}
+
+impl SchemaInput {
+    /// Creates a new [`SchemaInput`] with the given values for the top-level fields of the schema.
+    /// Use [`FieldValue::Fixed`] for magic, length, count, and checksum fields.
+    #[must_use]
+    pub fn new(schema: &Schema, values: Vec<FieldValue>) -> Self {
+        let mut input = Self {
+            values,
+            bytes: Vec::new(),
+        };
+        input.refresh_bytes(schema);
+        input
+    }
+
+    /// Parses an existing file of the format, e.g. to use it as seed.
+    /// Lengths and counts are taken from the file, checksums are ignored.
+    pub fn parse(schema: &Schema, bytes: &[u8]) -> Result<Self, Error> {
+        let (values, len) = parse_fields(schema.fields(), bytes)?;
+        if len != bytes.len() {
+            return Err(Error::illegal_argument(format!(
+                "{} trailing bytes after the last field",
+                bytes.len() - len
+            )));
+        }
+        Ok(Self::new(schema, values))
+    }
+
+    /// The values of the top-level fields
+    #[must_use]
+    pub fn values(&self) -> &[FieldValue] {
+        &self.values
+    }
+
+    /// The serialized input, with up-to-date lengths, counts, and checksums
+    #[must_use]
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Serializes the values again, after they changed
+    fn refresh_bytes(&mut self, schema: &Schema) {
+        self.bytes.clear();
+        serialize_fields(schema.fields(), &self.values, &mut self.bytes);
+    }
+
+    /// The number of fields, at any depth, for which `filter` is `true`
+    pub fn count_fields<F>(&mut self, schema: &Schema, mut filter: F) -> usize
+    where
+        F: FnMut(&FieldType) -> bool,
+    {
+        let mut count = 0;
+        visit_fields_mut(schema.fields(), &mut self.values, &mut |ty, _| {
+            if filter(ty) {
+                count += 1;
+            }
+            false
+        });
+        count
+    }
+
+    /// Calls `mutate` with the `n`th field, in depth-first order, for which `filter` is `true`,
+    /// and serializes the input again.
+    /// Returns the result of `mutate`, or `None` if there are not enough such fields.
+    pub fn mutate_nth_field<F, M, R>(
+        &mut self,
+        schema: &Schema,
+        n: usize,
+        mut filter: F,
+        mutate: M,
+    ) -> Option<R>
+    where
+        F: FnMut(&FieldType) -> bool,
+        M: FnOnce(&FieldType, &mut FieldValue) -> R,
+    {
+        let mut seen = 0;
+        let mut mutate = Some(mutate);
+        let mut result = None;
+        visit_fields_mut(schema.fields(), &mut self.values, &mut |ty, value| {
+            if !filter(ty) {
+                return false;
+            }
+            if seen < n {
+                seen += 1;
+                return false;
+            }
+            result = mutate.take().map(|mutate| mutate(ty, value));
+            true
+        });
+        if result.is_some() {
+            self.refresh_bytes(schema);
+        }
+        result
+    }
+}
+
+impl Input for SchemaInput {
+    fn generate_name(&self, _idx: usize) -> String {
+        let mut hasher = RandomState::with_seeds(0, 0, 0, 0).build_hasher();
+        hasher.write(&self.bytes);
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+impl HasTargetBytes for SchemaInput {
+    fn target_bytes(&self) -> OwnedSlice<u8> {
+        OwnedSlice::from(&self.bytes)
+    }
+}
+
+impl HasLen for SchemaInput {
+    /// The number of serialized bytes
+    fn len(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::{
+        adler32, ChecksumKind, Endian, Field, FieldValue, Schema, SchemaInput, CRC32_TABLE,
+    };
+
+    /// A PNG-like format: a signature, followed by length-prefixed, CRC-protected chunks
+    fn png_schema() -> Schema {
+        Schema::new(vec![
+            Field::magic("signature", b"\x89PNG\r\n\x1a\n"),
+            Field::array(
+                "chunks",
+                vec![
+                    Field::length_of("length", "data", 4, Endian::Big),
+                    Field::bytes("type", 4, 4),
+                    Field::bytes("data", 0, 1 << 16),
+                    Field::checksum("crc", ChecksumKind::Crc32, Endian::Big, &["type", "data"]),
+                ],
+                1,
+                16,
+            ),
+        ])
+        .unwrap()
+    }
+
+    fn chunk(ty: &[u8], data: &[u8]) -> FieldValue {
+        FieldValue::Struct(vec![
+            FieldValue::Fixed,
+            FieldValue::Bytes(ty.to_vec()),
+            FieldValue::Bytes(data.to_vec()),
+            FieldValue::Fixed,
+        ])
+    }
+
+    #[test]
+    fn test_schema_fixups_and_parse() {
+        assert_eq!(CRC32_TABLE[1], 0x7707_3096);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+
+        let schema = png_schema();
+        let input = SchemaInput::new(
+            &schema,
+            vec![
+                FieldValue::Fixed,
+                FieldValue::Array(vec![chunk(b"tEXt", b"hello"), chunk(b"IEND", b"")]),
+            ],
+        );
+        let bytes = input.bytes();
+        let iend: Vec<u8> = bytes[bytes.len() - 12..].to_vec();
+        assert_eq!(iend, b"\0\0\0\0IEND\xae\x42\x60\x82");
+        assert_eq!(&bytes[8..12], &[0, 0, 0, 5]);
+
+        let parsed = SchemaInput::parse(&schema, bytes).unwrap();
+        assert_eq!(parsed, input);
+        assert!(SchemaInput::parse(&schema, &bytes[..bytes.len() - 1]).is_err());
+
+        assert!(Schema::new(vec![Field::length_of("len", "missing", 4, Endian::Big)]).is_err());
+        assert!(Schema::new(vec![Field::int("odd", 3, Endian::Big)]).is_err());
+    }
+
+    #[test]
+    fn test_schema_announced_length() {
+        let schema = Schema::new(vec![
+            Field::length_of("length", "header", 1, Endian::Big),
+            Field::structure("header", vec![Field::int("version", 2, Endian::Big)]),
+            Field::bytes("rest", 0, 16),
+        ])
+        .unwrap();
+        let input = SchemaInput::parse(&schema, b"\x02\x00\x01tail").unwrap();
+        assert_eq!(input.bytes(), b"\x02\x00\x01tail");
+        // The header announces 3 bytes, but its only field takes 2
+        assert!(SchemaInput::parse(&schema, b"\x03\x00\x01tail").is_err());
+    }
+}
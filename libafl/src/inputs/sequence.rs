@@ -0,0 +1,114 @@
+//! Inputs for stateful targets, such as network protocol servers, that consume an ordered sequence of messages.
+//!
+//! Each message has the same type `I`, which can be an enum if a protocol has several kinds of messages.
+//! Mutators for the structure of the sequence live in [`crate::mutators::sequence`].
+
+use alloc::{string::String, vec::Vec};
+use core::hash::{BuildHasher, Hasher};
+
+use ahash::RandomState;
+use libafl_bolts::{ownedref::OwnedSlice, HasLen};
+use serde::{Deserialize, Serialize};
+
+use crate::inputs::{HasTargetBytes, Input};
+
+/// An input made of an ordered sequence of messages, sent to the target one after the other.
+///
+/// Only executors delivering [`HasTargetBytes::target_messages`] send the messages separately,
+/// such as the executors with a [`crate::executors::socket::SocketInput`]. All other executors
+/// deliver the concatenated [`HasTargetBytes::target_bytes`].
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct MessageSequenceInput<I> {
+    messages: Vec<I>,
+}
+
+impl<I> Default for MessageSequenceInput<I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I> MessageSequenceInput<I> {
+    /// Creates a new, empty message sequence.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            messages: Vec::new(),
+        }
+    }
+
+    /// The messages, in the order they are sent.
+    #[must_use]
+    pub fn messages(&self) -> &[I] {
+        &self.messages
+    }
+
+    /// The messages, mutably. The order of this [`Vec`] is the order the messages are sent in.
+    #[must_use]
+    pub fn messages_mut(&mut self) -> &mut Vec<I> {
+        &mut self.messages
+    }
+
+    /// Appends a message to the end of the sequence.
+    pub fn push(&mut self, message: I) {
+        self.messages.push(message);
+    }
+}
+
+impl<I> From<Vec<I>> for MessageSequenceInput<I> {
+    fn from(messages: Vec<I>) -> Self {
+        Self { messages }
+    }
+}
+
+impl<I> FromIterator<I> for MessageSequenceInput<I> {
+    fn from_iter<It: IntoIterator<Item = I>>(iter: It) -> Self {
+        Self {
+            messages: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<I> HasLen for MessageSequenceInput<I> {
+    /// The number of messages
+    fn len(&self) -> usize {
+        self.messages.len()
+    }
+}
+
+impl<I> Input for MessageSequenceInput<I>
+where
+    I: Input,
+{
+    fn generate_name(&self, idx: usize) -> String {
+        let mut hasher = RandomState::with_seeds(0, 0, 0, 0).build_hasher();
+        for message in &self.messages {
+            hasher.write(message.generate_name(idx).as_bytes());
+        }
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+impl<I> HasTargetBytes for MessageSequenceInput<I>
+where
+    I: HasTargetBytes,
+{
+    /// All messages, concatenated.
+    /// Targets that need to tell messages apart must either be able to split the stream themselves,
+    /// or get each message delivered separately, see [`HasTargetBytes::target_messages`].
+    fn target_bytes(&self) -> OwnedSlice<u8> {
+        let mut bytes = Vec::new();
+        for message in &self.messages {
+            bytes.extend_from_slice(&message.target_bytes());
+        }
+        OwnedSlice::from(bytes)
+    }
+
+    /// Each message on its own, in order
+    fn target_messages(&self) -> Vec<OwnedSlice<'_, u8>> {
+        self.messages
+            .iter()
+            .map(HasTargetBytes::target_bytes)
+            .collect()
+    }
+}
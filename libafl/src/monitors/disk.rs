@@ -1,17 +1,26 @@
 //! Monitors that wrap a base one and log on disk
 
-use alloc::{string::String, vec::Vec};
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+    vec::Vec,
+};
 use core::time::Duration;
 use std::{
-    fs::{File, OpenOptions},
-    io::Write,
-    path::PathBuf,
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process,
 };
 
 use libafl_bolts::{current_time, format_duration_hms, ClientId};
-use serde_json::json;
+use serde_json::{json, Value};
 
-use crate::monitors::{ClientStats, Monitor, NopMonitor};
+use crate::{
+    corpus::afl_queue::{AFL_PLOT_DATA_FILE, AFL_PLOT_DATA_HEADER},
+    monitors::{ClientStats, Monitor, NopMonitor, UserStats, UserStatsValue},
+};
 
 /// Wrap a monitor and log the current state of the monitor into a TOML file.
 #[derive(Debug, Clone)]
@@ -230,3 +239,414 @@ where
         self.base.display(event_msg, sender_id);
     }
 }
+
+/// The values of one AFL++ `fuzzer_stats` file, of one client or aggregated over all clients
+#[derive(Debug, Clone, Default)]
+struct AflStats {
+    start_time: Duration,
+    cycles_done: u64,
+    execs_done: u64,
+    execs_per_sec: f64,
+    corpus_count: u64,
+    corpus_favored: u64,
+    corpus_found: u64,
+    corpus_imported: u64,
+    pending_total: u64,
+    pending_favs: u64,
+    /// The stability in percent, if calibration reported it
+    stability: Option<f64>,
+    edges_found: u64,
+    total_edges: u64,
+    saved_crashes: u64,
+    saved_hangs: u64,
+    last_find: Duration,
+    last_crash: Duration,
+}
+
+/// The filled and total entries of a ratio user stat
+fn user_stats_ratio(stats: Option<&UserStats>) -> Option<(u64, u64)> {
+    match stats?.value() {
+        UserStatsValue::Ratio(filled, total) if *total > 0 => Some((*filled, *total)),
+        _ => None,
+    }
+}
+
+impl AflStats {
+    /// Collects the stats of a client from the [`ClientStats`] and the user stats of the `AflStatsStage`,
+    /// the calibration stage, and the map feedback reporting as `map_stats_name`
+    #[allow(clippy::cast_precision_loss)]
+    fn from_client(client: &mut ClientStats, map_stats_name: &str, cur_time: Duration) -> Self {
+        let afl_stats: Value = match client.get_user_stats("AflStats").map(UserStats::value) {
+            Some(UserStatsValue::String(json)) => serde_json::from_str(json).unwrap_or_default(),
+            _ => Value::Null,
+        };
+        let afl_stat = |name: &str| afl_stats[name].as_u64().unwrap_or_default();
+        let (edges_found, total_edges) =
+            user_stats_ratio(client.get_user_stats(map_stats_name)).unwrap_or_default();
+        let stability = user_stats_ratio(client.get_user_stats("stability"))
+            .map(|(stable, total)| stable as f64 * 100.0 / total as f64);
+        let saved_hangs = client
+            .objectives_by_exit_kind
+            .get("timeout")
+            .copied()
+            .unwrap_or_default();
+
+        Self {
+            start_time: client.start_time,
+            cycles_done: afl_stat("cycles_done"),
+            execs_done: client.executions,
+            execs_per_sec: client.execs_per_sec(cur_time),
+            corpus_count: client.corpus_size,
+            corpus_favored: afl_stat("favored"),
+            corpus_found: afl_stat("own_finds"),
+            corpus_imported: afl_stat("imported"),
+            pending_total: afl_stat("pending"),
+            pending_favs: afl_stat("pend_fav"),
+            stability,
+            edges_found,
+            total_edges,
+            saved_crashes: client.objective_size.saturating_sub(saved_hangs),
+            saved_hangs,
+            last_find: client.last_corpus_time,
+            last_crash: client.last_objective_time,
+        }
+    }
+
+    /// Aggregates the stats of all clients: counts are summed up, coverage is the best of all clients
+    #[allow(clippy::cast_precision_loss)]
+    fn aggregate(clients: &[Self], start_time: Duration) -> Self {
+        let mut total = Self {
+            start_time,
+            ..Self::default()
+        };
+        let mut stabilities = Vec::new();
+        for client in clients {
+            total.cycles_done = total.cycles_done.max(client.cycles_done);
+            total.execs_done += client.execs_done;
+            total.execs_per_sec += client.execs_per_sec;
+            total.corpus_count += client.corpus_count;
+            total.corpus_favored += client.corpus_favored;
+            total.corpus_found += client.corpus_found;
+            total.corpus_imported += client.corpus_imported;
+            total.pending_total += client.pending_total;
+            total.pending_favs += client.pending_favs;
+            stabilities.extend(client.stability);
+            if client.edges_found > total.edges_found {
+                total.edges_found = client.edges_found;
+                total.total_edges = client.total_edges;
+            }
+            total.saved_crashes += client.saved_crashes;
+            total.saved_hangs += client.saved_hangs;
+            total.last_find = total.last_find.max(client.last_find);
+            total.last_crash = total.last_crash.max(client.last_crash);
+        }
+        if !stabilities.is_empty() {
+            total.stability = Some(stabilities.iter().sum::<f64>() / stabilities.len() as f64);
+        }
+        total
+    }
+
+    /// The share of the map that is covered, in percent
+    #[allow(clippy::cast_precision_loss)]
+    fn bitmap_cvg(&self) -> f64 {
+        if self.total_edges == 0 {
+            0.0
+        } else {
+            self.edges_found as f64 * 100.0 / self.total_edges as f64
+        }
+    }
+}
+
+/// Wraps a base monitor and maintains the `fuzzer_stats` and `plot_data` files of AFL++,
+/// for tools like `afl-whatsup`, `afl-plot`, and dashboards built for AFL++.
+///
+/// Each client gets its own `client_<id>` directory in the output directory, which `afl-whatsup` reads like the
+/// directories of AFL++ instances syncing with each other. The stats aggregated over all clients are written to
+/// the output directory itself.
+///
+/// Corpus and objective counts come from the monitor itself. For pending and favored entries and queue cycles,
+/// add a [`crate::stages::AflStatsStage`], for the stability, a [`crate::stages::CalibrationStage`].
+/// The map density is the user stat of a map feedback, named after its map observer, `edges` by default.
+#[derive(Debug, Clone)]
+pub struct OnDiskAflStatsMonitor<M>
+where
+    M: Monitor,
+{
+    base: M,
+    out_dir: PathBuf,
+    map_stats_name: Cow<'static, str>,
+    last_update: Duration,
+    update_interval: Duration,
+}
+
+impl<M> Monitor for OnDiskAflStatsMonitor<M>
+where
+    M: Monitor,
+{
+    /// The client monitor, mutable
+    fn client_stats_mut(&mut self) -> &mut Vec<ClientStats> {
+        self.base.client_stats_mut()
+    }
+
+    /// The client monitor
+    fn client_stats(&self) -> &[ClientStats] {
+        self.base.client_stats()
+    }
+
+    /// Time this fuzzing run stated
+    fn start_time(&self) -> Duration {
+        self.base.start_time()
+    }
+
+    /// Set creation time
+    fn set_start_time(&mut self, time: Duration) {
+        self.base.set_start_time(time);
+    }
+
+    fn aggregate(&mut self, name: &str) {
+        self.base.aggregate(name);
+    }
+
+    fn display(&mut self, event_msg: &str, sender_id: ClientId) {
+        let cur_time = current_time();
+
+        if cur_time.saturating_sub(self.last_update) >= self.update_interval {
+            self.last_update = cur_time;
+
+            let mut clients = Vec::new();
+            let mut client_ids = Vec::new();
+            for (id, client) in self.base.client_stats_mut().iter_mut().enumerate() {
+                if client.enabled {
+                    clients.push(AflStats::from_client(
+                        client,
+                        &self.map_stats_name,
+                        cur_time,
+                    ));
+                    client_ids.push(id);
+                }
+            }
+            for (id, stats) in client_ids.iter().zip(&clients) {
+                let dir = self.out_dir.join(format!("client_{id}"));
+                Self::write_stats(&dir, stats, cur_time).expect("Failed to write the AFL++ stats");
+            }
+            let total = AflStats::aggregate(&clients, self.start_time());
+            Self::write_stats(&self.out_dir, &total, cur_time)
+                .expect("Failed to write the AFL++ stats");
+        }
+
+        self.base.display(event_msg, sender_id);
+    }
+}
+
+impl<M> OnDiskAflStatsMonitor<M>
+where
+    M: Monitor,
+{
+    /// Create new [`OnDiskAflStatsMonitor`], writing to `out_dir` every 5 seconds, as often as AFL++ plots
+    #[must_use]
+    pub fn new<P>(out_dir: P, base: M) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self::with_update_interval(out_dir, base, Duration::from_secs(5))
+    }
+
+    /// Create new [`OnDiskAflStatsMonitor`] with custom update interval
+    #[must_use]
+    pub fn with_update_interval<P>(out_dir: P, base: M, update_interval: Duration) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            base,
+            out_dir: out_dir.into(),
+            map_stats_name: Cow::Borrowed("edges"),
+            last_update: current_time().saturating_sub(update_interval),
+            update_interval,
+        }
+    }
+
+    /// Sets the name of the user stat the map density is taken from, the name of the map observer
+    #[must_use]
+    pub fn with_map_stats_name(mut self, map_stats_name: Cow<'static, str>) -> Self {
+        self.map_stats_name = map_stats_name;
+        self
+    }
+
+    /// Writes the `fuzzer_stats` file in `dir` and appends a row to its `plot_data`
+    fn write_stats(dir: &Path, stats: &AflStats, cur_time: Duration) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        let run_time = cur_time.saturating_sub(stats.start_time).as_secs();
+        let since = |time: Duration| {
+            if time > stats.start_time {
+                cur_time.saturating_sub(time).as_secs()
+            } else {
+                run_time
+            }
+        };
+        let stability = stats
+            .stability
+            .map_or_else(|| "0.00%".into(), |stability| format!("{stability:.2}%"));
+        let command_line = env::args().collect::<Vec<_>>().join(" ");
+        let banner = env::args()
+            .next()
+            .and_then(|arg| Some(Path::new(&arg).file_name()?.to_string_lossy().into_owned()))
+            .unwrap_or_default();
+
+        let fields: [(&str, String); 30] = [
+            ("start_time", stats.start_time.as_secs().to_string()),
+            ("last_update", cur_time.as_secs().to_string()),
+            ("run_time", run_time.to_string()),
+            ("fuzzer_pid", process::id().to_string()),
+            ("cycles_done", stats.cycles_done.to_string()),
+            ("cycles_wo_finds", "0".into()),
+            ("time_wo_finds", since(stats.last_find).to_string()),
+            ("execs_done", stats.execs_done.to_string()),
+            ("execs_per_sec", format!("{:.2}", stats.execs_per_sec)),
+            ("corpus_count", stats.corpus_count.to_string()),
+            ("corpus_favored", stats.corpus_favored.to_string()),
+            ("corpus_found", stats.corpus_found.to_string()),
+            ("corpus_imported", stats.corpus_imported.to_string()),
+            ("max_depth", "0".into()),
+            ("cur_item", "0".into()),
+            ("pending_favs", stats.pending_favs.to_string()),
+            ("pending_total", stats.pending_total.to_string()),
+            ("stability", stability),
+            ("bitmap_cvg", format!("{:.2}%", stats.bitmap_cvg())),
+            ("saved_crashes", stats.saved_crashes.to_string()),
+            ("saved_hangs", stats.saved_hangs.to_string()),
+            ("last_find", stats.last_find.as_secs().to_string()),
+            ("last_crash", stats.last_crash.as_secs().to_string()),
+            ("last_hang", "0".into()),
+            ("edges_found", stats.edges_found.to_string()),
+            ("total_edges", stats.total_edges.to_string()),
+            ("afl_banner", banner),
+            (
+                "afl_version",
+                format!("libafl-{}", env!("CARGO_PKG_VERSION")),
+            ),
+            ("target_mode", "default".into()),
+            ("command_line", command_line),
+        ];
+
+        // write and rename, so that readers never see a partial file
+        let tmp_path = dir.join(".fuzzer_stats.tmp");
+        let mut file = File::create(&tmp_path)?;
+        for (key, value) in fields {
+            writeln!(file, "{key:<18}: {value}")?;
+        }
+        drop(file);
+        fs::rename(tmp_path, dir.join("fuzzer_stats"))?;
+
+        let plot_path = dir.join(AFL_PLOT_DATA_FILE);
+        let new_plot = !plot_path.exists();
+        let mut plot = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(plot_path)?;
+        if new_plot {
+            writeln!(plot, "{AFL_PLOT_DATA_HEADER}")?;
+        }
+        writeln!(
+            plot,
+            "{}, {}, 0, {}, {}, {}, {:.2}%, {}, {}, 0, {:.2}, {}, {}",
+            run_time,
+            stats.cycles_done,
+            stats.corpus_count,
+            stats.pending_total,
+            stats.pending_favs,
+            stats.bitmap_cvg(),
+            stats.saved_crashes,
+            stats.saved_hangs,
+            stats.execs_per_sec,
+            stats.execs_done,
+            stats.edges_found
+        )
+    }
+}
+
+impl OnDiskAflStatsMonitor<NopMonitor> {
+    /// Create new [`OnDiskAflStatsMonitor`] without a base
+    #[must_use]
+    pub fn nop<P>(out_dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self::new(out_dir, NopMonitor::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{
+        borrow::Cow,
+        string::{String, ToString},
+        vec::Vec,
+    };
+    use core::time::Duration;
+    use std::{env, fs, process};
+
+    use libafl_bolts::ClientId;
+
+    use super::OnDiskAflStatsMonitor;
+    use crate::monitors::{AggregatorOps, Monitor, NopMonitor, UserStats, UserStatsValue};
+
+    #[test]
+    fn test_afl_stats_monitor() {
+        let dir = env::temp_dir().join(format!("libafl_afl_stats_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut monitor =
+            OnDiskAflStatsMonitor::with_update_interval(&dir, NopMonitor::new(), Duration::ZERO);
+
+        for (id, corpus_size, covered) in [(1, 10, 30), (2, 20, 40)] {
+            monitor.client_stats_insert(ClientId(id));
+            let client = monitor.client_stats_mut_for(ClientId(id));
+            client.update_corpus_size(corpus_size);
+            client.update_executions(1000, client.start_time);
+            client.update_user_stats(
+                Cow::Borrowed("edges"),
+                UserStats::new(UserStatsValue::Ratio(covered, 100), AggregatorOps::Avg),
+            );
+            client.update_user_stats(
+                Cow::Borrowed("AflStats"),
+                UserStats::new(
+                    UserStatsValue::String(Cow::Borrowed(
+                        r#"{"pending":3,"pend_fav":1,"favored":2,"own_finds":5,"imported":4,"cycles_done":1}"#,
+                    )),
+                    AggregatorOps::None,
+                ),
+            );
+        }
+        monitor.display("Test", ClientId(1));
+        monitor.display("Test", ClientId(2));
+
+        let stats = fs::read_to_string(dir.join("fuzzer_stats")).unwrap();
+        let field = |stats: &str, key: &str| -> String {
+            stats
+                .lines()
+                .find_map(|line| {
+                    let (k, v) = line.split_once(':')?;
+                    (k.trim() == key).then(|| v.trim().into())
+                })
+                .unwrap()
+        };
+        assert_eq!(field(&stats, "corpus_count"), "30");
+        assert_eq!(field(&stats, "execs_done"), "2000");
+        assert_eq!(field(&stats, "pending_total"), "6");
+        assert_eq!(field(&stats, "corpus_favored"), "4");
+        assert_eq!(field(&stats, "bitmap_cvg"), "40.00%");
+        assert_eq!(field(&stats, "fuzzer_pid"), process::id().to_string());
+
+        let client_stats = fs::read_to_string(dir.join("client_1").join("fuzzer_stats")).unwrap();
+        assert_eq!(field(&client_stats, "corpus_count"), "10");
+        assert_eq!(field(&client_stats, "edges_found"), "30");
+
+        let plot = fs::read_to_string(dir.join("plot_data")).unwrap();
+        let lines: Vec<&str> = plot.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("# relative_time"));
+        assert_eq!(lines[1].split(", ").count(), 13);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,236 @@
+//! Monitor that wraps a base one and adds statistics per configuration of an ensemble run,
+//! where different clients run differently configured fuzzers.
+
+use alloc::{string::String, vec::Vec};
+use core::{
+    fmt::{self, Debug, Formatter},
+    time::Duration,
+};
+
+use libafl_bolts::{current_time, ClientId};
+
+use crate::monitors::{prettify_float, ClientStats, Monitor, UserStatsValue};
+
+/// The name of the user stat each client of an ensemble reports its configuration name in
+pub const ENSEMBLE_MEMBER_STAT: &str = "ensemble";
+
+/// The statistics of all clients running one configuration of an ensemble
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnsembleStats {
+    /// The name of the configuration
+    pub name: String,
+    /// The number of clients running this configuration
+    pub clients: usize,
+    /// The number of new corpus entries found by these clients themselves, i.e., not imported from others
+    pub found: u64,
+    /// The number of objectives found by these clients
+    pub objective_size: u64,
+    /// The executions of these clients
+    pub executions: u64,
+    /// The executions per second of these clients
+    pub execs_per_sec: f64,
+}
+
+/// Wraps a monitor and additionally displays, for each event, the statistics of the configuration of the sending client.
+///
+/// Clients tell which configuration they run with the [`ENSEMBLE_MEMBER_STAT`] user stat,
+/// see `EnsembleMember::announce` for the `Launcher`.
+/// Clients that did not announce a configuration are not part of any [`EnsembleStats`].
+#[derive(Clone)]
+pub struct EnsembleMonitor<M, F>
+where
+    M: Monitor,
+    F: FnMut(&str),
+{
+    base: M,
+    print_fn: F,
+    /// The number of `Testcase` events of each client
+    found: Vec<u64>,
+}
+
+impl<M, F> Debug for EnsembleMonitor<M, F>
+where
+    M: Monitor + Debug,
+    F: FnMut(&str),
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EnsembleMonitor")
+            .field("base", &self.base)
+            .field("found", &self.found)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<M, F> Monitor for EnsembleMonitor<M, F>
+where
+    M: Monitor,
+    F: FnMut(&str),
+{
+    /// The client monitor, mutable
+    fn client_stats_mut(&mut self) -> &mut Vec<ClientStats> {
+        self.base.client_stats_mut()
+    }
+
+    /// The client monitor
+    fn client_stats(&self) -> &[ClientStats] {
+        self.base.client_stats()
+    }
+
+    /// Time this fuzzing run stated
+    fn start_time(&self) -> Duration {
+        self.base.start_time()
+    }
+
+    /// Set creation time
+    fn set_start_time(&mut self, time: Duration) {
+        self.base.set_start_time(time);
+    }
+
+    fn aggregate(&mut self, name: &str) {
+        self.base.aggregate(name);
+    }
+
+    fn display(&mut self, event_msg: &str, sender_id: ClientId) {
+        let idx = sender_id.0 as usize;
+        if event_msg == "Testcase" {
+            if self.found.len() <= idx {
+                self.found.resize(idx + 1, 0);
+            }
+            self.found[idx] += 1;
+        }
+
+        self.base.display(event_msg, sender_id);
+
+        let Some(name) = self
+            .client_stats()
+            .get(idx)
+            .and_then(ensemble_member)
+            .map(String::from)
+        else {
+            return;
+        };
+        if let Some(stats) = self
+            .ensemble_stats()
+            .into_iter()
+            .find(|stats| stats.name == name)
+        {
+            let fmt = format!(
+                "[Ensemble {}] clients: {}, found: {}, objectives: {}, executions: {}, exec/sec: {}",
+                stats.name,
+                stats.clients,
+                stats.found,
+                stats.objective_size,
+                stats.executions,
+                prettify_float(stats.execs_per_sec)
+            );
+            (self.print_fn)(&fmt);
+        }
+    }
+}
+
+impl<M, F> EnsembleMonitor<M, F>
+where
+    M: Monitor,
+    F: FnMut(&str),
+{
+    /// Wraps the `base` monitor, printing the statistics of each configuration with `print_fn`
+    pub fn new(base: M, print_fn: F) -> Self {
+        Self {
+            base,
+            print_fn,
+            found: Vec::new(),
+        }
+    }
+
+    /// The wrapped monitor
+    pub fn base(&self) -> &M {
+        &self.base
+    }
+
+    /// The statistics of each configuration that announced itself so far, sorted by name
+    pub fn ensemble_stats(&mut self) -> Vec<EnsembleStats> {
+        let cur_time = current_time();
+        let mut all: Vec<EnsembleStats> = Vec::new();
+        let found = &self.found;
+        for (idx, client) in self.base.client_stats_mut().iter_mut().enumerate() {
+            let Some(name) = ensemble_member(client) else {
+                continue;
+            };
+            let pos = if let Some(pos) = all.iter().position(|stats| stats.name == name) {
+                pos
+            } else {
+                all.push(EnsembleStats {
+                    name: String::from(name),
+                    ..EnsembleStats::default()
+                });
+                all.len() - 1
+            };
+            let execs_per_sec = client.execs_per_sec(cur_time);
+            let stats = &mut all[pos];
+            stats.clients += 1;
+            stats.found += found.get(idx).copied().unwrap_or(0);
+            stats.objective_size += client.objective_size;
+            stats.executions += client.executions;
+            stats.execs_per_sec += execs_per_sec;
+        }
+        all.sort_by(|a, b| a.name.cmp(&b.name));
+        all
+    }
+}
+
+/// The configuration a client announced, if any
+fn ensemble_member(client: &ClientStats) -> Option<&str> {
+    if !client.enabled {
+        return None;
+    }
+    match client.get_user_stats(ENSEMBLE_MEMBER_STAT)?.value() {
+        UserStatsValue::String(name) => Some(name),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::borrow::Cow;
+
+    use libafl_bolts::ClientId;
+
+    use super::{EnsembleMonitor, ENSEMBLE_MEMBER_STAT};
+    use crate::monitors::{AggregatorOps, Monitor, NopMonitor, UserStats, UserStatsValue};
+
+    #[test]
+    fn test_ensemble_stats() {
+        let mut monitor = EnsembleMonitor::new(NopMonitor::new(), |_| {});
+        for (id, name) in [(0, "cmplog"), (1, "plain"), (2, "cmplog")] {
+            monitor.client_stats_insert(ClientId(id));
+            monitor
+                .client_stats_mut_for(ClientId(id))
+                .update_user_stats(
+                    Cow::Borrowed(ENSEMBLE_MEMBER_STAT),
+                    UserStats::new(
+                        UserStatsValue::String(Cow::Borrowed(name)),
+                        AggregatorOps::None,
+                    ),
+                );
+            monitor.client_stats_mut_for(ClientId(id)).executions = 100;
+        }
+        // A client not in the ensemble
+        monitor.client_stats_insert(ClientId(3));
+
+        monitor.display("Testcase", ClientId(0));
+        monitor.display("Testcase", ClientId(2));
+        monitor.display("Testcase", ClientId(2));
+        monitor.display("Testcase", ClientId(3));
+        monitor.display("UserStats", ClientId(1));
+
+        let stats = monitor.ensemble_stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].name, "cmplog");
+        assert_eq!(stats[0].clients, 2);
+        assert_eq!(stats[0].found, 3);
+        assert_eq!(stats[0].executions, 200);
+        assert_eq!(stats[1].name, "plain");
+        assert_eq!(stats[1].clients, 1);
+        assert_eq!(stats[1].found, 0);
+    }
+}
@@ -3,6 +3,9 @@
 pub mod multi;
 pub use multi::MultiMonitor;
 
+pub mod ensemble;
+pub use ensemble::EnsembleMonitor;
+
 #[cfg(all(feature = "tui_monitor", feature = "std"))]
 #[allow(missing_docs)]
 pub mod tui;
@@ -20,10 +23,16 @@ use alloc::{borrow::Cow, fmt::Debug, string::String, vec::Vec};
 use core::{fmt, fmt::Write, time::Duration};
 
 #[cfg(feature = "std")]
-pub use disk::{OnDiskJSONMonitor, OnDiskTOMLMonitor};
+pub use disk::{OnDiskAflStatsMonitor, OnDiskJSONMonitor, OnDiskTOMLMonitor};
+#[cfg(feature = "std")]
+pub mod web;
 use hashbrown::HashMap;
 use libafl_bolts::{current_time, format_duration_hms, ClientId};
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+pub use web::WebMonitor;
+
+use crate::executors::ExitKind;
 
 #[cfg(feature = "afl_exec_sec")]
 const CLIENT_STATS_TIME_WINDOW_SECS: u64 = 5; // 5 seconds
@@ -353,6 +362,8 @@ pub struct ClientStats {
     pub objective_size: u64,
     /// The time for the last update of the objective size
     pub last_objective_time: Duration,
+    /// The number of objectives found for this client, by [`ExitKind`]
+    pub objectives_by_exit_kind: HashMap<String, u64>,
     /// The last reported executions for this client
     #[cfg(feature = "afl_exec_sec")]
     pub last_window_executions: u64,
@@ -408,6 +419,15 @@ impl ClientStats {
     /// We got a new information about objective corpus size for this client, insert them.
     pub fn update_objective_size(&mut self, objective_size: u64) {
         self.objective_size = objective_size;
+        self.last_objective_time = current_time();
+    }
+
+    /// A new objective with the given [`ExitKind`] was found by this client, count it.
+    pub fn update_objective_exit_kind(&mut self, exit_kind: &ExitKind) {
+        *self
+            .objectives_by_exit_kind
+            .entry(exit_kind.to_string())
+            .or_insert(0) += 1;
     }
 
     /// Get the calculated executions per second for this client
@@ -525,6 +545,17 @@ pub trait Monitor {
             .fold(0_u64, |acc, x| acc + x.objective_size)
     }
 
+    /// Amount of objectives found, by [`ExitKind`] (combined for all children)
+    fn objectives_by_exit_kind(&self) -> HashMap<String, u64> {
+        let mut combined = HashMap::new();
+        for client in self.client_stats() {
+            for (exit_kind, count) in &client.objectives_by_exit_kind {
+                *combined.entry(exit_kind.clone()).or_insert(0) += count;
+            }
+        }
+        combined
+    }
+
     /// Total executions
     #[inline]
     fn total_execs(&self) -> u64 {
@@ -783,6 +814,9 @@ where
             for (key, val) in &client.user_monitor {
                 write!(fmt, ", {key}: {val}").unwrap();
             }
+            for (exit_kind, count) in &client.objectives_by_exit_kind {
+                write!(fmt, ", objectives ({exit_kind}): {count}").unwrap();
+            }
         }
 
         (self.print_fn)(&fmt);
@@ -1310,3 +1344,43 @@ impl Default for ClientPerfMonitor {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloc::{rc::Rc, string::String, vec::Vec};
+    use core::cell::RefCell;
+
+    use libafl_bolts::ClientId;
+
+    use super::{Monitor, SimpleMonitor};
+    use crate::executors::{CustomExitKind, ExitKind};
+
+    #[test]
+    fn test_objectives_by_exit_kind() {
+        let lines = Rc::new(RefCell::new(Vec::<String>::new()));
+        let printed = lines.clone();
+        let mut monitor =
+            SimpleMonitor::with_user_monitor(move |s: &str| printed.borrow_mut().push(s.into()));
+        let leak = ExitKind::Custom(CustomExitKind::new(1));
+        for (id, exit_kind) in [(0, ExitKind::Crash), (0, leak), (1, leak)] {
+            monitor.client_stats_insert(ClientId(id));
+            let client = monitor.client_stats_mut_for(ClientId(id));
+            client.update_objective_size(client.objective_size + 1);
+            client.update_objective_exit_kind(&exit_kind);
+        }
+
+        let combined = monitor.objectives_by_exit_kind();
+        assert_eq!(combined.len(), 2);
+        assert_eq!(combined["crash"], 1);
+        assert_eq!(combined["custom_1"], 2);
+
+        monitor.display("Objective", ClientId(0));
+        monitor.display("Objective", ClientId(1));
+        let lines = lines.borrow();
+        assert!(lines[0].contains("objectives: 3"));
+        assert!(lines[0].contains("objectives (crash): 1"));
+        assert!(lines[0].contains("objectives (custom_1): 1"));
+        assert!(!lines[1].contains("objectives (crash)"));
+        assert!(lines[1].contains("objectives (custom_1): 1"));
+    }
+}
@@ -27,7 +27,10 @@ use serde_json::Value;
 
 #[cfg(feature = "introspection")]
 use super::{ClientPerfMonitor, PerfFeature};
-use crate::monitors::{Aggregator, AggregatorOps, ClientStats, Monitor, UserStats, UserStatsValue};
+use crate::{
+    feedbacks::CRASH_BUCKETS_STATS_NAME,
+    monitors::{Aggregator, AggregatorOps, ClientStats, Monitor, UserStats, UserStatsValue},
+};
 
 pub mod ui;
 use ui::TuiUI;
@@ -208,6 +211,8 @@ pub struct ClientTuiContext {
     pub map_density: String,
 
     pub cycles_done: u64,
+    /// The number of distinct crash buckets, if a `CrashBucketFeedback` reports them
+    pub crash_buckets: u64,
 
     pub process_timing: ProcessTiming,
     pub item_geometry: ItemGeometry,
@@ -261,6 +266,13 @@ impl ClientTuiContext {
             .map_or("0%".to_string(), ToString::to_string);
         self.item_geometry.stability = stability;
 
+        if let Some(UserStatsValue::Number(buckets)) = client
+            .get_user_stats(CRASH_BUCKETS_STATS_NAME)
+            .map(UserStats::value)
+        {
+            self.crash_buckets = *buckets;
+        }
+
         for (key, val) in &client.user_monitor {
             self.user_stats.insert(key.clone(), val.clone());
         }
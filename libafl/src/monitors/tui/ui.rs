@@ -643,6 +643,15 @@ impl TuiUI {
                             .map_or(0, |x| x.objectives)
                     ))),
                 ]),
+                Row::new(vec![
+                    Cell::from(Span::raw("crash buckets")),
+                    Cell::from(Span::raw(format!(
+                        "{}",
+                        app.clients
+                            .get(&self.clients_idx)
+                            .map_or(0, |x| x.crash_buckets)
+                    ))),
+                ]),
             ]
         };
 
@@ -0,0 +1,477 @@
+//! A monitor serving a web dashboard with charts of the fuzzing history, and the history as JSON.
+//!
+//! The dashboard needs no external services: open `http://<addr>/` in a browser.
+//! The same data is available as JSON at `http://<addr>/api/stats`, for scripts.
+
+use alloc::{
+    borrow::Cow,
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::time::Duration;
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Arc, RwLock},
+    thread,
+};
+
+use libafl_bolts::{current_time, ClientId, Error};
+use serde::Serialize;
+
+use crate::monitors::{ClientStats, Monitor, NopMonitor, UserStats, UserStatsValue};
+
+/// The number of samples kept per chart, before the history is downsampled
+const DEFAULT_MAX_SAMPLES: usize = 1024;
+
+/// The dashboard, polling `/api/stats`
+const DASHBOARD_HTML: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>LibAFL</title>
+<style>
+body { font-family: sans-serif; margin: 1em 2em; background: #fafafa; color: #222; }
+#charts { display: grid; grid-template-columns: repeat(auto-fit, minmax(480px, 1fr)); gap: 1em; }
+.chart { background: #fff; border: 1px solid #ddd; padding: 0.5em; }
+.chart h3 { margin: 0 0 0.3em 0; font-size: 1em; }
+canvas { width: 100%; height: 220px; }
+</style>
+</head>
+<body>
+<h2>LibAFL <span id="run_time"></span></h2>
+<p>Show <select id="client"><option value="total">all clients</option></select> <span id="summary"></span></p>
+<div id="charts"></div>
+<script>
+const charts = [
+  ["execs_per_sec", "exec/sec"],
+  ["corpus", "corpus"],
+  ["objectives", "objectives"],
+  ["edges", "edges"],
+];
+const container = document.getElementById("charts");
+for (const [key, title] of charts) {
+  container.insertAdjacentHTML("beforeend",
+    `<div class="chart"><h3>${title}</h3><canvas id="chart_${key}" width="960" height="440"></canvas></div>`);
+}
+const select = document.getElementById("client");
+
+function duration(secs) {
+  const h = Math.floor(secs / 3600), m = Math.floor(secs % 3600 / 60), s = Math.floor(secs % 60);
+  return `${h}h-${m}m-${s}s`;
+}
+
+function draw(canvas, samples, key) {
+  const ctx = canvas.getContext("2d");
+  const w = canvas.width, h = canvas.height, pad = 60;
+  ctx.clearRect(0, 0, w, h);
+  ctx.font = "20px sans-serif";
+  ctx.strokeStyle = "#999";
+  ctx.strokeRect(pad, 10, w - pad - 10, h - pad);
+  if (samples.length === 0) return;
+  const t0 = samples[0].time, t1 = Math.max(samples[samples.length - 1].time, t0 + 1);
+  const max = Math.max(...samples.map(s => s[key]), 1);
+  ctx.fillStyle = "#444";
+  ctx.fillText(max.toFixed(key === "execs_per_sec" ? 1 : 0), 2, 30);
+  ctx.fillText("0", 2, h - pad + 10);
+  ctx.fillText(duration(t0), pad, h - 20);
+  ctx.fillText(duration(t1), w - 150, h - 20);
+  ctx.strokeStyle = "#2a6fdb";
+  ctx.lineWidth = 3;
+  ctx.beginPath();
+  samples.forEach((s, i) => {
+    const x = pad + (s.time - t0) / (t1 - t0) * (w - pad - 10);
+    const y = 10 + (1 - s[key] / max) * (h - pad);
+    if (i === 0) ctx.moveTo(x, y); else ctx.lineTo(x, y);
+  });
+  ctx.stroke();
+}
+
+async function update() {
+  try {
+    const stats = await (await fetch("/api/stats")).json();
+    document.getElementById("run_time").textContent = duration(stats.run_time);
+    for (const id of Object.keys(stats.clients)) {
+      if (!select.querySelector(`option[value="${id}"]`)) {
+        select.insertAdjacentHTML("beforeend", `<option value="${id}">client ${id}</option>`);
+      }
+    }
+    const series = select.value === "total" ? stats.total : stats.clients[select.value];
+    const samples = series ? series.samples : [];
+    const last = samples[samples.length - 1];
+    document.getElementById("summary").textContent = last
+      ? `executions: ${last.executions}, exec/sec: ${last.execs_per_sec.toFixed(1)}, corpus: ${last.corpus}, objectives: ${last.objectives}, edges: ${last.edges}`
+      : "";
+    for (const [key] of charts) draw(document.getElementById(`chart_${key}`), samples, key);
+  } catch (e) {
+    document.getElementById("summary").textContent = `disconnected: ${e}`;
+  }
+}
+select.addEventListener("change", update);
+update();
+setInterval(update, 2000);
+</script>
+</body>
+</html>
+"##;
+
+/// One point in time of the history of a client, or of all clients
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct WebSample {
+    /// Seconds since the start of the fuzzing run
+    pub time: u64,
+    /// The executions per second
+    pub execs_per_sec: f64,
+    /// The total executions
+    pub executions: u64,
+    /// The corpus size
+    pub corpus: u64,
+    /// The number of objectives
+    pub objectives: u64,
+    /// The number of covered map entries
+    pub edges: u64,
+}
+
+/// The history of a client, or of all clients, with at most `max_samples` samples.
+///
+/// Once full, every other sample is dropped, and samples are taken half as often from then on,
+/// so that the history always spans the whole run.
+#[derive(Debug, Clone, Serialize)]
+pub struct WebSeries {
+    /// The samples, oldest first
+    pub samples: Vec<WebSample>,
+    /// The seconds between two samples
+    pub resolution: u64,
+    #[serde(skip)]
+    max_samples: usize,
+}
+
+impl WebSeries {
+    fn new(resolution: u64, max_samples: usize) -> Self {
+        Self {
+            samples: Vec::new(),
+            resolution: resolution.max(1),
+            max_samples: max_samples.max(2),
+        }
+    }
+
+    /// Adds a sample, if it is at least `resolution` seconds after the last one
+    fn add(&mut self, sample: WebSample) {
+        if let Some(last) = self.samples.last() {
+            if sample.time < last.time + self.resolution {
+                return;
+            }
+        }
+        self.samples.push(sample);
+        if self.samples.len() > self.max_samples {
+            // keep the first and the latest sample, and every other one in between
+            let last = self.samples.len() - 1;
+            let mut idx = 0;
+            self.samples.retain(|_| {
+                let keep = idx % 2 == 0 || idx == last;
+                idx += 1;
+                keep
+            });
+            self.resolution *= 2;
+        }
+    }
+}
+
+/// The history of the fuzzing run, shared with the HTTP server
+#[derive(Debug, Clone, Serialize)]
+struct WebHistory {
+    /// Seconds since the start of the fuzzing run
+    run_time: u64,
+    /// The history of all clients together
+    total: WebSeries,
+    /// The history of each client, by client id
+    clients: BTreeMap<u32, WebSeries>,
+}
+
+/// Wraps a base monitor and serves a web dashboard charting the executions per second, corpus size,
+/// objectives, and edges of each client and of all clients over time, at `http://<addr>/`.
+///
+/// The history is kept in memory, sampled every `sample_interval`, and downsampled to at most 1024 samples
+/// per client, see [`WebSeries`]. The history is also served as JSON at `http://<addr>/api/stats`.
+#[derive(Debug, Clone)]
+pub struct WebMonitor<M>
+where
+    M: Monitor,
+{
+    base: M,
+    history: Arc<RwLock<WebHistory>>,
+    local_addr: SocketAddr,
+    map_stats_name: Cow<'static, str>,
+    max_samples: usize,
+    last_sample: Duration,
+    sample_interval: Duration,
+}
+
+impl<M> Monitor for WebMonitor<M>
+where
+    M: Monitor,
+{
+    /// The client monitor, mutable
+    fn client_stats_mut(&mut self) -> &mut Vec<ClientStats> {
+        self.base.client_stats_mut()
+    }
+
+    /// The client monitor
+    fn client_stats(&self) -> &[ClientStats] {
+        self.base.client_stats()
+    }
+
+    /// Time this fuzzing run stated
+    fn start_time(&self) -> Duration {
+        self.base.start_time()
+    }
+
+    /// Set creation time
+    fn set_start_time(&mut self, time: Duration) {
+        self.base.set_start_time(time);
+    }
+
+    fn aggregate(&mut self, name: &str) {
+        self.base.aggregate(name);
+    }
+
+    fn display(&mut self, event_msg: &str, sender_id: ClientId) {
+        let cur_time = current_time();
+
+        if cur_time.saturating_sub(self.last_sample) >= self.sample_interval {
+            self.last_sample = cur_time;
+            let time = cur_time.saturating_sub(self.start_time()).as_secs();
+
+            let mut samples = Vec::new();
+            for (id, client) in self.base.client_stats_mut().iter_mut().enumerate() {
+                if !client.enabled {
+                    continue;
+                }
+                let edges = match client
+                    .get_user_stats(&self.map_stats_name)
+                    .map(UserStats::value)
+                {
+                    Some(UserStatsValue::Ratio(covered, _)) => *covered,
+                    _ => 0,
+                };
+                let sample = WebSample {
+                    time,
+                    execs_per_sec: client.execs_per_sec(cur_time),
+                    executions: client.executions,
+                    corpus: client.corpus_size,
+                    objectives: client.objective_size,
+                    edges,
+                };
+                samples.push((u32::try_from(id).unwrap(), sample));
+            }
+
+            let total = samples.iter().fold(
+                WebSample {
+                    time,
+                    ..WebSample::default()
+                },
+                |mut total, (_, sample)| {
+                    total.execs_per_sec += sample.execs_per_sec;
+                    total.executions += sample.executions;
+                    total.corpus += sample.corpus;
+                    total.objectives += sample.objectives;
+                    // the clients fuzz the same target, so the best client covers at least as much as any other
+                    total.edges = total.edges.max(sample.edges);
+                    total
+                },
+            );
+
+            let resolution = self.sample_interval.as_secs();
+            let mut history = self.history.write().unwrap();
+            history.run_time = time;
+            history.total.add(total);
+            for (id, sample) in samples {
+                history
+                    .clients
+                    .entry(id)
+                    .or_insert_with(|| WebSeries::new(resolution, self.max_samples))
+                    .add(sample);
+            }
+        }
+
+        self.base.display(event_msg, sender_id);
+    }
+}
+
+impl<M> WebMonitor<M>
+where
+    M: Monitor,
+{
+    /// Create a new [`WebMonitor`] serving the dashboard at `addr`, e.g., `127.0.0.1:8080`, sampling every second
+    pub fn new<A>(addr: A, base: M) -> Result<Self, Error>
+    where
+        A: ToSocketAddrs,
+    {
+        Self::with_sample_interval(addr, base, Duration::from_secs(1))
+    }
+
+    /// Create a new [`WebMonitor`] with a custom sample interval
+    pub fn with_sample_interval<A>(
+        addr: A,
+        base: M,
+        sample_interval: Duration,
+    ) -> Result<Self, Error>
+    where
+        A: ToSocketAddrs,
+    {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let history = Arc::new(RwLock::new(WebHistory {
+            run_time: 0,
+            total: WebSeries::new(sample_interval.as_secs(), DEFAULT_MAX_SAMPLES),
+            clients: BTreeMap::new(),
+        }));
+
+        let server_history = history.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if let Err(err) = serve(stream, &server_history) {
+                    log::debug!("Web monitor request failed: {err}");
+                }
+            }
+        });
+
+        Ok(Self {
+            base,
+            history,
+            local_addr,
+            map_stats_name: Cow::Borrowed("edges"),
+            max_samples: DEFAULT_MAX_SAMPLES,
+            last_sample: current_time().saturating_sub(sample_interval),
+            sample_interval,
+        })
+    }
+
+    /// Sets the name of the user stat the edges are taken from, the name of the map observer
+    #[must_use]
+    pub fn with_map_stats_name(mut self, map_stats_name: Cow<'static, str>) -> Self {
+        self.map_stats_name = map_stats_name;
+        self
+    }
+
+    /// Sets the number of samples kept per client before downsampling
+    #[must_use]
+    pub fn with_max_samples(mut self, max_samples: usize) -> Self {
+        self.max_samples = max_samples;
+        self.history.write().unwrap().total.max_samples = max_samples.max(2);
+        self
+    }
+
+    /// The address the dashboard is served at
+    #[must_use]
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl WebMonitor<NopMonitor> {
+    /// Create a new [`WebMonitor`] without a base
+    pub fn nop<A>(addr: A) -> Result<Self, Error>
+    where
+        A: ToSocketAddrs,
+    {
+        Self::new(addr, NopMonitor::new())
+    }
+}
+
+/// Answers one HTTP request with the dashboard or the history
+fn serve(mut stream: TcpStream, history: &RwLock<WebHistory>) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // skip the headers
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+    let path = path.split('?').next().unwrap_or(path);
+    let (status, content_type, body) = match path {
+        "/" | "/index.html" => ("200 OK", "text/html", DASHBOARD_HTML.to_string()),
+        "/api/stats" => {
+            let json = serde_json::to_string(&*history.read().unwrap())?;
+            ("200 OK", "application/json", json)
+        }
+        _ => ("404 Not Found", "text/plain", "Not Found".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}; charset=utf-8\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+    use core::time::Duration;
+    use std::{
+        io::{Read, Write},
+        net::{SocketAddr, TcpStream},
+    };
+
+    use libafl_bolts::ClientId;
+
+    use super::{WebMonitor, WebSample, WebSeries};
+    use crate::monitors::{Monitor, NopMonitor};
+
+    fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_web_series_downsampling() {
+        let mut series = WebSeries::new(1, 8);
+        for time in 0..100 {
+            series.add(WebSample {
+                time,
+                ..WebSample::default()
+            });
+        }
+        assert!(series.samples.len() <= 8);
+        assert_eq!(series.samples[0].time, 0);
+        assert!(series.samples.last().unwrap().time >= 100 - series.resolution);
+        assert!(series
+            .samples
+            .windows(2)
+            .all(|pair| pair[0].time < pair[1].time));
+    }
+
+    #[test]
+    fn test_web_monitor() {
+        let mut monitor =
+            WebMonitor::with_sample_interval("127.0.0.1:0", NopMonitor::new(), Duration::ZERO)
+                .unwrap();
+        monitor.client_stats_insert(ClientId(1));
+        monitor
+            .client_stats_mut_for(ClientId(1))
+            .update_corpus_size(42);
+        monitor.display("Test", ClientId(1));
+
+        let addr = monitor.local_addr();
+        let page = get(addr, "/");
+        assert!(page.starts_with("HTTP/1.1 200 OK"));
+        assert!(page.contains("<canvas"));
+
+        let response = get(addr, "/api/stats");
+        let body = response.split("\r\n\r\n").nth(1).unwrap();
+        let stats: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(stats["total"]["samples"][0]["corpus"], 42);
+        assert_eq!(stats["clients"]["1"]["samples"][0]["corpus"], 42);
+
+        assert!(get(addr, "/nothing").starts_with("HTTP/1.1 404"));
+    }
+}
@@ -0,0 +1,216 @@
+//! A [`ScheduledMutator`] that records its mutations to a [`SessionJournal`], or replays them from it.
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::{
+    fmt::{self, Debug},
+    marker::PhantomData,
+};
+
+use libafl_bolts::Named;
+
+use crate::{
+    corpus::CorpusId,
+    fuzzer::replay::{JournalEntry, SessionJournal},
+    mutators::{
+        ComposedByMutations, MutationId, MutationResult, Mutator, MutatorsTuple, ScheduledMutator,
+    },
+    state::HasRand,
+    Error, HasMetadata,
+};
+
+/// Wraps a [`ScheduledMutator`], journaling the mutations it stacks to the [`SessionJournal`] of the state.
+///
+/// When replaying, the journaled mutations are applied instead of the scheduled ones.
+/// The wrapped mutator still schedules as many mutations as were journaled,
+/// so that the [`libafl_bolts::rands::Rand`] stays in sync with the recording.
+/// Without a [`SessionJournal`], this behaves like the wrapped mutator.
+pub struct JournalingScheduledMutator<I, MT, S, SM>
+where
+    MT: MutatorsTuple<I, S>,
+    S: HasRand + HasMetadata,
+    SM: ScheduledMutator<I, MT, S>,
+{
+    name: Cow<'static, str>,
+    scheduled: SM,
+    phantom: PhantomData<(I, MT, S)>,
+}
+
+impl<I, MT, S, SM> Debug for JournalingScheduledMutator<I, MT, S, SM>
+where
+    MT: MutatorsTuple<I, S>,
+    S: HasRand + HasMetadata,
+    SM: ScheduledMutator<I, MT, S>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "JournalingScheduledMutator with {} mutations for Input type {}",
+            self.scheduled.mutations().len(),
+            core::any::type_name::<I>()
+        )
+    }
+}
+
+impl<I, MT, S, SM> Named for JournalingScheduledMutator<I, MT, S, SM>
+where
+    MT: MutatorsTuple<I, S>,
+    S: HasRand + HasMetadata,
+    SM: ScheduledMutator<I, MT, S>,
+{
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, MT, S, SM> Mutator<I, S> for JournalingScheduledMutator<I, MT, S, SM>
+where
+    MT: MutatorsTuple<I, S>,
+    S: HasRand + HasMetadata,
+    SM: ScheduledMutator<I, MT, S>,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        self.scheduled_mutate(state, input)
+    }
+
+    fn post_exec(&mut self, state: &mut S, corpus_idx: Option<CorpusId>) -> Result<(), Error> {
+        self.scheduled.post_exec(state, corpus_idx)
+    }
+}
+
+impl<I, MT, S, SM> ComposedByMutations<I, MT, S> for JournalingScheduledMutator<I, MT, S, SM>
+where
+    MT: MutatorsTuple<I, S>,
+    S: HasRand + HasMetadata,
+    SM: ScheduledMutator<I, MT, S>,
+{
+    #[inline]
+    fn mutations(&self) -> &MT {
+        self.scheduled.mutations()
+    }
+
+    #[inline]
+    fn mutations_mut(&mut self) -> &mut MT {
+        self.scheduled.mutations_mut()
+    }
+}
+
+impl<I, MT, S, SM> ScheduledMutator<I, MT, S> for JournalingScheduledMutator<I, MT, S, SM>
+where
+    MT: MutatorsTuple<I, S>,
+    S: HasRand + HasMetadata,
+    SM: ScheduledMutator<I, MT, S>,
+{
+    fn iterations(&self, state: &mut S, input: &I) -> u64 {
+        self.scheduled.iterations(state, input)
+    }
+
+    fn schedule(&self, state: &mut S, input: &I) -> MutationId {
+        self.scheduled.schedule(state, input)
+    }
+
+    fn scheduled_mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let replaying = match state.metadata_map().get::<SessionJournal>() {
+            Some(journal) => journal.is_replaying(),
+            None => return self.scheduled.scheduled_mutate(state, input),
+        };
+
+        let num = self.iterations(state, input);
+        let ids = if replaying {
+            let ids = state.metadata_mut::<SessionJournal>()?.replay_mutations()?;
+            for _ in 0..ids.len() {
+                self.schedule(state, input);
+            }
+            ids
+        } else {
+            let ids: Vec<MutationId> = (0..num).map(|_| self.schedule(state, input)).collect();
+            state
+                .metadata_mut::<SessionJournal>()?
+                .record(&JournalEntry::Mutations(ids.clone()))?;
+            ids
+        };
+
+        let mut r = MutationResult::Skipped;
+        for idx in ids {
+            let outcome = self.mutations_mut().get_and_mutate(idx, state, input)?;
+            if outcome == MutationResult::Mutated {
+                r = MutationResult::Mutated;
+            }
+        }
+        Ok(r)
+    }
+}
+
+impl<I, MT, S, SM> JournalingScheduledMutator<I, MT, S, SM>
+where
+    MT: MutatorsTuple<I, S>,
+    S: HasRand + HasMetadata,
+    SM: ScheduledMutator<I, MT, S>,
+{
+    /// Create a new [`JournalingScheduledMutator`] wrapping the given [`ScheduledMutator`]
+    pub fn new(scheduled: SM) -> Self {
+        Self {
+            name: Cow::from(format!("JournalingScheduledMutator[{}]", scheduled.name())),
+            scheduled,
+            phantom: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use libafl_bolts::rands::StdRand;
+
+    use super::JournalingScheduledMutator;
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        fuzzer::replay::SessionJournal,
+        inputs::{BytesInput, HasMutatorBytes},
+        mutators::{havoc_mutations_no_crossover, Mutator, StdScheduledMutator},
+        state::StdState,
+    };
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_journaling_mutator_replays() {
+        let path = env::temp_dir().join(format!("libafl_mutator_journal_{}", std::process::id()));
+        let mut corpus = InMemoryCorpus::<BytesInput>::new();
+        corpus
+            .add(Testcase::new(BytesInput::new(b"hello world".to_vec())))
+            .unwrap();
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut mutator = JournalingScheduledMutator::new(StdScheduledMutator::new(
+            havoc_mutations_no_crossover(),
+        ));
+
+        let run = |state: &mut _, mutator: &mut JournalingScheduledMutator<_, _, _, _>| {
+            let mut input = BytesInput::new(b"hello world".to_vec());
+            for _ in 0..16 {
+                mutator.mutate(state, &mut input).unwrap();
+            }
+            input.bytes().to_vec()
+        };
+
+        SessionJournal::start_recording(&mut state, &path, 42).unwrap();
+        let recorded = run(&mut state, &mut mutator);
+
+        SessionJournal::start_replay(&mut state, &path).unwrap();
+        assert_eq!(run(&mut state, &mut mutator), recorded);
+        assert!(mutator
+            .mutate(&mut state, &mut BytesInput::new(vec![0]))
+            .is_err());
+
+        fs::remove_file(path).unwrap();
+    }
+}
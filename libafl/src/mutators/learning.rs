@@ -0,0 +1,309 @@
+//! A [`ScheduledMutator`] that learns which of its mutations find new corpus entries, and picks those more often.
+//!
+//! Unlike [`crate::mutators::StdMOptMutator`], it keeps a single distribution over the mutations,
+//! updated online after every execution from the corpus entries each mutation yields.
+
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
+    fmt::{self, Debug},
+    marker::PhantomData,
+};
+
+use libafl_bolts::{impl_serdeany, rands::Rand, Named};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::CorpusId,
+    mutators::{
+        ComposedByMutations, MutationId, MutationResult, Mutator, MutatorsTuple, ScheduledMutator,
+    },
+    state::HasRand,
+    Error, HasMetadata,
+};
+
+/// The default share of picks that ignore the learned weights, so that no mutation starves
+pub const DEFAULT_EXPLORATION: f64 = 0.1;
+
+/// The weights learned by a [`LearningScheduledMutator`], kept in the state so they survive restarts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct MutationWeightsMetadata {
+    /// The name of each mutation, by [`MutationId`]
+    pub names: Vec<String>,
+    /// How many mutated inputs each mutation was applied to
+    pub applied: Vec<u64>,
+    /// How many of those were added to the corpus
+    pub found: Vec<u64>,
+    /// The probability to pick each mutation
+    pub weights: Vec<f64>,
+    /// The share of picks that are uniform instead
+    pub exploration: f64,
+}
+
+impl_serdeany!(MutationWeightsMetadata);
+
+impl MutationWeightsMetadata {
+    /// Creates a new [`struct@MutationWeightsMetadata`], starting with uniform weights
+    #[must_use]
+    pub fn new(names: Vec<String>, exploration: f64) -> Self {
+        let len = names.len();
+        let mut meta = Self {
+            names,
+            applied: vec![0; len],
+            found: vec![0; len],
+            weights: vec![0.0; len],
+            exploration: exploration.clamp(0.0, 1.0),
+        };
+        meta.update_weights();
+        meta
+    }
+
+    /// Records that the `mutations` were applied to one input, and whether it was added to the corpus.
+    ///
+    /// Each mutation is counted once, even if it was stacked more than once.
+    pub fn record(&mut self, mutations: &[MutationId], found: bool) {
+        let mut distinct: Vec<usize> = mutations
+            .iter()
+            .map(|id| id.0)
+            .filter(|id| *id < self.applied.len())
+            .collect();
+        distinct.sort_unstable();
+        distinct.dedup();
+        for id in distinct {
+            self.applied[id] += 1;
+            if found {
+                self.found[id] += 1;
+            }
+        }
+        self.update_weights();
+    }
+
+    /// Recomputes the weights from the estimated yield of each mutation, `(found + 1) / (applied + 2)`,
+    /// mixed with the uniform distribution by [`Self::exploration`](struct@MutationWeightsMetadata).
+    #[allow(clippy::cast_precision_loss)]
+    fn update_weights(&mut self) {
+        let len = self.weights.len();
+        if len == 0 {
+            return;
+        }
+        let uniform = 1.0 / len as f64;
+        let mut total = 0.0;
+        for (weight, (applied, found)) in self
+            .weights
+            .iter_mut()
+            .zip(self.applied.iter().zip(&self.found))
+        {
+            *weight = (*found as f64 + 1.0) / (*applied as f64 + 2.0);
+            total += *weight;
+        }
+        for weight in &mut self.weights {
+            *weight = (1.0 - self.exploration) * *weight / total + self.exploration * uniform;
+        }
+    }
+
+    /// Picks a mutation, given a `coin` in `[0, 1)`
+    #[must_use]
+    pub fn sample(&self, coin: f64) -> MutationId {
+        let mut cumulative = 0.0;
+        for (id, weight) in self.weights.iter().enumerate() {
+            cumulative += weight;
+            if coin < cumulative {
+                return id.into();
+            }
+        }
+        // Rounding errors
+        self.weights.len().saturating_sub(1).into()
+    }
+}
+
+/// A [`Mutator`] that schedules one of the embedded mutations on each call,
+/// preferring the mutations that found the most new corpus entries so far.
+///
+/// The learned weights are kept in a [`struct@MutationWeightsMetadata`] in the state,
+/// use a [`crate::stages::MutatorStatsStage`] to report them to the monitor.
+pub struct LearningScheduledMutator<I, MT, S>
+where
+    MT: MutatorsTuple<I, S>,
+    S: HasRand + HasMetadata,
+{
+    name: Cow<'static, str>,
+    mutations: MT,
+    max_stack_pow: usize,
+    mutation_log: Vec<MutationId>,
+    phantom: PhantomData<(I, S)>,
+}
+
+impl<I, MT, S> Debug for LearningScheduledMutator<I, MT, S>
+where
+    MT: MutatorsTuple<I, S>,
+    S: HasRand + HasMetadata,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "LearningScheduledMutator with {} mutations for Input type {}",
+            self.mutations.len(),
+            core::any::type_name::<I>()
+        )
+    }
+}
+
+impl<I, MT, S> Named for LearningScheduledMutator<I, MT, S>
+where
+    MT: MutatorsTuple<I, S>,
+    S: HasRand + HasMetadata,
+{
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, MT, S> Mutator<I, S> for LearningScheduledMutator<I, MT, S>
+where
+    MT: MutatorsTuple<I, S>,
+    S: HasRand + HasMetadata,
+{
+    #[inline]
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        self.scheduled_mutate(state, input)
+    }
+
+    fn post_exec(&mut self, state: &mut S, new_corpus_idx: Option<CorpusId>) -> Result<(), Error> {
+        let mutations = core::mem::take(&mut self.mutation_log);
+        state
+            .metadata_mut::<MutationWeightsMetadata>()?
+            .record(&mutations, new_corpus_idx.is_some());
+        Ok(())
+    }
+}
+
+impl<I, MT, S> ComposedByMutations<I, MT, S> for LearningScheduledMutator<I, MT, S>
+where
+    MT: MutatorsTuple<I, S>,
+    S: HasRand + HasMetadata,
+{
+    /// Get the mutations
+    #[inline]
+    fn mutations(&self) -> &MT {
+        &self.mutations
+    }
+
+    // Get the mutations (mutable)
+    #[inline]
+    fn mutations_mut(&mut self) -> &mut MT {
+        &mut self.mutations
+    }
+}
+
+impl<I, MT, S> ScheduledMutator<I, MT, S> for LearningScheduledMutator<I, MT, S>
+where
+    MT: MutatorsTuple<I, S>,
+    S: HasRand + HasMetadata,
+{
+    /// Compute the number of iterations used to apply stacked mutations
+    fn iterations(&self, state: &mut S, _: &I) -> u64 {
+        1 << (1 + state.rand_mut().below(self.max_stack_pow))
+    }
+
+    /// Get the next mutation to apply, according to the learned weights
+    fn schedule(&self, state: &mut S, _: &I) -> MutationId {
+        debug_assert!(self.mutations.len() != 0);
+        let coin = state.rand_mut().next_float();
+        // The metadata is added in the constructor
+        state
+            .metadata::<MutationWeightsMetadata>()
+            .map_or(0.into(), |meta| meta.sample(coin))
+    }
+
+    fn scheduled_mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let mut r = MutationResult::Skipped;
+        let num = self.iterations(state, input);
+        self.mutation_log.clear();
+        for _ in 0..num {
+            let idx = self.schedule(state, input);
+            self.mutation_log.push(idx);
+            let outcome = self.mutations_mut().get_and_mutate(idx, state, input)?;
+            if outcome == MutationResult::Mutated {
+                r = MutationResult::Mutated;
+            }
+        }
+        Ok(r)
+    }
+}
+
+impl<I, MT, S> LearningScheduledMutator<I, MT, S>
+where
+    MT: MutatorsTuple<I, S>,
+    S: HasRand + HasMetadata,
+{
+    /// Create a new [`LearningScheduledMutator`] instance specifying mutations, exploring with [`DEFAULT_EXPLORATION`]
+    pub fn new(state: &mut S, mutations: MT) -> Self {
+        Self::with_exploration(state, mutations, DEFAULT_EXPLORATION)
+    }
+
+    /// Create a new [`LearningScheduledMutator`] instance, picking a uniformly random mutation for an `exploration` share of the picks.
+    ///
+    /// If the state already holds weights for the same mutations, e.g., after a restart, they are kept.
+    pub fn with_exploration(state: &mut S, mutations: MT, exploration: f64) -> Self {
+        let names: Vec<String> = mutations.names().iter().map(ToString::to_string).collect();
+        let restored = state
+            .metadata::<MutationWeightsMetadata>()
+            .is_ok_and(|meta| meta.names == names);
+        if !restored {
+            state.add_metadata(MutationWeightsMetadata::new(names, exploration));
+        }
+        Self {
+            name: Cow::from(format!(
+                "LearningScheduledMutator[{}]",
+                mutations.names().join(", ")
+            )),
+            mutations,
+            max_stack_pow: 7,
+            mutation_log: Vec::new(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec::Vec};
+
+    use super::MutationWeightsMetadata;
+    use crate::mutators::MutationId;
+
+    #[test]
+    fn test_mutation_weights() {
+        let names = ["a", "b", "c"].iter().map(ToString::to_string).collect();
+        let mut meta = MutationWeightsMetadata::new(names, 0.1);
+        assert!(meta
+            .weights
+            .iter()
+            .all(|weight| (weight - 1.0 / 3.0).abs() < 1e-9));
+
+        let b = MutationId::from(1_usize);
+        let c = MutationId::from(2_usize);
+        for _ in 0..100 {
+            meta.record(&[b, b], true);
+            meta.record(&[c], false);
+        }
+        // Stacked mutations count once
+        assert_eq!(meta.applied, [0, 100, 100]);
+        assert_eq!(meta.found, [0, 100, 0]);
+        assert!((meta.weights.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!(meta.weights[1] > meta.weights[0] && meta.weights[0] > meta.weights[2]);
+        // Exploration keeps a floor
+        assert!(meta.weights[2] >= 0.1 / 3.0);
+
+        let picks: Vec<MutationId> = (0..10).map(|i| meta.sample(f64::from(i) / 10.0)).collect();
+        assert!(picks.iter().filter(|id| **id == b).count() > 5);
+        assert_eq!(meta.sample(0.999_999_999), c);
+    }
+}
@@ -0,0 +1,286 @@
+//! Track where each testcase came from, and how well each mutation finds new testcases.
+//!
+//! The [`LineageScheduledMutator`] attaches a [`LineageMetadata`] to each testcase it found,
+//! and counts per mutation how often it was applied and how often it found a new testcase in a [`MutatorStatsMetadata`].
+//! See [`crate::stages::MutatorStatsStage`] to report these counts to the monitor,
+//! and [`crate::corpus::LineageGraph`] to export the lineage of a corpus.
+
+use alloc::{borrow::Cow, collections::BTreeMap, string::String, vec::Vec};
+use core::{
+    fmt::{self, Debug},
+    marker::PhantomData,
+};
+
+use libafl_bolts::{impl_serdeany, tuples::NamedTuple, Named};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{Corpus, CorpusId},
+    mutators::{
+        ComposedByMutations, MutationId, MutationResult, Mutator, MutatorsTuple, ScheduledMutator,
+    },
+    stages::{HasCurrentStage, StageId},
+    state::{HasCorpus, HasRand},
+    Error, HasMetadata,
+};
+
+/// A testcase metadata recording how the testcase was derived from its parent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct LineageMetadata {
+    /// The corpus entry that was mutated
+    pub parent: Option<CorpusId>,
+    /// The mutations applied, in order
+    pub mutations: Vec<MutationId>,
+    /// The names of the applied mutations, in order
+    pub mutation_names: Vec<Cow<'static, str>>,
+    /// The stage that found the testcase
+    pub stage: Option<StageId>,
+}
+
+impl_serdeany!(LineageMetadata);
+
+/// How well a single mutation performs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MutatorStats {
+    /// How many mutated inputs this mutation was applied to
+    pub applied: u64,
+    /// How many of those were added to the corpus
+    pub found: u64,
+}
+
+/// A state metadata holding the [`MutatorStats`] of each mutation, by name
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct MutatorStatsMetadata {
+    /// mutation name -> stats
+    pub stats: BTreeMap<String, MutatorStats>,
+}
+
+impl_serdeany!(MutatorStatsMetadata);
+
+impl MutatorStatsMetadata {
+    /// Creates a new [`struct@MutatorStatsMetadata`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Wraps a [`ScheduledMutator`], recording the lineage of each testcase it finds and the effectiveness of its mutations.
+pub struct LineageScheduledMutator<I, MT, S, SM>
+where
+    MT: MutatorsTuple<I, S> + NamedTuple,
+    S: HasRand + HasCorpus + HasMetadata + HasCurrentStage,
+    SM: ScheduledMutator<I, MT, S>,
+{
+    name: Cow<'static, str>,
+    scheduled: SM,
+    mutation_log: Vec<MutationId>,
+    phantom: PhantomData<(I, MT, S)>,
+}
+
+impl<I, MT, S, SM> Debug for LineageScheduledMutator<I, MT, S, SM>
+where
+    MT: MutatorsTuple<I, S> + NamedTuple,
+    S: HasRand + HasCorpus + HasMetadata + HasCurrentStage,
+    SM: ScheduledMutator<I, MT, S>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "LineageScheduledMutator with {} mutations for Input type {}",
+            MT::LEN,
+            core::any::type_name::<I>()
+        )
+    }
+}
+
+impl<I, MT, S, SM> Named for LineageScheduledMutator<I, MT, S, SM>
+where
+    MT: MutatorsTuple<I, S> + NamedTuple,
+    S: HasRand + HasCorpus + HasMetadata + HasCurrentStage,
+    SM: ScheduledMutator<I, MT, S>,
+{
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, MT, S, SM> Mutator<I, S> for LineageScheduledMutator<I, MT, S, SM>
+where
+    MT: MutatorsTuple<I, S> + NamedTuple,
+    S: HasRand + HasCorpus + HasMetadata + HasCurrentStage,
+    SM: ScheduledMutator<I, MT, S>,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        self.scheduled_mutate(state, input)
+    }
+
+    fn post_exec(&mut self, state: &mut S, corpus_idx: Option<CorpusId>) -> Result<(), Error> {
+        let mutations = core::mem::take(&mut self.mutation_log);
+        let mutation_names: Vec<Cow<'static, str>> = mutations
+            .iter()
+            .map(|id| {
+                self.scheduled
+                    .mutations()
+                    .name(id.0)
+                    .cloned()
+                    .unwrap_or(Cow::Borrowed("<unknown>"))
+            })
+            .collect();
+
+        // Count each mutation once per mutated input, even if it was stacked more than once
+        let mut distinct_names = mutation_names.clone();
+        distinct_names.sort();
+        distinct_names.dedup();
+        let meta = state.metadata_or_insert_with(MutatorStatsMetadata::new);
+        for name in &distinct_names {
+            let stats = meta.stats.entry(name.clone().into_owned()).or_default();
+            stats.applied += 1;
+            if corpus_idx.is_some() {
+                stats.found += 1;
+            }
+        }
+
+        if let Some(idx) = corpus_idx {
+            let parent = *state.corpus().current();
+            let stage = state.current_stage_idx()?;
+            let mut testcase = state.corpus().get(idx)?.borrow_mut();
+            testcase.add_metadata(LineageMetadata {
+                parent,
+                mutations,
+                mutation_names,
+                stage,
+            });
+        }
+        self.scheduled.post_exec(state, corpus_idx)
+    }
+}
+
+impl<I, MT, S, SM> ComposedByMutations<I, MT, S> for LineageScheduledMutator<I, MT, S, SM>
+where
+    MT: MutatorsTuple<I, S> + NamedTuple,
+    S: HasRand + HasCorpus + HasMetadata + HasCurrentStage,
+    SM: ScheduledMutator<I, MT, S>,
+{
+    #[inline]
+    fn mutations(&self) -> &MT {
+        self.scheduled.mutations()
+    }
+
+    #[inline]
+    fn mutations_mut(&mut self) -> &mut MT {
+        self.scheduled.mutations_mut()
+    }
+}
+
+impl<I, MT, S, SM> ScheduledMutator<I, MT, S> for LineageScheduledMutator<I, MT, S, SM>
+where
+    MT: MutatorsTuple<I, S> + NamedTuple,
+    S: HasRand + HasCorpus + HasMetadata + HasCurrentStage,
+    SM: ScheduledMutator<I, MT, S>,
+{
+    fn iterations(&self, state: &mut S, input: &I) -> u64 {
+        self.scheduled.iterations(state, input)
+    }
+
+    fn schedule(&self, state: &mut S, input: &I) -> MutationId {
+        self.scheduled.schedule(state, input)
+    }
+
+    fn scheduled_mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let mut r = MutationResult::Skipped;
+        let num = self.iterations(state, input);
+        self.mutation_log.clear();
+        for _ in 0..num {
+            let idx = self.schedule(state, input);
+            self.mutation_log.push(idx);
+            let outcome = self.mutations_mut().get_and_mutate(idx, state, input)?;
+            if outcome == MutationResult::Mutated {
+                r = MutationResult::Mutated;
+            }
+        }
+        Ok(r)
+    }
+}
+
+impl<I, MT, S, SM> LineageScheduledMutator<I, MT, S, SM>
+where
+    MT: MutatorsTuple<I, S> + NamedTuple,
+    S: HasRand + HasCorpus + HasMetadata + HasCurrentStage,
+    SM: ScheduledMutator<I, MT, S>,
+{
+    /// Create a new [`LineageScheduledMutator`] wrapping the given [`ScheduledMutator`]
+    pub fn new(scheduled: SM) -> Self {
+        Self {
+            name: Cow::from(format!("LineageScheduledMutator[{}]", scheduled.name())),
+            scheduled,
+            mutation_log: Vec::new(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::{LineageMetadata, LineageScheduledMutator, MutatorStatsMetadata};
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        inputs::BytesInput,
+        mutators::{havoc_mutations_no_crossover, Mutator, StdScheduledMutator},
+        state::{HasCorpus, StdState},
+        HasMetadata,
+    };
+
+    #[test]
+    fn test_lineage_mutator() {
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let parent = state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(b"parent".to_vec())))
+            .unwrap();
+        *state.corpus_mut().current_mut() = Some(parent);
+
+        let mut mutator =
+            LineageScheduledMutator::new(StdScheduledMutator::new(havoc_mutations_no_crossover()));
+        let mut input = BytesInput::new(b"parent".to_vec());
+        mutator.mutate(&mut state, &mut input).unwrap();
+        mutator.post_exec(&mut state, None).unwrap();
+
+        mutator.mutate(&mut state, &mut input).unwrap();
+        let child = state.corpus_mut().add(Testcase::new(input)).unwrap();
+        mutator.post_exec(&mut state, Some(child)).unwrap();
+
+        let testcase = state.corpus().get(child).unwrap().borrow();
+        let lineage = testcase.metadata::<LineageMetadata>().unwrap();
+        assert_eq!(lineage.parent, Some(parent));
+        assert!(!lineage.mutations.is_empty());
+        assert_eq!(lineage.mutations.len(), lineage.mutation_names.len());
+
+        let stats = state.metadata::<MutatorStatsMetadata>().unwrap();
+        assert!(stats
+            .stats
+            .values()
+            .all(|stats| stats.found <= stats.applied));
+        assert!(stats.stats.values().any(|stats| stats.found == 1));
+    }
+}
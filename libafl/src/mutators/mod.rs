@@ -1,5 +1,25 @@
 //! [`Mutator`]`s` mutate input during fuzzing.
 
+/// Implements [`Mutator::mutate`] with [`Mutator::mutate_undoable`], and [`Mutator::can_undo`],
+/// inside an `impl Mutator<$input, S>` block of a mutator that records all of its changes.
+macro_rules! mutate_with_undo {
+    ($input:ty) => {
+        #[inline]
+        fn mutate(
+            &mut self,
+            state: &mut S,
+            input: &mut $input,
+        ) -> Result<$crate::mutators::MutationResult, $crate::Error> {
+            self.mutate_undoable(state, input, &mut $crate::mutators::UndoLog::disabled())
+        }
+
+        #[inline]
+        fn can_undo(&self) -> bool {
+            true
+        }
+    };
+}
+
 pub mod scheduled;
 use core::fmt;
 
@@ -19,6 +39,22 @@ pub mod grimoire;
 pub use grimoire::*;
 pub mod tuneable;
 pub use tuneable::*;
+pub mod sequence;
+pub use sequence::*;
+pub mod schema;
+pub use schema::*;
+pub mod lineage;
+pub use lineage::*;
+pub mod learning;
+pub use learning::*;
+pub mod typed;
+pub use typed::*;
+pub mod undo;
+pub use undo::*;
+#[cfg(feature = "std")]
+pub mod journal;
+#[cfg(feature = "std")]
+pub use journal::*;
 
 #[cfg(feature = "unicode")]
 pub mod string;
@@ -103,6 +139,29 @@ pub trait Mutator<I, S>: Named {
     ) -> Result<(), Error> {
         Ok(())
     }
+
+    /// If this [`Mutator`] implements [`Mutator::mutate_undoable`]
+    #[inline]
+    fn can_undo(&self) -> bool {
+        false
+    }
+
+    /// Mutate a given input like [`Mutator::mutate`], recording every change in `undo`,
+    /// so that [`UndoLog::revert`] restores the original input.
+    ///
+    /// Only called if [`Mutator::can_undo`] returns `true`.
+    #[inline]
+    fn mutate_undoable(
+        &mut self,
+        _state: &mut S,
+        _input: &mut I,
+        _undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
+        Err(Error::not_implemented(format!(
+            "{} can not undo its mutations",
+            self.name()
+        )))
+    }
 }
 
 /// A mutator that takes input, and returns a vector of mutated inputs.
@@ -160,6 +219,18 @@ pub trait MutatorsTuple<I, S>: HasLen {
         corpus_idx: Option<CorpusId>,
     ) -> Result<(), Error>;
 
+    /// Returns if all [`Mutator`]`s` in this `Tuple` implement [`Mutator::mutate_undoable`].
+    fn can_undo_all(&self) -> bool;
+
+    /// Gets the [`Mutator`] at the given index and runs the `mutate_undoable` function on it.
+    fn get_and_mutate_undoable(
+        &mut self,
+        index: MutationId,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error>;
+
     /// Gets all names of the wrapped [`Mutator`]`s`, reversed.
     fn names_reversed(&self) -> Vec<&str>;
 
@@ -202,6 +273,22 @@ impl<I, S> MutatorsTuple<I, S> for () {
         Ok(())
     }
 
+    #[inline]
+    fn can_undo_all(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn get_and_mutate_undoable(
+        &mut self,
+        _index: MutationId,
+        _state: &mut S,
+        _input: &mut I,
+        _undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
+        Ok(MutationResult::Skipped)
+    }
+
     #[inline]
     fn names_reversed(&self) -> Vec<&str> {
         Vec::new()
@@ -262,6 +349,25 @@ where
         }
     }
 
+    fn can_undo_all(&self) -> bool {
+        self.0.can_undo() && self.1.can_undo_all()
+    }
+
+    fn get_and_mutate_undoable(
+        &mut self,
+        index: MutationId,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
+        if index.0 == 0 {
+            self.0.mutate_undoable(state, input, undo)
+        } else {
+            self.1
+                .get_and_mutate_undoable((index.0 - 1).into(), state, input, undo)
+        }
+    }
+
     fn names_reversed(&self) -> Vec<&str> {
         let mut ret = self.1.names_reversed();
         ret.push(self.0.name());
@@ -328,6 +434,20 @@ where
         self.0.get_and_post_exec(index, state, new_corpus_idx)
     }
 
+    fn can_undo_all(&self) -> bool {
+        self.0.can_undo_all()
+    }
+
+    fn get_and_mutate_undoable(
+        &mut self,
+        index: MutationId,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
+        self.0.get_and_mutate_undoable(index, state, input, undo)
+    }
+
     fn names(&self) -> Vec<&str> {
         self.0.names()
     }
@@ -393,6 +513,23 @@ impl<I, S> MutatorsTuple<I, S> for Vec<Box<dyn Mutator<I, S>>> {
         mutator.post_exec(state, new_corpus_idx)
     }
 
+    fn can_undo_all(&self) -> bool {
+        self.iter().all(|mutator| mutator.can_undo())
+    }
+
+    fn get_and_mutate_undoable(
+        &mut self,
+        index: MutationId,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
+        let mutator = self
+            .get_mut(index.0)
+            .ok_or_else(|| Error::key_not_found("Mutator with id {index:?} not found."))?;
+        mutator.mutate_undoable(state, input, undo)
+    }
+
     fn names_reversed(&self) -> Vec<&str> {
         self.iter().rev().map(|x| x.name().as_ref()).collect()
     }
@@ -2,16 +2,17 @@
 
 use alloc::{
     borrow::{Cow, ToOwned},
+    string::String,
     vec::Vec,
 };
-use core::{cmp::min, marker::PhantomData, mem::size_of, ops::Range};
+use core::{cmp::min, fmt::Write, marker::PhantomData, mem::size_of, ops::Range};
 
 use libafl_bolts::{rands::Rand, Named};
 
 use crate::{
     corpus::Corpus,
     inputs::HasMutatorBytes,
-    mutators::{MutationResult, Mutator},
+    mutators::{MutationResult, Mutator, UndoLog},
     random_corpus_id_with_disabled,
     state::{HasCorpus, HasMaxSize, HasRand},
     Error,
@@ -125,13 +126,21 @@ where
     S: HasRand,
     I: HasMutatorBytes,
 {
-    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+    mutate_with_undo!(I);
+
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
         if input.bytes().is_empty() {
             Ok(MutationResult::Skipped)
         } else {
             let bit = 1 << state.rand_mut().choose(0..8).unwrap();
-            let byte = state.rand_mut().choose(input.bytes_mut()).unwrap();
-            *byte ^= bit;
+            let idx = state.rand_mut().below(input.bytes().len());
+            undo.record_overwrite(input, idx..idx + 1);
+            input.bytes_mut()[idx] ^= bit;
             Ok(MutationResult::Mutated)
         }
     }
@@ -161,11 +170,21 @@ where
     S: HasRand,
     I: HasMutatorBytes,
 {
-    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+    mutate_with_undo!(I);
+
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
         if input.bytes().is_empty() {
             Ok(MutationResult::Skipped)
         } else {
-            *state.rand_mut().choose(input.bytes_mut()).unwrap() ^= 0xff;
+            let idx = state.rand_mut().below(input.bytes().len());
+            undo.record_overwrite(input, idx..idx + 1);
+            let byte = &mut input.bytes_mut()[idx];
+            *byte ^= 0xff;
             Ok(MutationResult::Mutated)
         }
     }
@@ -195,11 +214,20 @@ where
     S: HasRand,
     I: HasMutatorBytes,
 {
-    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+    mutate_with_undo!(I);
+
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
         if input.bytes().is_empty() {
             Ok(MutationResult::Skipped)
         } else {
-            let byte = state.rand_mut().choose(input.bytes_mut()).unwrap();
+            let idx = state.rand_mut().below(input.bytes().len());
+            undo.record_overwrite(input, idx..idx + 1);
+            let byte = &mut input.bytes_mut()[idx];
             *byte = byte.wrapping_add(1);
             Ok(MutationResult::Mutated)
         }
@@ -230,11 +258,20 @@ where
     S: HasRand,
     I: HasMutatorBytes,
 {
-    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+    mutate_with_undo!(I);
+
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
         if input.bytes().is_empty() {
             Ok(MutationResult::Skipped)
         } else {
-            let byte = state.rand_mut().choose(input.bytes_mut()).unwrap();
+            let idx = state.rand_mut().below(input.bytes().len());
+            undo.record_overwrite(input, idx..idx + 1);
+            let byte = &mut input.bytes_mut()[idx];
             *byte = byte.wrapping_sub(1);
             Ok(MutationResult::Mutated)
         }
@@ -265,11 +302,20 @@ where
     S: HasRand,
     I: HasMutatorBytes,
 {
-    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+    mutate_with_undo!(I);
+
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
         if input.bytes().is_empty() {
             Ok(MutationResult::Skipped)
         } else {
-            let byte = state.rand_mut().choose(input.bytes_mut()).unwrap();
+            let idx = state.rand_mut().below(input.bytes().len());
+            undo.record_overwrite(input, idx..idx + 1);
+            let byte = &mut input.bytes_mut()[idx];
             *byte = (!(*byte)).wrapping_add(1);
             Ok(MutationResult::Mutated)
         }
@@ -300,11 +346,20 @@ where
     S: HasRand,
     I: HasMutatorBytes,
 {
-    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+    mutate_with_undo!(I);
+
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
         if input.bytes().is_empty() {
             Ok(MutationResult::Skipped)
         } else {
-            let byte = state.rand_mut().choose(input.bytes_mut()).unwrap();
+            let idx = state.rand_mut().below(input.bytes().len());
+            undo.record_overwrite(input, idx..idx + 1);
+            let byte = &mut input.bytes_mut()[idx];
             *byte ^= 1 + state.rand_mut().below(254) as u8;
             Ok(MutationResult::Mutated)
         }
@@ -340,11 +395,13 @@ macro_rules! add_mutator_impl {
             S: HasRand,
             I: HasMutatorBytes,
         {
-            fn mutate(
+            mutate_with_undo!(I);
+
+            fn mutate_undoable(
                 &mut self,
                 state: &mut S,
                 input: &mut I,
-
+                undo: &mut UndoLog<I>,
             ) -> Result<MutationResult, Error> {
                 if input.bytes().len() < size_of::<$size>() {
                     Ok(MutationResult::Skipped)
@@ -365,6 +422,7 @@ macro_rules! add_mutator_impl {
                     };
 
                     // set bytes to mutated value
+                    undo.record_overwrite(input, index..index + size_of::<$size>());
                     let new_bytes = &mut input.bytes_mut()[index..index + size_of::<$size>()];
                     new_bytes.copy_from_slice(&new_val.to_ne_bytes());
                     Ok(MutationResult::Mutated)
@@ -407,20 +465,27 @@ macro_rules! interesting_mutator_impl {
             S: HasRand,
             I: HasMutatorBytes,
         {
+            mutate_with_undo!(I);
+
             #[allow(clippy::cast_sign_loss)]
-            fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+            fn mutate_undoable(
+                &mut self,
+                state: &mut S,
+                input: &mut I,
+                undo: &mut UndoLog<I>,
+            ) -> Result<MutationResult, Error> {
                 if input.bytes().len() < size_of::<$size>() {
                     Ok(MutationResult::Skipped)
                 } else {
-                    let bytes = input.bytes_mut();
-                    let upper_bound = (bytes.len() + 1 - size_of::<$size>());
+                    let upper_bound = (input.bytes().len() + 1 - size_of::<$size>());
                     let idx = state.rand_mut().below(upper_bound);
                     let val = *state.rand_mut().choose(&$interesting).unwrap() as $size;
                     let new_bytes = match state.rand_mut().choose(&[0, 1]).unwrap() {
                         0 => val.to_be_bytes(),
                         _ => val.to_le_bytes(),
                     };
-                    bytes[idx..idx + size_of::<$size>()].copy_from_slice(&new_bytes);
+                    undo.record_overwrite(input, idx..idx + size_of::<$size>());
+                    input.bytes_mut()[idx..idx + size_of::<$size>()].copy_from_slice(&new_bytes);
                     Ok(MutationResult::Mutated)
                 }
             }
@@ -456,7 +521,14 @@ where
     S: HasRand,
     I: HasMutatorBytes,
 {
-    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+    mutate_with_undo!(I);
+
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
         let size = input.bytes().len();
         if size <= 2 {
             return Ok(MutationResult::Skipped);
@@ -464,6 +536,7 @@ where
 
         let range = rand_range(state, size, size - 1);
 
+        undo.record_remove(input, range.clone());
         input.drain(range);
 
         Ok(MutationResult::Mutated)
@@ -494,7 +567,14 @@ where
     S: HasRand + HasMaxSize,
     I: HasMutatorBytes,
 {
-    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+    mutate_with_undo!(I);
+
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
         let max_size = state.max_size();
         let size = input.bytes().len();
         if size == 0 || size >= max_size {
@@ -503,6 +583,7 @@ where
 
         let range = rand_range(state, size, min(16, max_size - size));
 
+        undo.record_insert(range.start, range.len());
         input.resize(size + range.len(), 0);
         unsafe {
             buffer_self_copy(
@@ -541,7 +622,14 @@ where
     S: HasRand + HasMaxSize,
     I: HasMutatorBytes,
 {
-    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+    mutate_with_undo!(I);
+
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
         let max_size = state.max_size();
         let size = input.bytes().len();
         if size == 0 || size >= max_size {
@@ -561,6 +649,7 @@ where
 
         let val = input.bytes()[state.rand_mut().below(size)];
 
+        undo.record_insert(offset, amount);
         input.resize(size + amount, 0);
         unsafe {
             buffer_self_copy(input.bytes_mut(), offset, offset + amount, size - offset);
@@ -595,7 +684,14 @@ where
     S: HasRand + HasMaxSize,
     I: HasMutatorBytes,
 {
-    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+    mutate_with_undo!(I);
+
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
         let max_size = state.max_size();
         let size = input.bytes().len();
         if size >= max_size {
@@ -615,6 +711,7 @@ where
 
         let val = state.rand_mut().next() as u8;
 
+        undo.record_insert(offset, amount);
         input.resize(size + amount, 0);
         unsafe {
             buffer_self_copy(input.bytes_mut(), offset, offset + amount, size - offset);
@@ -649,7 +746,14 @@ where
     S: HasRand,
     I: HasMutatorBytes,
 {
-    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+    mutate_with_undo!(I);
+
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
         let size = input.bytes().len();
         if size == 0 {
             return Ok(MutationResult::Skipped);
@@ -658,6 +762,7 @@ where
 
         let val = *state.rand_mut().choose(input.bytes()).unwrap();
         let quantity = range.len();
+        undo.record_overwrite(input, range.clone());
         buffer_set(input.bytes_mut(), range.start, quantity, val);
 
         Ok(MutationResult::Mutated)
@@ -688,7 +793,14 @@ where
     S: HasRand,
     I: HasMutatorBytes,
 {
-    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+    mutate_with_undo!(I);
+
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
         let size = input.bytes().len();
         if size == 0 {
             return Ok(MutationResult::Skipped);
@@ -697,6 +809,7 @@ where
 
         let val = state.rand_mut().next() as u8;
         let quantity = range.len();
+        undo.record_overwrite(input, range.clone());
         buffer_set(input.bytes_mut(), range.start, quantity, val);
 
         Ok(MutationResult::Mutated)
@@ -727,7 +840,14 @@ where
     S: HasRand,
     I: HasMutatorBytes,
 {
-    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+    mutate_with_undo!(I);
+
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
         let size = input.bytes().len();
         if size <= 1 {
             return Ok(MutationResult::Skipped);
@@ -736,6 +856,7 @@ where
         let target = state.rand_mut().below(size);
         let range = rand_range(state, size, size - target);
 
+        undo.record_overwrite(input, target..target + range.len());
         unsafe {
             buffer_self_copy(input.bytes_mut(), range.start, target, range.len());
         }
@@ -770,7 +891,14 @@ where
     S: HasRand + HasMaxSize,
     I: HasMutatorBytes,
 {
-    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+    mutate_with_undo!(I);
+
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
         let size = input.bytes().len();
         if size <= 1 || size >= state.max_size() {
             return Ok(MutationResult::Skipped);
@@ -781,6 +909,7 @@ where
         let max_insert_len = min(size - target, state.max_size() - size);
         let range = rand_range(state, size, min(16, max_insert_len));
 
+        undo.record_insert(target, range.len());
         input.resize(size + range.len(), 0);
         self.tmp_buf.resize(range.len(), 0);
         unsafe {
@@ -831,7 +960,14 @@ where
     S: HasRand,
     I: HasMutatorBytes,
 {
-    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+    mutate_with_undo!(I);
+
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
         let size = input.bytes().len();
         if size <= 1 {
             return Ok(MutationResult::Skipped);
@@ -842,6 +978,7 @@ where
             // The second range comes before first.
 
             let second = rand_range(state, first.start, first.start);
+            undo.record_overwrite(input, second.start..first.end);
             self.tmp_buf.resize(first.len(), 0);
             unsafe {
                 // If range first is larger
@@ -926,6 +1063,7 @@ where
             second.start += first.end;
             second.end += first.end;
 
+            undo.record_overwrite(input, first.start..second.end);
             self.tmp_buf.resize(second.len(), 0);
             unsafe {
                 if second.len() >= first.len() {
@@ -1066,7 +1204,14 @@ where
     S::Input: HasMutatorBytes,
     I: HasMutatorBytes,
 {
-    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+    mutate_with_undo!(I);
+
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
         let size = input.bytes().len();
         let max_size = state.max_size();
         if size >= max_size {
@@ -1098,6 +1243,7 @@ where
         // No need to load the input again, it'll still be cached.
         let other = other_testcase.input().as_ref().unwrap();
 
+        undo.record_insert(target, range.len());
         Ok(Self::crossover_insert(input, size, target, range, other))
     }
 }
@@ -1151,7 +1297,14 @@ where
     S::Input: HasMutatorBytes,
     I: HasMutatorBytes,
 {
-    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+    mutate_with_undo!(I);
+
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
         let size = input.bytes().len();
         if size == 0 {
             return Ok(MutationResult::Skipped);
@@ -1181,6 +1334,7 @@ where
         // No need to load the input again, it'll still be cached.
         let other = other_testcase.input().as_ref().unwrap();
 
+        undo.record_overwrite(input, target..target + range.len());
         Ok(Self::crossover_replace(input, target, range, other))
     }
 }
@@ -1228,8 +1382,15 @@ where
     S: HasCorpus + HasRand,
     S::Input: HasMutatorBytes,
 {
+    mutate_with_undo!(S::Input);
+
     #[allow(clippy::cast_sign_loss)]
-    fn mutate(&mut self, state: &mut S, input: &mut S::Input) -> Result<MutationResult, Error> {
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut S::Input,
+        undo: &mut UndoLog<S::Input>,
+    ) -> Result<MutationResult, Error> {
         // We don't want to use the testcase we're already using for splicing
         let idx = random_corpus_id_with_disabled!(state.corpus(), state.rand_mut());
         if let Some(cur) = state.corpus().current() {
@@ -1257,6 +1418,11 @@ where
         // Input will already be loaded.
         let other = other_testcase.input().as_ref().unwrap();
 
+        undo.record_splice(
+            input,
+            split_at..input.bytes().len(),
+            other.bytes().len() - split_at,
+        );
         input.splice(split_at.., other.bytes()[split_at..].iter().copied());
 
         Ok(MutationResult::Mutated)
@@ -1320,6 +1486,21 @@ pub fn str_decode(item: &str) -> Result<Vec<u8>, Error> {
     Ok(token)
 }
 
+/// Encodes a dictionary token, so that [`str_decode`] decodes it again: 'fooA\and"bar\0' -> 'fooA\\and\"bar\x00'
+#[must_use]
+pub fn str_encode(token: &[u8]) -> String {
+    let mut item = String::with_capacity(token.len());
+    for &c in token {
+        match c {
+            b'\\' => item.push_str("\\\\"),
+            b'"' => item.push_str("\\\""),
+            0x20..=0x7e => item.push(char::from(c)),
+            _ => write!(item, "\\x{c:02x}").unwrap(),
+        }
+    }
+    item
+}
+
 #[cfg(test)]
 mod tests {
     use libafl_bolts::{
@@ -1445,6 +1626,36 @@ mod tests {
         }
     }
 
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_mutators_undo() {
+        let inputs = [
+            BytesInput::new(vec![0x13, 0x37]),
+            BytesInput::new((0..=255).collect()),
+            BytesInput::new(vec![]),
+            BytesInput::new(vec![1; 4]),
+        ];
+
+        let mut state = test_state();
+        let mut mutations = test_mutations();
+
+        let mut undo = UndoLog::new();
+        for input in &inputs {
+            let mut mutant = input.clone();
+            for _ in 0..100 {
+                // stack a few mutations, then undo them all at once
+                for _ in 0..4 {
+                    let idx = state.rand_mut().below(TestMutatorsTupleType::LEN);
+                    mutations
+                        .get_and_mutate_undoable(idx.into(), &mut state, &mut mutant, &mut undo)
+                        .unwrap();
+                }
+                undo.revert(&mut mutant);
+                assert_eq!(&mutant, input);
+            }
+        }
+    }
+
     /// This test guarantees that the deletion of each byte is equally likely
     #[test]
     fn test_delete() -> Result<(), Error> {
@@ -1648,4 +1859,12 @@ mod tests {
             < 500));
         Ok(())
     }
+
+    #[test]
+    fn test_str_encode() {
+        let token = b"foo\\and\"bar\x00\xff\n".to_vec();
+        let item = str_encode(&token);
+        assert_eq!(item, "foo\\\\and\\\"bar\\x00\\xff\\x0a");
+        assert_eq!(str_decode(&item).unwrap(), token);
+    }
 }
@@ -27,7 +27,7 @@ use crate::{
             DwordInterestingMutator, QwordAddMutator, WordAddMutator, WordInterestingMutator,
         },
         token_mutations::{TokenInsert, TokenReplace},
-        MutationResult, Mutator, MutatorsTuple,
+        MutationResult, Mutator, MutatorsTuple, UndoLog,
     },
     state::{HasCorpus, HasRand},
     Error, HasMetadata,
@@ -104,6 +104,28 @@ where
         }
         Ok(r)
     }
+
+    /// Like [`ScheduledMutator::scheduled_mutate`], recording the changes of the stacked mutations in `undo`.
+    /// Implementations may forward `mutate_undoable()` to this method if all their mutations can undo.
+    fn scheduled_mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
+        let mut r = MutationResult::Skipped;
+        let num = self.iterations(state, input);
+        for _ in 0..num {
+            let idx = self.schedule(state, input);
+            let outcome = self
+                .mutations_mut()
+                .get_and_mutate_undoable(idx, state, input, undo)?;
+            if outcome == MutationResult::Mutated {
+                r = MutationResult::Mutated;
+            }
+        }
+        Ok(r)
+    }
 }
 
 /// A [`Mutator`] that schedules one of the embedded mutations on each call.
@@ -152,6 +174,21 @@ where
     fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
         self.scheduled_mutate(state, input)
     }
+
+    #[inline]
+    fn can_undo(&self) -> bool {
+        self.mutations.can_undo_all()
+    }
+
+    #[inline]
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
+        self.scheduled_mutate_undoable(state, input, undo)
+    }
 }
 
 impl<I, MT, S> ComposedByMutations<I, MT, S> for StdScheduledMutator<I, MT, S>
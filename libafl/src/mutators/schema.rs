@@ -0,0 +1,417 @@
+//! Mutators for [`SchemaInput`]s, changing the fields described by the [`crate::inputs::schema::Schema`].
+//!
+//! Length, count and checksum fields are never mutated, they are fixed up when the input is serialized.
+
+use alloc::{borrow::Cow, sync::Arc};
+use core::mem;
+
+use libafl_bolts::{
+    rands::Rand,
+    tuples::{tuple_list, tuple_list_type},
+    Named,
+};
+
+use crate::{
+    corpus::CorpusId,
+    inputs::{
+        schema::{generate_fields, truncate, FieldType, FieldValue, Schema},
+        BytesInput, SchemaInput,
+    },
+    mutators::{
+        mutations::{ARITH_MAX, INTERESTING_32},
+        MutationResult, Mutator,
+    },
+    state::HasRand,
+    Error,
+};
+
+/// Tuple type of the mutations that change integers and arrays of a [`SchemaInput`]
+pub type SchemaMutationsType = tuple_list_type!(
+    SchemaIntMutator,
+    SchemaArrayInsertMutator,
+    SchemaArrayDeleteMutator,
+    SchemaArraySwapMutator,
+);
+
+/// Get the mutations that change integers and arrays of [`SchemaInput`]s of the given schema.
+/// Combine them with a [`SchemaBytesMutator`] to also mutate the raw bytes fields.
+#[must_use]
+pub fn schema_mutations(schema: &Arc<Schema>) -> SchemaMutationsType {
+    tuple_list!(
+        SchemaIntMutator::new(schema.clone()),
+        SchemaArrayInsertMutator::new(schema.clone()),
+        SchemaArrayDeleteMutator::new(schema.clone()),
+        SchemaArraySwapMutator::new(schema.clone()),
+    )
+}
+
+/// Picks a random field for which `filter` is `true` and calls `mutate` with it
+fn mutate_random_field<S, F, M>(
+    state: &mut S,
+    schema: &Schema,
+    input: &mut SchemaInput,
+    filter: F,
+    mutate: M,
+) -> Result<MutationResult, Error>
+where
+    S: HasRand,
+    F: Fn(&FieldType) -> bool,
+    M: FnOnce(&mut S, &FieldType, &mut FieldValue) -> Result<MutationResult, Error>,
+{
+    let count = input.count_fields(schema, &filter);
+    if count == 0 {
+        return Ok(MutationResult::Skipped);
+    }
+    let n = state.rand_mut().below(count);
+    input
+        .mutate_nth_field(schema, n, filter, |ty, value| mutate(state, ty, value))
+        .unwrap_or(Ok(MutationResult::Skipped))
+}
+
+/// Changes a random integer field: sets it to a random or interesting value, adds or subtracts a small number,
+/// or flips a bit.
+#[derive(Debug, Clone)]
+pub struct SchemaIntMutator {
+    schema: Arc<Schema>,
+}
+
+impl SchemaIntMutator {
+    /// Creates a new [`SchemaIntMutator`] for inputs of the given schema
+    #[must_use]
+    pub fn new(schema: Arc<Schema>) -> Self {
+        Self { schema }
+    }
+}
+
+impl Named for SchemaIntMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SchemaIntMutator");
+        &NAME
+    }
+}
+
+impl<S> Mutator<SchemaInput, S> for SchemaIntMutator
+where
+    S: HasRand,
+{
+    #[allow(clippy::cast_sign_loss)]
+    fn mutate(&mut self, state: &mut S, input: &mut SchemaInput) -> Result<MutationResult, Error> {
+        mutate_random_field(
+            state,
+            &self.schema,
+            input,
+            |ty| matches!(ty, FieldType::Int { .. }),
+            |state, ty, value| {
+                let (FieldType::Int { width, .. }, FieldValue::Int(value)) = (ty, value) else {
+                    return Ok(MutationResult::Skipped);
+                };
+                let rand = state.rand_mut();
+                let new_value = match rand.below(4) {
+                    0 => rand.next(),
+                    1 => {
+                        let delta = 1 + rand.below(ARITH_MAX) as u64;
+                        if rand.coinflip(0.5) {
+                            value.wrapping_add(delta)
+                        } else {
+                            value.wrapping_sub(delta)
+                        }
+                    }
+                    2 => i64::from(rand.choose(INTERESTING_32).unwrap()) as u64,
+                    _ => *value ^ (1 << rand.below(width * 8)),
+                };
+                let new_value = truncate(new_value, *width);
+                if new_value == *value {
+                    return Ok(MutationResult::Skipped);
+                }
+                *value = new_value;
+                Ok(MutationResult::Mutated)
+            },
+        )
+    }
+}
+
+/// Mutates the content of a random bytes field with the wrapped mutator,
+/// for example a [`crate::mutators::StdScheduledMutator`] using [`crate::mutators::havoc_mutations_no_crossover`].
+/// The result is cut or zero-padded to fit the bounds of the field.
+#[derive(Debug)]
+pub struct SchemaBytesMutator<M> {
+    name: Cow<'static, str>,
+    schema: Arc<Schema>,
+    inner: M,
+}
+
+impl<M> SchemaBytesMutator<M>
+where
+    M: Named,
+{
+    /// Creates a new [`SchemaBytesMutator`] for inputs of the given schema, wrapping the given mutator
+    pub fn new(schema: Arc<Schema>, inner: M) -> Self {
+        let name = Cow::from(format!("SchemaBytesMutator<{}>", inner.name()));
+        Self {
+            name,
+            schema,
+            inner,
+        }
+    }
+}
+
+impl<M> Named for SchemaBytesMutator<M> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<M, S> Mutator<SchemaInput, S> for SchemaBytesMutator<M>
+where
+    M: Mutator<BytesInput, S>,
+    S: HasRand,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut SchemaInput) -> Result<MutationResult, Error> {
+        let inner = &mut self.inner;
+        mutate_random_field(
+            state,
+            &self.schema,
+            input,
+            |ty| matches!(ty, FieldType::Bytes { max_len, .. } if *max_len > 0),
+            |state, ty, value| {
+                let (FieldType::Bytes { min_len, max_len }, FieldValue::Bytes(data)) = (ty, value)
+                else {
+                    return Ok(MutationResult::Skipped);
+                };
+                let mut bytes = BytesInput::new(mem::take(data));
+                let result = inner.mutate(state, &mut bytes);
+                *data = bytes.into();
+                if data.len() > *max_len {
+                    data.truncate(*max_len);
+                } else if data.len() < *min_len {
+                    data.resize(*min_len, 0);
+                }
+                result
+            },
+        )
+    }
+
+    fn post_exec(&mut self, state: &mut S, new_corpus_idx: Option<CorpusId>) -> Result<(), Error> {
+        self.inner.post_exec(state, new_corpus_idx)
+    }
+}
+
+/// Inserts an element into a random array: either a copy of an existing element, or a newly generated one.
+#[derive(Debug, Clone)]
+pub struct SchemaArrayInsertMutator {
+    schema: Arc<Schema>,
+}
+
+impl SchemaArrayInsertMutator {
+    /// Creates a new [`SchemaArrayInsertMutator`] for inputs of the given schema
+    #[must_use]
+    pub fn new(schema: Arc<Schema>) -> Self {
+        Self { schema }
+    }
+}
+
+impl Named for SchemaArrayInsertMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SchemaArrayInsertMutator");
+        &NAME
+    }
+}
+
+impl<S> Mutator<SchemaInput, S> for SchemaArrayInsertMutator
+where
+    S: HasRand,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut SchemaInput) -> Result<MutationResult, Error> {
+        mutate_random_field(
+            state,
+            &self.schema,
+            input,
+            |ty| matches!(ty, FieldType::Array { .. }),
+            |state, ty, value| {
+                let (
+                    FieldType::Array {
+                        element, max_len, ..
+                    },
+                    FieldValue::Array(items),
+                ) = (ty, value)
+                else {
+                    return Ok(MutationResult::Skipped);
+                };
+                if items.len() >= *max_len {
+                    return Ok(MutationResult::Skipped);
+                }
+                let rand = state.rand_mut();
+                let item = if !items.is_empty() && rand.coinflip(0.5) {
+                    items[rand.below(items.len())].clone()
+                } else {
+                    FieldValue::Struct(generate_fields(element, rand))
+                };
+                let idx = rand.below(items.len() + 1);
+                items.insert(idx, item);
+                Ok(MutationResult::Mutated)
+            },
+        )
+    }
+}
+
+/// Deletes a random element of a random array.
+#[derive(Debug, Clone)]
+pub struct SchemaArrayDeleteMutator {
+    schema: Arc<Schema>,
+}
+
+impl SchemaArrayDeleteMutator {
+    /// Creates a new [`SchemaArrayDeleteMutator`] for inputs of the given schema
+    #[must_use]
+    pub fn new(schema: Arc<Schema>) -> Self {
+        Self { schema }
+    }
+}
+
+impl Named for SchemaArrayDeleteMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SchemaArrayDeleteMutator");
+        &NAME
+    }
+}
+
+impl<S> Mutator<SchemaInput, S> for SchemaArrayDeleteMutator
+where
+    S: HasRand,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut SchemaInput) -> Result<MutationResult, Error> {
+        mutate_random_field(
+            state,
+            &self.schema,
+            input,
+            |ty| matches!(ty, FieldType::Array { .. }),
+            |state, ty, value| {
+                let (FieldType::Array { min_len, .. }, FieldValue::Array(items)) = (ty, value)
+                else {
+                    return Ok(MutationResult::Skipped);
+                };
+                if items.len() <= *min_len {
+                    return Ok(MutationResult::Skipped);
+                }
+                let idx = state.rand_mut().below(items.len());
+                items.remove(idx);
+                Ok(MutationResult::Mutated)
+            },
+        )
+    }
+}
+
+/// Swaps two elements of a random array.
+#[derive(Debug, Clone)]
+pub struct SchemaArraySwapMutator {
+    schema: Arc<Schema>,
+}
+
+impl SchemaArraySwapMutator {
+    /// Creates a new [`SchemaArraySwapMutator`] for inputs of the given schema
+    #[must_use]
+    pub fn new(schema: Arc<Schema>) -> Self {
+        Self { schema }
+    }
+}
+
+impl Named for SchemaArraySwapMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SchemaArraySwapMutator");
+        &NAME
+    }
+}
+
+impl<S> Mutator<SchemaInput, S> for SchemaArraySwapMutator
+where
+    S: HasRand,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut SchemaInput) -> Result<MutationResult, Error> {
+        mutate_random_field(
+            state,
+            &self.schema,
+            input,
+            |ty| matches!(ty, FieldType::Array { .. }),
+            |state, _ty, value| {
+                let FieldValue::Array(items) = value else {
+                    return Ok(MutationResult::Skipped);
+                };
+                let count = items.len();
+                if count < 2 {
+                    return Ok(MutationResult::Skipped);
+                }
+                let first = state.rand_mut().below(count);
+                let second = (first + 1 + state.rand_mut().below(count - 1)) % count;
+                if items[first] == items[second] {
+                    return Ok(MutationResult::Skipped);
+                }
+                items.swap(first, second);
+                Ok(MutationResult::Mutated)
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::{rands::StdRand, tuples::HasConstLen};
+
+    use super::*;
+    use crate::{
+        inputs::schema::{ChecksumKind, Endian, Field},
+        mutators::{havoc_mutations_no_crossover, MutatorsTuple, StdScheduledMutator},
+        state::NopState,
+    };
+
+    #[test]
+    fn test_schema_mutations_keep_fixups() {
+        let schema = Arc::new(
+            Schema::new(vec![
+                Field::magic("magic", b"LAFL"),
+                Field::count_of("count", "records", 1, Endian::Little),
+                Field::array(
+                    "records",
+                    vec![
+                        Field::int("tag", 2, Endian::Little),
+                        Field::length_of("length", "payload", 2, Endian::Big),
+                        Field::bytes("payload", 1, 32),
+                        Field::checksum(
+                            "adler",
+                            ChecksumKind::Adler32,
+                            Endian::Little,
+                            &["tag", "payload"],
+                        ),
+                    ],
+                    1,
+                    8,
+                ),
+            ])
+            .unwrap(),
+        );
+
+        let mut state: NopState<SchemaInput> = NopState::new();
+        let mut input = SchemaInput::new(
+            &schema,
+            generate_fields(schema.fields(), &mut StdRand::with_seed(1337)),
+        );
+        let mut mutations = schema_mutations(&schema);
+        let mut bytes_mutator = SchemaBytesMutator::new(
+            schema.clone(),
+            StdScheduledMutator::new(havoc_mutations_no_crossover()),
+        );
+
+        for i in 0..256 {
+            if i % 2 == 0 {
+                let idx = state.rand_mut().below(SchemaMutationsType::LEN);
+                mutations
+                    .get_and_mutate(idx.into(), &mut state, &mut input)
+                    .unwrap();
+            } else {
+                bytes_mutator.mutate(&mut state, &mut input).unwrap();
+            }
+            // Every mutant is well-formed, so it parses back to the same input
+            let parsed = SchemaInput::parse(&schema, input.bytes()).unwrap();
+            assert_eq!(parsed, input);
+        }
+    }
+}
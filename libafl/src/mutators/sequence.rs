@@ -0,0 +1,410 @@
+//! Mutators for [`MessageSequenceInput`]s, changing which messages are sent and in which order.
+//!
+//! The messages themselves are mutated by wrapping a [`Mutator`] for a single message in a [`SequenceMessageMutator`].
+
+use alloc::borrow::Cow;
+use core::cmp::min;
+
+use libafl_bolts::{
+    rands::Rand,
+    tuples::{tuple_list, tuple_list_type},
+    Named,
+};
+
+use crate::{
+    corpus::{Corpus, CorpusId},
+    inputs::{Input, MessageSequenceInput},
+    mutators::{MutationResult, Mutator},
+    random_corpus_id_with_disabled,
+    state::{HasCorpus, HasRand},
+    Error,
+};
+
+/// The default maximum number of messages a sequence grows to
+pub const DEFAULT_MAX_MESSAGES: usize = 64;
+
+/// Tuple type of the mutations that change the structure of a [`MessageSequenceInput`]
+pub type SequenceMutationsType = tuple_list_type!(
+    SequenceInsertMutator,
+    SequenceDeleteMutator,
+    SequenceReorderMutator,
+    SequenceSpliceMutator,
+);
+
+/// Get the mutations that change the structure of a [`MessageSequenceInput`].
+/// Combine them with a [`SequenceMessageMutator`] to also mutate the messages.
+#[must_use]
+pub fn sequence_mutations() -> SequenceMutationsType {
+    tuple_list!(
+        SequenceInsertMutator::new(),
+        SequenceDeleteMutator::new(),
+        SequenceReorderMutator::new(),
+        SequenceSpliceMutator::new(),
+    )
+}
+
+/// Mutates a single, random message of a [`MessageSequenceInput`] with the wrapped mutator,
+/// for example a [`crate::mutators::StdScheduledMutator`] using [`crate::mutators::havoc_mutations_no_crossover`].
+#[derive(Debug)]
+pub struct SequenceMessageMutator<M> {
+    name: Cow<'static, str>,
+    inner: M,
+}
+
+impl<M> SequenceMessageMutator<M>
+where
+    M: Named,
+{
+    /// Creates a new [`SequenceMessageMutator`] wrapping the given mutator
+    pub fn new(inner: M) -> Self {
+        let name = Cow::from(format!("SequenceMessageMutator<{}>", inner.name()));
+        Self { name, inner }
+    }
+}
+
+impl<M> Named for SequenceMessageMutator<M> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, M, S> Mutator<MessageSequenceInput<I>, S> for SequenceMessageMutator<M>
+where
+    M: Mutator<I, S>,
+    S: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut MessageSequenceInput<I>,
+    ) -> Result<MutationResult, Error> {
+        let count = input.messages().len();
+        if count == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+        let idx = state.rand_mut().below(count);
+        self.inner.mutate(state, &mut input.messages_mut()[idx])
+    }
+
+    fn post_exec(&mut self, state: &mut S, new_corpus_idx: Option<CorpusId>) -> Result<(), Error> {
+        self.inner.post_exec(state, new_corpus_idx)
+    }
+}
+
+/// Inserts a message, taken from a random corpus entry, at a random position.
+#[derive(Debug, Clone)]
+pub struct SequenceInsertMutator {
+    max_messages: usize,
+}
+
+impl SequenceInsertMutator {
+    /// Creates a new [`SequenceInsertMutator`], growing sequences up to [`DEFAULT_MAX_MESSAGES`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_max_messages(DEFAULT_MAX_MESSAGES)
+    }
+
+    /// Creates a new [`SequenceInsertMutator`], growing sequences up to `max_messages`
+    #[must_use]
+    pub fn with_max_messages(max_messages: usize) -> Self {
+        Self { max_messages }
+    }
+}
+
+impl Default for SequenceInsertMutator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Named for SequenceInsertMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SequenceInsertMutator");
+        &NAME
+    }
+}
+
+impl<I, S> Mutator<MessageSequenceInput<I>, S> for SequenceInsertMutator
+where
+    I: Input,
+    S: HasCorpus<Input = MessageSequenceInput<I>> + HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut MessageSequenceInput<I>,
+    ) -> Result<MutationResult, Error> {
+        let count = input.messages().len();
+        if count >= self.max_messages {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let idx = random_corpus_id_with_disabled!(state.corpus(), state.rand_mut());
+        let other_count = {
+            let mut testcase = state.corpus().get_from_all(idx)?.borrow_mut();
+            testcase.load_input(state.corpus())?.messages().len()
+        };
+        if other_count == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let from = state.rand_mut().below(other_count);
+        let to = state.rand_mut().below(count + 1);
+
+        let other_testcase = state.corpus().get_from_all(idx)?.borrow();
+        // No need to load the input again, it'll still be cached.
+        let message = other_testcase.input().as_ref().unwrap().messages()[from].clone();
+        input.messages_mut().insert(to, message);
+        Ok(MutationResult::Mutated)
+    }
+}
+
+/// Deletes a random message, keeping at least one.
+#[derive(Debug, Clone, Default)]
+pub struct SequenceDeleteMutator;
+
+impl SequenceDeleteMutator {
+    /// Creates a new [`SequenceDeleteMutator`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Named for SequenceDeleteMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SequenceDeleteMutator");
+        &NAME
+    }
+}
+
+impl<I, S> Mutator<MessageSequenceInput<I>, S> for SequenceDeleteMutator
+where
+    S: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut MessageSequenceInput<I>,
+    ) -> Result<MutationResult, Error> {
+        let count = input.messages().len();
+        if count < 2 {
+            return Ok(MutationResult::Skipped);
+        }
+        let idx = state.rand_mut().below(count);
+        input.messages_mut().remove(idx);
+        Ok(MutationResult::Mutated)
+    }
+}
+
+/// Moves a random message to another position.
+#[derive(Debug, Clone, Default)]
+pub struct SequenceReorderMutator;
+
+impl SequenceReorderMutator {
+    /// Creates a new [`SequenceReorderMutator`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Named for SequenceReorderMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SequenceReorderMutator");
+        &NAME
+    }
+}
+
+impl<I, S> Mutator<MessageSequenceInput<I>, S> for SequenceReorderMutator
+where
+    S: HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut MessageSequenceInput<I>,
+    ) -> Result<MutationResult, Error> {
+        let count = input.messages().len();
+        if count < 2 {
+            return Ok(MutationResult::Skipped);
+        }
+        let from = state.rand_mut().below(count);
+        // Pick a different position, so the sequence always changes
+        let to = (from + 1 + state.rand_mut().below(count - 1)) % count;
+
+        let messages = input.messages_mut();
+        let message = messages.remove(from);
+        messages.insert(to, message);
+        Ok(MutationResult::Mutated)
+    }
+}
+
+/// Keeps the messages up to a random position, and replaces the rest with the tail of another corpus entry,
+/// similar to how `AFLNet` splices message sequences.
+#[derive(Debug, Clone)]
+pub struct SequenceSpliceMutator {
+    max_messages: usize,
+}
+
+impl SequenceSpliceMutator {
+    /// Creates a new [`SequenceSpliceMutator`], growing sequences up to [`DEFAULT_MAX_MESSAGES`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_max_messages(DEFAULT_MAX_MESSAGES)
+    }
+
+    /// Creates a new [`SequenceSpliceMutator`], growing sequences up to `max_messages`
+    #[must_use]
+    pub fn with_max_messages(max_messages: usize) -> Self {
+        Self { max_messages }
+    }
+}
+
+impl Default for SequenceSpliceMutator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Named for SequenceSpliceMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("SequenceSpliceMutator");
+        &NAME
+    }
+}
+
+impl<I, S> Mutator<MessageSequenceInput<I>, S> for SequenceSpliceMutator
+where
+    I: Input,
+    S: HasCorpus<Input = MessageSequenceInput<I>> + HasRand,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut MessageSequenceInput<I>,
+    ) -> Result<MutationResult, Error> {
+        let count = input.messages().len();
+        if count == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+
+        // We don't want to use the testcase we're already using for splicing
+        let idx = random_corpus_id_with_disabled!(state.corpus(), state.rand_mut());
+        if let Some(cur) = state.corpus().current() {
+            if idx == *cur {
+                return Ok(MutationResult::Skipped);
+            }
+        }
+
+        let other_count = {
+            let mut testcase = state.corpus().get_from_all(idx)?.borrow_mut();
+            testcase.load_input(state.corpus())?.messages().len()
+        };
+        if other_count < 2 {
+            return Ok(MutationResult::Skipped);
+        }
+
+        // Keep at least one message of our own, and take at least one of the other
+        let keep = 1 + state.rand_mut().below(count);
+        let from = 1 + state.rand_mut().below(other_count - 1);
+        let take = min(other_count - from, self.max_messages.saturating_sub(keep));
+        if take == 0 {
+            return Ok(MutationResult::Skipped);
+        }
+
+        let other_testcase = state.corpus().get_from_all(idx)?.borrow();
+        let other = other_testcase.input().as_ref().unwrap();
+        let messages = input.messages_mut();
+        messages.truncate(keep);
+        messages.extend_from_slice(&other.messages()[from..from + take]);
+        Ok(MutationResult::Mutated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use libafl_bolts::rands::StdRand;
+
+    use super::*;
+    use crate::{
+        corpus::InMemoryCorpus,
+        feedbacks::ConstFeedback,
+        inputs::{BytesInput, HasMutatorBytes},
+        mutators::{havoc_mutations_no_crossover, StdScheduledMutator},
+        state::StdState,
+    };
+
+    fn sequence(messages: &[&[u8]]) -> MessageSequenceInput<BytesInput> {
+        messages
+            .iter()
+            .map(|message| BytesInput::new(message.to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn test_sequence_mutators() {
+        let mut corpus = InMemoryCorpus::new();
+        corpus
+            .add(sequence(&[b"USER x", b"PASS y", b"LIST", b"QUIT"]).into())
+            .unwrap();
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+
+        let original = sequence(&[b"HELO", b"MAIL", b"DATA"]);
+
+        let mut input = original.clone();
+        SequenceDeleteMutator::new()
+            .mutate(&mut state, &mut input)
+            .unwrap();
+        assert_eq!(input.messages().len(), 2);
+
+        let mut input = original.clone();
+        SequenceReorderMutator::new()
+            .mutate(&mut state, &mut input)
+            .unwrap();
+        assert_ne!(input, original);
+        let mut sorted = input.messages().to_vec();
+        sorted.sort_by(|a, b| a.bytes().cmp(b.bytes()));
+        let mut expected = original.messages().to_vec();
+        expected.sort_by(|a, b| a.bytes().cmp(b.bytes()));
+        assert_eq!(sorted, expected);
+
+        let mut input = original.clone();
+        SequenceInsertMutator::new()
+            .mutate(&mut state, &mut input)
+            .unwrap();
+        assert_eq!(input.messages().len(), 4);
+
+        let mut input = original.clone();
+        for _ in 0..16 {
+            SequenceSpliceMutator::with_max_messages(4)
+                .mutate(&mut state, &mut input)
+                .unwrap();
+            assert!(input.messages().len() <= 4);
+        }
+
+        let mut input = original.clone();
+        let mut message_mutator =
+            SequenceMessageMutator::new(StdScheduledMutator::new(havoc_mutations_no_crossover()));
+        while message_mutator.mutate(&mut state, &mut input).unwrap() == MutationResult::Skipped {}
+        assert_eq!(input.messages().len(), 3);
+        let changed: Vec<_> = input
+            .messages()
+            .iter()
+            .zip(original.messages())
+            .filter(|(a, b)| a != b)
+            .collect();
+        assert!(changed.len() <= 1);
+    }
+}
@@ -12,21 +12,22 @@ use core::{
 #[cfg(feature = "std")]
 use std::{
     fs::File,
-    io::{BufRead, BufReader},
+    io::{BufRead, BufReader, BufWriter, Write},
     path::Path,
 };
 
-use hashbrown::HashSet;
+use hashbrown::HashMap;
 use libafl_bolts::{rands::Rand, AsSlice};
 use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "std")]
-use crate::mutators::str_decode;
+use crate::mutators::{str_decode, str_encode};
 use crate::{
     corpus::{CorpusId, HasCurrentCorpusId},
     inputs::{HasMutatorBytes, UsesInput},
     mutators::{
         buffer_self_copy, mutations::buffer_copy, MultiMutator, MutationResult, Mutator, Named,
+        UndoLog,
     },
     observers::cmp::{AFLppCmpValuesMetadata, CmpValues, CmpValuesMetadata},
     stages::TaintMetadata,
@@ -38,9 +39,11 @@ use crate::{
 #[allow(clippy::unsafe_derive_deserialize)]
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Tokens {
-    // We keep a vec and a set, set for faster deduplication, vec for access
+    // We keep a vec and a map, map from each token to its index in the vec for faster deduplication, vec for access
     tokens_vec: Vec<Vec<u8>>,
-    tokens_set: HashSet<Vec<u8>>,
+    token_indexes: HashMap<Vec<u8>, usize>,
+    // How often each token in the vec was added, duplicates included
+    hits: Vec<u64>,
 }
 
 libafl_bolts::impl_serdeany!(Tokens);
@@ -146,13 +149,36 @@ impl Tokens {
     /// Returns `false` if the token was already present and did not get added.
     #[allow(clippy::ptr_arg)]
     pub fn add_token(&mut self, token: &Vec<u8>) -> bool {
-        if !self.tokens_set.insert(token.clone()) {
+        self.add_token_with_hits(token, 1)
+    }
+
+    /// Adds a token to a dictionary, that was seen `hits` times.
+    /// If the token is a duplicate, its hits are added to the ones of the present token.
+    /// Returns `false` if the token was already present and did not get added.
+    pub fn add_token_with_hits(&mut self, token: &[u8], hits: u64) -> bool {
+        if let Some(&pos) = self.token_indexes.get(token) {
+            self.hits[pos] = self.hits[pos].saturating_add(hits);
             return false;
         }
-        self.tokens_vec.push(token.clone());
+        self.token_indexes.insert(token.to_vec(), self.tokens_vec.len());
+        self.tokens_vec.push(token.to_vec());
+        self.hits.push(hits);
         true
     }
 
+    /// Returns `true` if the token is in this dictionary
+    #[must_use]
+    pub fn contains_token(&self, token: &[u8]) -> bool {
+        self.token_indexes.contains_key(token)
+    }
+
+    /// Adds all tokens of `other`, with their hits
+    fn merge(&mut self, other: &Self) {
+        for (token, hits) in other.tokens_vec.iter().zip(&other.hits) {
+            self.add_token_with_hits(token, *hits);
+        }
+    }
+
     /// Reads a tokens file, returning the count of new entries read
     #[cfg(feature = "std")]
     pub fn add_from_file<P>(&mut self, file: P) -> Result<&mut Self, Error>
@@ -163,6 +189,8 @@ impl Tokens {
 
         let file = File::open(file)?; // panic if not found
         let reader = BufReader::new(file);
+        // set by a `# hits: <n>` comment, as written by `write_to_file`, for the next token
+        let mut hits = 1;
 
         for line in reader.lines() {
             let line = line.unwrap();
@@ -171,6 +199,12 @@ impl Tokens {
             // we are only interested in '"..."', not prefixed 'foo = '
             let start = line.chars().next();
             if line.is_empty() || start == Some('#') {
+                if let Some(n) = line
+                    .strip_prefix("# hits:")
+                    .and_then(|n| n.trim().parse().ok())
+                {
+                    hits = n;
+                }
                 continue;
             }
             let Some(pos_quote) = line.find('\"') else {
@@ -199,12 +233,37 @@ impl Tokens {
             };
 
             // add
-            self.add_token(&token);
+            self.add_token_with_hits(&token, hits);
+            hits = 1;
         }
 
         Ok(self)
     }
 
+    /// Writes the tokens to a dictionary file in AFL format, as read by [`Tokens::add_from_file`] and by `libFuzzer`.
+    ///
+    /// Each token is written as an escaped `"token"` line, preceded by a `# hits: <n>` comment, the most hit tokens first.
+    #[cfg(feature = "std")]
+    pub fn write_to_file<P>(&self, file: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut order: Vec<usize> = (0..self.tokens_vec.len()).collect();
+        order.sort_by_key(|idx| core::cmp::Reverse(self.hits[*idx]));
+
+        let mut writer = BufWriter::new(File::create(file)?);
+        for idx in order {
+            let token = &self.tokens_vec[idx];
+            if token.is_empty() {
+                continue;
+            }
+            writeln!(writer, "# hits: {}", self.hits[idx])?;
+            writeln!(writer, "\"{}\"", str_encode(token))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
     /// Returns the amount of tokens in this Tokens instance
     #[inline]
     #[must_use]
@@ -225,6 +284,12 @@ impl Tokens {
         &self.tokens_vec
     }
 
+    /// Gets how often each token was added, duplicates included, in the order of [`Tokens::tokens`]
+    #[must_use]
+    pub fn hits(&self) -> &[u64] {
+        &self.hits
+    }
+
     /// Returns an iterator over the tokens.
     pub fn iter(&self) -> Iter<'_, Vec<u8>> {
         <&Self as IntoIterator>::into_iter(self)
@@ -233,7 +298,7 @@ impl Tokens {
 
 impl AddAssign for Tokens {
     fn add_assign(&mut self, other: Self) {
-        self.add_tokens(&other);
+        self.merge(&other);
     }
 }
 
@@ -256,7 +321,9 @@ impl Add for Tokens {
     type Output = Self;
 
     fn add(self, other: Self) -> Self {
-        self.add(other.tokens_vec.as_slice())
+        let mut ret = self;
+        ret.merge(&other);
+        ret
     }
 }
 
@@ -284,7 +351,7 @@ impl Add for &Tokens {
 
     fn add(self, other: Self) -> Tokens {
         let mut ret: Tokens = self.clone();
-        ret.add_tokens(other);
+        ret.merge(other);
         ret
     }
 }
@@ -307,7 +374,14 @@ where
     S: HasMetadata + HasRand + HasMaxSize,
     I: HasMutatorBytes,
 {
-    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+    mutate_with_undo!(I);
+
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
         let max_size = state.max_size();
         let tokens_len = {
             let Some(meta) = state.metadata_map().get::<Tokens>() else {
@@ -335,6 +409,7 @@ where
             }
         }
 
+        undo.record_insert(off, len);
         input.resize(size + len, 0);
         unsafe {
             buffer_self_copy(input.bytes_mut(), off, off + len, size - off);
@@ -370,7 +445,14 @@ where
     S: UsesInput + HasMetadata + HasRand + HasMaxSize,
     I: HasMutatorBytes,
 {
-    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+    mutate_with_undo!(I);
+
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
         let size = input.bytes().len();
         if size == 0 {
             return Ok(MutationResult::Skipped);
@@ -396,6 +478,7 @@ where
             len = size - off;
         }
 
+        undo.record_overwrite(input, off..off + len);
         unsafe {
             buffer_copy(input.bytes_mut(), token, 0, off, len);
         }
@@ -429,8 +512,15 @@ where
     S: UsesInput + HasMetadata + HasRand + HasMaxSize,
     I: HasMutatorBytes,
 {
+    mutate_with_undo!(I);
+
     #[allow(clippy::too_many_lines)]
-    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+    fn mutate_undoable(
+        &mut self,
+        state: &mut S,
+        input: &mut I,
+        undo: &mut UndoLog<I>,
+    ) -> Result<MutationResult, Error> {
         let size = input.bytes().len();
         if size == 0 {
             return Ok(MutationResult::Skipped);
@@ -458,12 +548,14 @@ where
         let mut result = MutationResult::Skipped;
         match cmp_values {
             CmpValues::U8(v) => {
-                for byte in bytes.iter_mut().take(len).skip(off) {
+                for (i, byte) in bytes.iter_mut().take(len).skip(off).enumerate() {
                     if *byte == v.0 {
+                        undo.record_replace(off + i, &[v.0], 1);
                         *byte = v.1;
                         result = MutationResult::Mutated;
                         break;
                     } else if *byte == v.1 {
+                        undo.record_replace(off + i, &[v.1], 1);
                         *byte = v.0;
                         result = MutationResult::Mutated;
                         break;
@@ -477,21 +569,25 @@ where
                             u16::from_ne_bytes(bytes[i..i + size_of::<u16>()].try_into().unwrap());
                         if val == v.0 {
                             let new_bytes = v.1.to_ne_bytes();
+                            undo.record_replace(i, &val.to_ne_bytes(), size_of::<u16>());
                             bytes[i..i + size_of::<u16>()].copy_from_slice(&new_bytes);
                             result = MutationResult::Mutated;
                             break;
                         } else if val.swap_bytes() == v.0 {
                             let new_bytes = v.1.swap_bytes().to_ne_bytes();
+                            undo.record_replace(i, &val.to_ne_bytes(), size_of::<u16>());
                             bytes[i..i + size_of::<u16>()].copy_from_slice(&new_bytes);
                             result = MutationResult::Mutated;
                             break;
                         } else if val == v.1 {
                             let new_bytes = v.0.to_ne_bytes();
+                            undo.record_replace(i, &val.to_ne_bytes(), size_of::<u16>());
                             bytes[i..i + size_of::<u16>()].copy_from_slice(&new_bytes);
                             result = MutationResult::Mutated;
                             break;
                         } else if val.swap_bytes() == v.1 {
                             let new_bytes = v.0.swap_bytes().to_ne_bytes();
+                            undo.record_replace(i, &val.to_ne_bytes(), size_of::<u16>());
                             bytes[i..i + size_of::<u16>()].copy_from_slice(&new_bytes);
                             result = MutationResult::Mutated;
                             break;
@@ -506,21 +602,25 @@ where
                             u32::from_ne_bytes(bytes[i..i + size_of::<u32>()].try_into().unwrap());
                         if val == v.0 {
                             let new_bytes = v.1.to_ne_bytes();
+                            undo.record_replace(i, &val.to_ne_bytes(), size_of::<u32>());
                             bytes[i..i + size_of::<u32>()].copy_from_slice(&new_bytes);
                             result = MutationResult::Mutated;
                             break;
                         } else if val.swap_bytes() == v.0 {
                             let new_bytes = v.1.swap_bytes().to_ne_bytes();
+                            undo.record_replace(i, &val.to_ne_bytes(), size_of::<u32>());
                             bytes[i..i + size_of::<u32>()].copy_from_slice(&new_bytes);
                             result = MutationResult::Mutated;
                             break;
                         } else if val == v.1 {
                             let new_bytes = v.0.to_ne_bytes();
+                            undo.record_replace(i, &val.to_ne_bytes(), size_of::<u32>());
                             bytes[i..i + size_of::<u32>()].copy_from_slice(&new_bytes);
                             result = MutationResult::Mutated;
                             break;
                         } else if val.swap_bytes() == v.1 {
                             let new_bytes = v.0.swap_bytes().to_ne_bytes();
+                            undo.record_replace(i, &val.to_ne_bytes(), size_of::<u32>());
                             bytes[i..i + size_of::<u32>()].copy_from_slice(&new_bytes);
                             result = MutationResult::Mutated;
                             break;
@@ -535,21 +635,25 @@ where
                             u64::from_ne_bytes(bytes[i..i + size_of::<u64>()].try_into().unwrap());
                         if val == v.0 {
                             let new_bytes = v.1.to_ne_bytes();
+                            undo.record_replace(i, &val.to_ne_bytes(), size_of::<u64>());
                             bytes[i..i + size_of::<u64>()].copy_from_slice(&new_bytes);
                             result = MutationResult::Mutated;
                             break;
                         } else if val.swap_bytes() == v.0 {
                             let new_bytes = v.1.swap_bytes().to_ne_bytes();
+                            undo.record_replace(i, &val.to_ne_bytes(), size_of::<u64>());
                             bytes[i..i + size_of::<u64>()].copy_from_slice(&new_bytes);
                             result = MutationResult::Mutated;
                             break;
                         } else if val == v.1 {
                             let new_bytes = v.0.to_ne_bytes();
+                            undo.record_replace(i, &val.to_ne_bytes(), size_of::<u64>());
                             bytes[i..i + size_of::<u64>()].copy_from_slice(&new_bytes);
                             result = MutationResult::Mutated;
                             break;
                         } else if val.swap_bytes() == v.1 {
                             let new_bytes = v.0.swap_bytes().to_ne_bytes();
+                            undo.record_replace(i, &val.to_ne_bytes(), size_of::<u64>());
                             bytes[i..i + size_of::<u64>()].copy_from_slice(&new_bytes);
                             result = MutationResult::Mutated;
                             break;
@@ -562,6 +666,7 @@ where
                     let mut size = core::cmp::min(v.0.len(), len - i);
                     while size != 0 {
                         if v.0[0..size] == input.bytes()[i..i + size] {
+                            undo.record_overwrite(input, i..i + size);
                             unsafe {
                                 buffer_copy(input.bytes_mut(), &v.1, 0, i, size);
                             }
@@ -573,6 +678,7 @@ where
                     size = core::cmp::min(v.1.len(), len - i);
                     while size != 0 {
                         if v.1[0..size] == input.bytes()[i..i + size] {
+                            undo.record_overwrite(input, i..i + size);
                             unsafe {
                                 buffer_copy(input.bytes_mut(), &v.0, 0, i, size);
                             }
@@ -1886,6 +1992,28 @@ token2="B"
         let _res = fs::remove_file("test.tkns");
     }
 
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_write_tokens() {
+        let _res = fs::remove_file("test_write.tkns");
+        let mut tokens = Tokens::new();
+        tokens.add_tokens([
+            b"quote\"d".to_vec(),
+            b"\x00\x01\\".to_vec(),
+            b"plain".to_vec(),
+        ]);
+        tokens.add_token(&b"plain".to_vec());
+        assert_eq!(tokens.hits(), [1, 1, 2]);
+        tokens.write_to_file("test_write.tkns").unwrap();
+        let read = Tokens::from_file("test_write.tkns").unwrap();
+        // Most hits first
+        assert_eq!(read.tokens()[0], b"plain");
+        assert_eq!(read.hits(), [2, 1, 1]);
+        assert_eq!(read.len(), tokens.len());
+        assert!(read.iter().all(|token| tokens.contains_token(token)));
+        let _res = fs::remove_file("test_write.tkns");
+    }
+
     #[cfg(feature = "std")]
     #[test]
     fn test_token_mutations() {
@@ -0,0 +1,567 @@
+//! Mutators for typed fields of an input, i.e., integers, floats and ASCII decimal numbers at known offsets.
+//!
+//! The fields are inferred by the [`crate::stages::TypeInferenceStage`] and kept in a [`TypedFieldsMetadata`]
+//! of each testcase. Unlike the byte mutators, these mutators respect the width and byte order of a field.
+
+use alloc::{borrow::Cow, format, vec::Vec};
+
+use libafl_bolts::{
+    impl_serdeany,
+    rands::Rand,
+    tuples::{tuple_list, tuple_list_type},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::Corpus,
+    inputs::{schema::Endian, HasMutatorBytes},
+    mutators::{MutationResult, Mutator, ARITH_MAX, INTERESTING_32},
+    state::{HasCorpus, HasMaxSize, HasRand},
+    Error, HasMetadata,
+};
+
+/// The kind of a [`TypedField`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum FieldKind {
+    /// A 16 bit integer
+    U16,
+    /// A 32 bit integer
+    U32,
+    /// A 64 bit integer
+    U64,
+    /// A 32 bit IEEE 754 float
+    F32,
+    /// A 64 bit IEEE 754 float
+    F64,
+    /// An integer in ASCII decimal notation, optionally with a leading `-`
+    Decimal,
+}
+
+impl FieldKind {
+    /// Returns if the field holds an integer, binary or decimal
+    #[must_use]
+    pub fn is_int(self) -> bool {
+        matches!(self, Self::U16 | Self::U32 | Self::U64 | Self::Decimal)
+    }
+
+    /// Returns if the field holds a float
+    #[must_use]
+    pub fn is_float(self) -> bool {
+        matches!(self, Self::F32 | Self::F64)
+    }
+}
+
+/// A field of a known kind in an input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct TypedField {
+    /// The offset of the field in the input
+    pub offset: usize,
+    /// The length of the field in bytes
+    pub len: usize,
+    /// The kind of the field
+    pub kind: FieldKind,
+    /// The byte order of the field, meaningless for [`FieldKind::Decimal`]
+    pub endian: Endian,
+}
+
+impl TypedField {
+    /// Creates a new binary [`TypedField`], as long as its kind
+    #[must_use]
+    pub fn new(offset: usize, kind: FieldKind, endian: Endian) -> Self {
+        let len = match kind {
+            FieldKind::U16 => 2,
+            FieldKind::U32 | FieldKind::F32 => 4,
+            FieldKind::U64 | FieldKind::F64 => 8,
+            FieldKind::Decimal => 0,
+        };
+        Self {
+            offset,
+            len,
+            kind,
+            endian,
+        }
+    }
+
+    /// Creates a new [`FieldKind::Decimal`] field of `len` bytes
+    #[must_use]
+    pub fn decimal(offset: usize, len: usize) -> Self {
+        Self {
+            offset,
+            len,
+            kind: FieldKind::Decimal,
+            endian: Endian::Little,
+        }
+    }
+}
+
+/// The typed fields of a testcase, as inferred by the [`crate::stages::TypeInferenceStage`]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct TypedFieldsMetadata {
+    /// The fields, sorted by offset
+    pub fields: Vec<TypedField>,
+}
+
+impl_serdeany!(TypedFieldsMetadata);
+
+impl TypedFieldsMetadata {
+    /// Creates a new [`struct@TypedFieldsMetadata`]
+    #[must_use]
+    pub fn new(fields: Vec<TypedField>) -> Self {
+        Self { fields }
+    }
+}
+
+/// Boundary values for [`FieldKind::Decimal`] fields, next to [`INTERESTING_32`]
+const DECIMAL_BOUNDARIES: [i64; 12] = [
+    i64::MIN,
+    i64::MAX,
+    -(1 << 31) - 1,
+    1 << 31,
+    (1 << 32) - 1,
+    1 << 32,
+    -(1 << 15) - 1,
+    1 << 15,
+    (1 << 16) - 1,
+    1 << 16,
+    -9,
+    9,
+];
+
+/// Picks a random field of the current testcase matching `filter`, that still fits into the input
+fn choose_field<S, F>(
+    state: &mut S,
+    input_len: usize,
+    filter: F,
+) -> Result<Option<TypedField>, Error>
+where
+    S: HasCorpus + HasRand,
+    F: Fn(FieldKind) -> bool,
+{
+    let Some(id) = *state.corpus().current() else {
+        return Ok(None);
+    };
+    let fields: Vec<TypedField> = {
+        let testcase = state.corpus().get(id)?.borrow();
+        let Ok(meta) = testcase.metadata::<TypedFieldsMetadata>() else {
+            return Ok(None);
+        };
+        meta.fields
+            .iter()
+            .filter(|field| filter(field.kind) && field.offset + field.len <= input_len)
+            .copied()
+            .collect()
+    };
+    Ok(state.rand_mut().choose(fields))
+}
+
+/// Reads the unsigned integer of `bytes.len()` bytes
+pub(crate) fn read_uint(bytes: &[u8], endian: Endian) -> u64 {
+    let fold = |acc: u64, byte: &u8| (acc << 8) | u64::from(*byte);
+    match endian {
+        Endian::Little => bytes.iter().rev().fold(0, fold),
+        Endian::Big => bytes.iter().fold(0, fold),
+    }
+}
+
+/// Writes the lower `bytes.len()` bytes of `val`
+#[allow(clippy::cast_possible_truncation)]
+fn write_uint(bytes: &mut [u8], mut val: u64, endian: Endian) {
+    let len = bytes.len();
+    for idx in 0..len {
+        let pos = match endian {
+            Endian::Little => idx,
+            Endian::Big => len - 1 - idx,
+        };
+        bytes[pos] = val as u8;
+        val >>= 8;
+    }
+}
+
+/// Parses the [`FieldKind::Decimal`] in `bytes`
+pub(crate) fn read_decimal(bytes: &[u8]) -> Option<i64> {
+    core::str::from_utf8(bytes).ok()?.parse().ok()
+}
+
+/// Replaces the integer `field` in `input` with `val`, in the field's encoding
+#[allow(clippy::cast_sign_loss)]
+fn write_int<I, S>(state: &S, input: &mut I, field: &TypedField, val: i64) -> MutationResult
+where
+    I: HasMutatorBytes,
+    S: HasMaxSize,
+{
+    let range = field.offset..field.offset + field.len;
+    if field.kind == FieldKind::Decimal {
+        let digits = format!("{val}");
+        if input.bytes().len() - field.len + digits.len() > state.max_size()
+            || input.bytes()[range.clone()] == *digits.as_bytes()
+        {
+            return MutationResult::Skipped;
+        }
+        input.splice(range, digits.bytes());
+    } else {
+        let bytes = &mut input.bytes_mut()[range];
+        let old = read_uint(bytes, field.endian);
+        write_uint(bytes, val as u64, field.endian);
+        if read_uint(bytes, field.endian) == old {
+            return MutationResult::Skipped;
+        }
+    }
+    MutationResult::Mutated
+}
+
+/// Sets a random integer field of the current testcase to a boundary value of its width, or an interesting value.
+#[derive(Default, Debug)]
+pub struct IntFieldBoundaryMutator;
+
+impl<I, S> Mutator<I, S> for IntFieldBoundaryMutator
+where
+    S: HasRand + HasCorpus + HasMaxSize,
+    I: HasMutatorBytes,
+{
+    #[allow(clippy::cast_possible_wrap)]
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let Some(field) = choose_field(state, input.bytes().len(), FieldKind::is_int)? else {
+            return Ok(MutationResult::Skipped);
+        };
+        let val = if state.rand_mut().coinflip(0.5) {
+            i64::from(*state.rand_mut().choose(&INTERESTING_32).unwrap())
+        } else if field.kind == FieldKind::Decimal {
+            *state.rand_mut().choose(&DECIMAL_BOUNDARIES).unwrap()
+        } else {
+            let unsigned_max = u64::MAX >> (64 - field.len * 8);
+            let signed_max = unsigned_max >> 1;
+            let signed_min = signed_max + 1;
+            state
+                .rand_mut()
+                .choose([
+                    unsigned_max,
+                    unsigned_max - 1,
+                    signed_max,
+                    signed_max - 1,
+                    signed_min,
+                    signed_min + 1,
+                ])
+                .unwrap() as i64
+        };
+        Ok(write_int(state, input, &field, val))
+    }
+}
+
+impl Named for IntFieldBoundaryMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("IntFieldBoundaryMutator");
+        &NAME
+    }
+}
+
+impl IntFieldBoundaryMutator {
+    /// Creates a new [`IntFieldBoundaryMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Adds or subtracts a random value up to [`ARITH_MAX`] to a random integer field of the current testcase,
+/// in the byte order of the field.
+#[derive(Default, Debug)]
+pub struct IntFieldArithMutator;
+
+impl<I, S> Mutator<I, S> for IntFieldArithMutator
+where
+    S: HasRand + HasCorpus + HasMaxSize,
+    I: HasMutatorBytes,
+{
+    #[allow(clippy::cast_possible_wrap)]
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let Some(field) = choose_field(state, input.bytes().len(), FieldKind::is_int)? else {
+            return Ok(MutationResult::Skipped);
+        };
+        let bytes = &input.bytes()[field.offset..field.offset + field.len];
+        let old = if field.kind == FieldKind::Decimal {
+            let Some(old) = read_decimal(bytes) else {
+                return Ok(MutationResult::Skipped);
+            };
+            old
+        } else {
+            read_uint(bytes, field.endian) as i64
+        };
+        let num = 1 + state.rand_mut().below(ARITH_MAX) as i64;
+        let val = if state.rand_mut().coinflip(0.5) {
+            old.wrapping_add(num)
+        } else {
+            old.wrapping_sub(num)
+        };
+        Ok(write_int(state, input, &field, val))
+    }
+}
+
+impl Named for IntFieldArithMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("IntFieldArithMutator");
+        &NAME
+    }
+}
+
+impl IntFieldArithMutator {
+    /// Creates a new [`IntFieldArithMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Special `f64` values: NaN, infinities, zeroes, denormals and the extremes
+const SPECIAL_F64: [f64; 13] = [
+    f64::NAN,
+    f64::INFINITY,
+    f64::NEG_INFINITY,
+    0.0,
+    -0.0,
+    1.0,
+    -1.0,
+    f64::EPSILON,
+    f64::MIN_POSITIVE,
+    f64::MAX,
+    f64::MIN,
+    // smallest and largest denormal
+    f64::from_bits(1),
+    f64::from_bits(0x000f_ffff_ffff_ffff),
+];
+
+/// Special `f32` values: NaN, infinities, zeroes, denormals and the extremes
+const SPECIAL_F32: [f32; 13] = [
+    f32::NAN,
+    f32::INFINITY,
+    f32::NEG_INFINITY,
+    0.0,
+    -0.0,
+    1.0,
+    -1.0,
+    f32::EPSILON,
+    f32::MIN_POSITIVE,
+    f32::MAX,
+    f32::MIN,
+    // smallest and largest denormal
+    f32::from_bits(1),
+    f32::from_bits(0x007f_ffff),
+];
+
+/// Writes the bits of a float `field`, if they changed
+fn write_float_bits<I>(input: &mut I, field: &TypedField, bits: u64) -> MutationResult
+where
+    I: HasMutatorBytes,
+{
+    let bytes = &mut input.bytes_mut()[field.offset..field.offset + field.len];
+    if read_uint(bytes, field.endian) == bits {
+        return MutationResult::Skipped;
+    }
+    write_uint(bytes, bits, field.endian);
+    MutationResult::Mutated
+}
+
+/// Sets a random float field of the current testcase to a special value, such as NaN, infinity or a denormal.
+#[derive(Default, Debug)]
+pub struct FloatFieldSpecialMutator;
+
+impl<I, S> Mutator<I, S> for FloatFieldSpecialMutator
+where
+    S: HasRand + HasCorpus,
+    I: HasMutatorBytes,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let Some(field) = choose_field(state, input.bytes().len(), FieldKind::is_float)? else {
+            return Ok(MutationResult::Skipped);
+        };
+        let bits = if field.kind == FieldKind::F32 {
+            u64::from(state.rand_mut().choose(SPECIAL_F32).unwrap().to_bits())
+        } else {
+            state.rand_mut().choose(SPECIAL_F64).unwrap().to_bits()
+        };
+        Ok(write_float_bits(input, &field, bits))
+    }
+}
+
+impl Named for FloatFieldSpecialMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("FloatFieldSpecialMutator");
+        &NAME
+    }
+}
+
+impl FloatFieldSpecialMutator {
+    /// Creates a new [`FloatFieldSpecialMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Applies arithmetic to a random float field of the current testcase:
+/// negating, doubling, halving, adding or subtracting one, truncating, or stepping to a neighbouring float.
+#[derive(Default, Debug)]
+pub struct FloatFieldArithMutator;
+
+impl<I, S> Mutator<I, S> for FloatFieldArithMutator
+where
+    S: HasRand + HasCorpus,
+    I: HasMutatorBytes,
+{
+    #[allow(clippy::cast_possible_truncation)]
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let Some(field) = choose_field(state, input.bytes().len(), FieldKind::is_float)? else {
+            return Ok(MutationResult::Skipped);
+        };
+        let old_bits = read_uint(
+            &input.bytes()[field.offset..field.offset + field.len],
+            field.endian,
+        );
+        let op = state.rand_mut().below(8);
+        let bits = if field.kind == FieldKind::F32 {
+            let old = f32::from_bits(old_bits as u32);
+            let val = match op {
+                0 => -old,
+                1 => old * 2.0,
+                2 => old / 2.0,
+                3 => old + 1.0,
+                4 => old - 1.0,
+                5 => libm::truncf(old),
+                6 => f32::from_bits(old.to_bits().wrapping_add(1)),
+                _ => f32::from_bits(old.to_bits().wrapping_sub(1)),
+            };
+            u64::from(val.to_bits())
+        } else {
+            let old = f64::from_bits(old_bits);
+            let val = match op {
+                0 => -old,
+                1 => old * 2.0,
+                2 => old / 2.0,
+                3 => old + 1.0,
+                4 => old - 1.0,
+                5 => libm::trunc(old),
+                6 => f64::from_bits(old.to_bits().wrapping_add(1)),
+                _ => f64::from_bits(old.to_bits().wrapping_sub(1)),
+            };
+            val.to_bits()
+        };
+        Ok(write_float_bits(input, &field, bits))
+    }
+}
+
+impl Named for FloatFieldArithMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("FloatFieldArithMutator");
+        &NAME
+    }
+}
+
+impl FloatFieldArithMutator {
+    /// Creates a new [`FloatFieldArithMutator`].
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+/// Tuple type of the mutations on typed fields
+pub type TypedFieldMutationsType = tuple_list_type!(
+    IntFieldBoundaryMutator,
+    IntFieldArithMutator,
+    FloatFieldSpecialMutator,
+    FloatFieldArithMutator,
+);
+
+/// Get the mutations on the typed fields inferred by the [`crate::stages::TypeInferenceStage`]
+#[must_use]
+pub fn typed_field_mutations() -> TypedFieldMutationsType {
+    tuple_list!(
+        IntFieldBoundaryMutator::new(),
+        IntFieldArithMutator::new(),
+        FloatFieldSpecialMutator::new(),
+        FloatFieldArithMutator::new(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use libafl_bolts::rands::StdRand;
+
+    use super::{
+        read_decimal, read_uint, typed_field_mutations, FieldKind, TypedField, TypedFieldsMetadata,
+    };
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        inputs::{schema::Endian, BytesInput, HasMutatorBytes},
+        mutators::{MutationResult, MutatorsTuple},
+        state::{HasCorpus, StdState},
+        HasMetadata,
+    };
+
+    #[test]
+    fn test_typed_field_mutations() {
+        // a u32 BE length, an f64 LE and a decimal
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&1000_u32.to_be_bytes());
+        bytes.extend_from_slice(&2.5_f64.to_le_bytes());
+        bytes.extend_from_slice(b"len=123;");
+        let fields = vec![
+            TypedField::new(0, FieldKind::U32, Endian::Big),
+            TypedField::new(4, FieldKind::F64, Endian::Little),
+            TypedField::decimal(16, 3),
+        ];
+
+        let mut corpus = InMemoryCorpus::new();
+        let mut testcase = Testcase::new(BytesInput::new(bytes.clone()));
+        testcase.add_metadata(TypedFieldsMetadata::new(fields));
+        let id = corpus.add(testcase).unwrap();
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        *state.corpus_mut().current_mut() = Some(id);
+
+        let mut mutations = typed_field_mutations();
+        let mut mutated_any = [false; 4];
+        for (idx, mutated_any) in mutated_any.iter_mut().enumerate() {
+            for _ in 0..16 {
+                let mut input = BytesInput::new(bytes.clone());
+                let result = mutations
+                    .get_and_mutate(idx.into(), &mut state, &mut input)
+                    .unwrap();
+                if result == MutationResult::Skipped {
+                    continue;
+                }
+                *mutated_any = true;
+                let mutated = input.bytes();
+                if idx < 2 {
+                    // The float stays, the decimal stays a number
+                    assert_eq!(mutated[4..16], bytes[4..16]);
+                    assert!(read_decimal(&mutated[16..mutated.len() - 1]).is_some());
+                    assert_eq!(mutated.last(), Some(&b';'));
+                } else {
+                    assert_eq!(mutated[..4], bytes[..4]);
+                    assert_eq!(mutated[12..], bytes[12..]);
+                    assert_ne!(
+                        read_uint(&mutated[4..12], Endian::Little),
+                        2.5_f64.to_bits()
+                    );
+                }
+            }
+        }
+        assert_eq!(mutated_any, [true; 4]);
+    }
+}
@@ -0,0 +1,160 @@
+//! Undo logs, recording the changes of a [`crate::mutators::Mutator`] so that they can be reverted.
+//!
+//! With [`crate::mutators::Mutator::mutate_undoable`], a mutational stage can mutate one input in place,
+//! execute it, and revert it afterwards, instead of cloning the input for each execution.
+
+use alloc::vec::Vec;
+use core::{fmt, ops::Range};
+
+use crate::inputs::HasMutatorBytes;
+
+/// One change to the bytes of an input: `new_len` bytes at `offset` replaced `old`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Splice {
+    offset: usize,
+    new_len: usize,
+    old: Vec<u8>,
+}
+
+/// Reverts one [`Splice`] on an input with bytes
+fn revert_splice<I>(input: &mut I, splice: Splice)
+where
+    I: HasMutatorBytes,
+{
+    input.splice(splice.offset..splice.offset + splice.new_len, splice.old);
+}
+
+/// The changes made to an input by [`crate::mutators::Mutator::mutate_undoable`], in order.
+///
+/// Mutators record each change right before making it. [`UndoLog::revert`] then reverts them in reverse order.
+pub struct UndoLog<I> {
+    recording: bool,
+    splices: Vec<Splice>,
+    revert_fn: Option<fn(&mut I, Splice)>,
+}
+
+impl<I> fmt::Debug for UndoLog<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UndoLog")
+            .field("recording", &self.recording)
+            .field("splices", &self.splices)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<I> Default for UndoLog<I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I> UndoLog<I> {
+    /// Creates a new, empty [`UndoLog`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            recording: true,
+            splices: Vec::new(),
+            revert_fn: None,
+        }
+    }
+
+    /// Creates an [`UndoLog`] that records nothing,
+    /// for mutators implementing [`crate::mutators::Mutator::mutate`] with [`crate::mutators::Mutator::mutate_undoable`]
+    #[must_use]
+    pub fn disabled() -> Self {
+        Self {
+            recording: false,
+            splices: Vec::new(),
+            revert_fn: None,
+        }
+    }
+
+    /// Returns if no changes were recorded since the last [`UndoLog::revert`]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.splices.is_empty()
+    }
+
+    /// Forgets the recorded changes, keeping the input as it is
+    pub fn clear(&mut self) {
+        self.splices.clear();
+    }
+
+    /// Reverts all recorded changes on `input`, the latest first, and clears the log
+    pub fn revert(&mut self, input: &mut I) {
+        while let Some(splice) = self.splices.pop() {
+            // a splice is only recorded together with its revert function
+            (self.revert_fn.unwrap())(input, splice);
+        }
+    }
+}
+
+impl<I> UndoLog<I>
+where
+    I: HasMutatorBytes,
+{
+    /// Records that the bytes `old`, at `offset` of the input, are about to be replaced by `new_len` other bytes
+    pub fn record_replace(&mut self, offset: usize, old: &[u8], new_len: usize) {
+        if !self.recording {
+            return;
+        }
+        self.revert_fn = Some(revert_splice::<I>);
+        self.splices.push(Splice {
+            offset,
+            new_len,
+            old: old.to_vec(),
+        });
+    }
+
+    /// Records that the bytes in `range` of `input` are about to be replaced by `new_len` other bytes
+    pub fn record_splice(&mut self, input: &I, range: Range<usize>, new_len: usize) {
+        self.record_replace(range.start, &input.bytes()[range], new_len);
+    }
+
+    /// Records that the bytes in `range` of `input` are about to be overwritten, keeping the length
+    pub fn record_overwrite(&mut self, input: &I, range: Range<usize>) {
+        let len = range.len();
+        self.record_splice(input, range, len);
+    }
+
+    /// Records that `len` bytes are about to be inserted at `offset`
+    pub fn record_insert(&mut self, offset: usize, len: usize) {
+        self.record_replace(offset, &[], len);
+    }
+
+    /// Records that the bytes in `range` of `input` are about to be removed
+    pub fn record_remove(&mut self, input: &I, range: Range<usize>) {
+        self.record_splice(input, range, 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UndoLog;
+    use crate::inputs::{BytesInput, HasMutatorBytes};
+
+    #[test]
+    fn test_undo_log() {
+        let mut input = BytesInput::new(b"hello world".to_vec());
+        let mut undo = UndoLog::new();
+
+        undo.record_overwrite(&input, 0..1);
+        input.bytes_mut()[0] = b'j';
+        undo.record_remove(&input, 5..11);
+        input.drain(5..11);
+        undo.record_insert(0, 2);
+        input.splice(0..0, b">>".iter().copied());
+        undo.record_splice(&input, 2..4, 3);
+        input.splice(2..4, b"abc".iter().copied());
+        assert_eq!(input.bytes(), b">>abcllo");
+
+        undo.revert(&mut input);
+        assert_eq!(input.bytes(), b"hello world");
+        assert!(undo.is_empty());
+
+        let mut disabled = UndoLog::disabled();
+        disabled.record_overwrite(&input, 0..5);
+        assert!(disabled.is_empty());
+    }
+}
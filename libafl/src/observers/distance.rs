@@ -0,0 +1,169 @@
+//! The [`DistanceObserver`] computes how close an execution came to the targets of directed fuzzing.
+
+use alloc::{borrow::Cow, vec::Vec};
+#[cfg(feature = "std")]
+use std::{fs, path::Path};
+
+use libafl_bolts::{ownedref::OwnedSlice, AsSlice, Named};
+use serde::{Deserialize, Serialize};
+
+use crate::{executors::ExitKind, inputs::UsesInput, observers::Observer, Error};
+
+/// Observes the mean distance to the targets of the edges hit in the coverage map, like `AFLGo`.
+///
+/// The distance of each edge is computed at compile time, see `libafl_cc::distance`.
+/// Edges without a distance can't reach any target and are ignored.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DistanceObserver<'a> {
+    name: Cow<'static, str>,
+    /// The coverage map the distances refer to
+    map: OwnedSlice<'a, u8>,
+    /// (map index, distance), sorted by index
+    distances: Vec<(usize, f64)>,
+    /// The distance of the last execution, if it hit any edge with a distance
+    last_distance: Option<f64>,
+}
+
+impl<'a> DistanceObserver<'a> {
+    /// Creates a new [`DistanceObserver`] for the coverage `map`, given the distance of each map index.
+    #[must_use]
+    pub fn new<I>(name: &'static str, map: OwnedSlice<'a, u8>, distances: I) -> Self
+    where
+        I: IntoIterator<Item = (usize, f64)>,
+    {
+        let mut distances: Vec<(usize, f64)> = distances.into_iter().collect();
+        distances.sort_by_key(|(idx, _)| *idx);
+        Self {
+            name: Cow::from(name),
+            map,
+            distances,
+            last_distance: None,
+        }
+    }
+
+    /// Creates a new [`DistanceObserver`], loading the distances from a file with one `index,distance` line per edge,
+    /// as written by `libafl_cc::distance::write_distances`.
+    #[cfg(feature = "std")]
+    pub fn from_file<P>(name: &'static str, map: OwnedSlice<'a, u8>, path: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let content = fs::read_to_string(path)?;
+        let mut distances = Vec::new();
+        for line in content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+        {
+            let parsed = line.split_once(',').and_then(|(idx, distance)| {
+                Some((idx.trim().parse().ok()?, distance.trim().parse().ok()?))
+            });
+            let Some(entry) = parsed else {
+                return Err(Error::illegal_argument(format!(
+                    "Invalid line in distance file: {line}"
+                )));
+            };
+            distances.push(entry);
+        }
+        Ok(Self::new(name, map, distances))
+    }
+
+    /// The mean distance of the edges hit by the last execution, if it hit any edge with a distance
+    #[must_use]
+    pub fn distance(&self) -> Option<f64> {
+        self.last_distance
+    }
+
+    /// The distance of each map index, sorted by index
+    #[must_use]
+    pub fn distances(&self) -> &[(usize, f64)] {
+        &self.distances
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn compute_distance(&self) -> Option<f64> {
+        let map = self.map.as_slice();
+        let mut sum = 0.0;
+        let mut count = 0_usize;
+        for (idx, distance) in &self.distances {
+            if map.get(*idx).is_some_and(|hits| *hits != 0) {
+                sum += distance;
+                count += 1;
+            }
+        }
+        (count > 0).then(|| sum / count as f64)
+    }
+}
+
+impl Named for DistanceObserver<'_> {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<S> Observer<S> for DistanceObserver<'_>
+where
+    S: UsesInput,
+{
+    fn pre_exec(&mut self, _state: &mut S, _input: &S::Input) -> Result<(), Error> {
+        self.last_distance = None;
+        Ok(())
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _input: &S::Input,
+        _exit_kind: &ExitKind,
+    ) -> Result<(), Error> {
+        self.last_distance = self.compute_distance();
+        Ok(())
+    }
+}
+
+impl AsRef<Self> for DistanceObserver<'_> {
+    fn as_ref(&self) -> &Self {
+        self
+    }
+}
+
+impl AsMut<Self> for DistanceObserver<'_> {
+    fn as_mut(&mut self) -> &mut Self {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::ownedref::OwnedSlice;
+
+    use super::DistanceObserver;
+    use crate::{executors::ExitKind, inputs::NopInput, observers::Observer, state::NopState};
+
+    #[test]
+    fn test_distance_observer() {
+        let map = [0_u8, 3, 0, 1, 0];
+        let mut observer = DistanceObserver::new(
+            "distance",
+            OwnedSlice::from(&map[..]),
+            [(4, 1.0), (1, 2.0), (3, 4.0), (7, 0.0)],
+        );
+        let mut state = NopState::<NopInput>::new();
+        let input = NopInput {};
+        observer.pre_exec(&mut state, &input).unwrap();
+        assert_eq!(observer.distance(), None);
+        observer
+            .post_exec(&mut state, &input, &ExitKind::Ok)
+            .unwrap();
+        assert_eq!(observer.distance(), Some(3.0));
+        assert_eq!(observer.distances()[0], (1, 2.0));
+
+        let map = [0_u8; 5];
+        let mut observer =
+            DistanceObserver::new("distance", OwnedSlice::from(&map[..]), [(1, 2.0)]);
+        observer
+            .post_exec(&mut state, &input, &ExitKind::Ok)
+            .unwrap();
+        assert_eq!(observer.distance(), None);
+    }
+}
@@ -9,6 +9,14 @@ pub mod stdio;
 #[cfg(feature = "std")]
 pub use stdio::{StdErrObserver, StdOutObserver};
 
+#[cfg(feature = "std")]
+pub mod socket;
+#[cfg(feature = "std")]
+pub use socket::SocketResponseObserver;
+
+pub mod protocol_state;
+pub use protocol_state::{ProtocolStateObserver, ResponseCodeParser};
+
 #[cfg(feature = "regex")]
 pub mod stacktrace;
 #[cfg(feature = "regex")]
@@ -21,6 +29,10 @@ pub mod profiling;
 pub use profiling::*;
 
 pub mod concolic;
+
+pub mod distance;
+pub use distance::DistanceObserver;
+
 pub mod map;
 pub use map::*;
 
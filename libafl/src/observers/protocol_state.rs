@@ -0,0 +1,187 @@
+//! The [`ProtocolStateObserver`] tracks the states a stateful target, such as a network protocol server, went through.
+//!
+//! Like `AFLNet`, it uses the response codes the target sent back as states:
+//! each response code is one state ID, and the sequence of codes is the path through the protocol's state machine.
+//! The executor must explicitly support this observer, see [`crate::executors::socket::SocketInput`].
+
+use alloc::{borrow::Cow, vec::Vec};
+
+use libafl_bolts::Named;
+use serde::{Deserialize, Serialize};
+
+use crate::{inputs::UsesInput, observers::Observer, state::State, Error};
+
+/// The highest number of decimal digits a text response code may have
+const MAX_TEXT_CODE_DIGITS: usize = 9;
+
+/// How to extract response codes from a raw response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ResponseCodeParser {
+    /// Line-based text protocols, such as FTP, SMTP, RTSP, or HTTP.
+    /// Each line starting with a decimal number, e.g. `220 ready` or `250-extension`,
+    /// or a protocol version followed by a number, e.g. `RTSP/1.0 200 OK`, yields one code.
+    /// Consecutive lines with the same code, such as multiline replies, are merged.
+    TextStatusLine,
+    /// Binary protocols with the response code at a fixed position of the response.
+    /// Reads a single big-endian code of `width` bytes (at most 4) at `offset`.
+    Binary {
+        /// The offset of the code in the response
+        offset: usize,
+        /// The width of the code in bytes
+        width: usize,
+    },
+}
+
+impl ResponseCodeParser {
+    /// Extracts the response codes from `response`, in the order they appear.
+    #[must_use]
+    pub fn parse(&self, response: &[u8]) -> Vec<u32> {
+        match *self {
+            ResponseCodeParser::TextStatusLine => {
+                let mut codes: Vec<u32> = Vec::new();
+                for line in response.split(|&b| b == b'\n') {
+                    if let Some(code) = Self::parse_status_line(line) {
+                        if codes.last() != Some(&code) {
+                            codes.push(code);
+                        }
+                    }
+                }
+                codes
+            }
+            ResponseCodeParser::Binary { offset, width } => {
+                let width = width.clamp(1, 4);
+                match offset
+                    .checked_add(width)
+                    .and_then(|end| response.get(offset..end))
+                {
+                    Some(bytes) => {
+                        let code = bytes
+                            .iter()
+                            .fold(0_u32, |code, &b| (code << 8) | u32::from(b));
+                        alloc::vec![code]
+                    }
+                    None => Vec::new(),
+                }
+            }
+        }
+    }
+
+    fn parse_status_line(line: &[u8]) -> Option<u32> {
+        let mut tokens = line
+            .split(u8::is_ascii_whitespace)
+            .filter(|token| !token.is_empty());
+        let first = tokens.next()?;
+        let code = if first.contains(&b'/') {
+            tokens.next()?
+        } else {
+            first
+        };
+        // `250-` starts an FTP or SMTP multiline reply
+        let code = code.strip_suffix(b"-").unwrap_or(code);
+        if code.is_empty()
+            || code.len() > MAX_TEXT_CODE_DIGITS
+            || !code.iter().all(u8::is_ascii_digit)
+        {
+            return None;
+        }
+        Some(
+            code.iter()
+                .fold(0, |code, &digit| code * 10 + u32::from(digit - b'0')),
+        )
+    }
+}
+
+/// An observer recording the protocol states a target went through, parsed from its responses.
+/// Only works for supported executors.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ProtocolStateObserver {
+    name: Cow<'static, str>,
+    parser: ResponseCodeParser,
+    states: Vec<u32>,
+}
+
+impl ProtocolStateObserver {
+    /// Creates a new [`ProtocolStateObserver`], using `parser` to get the states from responses.
+    #[must_use]
+    pub fn new(name: &'static str, parser: ResponseCodeParser) -> Self {
+        Self {
+            name: Cow::from(name),
+            parser,
+            states: Vec::new(),
+        }
+    }
+
+    /// React to a new response.
+    /// If the target responds more than once during a run, e.g. once per message, the states are appended.
+    pub fn observe_response(&mut self, response: &[u8]) {
+        let states = self.parser.parse(response);
+        self.states.extend(states);
+    }
+
+    /// The states reached during the last run, in order
+    #[must_use]
+    pub fn states(&self) -> &[u32] {
+        &self.states
+    }
+
+    /// The state the target was in at the end of the last run, if any response was observed
+    #[must_use]
+    pub fn last_state(&self) -> Option<u32> {
+        self.states.last().copied()
+    }
+}
+
+impl Named for ProtocolStateObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<S> Observer<S> for ProtocolStateObserver
+where
+    S: State,
+{
+    fn pre_exec(&mut self, _state: &mut S, _input: &<S as UsesInput>::Input) -> Result<(), Error> {
+        self.states.clear();
+        Ok(())
+    }
+
+    fn pre_exec_child(
+        &mut self,
+        _state: &mut S,
+        _input: &<S as UsesInput>::Input,
+    ) -> Result<(), Error> {
+        self.states.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProtocolStateObserver, ResponseCodeParser};
+
+    #[test]
+    fn test_response_code_parser() {
+        let ftp = b"220-Welcome\r\n220 ready\r\n331 password?\r\n230 logged in\r\n";
+        assert_eq!(
+            ResponseCodeParser::TextStatusLine.parse(ftp),
+            [220, 331, 230]
+        );
+
+        let rtsp = b"RTSP/1.0 200 OK\r\nCSeq: 1\r\n\r\nRTSP/1.0 454 Session Not Found\r\n";
+        assert_eq!(ResponseCodeParser::TextStatusLine.parse(rtsp), [200, 454]);
+
+        let binary = ResponseCodeParser::Binary {
+            offset: 2,
+            width: 2,
+        };
+        assert_eq!(binary.parse(&[0xff, 0xff, 0x01, 0x02, 0xff]), [0x0102]);
+        assert!(binary.parse(&[0xff, 0xff, 0x01]).is_empty());
+
+        let mut observer = ProtocolStateObserver::new("state", ResponseCodeParser::TextStatusLine);
+        observer.observe_response(b"220 hi\r\n");
+        observer.observe_response(b"500 what?\r\n");
+        assert_eq!(observer.states(), [220, 500]);
+        assert_eq!(observer.last_state(), Some(500));
+    }
+}
@@ -0,0 +1,60 @@
+//! The [`SocketResponseObserver`] looks at what a target sent back over the socket it received its input on.
+//! The executor must explicitly support this observer, see [`crate::executors::socket::SocketInput`].
+
+use alloc::{borrow::Cow, vec::Vec};
+
+use libafl_bolts::Named;
+use serde::{Deserialize, Serialize};
+
+use crate::{inputs::UsesInput, observers::Observer, state::State, Error};
+
+/// An observer that captures the response of a target to an input delivered over a socket.
+/// Only works for supported executors.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SocketResponseObserver {
+    /// The name of the observer.
+    pub name: Cow<'static, str>,
+    /// The response of the target during its last execution.
+    pub response: Option<Vec<u8>>,
+}
+
+impl SocketResponseObserver {
+    /// Create a new [`SocketResponseObserver`] with the given name.
+    #[must_use]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name: Cow::from(name),
+            response: None,
+        }
+    }
+
+    /// React to a new response
+    pub fn observe_response(&mut self, response: &[u8]) {
+        self.response = Some(response.into());
+    }
+}
+
+impl Named for SocketResponseObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<S> Observer<S> for SocketResponseObserver
+where
+    S: State,
+{
+    fn pre_exec(&mut self, _state: &mut S, _input: &<S as UsesInput>::Input) -> Result<(), Error> {
+        self.response = None;
+        Ok(())
+    }
+
+    fn pre_exec_child(
+        &mut self,
+        _state: &mut S,
+        _input: &<S as UsesInput>::Input,
+    ) -> Result<(), Error> {
+        self.response = None;
+        Ok(())
+    }
+}
@@ -1,11 +1,14 @@
 //! the ``StacktraceObserver`` looks up the stacktrace on the execution thread and computes a hash for it for dedupe
 
-use alloc::{borrow::Cow, string::String, vec::Vec};
+use alloc::{
+    borrow::Cow,
+    string::{String, ToString},
+    vec::Vec,
+};
 #[cfg(feature = "casr")]
 use std::{
     collections::hash_map::DefaultHasher,
     hash::{Hash, Hasher},
-    string::ToString,
 };
 use std::{
     fmt::Debug,
@@ -34,7 +37,6 @@ use libcasr::{
         STACK_FRAME_FUNCTION_IGNORE_REGEXES,
     },
 };
-#[cfg(not(feature = "casr"))]
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 
@@ -272,6 +274,10 @@ pub fn get_asan_runtime_flags() -> String {
 pub struct AsanBacktraceObserver {
     observer_name: Cow<'static, str>,
     hash: Option<u64>,
+    /// The addresses of the reported frames, innermost first
+    frames: Vec<u64>,
+    /// The bug class from the report header, e.g. `heap-buffer-overflow`
+    report_class: Option<String>,
 }
 
 impl AsanBacktraceObserver {
@@ -285,6 +291,8 @@ impl AsanBacktraceObserver {
         Self {
             observer_name: observer_name.into(),
             hash: None,
+            frames: Vec::new(),
+            report_class: None,
         }
     }
 
@@ -299,6 +307,8 @@ impl AsanBacktraceObserver {
         Self {
             observer_name: observer_name.into(),
             hash: None,
+            frames: Vec::new(),
+            report_class: None,
         }
     }
 
@@ -329,18 +339,15 @@ impl AsanBacktraceObserver {
     #[cfg(not(feature = "casr"))]
     /// parse ASAN error output emited by the target command and compute the hash
     pub fn parse_asan_output(&mut self, output: &str) {
-        let mut hash = 0;
-        let matcher = Regex::new("\\s*#[0-9]*\\s0x([0-9a-f]*)\\s.*").unwrap();
-        matcher.captures_iter(output).for_each(|m| {
-            let g = m.get(1).unwrap();
-            hash ^= u64::from_str_radix(g.as_str(), 16).unwrap();
-        });
+        self.parse_asan_report(output);
+        let hash = self.frames.iter().fold(0, |hash, frame| hash ^ frame);
         self.update_hash(hash);
     }
 
     #[cfg(feature = "casr")]
     /// parse ASAN error output emited by the target command and compute the hash
     pub fn parse_asan_output(&mut self, output: &str) {
+        self.parse_asan_report(output);
         let mut hash = 0;
         if let Ok(st_vec) = AsanStacktrace::extract_stacktrace(output) {
             if let Ok(mut stacktrace) = AsanStacktrace::parse_stacktrace(&st_vec) {
@@ -353,6 +360,32 @@ impl AsanBacktraceObserver {
         self.update_hash(hash);
     }
 
+    /// Extracts the bug class and the frame addresses from an ASAN report
+    fn parse_asan_report(&mut self, output: &str) {
+        let frame_matcher = Regex::new("\\s*#[0-9]*\\s0x([0-9a-f]*)\\s.*").unwrap();
+        self.frames = frame_matcher
+            .captures_iter(output)
+            .filter_map(|m| u64::from_str_radix(m.get(1).unwrap().as_str(), 16).ok())
+            .collect();
+
+        let class_matcher = Regex::new("ERROR: AddressSanitizer: ([a-zA-Z0-9_-]+)").unwrap();
+        self.report_class = class_matcher
+            .captures(output)
+            .map(|m| m.get(1).unwrap().as_str().to_string());
+    }
+
+    /// The frame addresses of the last parsed report, innermost first
+    #[must_use]
+    pub fn frames(&self) -> &[u64] {
+        &self.frames
+    }
+
+    /// The bug class of the last parsed report, such as `heap-buffer-overflow` or `SEGV`
+    #[must_use]
+    pub fn report_class(&self) -> Option<&str> {
+        self.report_class.as_deref()
+    }
+
     /// Updates the hash value of this observer.
     fn update_hash(&mut self, hash: u64) {
         self.hash = Some(hash);
@@ -0,0 +1,440 @@
+//! Multi-armed bandits to pick adaptively between fuzzing strategies.
+//!
+//! [`ThompsonSampling`] keeps a Beta posterior of the probability that an execution finds a new corpus entry, for each arm.
+//! The [`BanditPowerScheduler`] uses it to pick the [`PowerSchedule`] for each scheduled entry,
+//! [`crate::stages::BanditStage`] to pick one of several stages.
+//! Both keep their posterior in the state metadata, so it survives restarts.
+
+use alloc::vec::Vec;
+
+use libafl_bolts::{impl_serdeany, rands::Rand};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{Corpus, CorpusId, Testcase},
+    inputs::UsesInput,
+    observers::ObserversTuple,
+    schedulers::{
+        powersched::{PowerSchedule, SchedulerMetadata},
+        RemovableScheduler, Scheduler,
+    },
+    state::{HasCorpus, HasExecutions, HasRand, UsesState},
+    Error, HasMetadata,
+};
+
+/// The posterior of an arm: `Beta(1 + successes, 1 + failures)`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct BetaArm {
+    /// The (discounted) number of executions that found a new corpus entry
+    pub successes: f64,
+    /// The (discounted) number of executions that did not
+    pub failures: f64,
+    /// How often this arm was picked
+    pub pulls: u64,
+}
+
+impl BetaArm {
+    /// The mean of the posterior, i.e., the expected probability of a success
+    #[must_use]
+    pub fn mean(&self) -> f64 {
+        (self.successes + 1.0) / (self.successes + self.failures + 2.0)
+    }
+}
+
+/// Thompson sampling over Bernoulli arms: picks each arm with the probability that it is the best one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThompsonSampling {
+    arms: Vec<BetaArm>,
+    discount: f64,
+}
+
+impl ThompsonSampling {
+    /// Creates a new [`ThompsonSampling`] over `arms` arms, with a uniform prior
+    #[must_use]
+    pub fn new(arms: usize) -> Self {
+        Self::with_discount(arms, 1.0)
+    }
+
+    /// Creates a new [`ThompsonSampling`] over `arms` arms.
+    ///
+    /// Before each update, the observations of the updated arm are multiplied by `discount` in `(0, 1]`,
+    /// so that the bandit can follow arms that get better or worse over time.
+    #[must_use]
+    pub fn with_discount(arms: usize, discount: f64) -> Self {
+        Self {
+            arms: vec![BetaArm::default(); arms],
+            discount,
+        }
+    }
+
+    /// The posteriors of the arms
+    #[must_use]
+    pub fn arms(&self) -> &[BetaArm] {
+        &self.arms
+    }
+
+    /// The number of arms
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.arms.len()
+    }
+
+    /// If there are no arms
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.arms.is_empty()
+    }
+
+    /// Picks an arm, by drawing a success probability from each posterior and taking the largest
+    pub fn select<R>(&mut self, rand: &mut R) -> Result<usize, Error>
+    where
+        R: Rand,
+    {
+        let mut best = None;
+        let mut best_sample = f64::MIN;
+        for (idx, arm) in self.arms.iter().enumerate() {
+            let sample = sample_beta(rand, arm.successes + 1.0, arm.failures + 1.0);
+            if sample > best_sample {
+                best = Some(idx);
+                best_sample = sample;
+            }
+        }
+        let best = best.ok_or_else(|| Error::empty("No arms to select from"))?;
+        self.arms[best].pulls += 1;
+        Ok(best)
+    }
+
+    /// Records that `successes` of `trials` executions with `arm` found a new corpus entry
+    #[allow(clippy::cast_precision_loss)]
+    pub fn update(&mut self, arm: usize, successes: u64, trials: u64) -> Result<(), Error> {
+        let discount = self.discount;
+        let arm = self
+            .arms
+            .get_mut(arm)
+            .ok_or_else(|| Error::key_not_found(format!("No arm {arm}")))?;
+        let successes = successes.min(trials);
+        arm.successes = arm.successes * discount + successes as f64;
+        arm.failures = arm.failures * discount + (trials - successes) as f64;
+        Ok(())
+    }
+}
+
+/// A standard normal sample, using the Box-Muller transform
+fn sample_normal<R>(rand: &mut R) -> f64
+where
+    R: Rand,
+{
+    // in (0, 1], so the log is finite
+    let u1 = 1.0 - rand.next_float();
+    let u2 = rand.next_float();
+    libm::sqrt(-2.0 * libm::log(u1)) * libm::cos(2.0 * core::f64::consts::PI * u2)
+}
+
+/// A `Gamma(shape, 1)` sample for `shape >= 1`, see Marsaglia and Tsang,
+/// [A simple method for generating gamma variables](https://dl.acm.org/doi/10.1145/358407.358414)
+#[allow(clippy::many_single_char_names)]
+fn sample_gamma<R>(rand: &mut R, shape: f64) -> f64
+where
+    R: Rand,
+{
+    debug_assert!(shape >= 1.0);
+    let d = shape - 1.0 / 3.0;
+    let c = 1.0 / libm::sqrt(9.0 * d);
+    loop {
+        let x = sample_normal(rand);
+        let v = 1.0 + c * x;
+        if v <= 0.0 {
+            continue;
+        }
+        let v = v * v * v;
+        let u = 1.0 - rand.next_float();
+        if libm::log(u) < 0.5 * x * x + d - d * v + d * libm::log(v) {
+            return d * v;
+        }
+    }
+}
+
+/// A `Beta(alpha, beta)` sample for `alpha, beta >= 1`
+fn sample_beta<R>(rand: &mut R, alpha: f64, beta: f64) -> f64
+where
+    R: Rand,
+{
+    let x = sample_gamma(rand, alpha);
+    let y = sample_gamma(rand, beta);
+    x / (x + y)
+}
+
+/// The arm currently played, and the progress of the fuzzer when it was picked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BanditRound {
+    /// The arm played
+    pub arm: usize,
+    corpus_count: usize,
+    executions: u64,
+}
+
+impl BanditRound {
+    /// Starts playing `arm`
+    pub fn start<S>(state: &S, arm: usize) -> Self
+    where
+        S: HasCorpus + HasExecutions,
+    {
+        Self {
+            arm,
+            corpus_count: state.corpus().count(),
+            executions: *state.executions(),
+        }
+    }
+
+    /// Stops playing, returning the new corpus entries and the executions since the start
+    pub fn finish<S>(&self, state: &S) -> (u64, u64)
+    where
+        S: HasCorpus + HasExecutions,
+    {
+        let found = state.corpus().count().saturating_sub(self.corpus_count);
+        let executions = state.executions().saturating_sub(self.executions);
+        (found as u64, executions)
+    }
+}
+
+/// The state of the [`BanditPowerScheduler`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct PowerScheduleBanditMetadata {
+    /// The power schedules to pick from
+    pub schedules: Vec<PowerSchedule>,
+    /// The posterior of each power schedule
+    pub bandit: ThompsonSampling,
+    /// The power schedule currently played
+    pub round: Option<BanditRound>,
+}
+
+impl_serdeany!(PowerScheduleBanditMetadata);
+
+/// Picks the [`PowerSchedule`] for each entry scheduled by the wrapped power scheduler with [`ThompsonSampling`],
+/// rewarding a schedule for each execution that found a new corpus entry while it was active.
+///
+/// The schedule is set in the [`SchedulerMetadata`], so the wrapped scheduler and the power stage pick it up.
+#[derive(Debug, Clone)]
+pub struct BanditPowerScheduler<CS> {
+    base: CS,
+}
+
+impl<CS> UsesState for BanditPowerScheduler<CS>
+where
+    CS: UsesState,
+{
+    type State = CS::State;
+}
+
+impl<CS> Scheduler for BanditPowerScheduler<CS>
+where
+    CS: Scheduler,
+    CS::State: HasCorpus + HasMetadata + HasRand + HasExecutions,
+{
+    fn on_add(&mut self, state: &mut Self::State, idx: CorpusId) -> Result<(), Error> {
+        self.base.on_add(state, idx)
+    }
+
+    fn on_evaluation<OT>(
+        &mut self,
+        state: &mut Self::State,
+        input: &<Self::State as UsesInput>::Input,
+        observers: &OT,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<Self::State>,
+    {
+        self.base.on_evaluation(state, input, observers)
+    }
+
+    fn next(&mut self, state: &mut Self::State) -> Result<CorpusId, Error> {
+        let mut meta = state
+            .metadata_map_mut()
+            .remove::<PowerScheduleBanditMetadata>()
+            .ok_or_else(|| Error::key_not_found("PowerScheduleBanditMetadata not found"))?;
+        if let Some(round) = meta.round.take() {
+            let (found, executions) = round.finish(state);
+            meta.bandit.update(round.arm, found, executions)?;
+        }
+        let arm = meta.bandit.select(state.rand_mut())?;
+        let schedule = meta.schedules[arm];
+        meta.round = Some(BanditRound::start(state, arm));
+        state.metadata_map_mut().insert_boxed(meta);
+
+        state
+            .metadata_mut::<SchedulerMetadata>()?
+            .set_strat(Some(schedule));
+        self.base.next(state)
+    }
+
+    fn set_current_scheduled(
+        &mut self,
+        _state: &mut Self::State,
+        _next_idx: Option<CorpusId>,
+    ) -> Result<(), Error> {
+        // We do nothing here, the inner scheduler will take care of it
+        Ok(())
+    }
+}
+
+impl<CS> RemovableScheduler for BanditPowerScheduler<CS>
+where
+    CS: RemovableScheduler,
+    CS::State: HasCorpus + HasMetadata + HasRand + HasExecutions,
+{
+    fn on_remove(
+        &mut self,
+        state: &mut Self::State,
+        idx: CorpusId,
+        testcase: &Option<Testcase<<Self::State as UsesInput>::Input>>,
+    ) -> Result<(), Error> {
+        self.base.on_remove(state, idx, testcase)
+    }
+
+    fn on_replace(
+        &mut self,
+        state: &mut Self::State,
+        idx: CorpusId,
+        prev: &Testcase<<Self::State as UsesInput>::Input>,
+    ) -> Result<(), Error> {
+        self.base.on_replace(state, idx, prev)
+    }
+}
+
+impl<CS> BanditPowerScheduler<CS> {
+    /// Creates a new [`BanditPowerScheduler`], picking between the given power `schedules` for the `base` scheduler.
+    ///
+    /// If the state already holds a posterior for the same schedules, e.g., after a restart, it is kept.
+    pub fn new<S>(state: &mut S, base: CS, schedules: &[PowerSchedule]) -> Result<Self, Error>
+    where
+        S: HasMetadata,
+    {
+        Self::with_discount(state, base, schedules, 1.0)
+    }
+
+    /// Creates a new [`BanditPowerScheduler`], discounting older observations, see [`ThompsonSampling::with_discount`]
+    pub fn with_discount<S>(
+        state: &mut S,
+        base: CS,
+        schedules: &[PowerSchedule],
+        discount: f64,
+    ) -> Result<Self, Error>
+    where
+        S: HasMetadata,
+    {
+        if schedules.is_empty() {
+            return Err(Error::illegal_argument(
+                "BanditPowerScheduler needs at least one power schedule",
+            ));
+        }
+        let restored = state
+            .metadata::<PowerScheduleBanditMetadata>()
+            .is_ok_and(|meta| meta.schedules == schedules);
+        if !restored {
+            state.add_metadata(PowerScheduleBanditMetadata {
+                schedules: schedules.to_vec(),
+                bandit: ThompsonSampling::with_discount(schedules.len(), discount),
+                round: None,
+            });
+        }
+        Ok(Self { base })
+    }
+
+    /// The wrapped scheduler
+    pub fn base(&self) -> &CS {
+        &self.base
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::{BanditPowerScheduler, PowerScheduleBanditMetadata, ThompsonSampling};
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        inputs::BytesInput,
+        schedulers::{
+            powersched::{PowerSchedule, SchedulerMetadata},
+            QueueScheduler, Scheduler,
+        },
+        state::{HasCorpus, HasExecutions, StdState},
+        HasMetadata,
+    };
+
+    #[test]
+    fn test_thompson_sampling() {
+        let mut rand = StdRand::with_seed(1337);
+        let mut bandit = ThompsonSampling::new(3);
+        bandit.update(0, 1, 1000).unwrap();
+        bandit.update(1, 100, 1000).unwrap();
+        bandit.update(2, 10, 1000).unwrap();
+        let mut picks = [0; 3];
+        for _ in 0..100 {
+            picks[bandit.select(&mut rand).unwrap()] += 1;
+        }
+        assert!(picks[1] > 90);
+        assert_eq!(bandit.arms()[1].pulls, picks[1]);
+        assert!(bandit.arms()[1].mean() > bandit.arms()[2].mean());
+        assert!(bandit.update(3, 1, 1).is_err());
+    }
+
+    #[test]
+    fn test_bandit_power_scheduler() {
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        state.add_metadata(SchedulerMetadata::new(None));
+        let schedules = [PowerSchedule::EXPLORE, PowerSchedule::FAST];
+        let mut scheduler =
+            BanditPowerScheduler::new(&mut state, QueueScheduler::new(), &schedules).unwrap();
+        let idx = state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(b"a".to_vec())))
+            .unwrap();
+        scheduler.on_add(&mut state, idx).unwrap();
+
+        scheduler.next(&mut state).unwrap();
+        let arm = state
+            .metadata::<PowerScheduleBanditMetadata>()
+            .unwrap()
+            .round
+            .unwrap()
+            .arm;
+        assert_eq!(
+            state.metadata::<SchedulerMetadata>().unwrap().strat(),
+            Some(schedules[arm])
+        );
+
+        *state.executions_mut() += 10;
+        let idx = state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(b"b".to_vec())))
+            .unwrap();
+        scheduler.on_add(&mut state, idx).unwrap();
+        scheduler.next(&mut state).unwrap();
+        let meta = state.metadata::<PowerScheduleBanditMetadata>().unwrap();
+        assert!((meta.bandit.arms()[arm].successes - 1.0).abs() < f64::EPSILON);
+        assert!((meta.bandit.arms()[arm].failures - 9.0).abs() < f64::EPSILON);
+
+        // The posterior is kept for the same schedules
+        let _scheduler = BanditPowerScheduler::new(&mut state, scheduler.base, &schedules).unwrap();
+        let meta = state.metadata::<PowerScheduleBanditMetadata>().unwrap();
+        assert_eq!(
+            meta.bandit.arms().iter().map(|arm| arm.pulls).sum::<u64>(),
+            2
+        );
+    }
+}
@@ -0,0 +1,347 @@
+//! Directed greybox fuzzing like [AFLGo](https://dl.acm.org/doi/10.1145/3133956.3134020).
+//!
+//! The [`DirectedScheduler`] records the distance to the targets of each corpus entry, as observed by a [`DistanceObserver`].
+//! The [`DirectedPowerTestcaseScore`] then gives entries closer to the targets more energy, using simulated annealing:
+//! early on, all entries get about the same energy, and the closer ones are favored more and more over time.
+
+use alloc::string::ToString;
+use core::{marker::PhantomData, time::Duration};
+
+use libafl_bolts::{
+    current_time, impl_serdeany,
+    tuples::{Handle, Handled, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{Corpus, CorpusId, Testcase},
+    inputs::UsesInput,
+    observers::{DistanceObserver, ObserversTuple},
+    schedulers::{
+        testcase_score::CorpusPowerTestcaseScore, RemovableScheduler, Scheduler, TestcaseScore,
+    },
+    state::{HasCorpus, HasStartTime, UsesState},
+    Error, HasMetadata,
+};
+
+/// The default time after which the closest entries are favored the most, `t_x` in `AFLGo`
+pub const DEFAULT_TIME_TO_EXPLOITATION: Duration = Duration::from_secs(3600);
+
+/// The maximum factor by which the energy of an entry is increased or decreased, `MAX_FACTOR` in `AFLGo`
+pub const MAX_POWER_FACTOR: f64 = 32.0;
+
+/// The distance to the targets of a testcase, as observed by a [`DistanceObserver`]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct DistanceTestcaseMetadata {
+    /// The mean distance of the edges this testcase hits
+    pub distance: f64,
+}
+
+impl_serdeany!(DistanceTestcaseMetadata);
+
+/// The global state of directed fuzzing: the range of distances seen so far, and the annealing schedule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct DirectedMetadata {
+    min_distance: f64,
+    max_distance: f64,
+    time_to_exploitation: Duration,
+}
+
+impl_serdeany!(DirectedMetadata);
+
+impl DirectedMetadata {
+    /// Creates a new [`struct@DirectedMetadata`], cooling down over `time_to_exploitation`
+    #[must_use]
+    pub fn new(time_to_exploitation: Duration) -> Self {
+        Self {
+            min_distance: f64::MAX,
+            max_distance: 0.0,
+            time_to_exploitation,
+        }
+    }
+
+    /// The smallest distance of any corpus entry
+    #[must_use]
+    pub fn min_distance(&self) -> f64 {
+        self.min_distance
+    }
+
+    /// The largest distance of any corpus entry
+    #[must_use]
+    pub fn max_distance(&self) -> f64 {
+        self.max_distance
+    }
+
+    /// The time after which the closest entries are favored the most
+    #[must_use]
+    pub fn time_to_exploitation(&self) -> Duration {
+        self.time_to_exploitation
+    }
+
+    /// Widens the range of distances by the distance of a new corpus entry
+    pub fn update(&mut self, distance: f64) {
+        self.min_distance = self.min_distance.min(distance);
+        self.max_distance = self.max_distance.max(distance);
+    }
+
+    /// The distance, normalized to `[0, 1]` in the range seen so far
+    #[must_use]
+    pub fn normalized_distance(&self, distance: f64) -> f64 {
+        if self.max_distance > self.min_distance {
+            ((distance - self.min_distance) / (self.max_distance - self.min_distance))
+                .clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// The temperature of the exponential cooling schedule after `elapsed` time, from 1 down towards 0.
+    /// It reaches 0.05 at the time to exploitation.
+    #[must_use]
+    pub fn temperature(&self, elapsed: Duration) -> f64 {
+        let t_x = self.time_to_exploitation.as_secs_f64();
+        if t_x <= 0.0 {
+            return 0.0;
+        }
+        libm::pow(20.0, -(elapsed.as_secs_f64() / t_x))
+    }
+
+    /// The factor to multiply the energy of an entry at `distance` with, after `elapsed` time.
+    ///
+    /// Between `1 / MAX_POWER_FACTOR` for the farthest and `MAX_POWER_FACTOR` for the closest entries,
+    /// but the closer to 1, the hotter the schedule still is.
+    #[must_use]
+    pub fn power_factor(&self, distance: f64, elapsed: Duration) -> f64 {
+        let temperature = self.temperature(elapsed);
+        let p =
+            (1.0 - self.normalized_distance(distance)) * (1.0 - temperature) + 0.5 * temperature;
+        libm::pow(2.0, 2.0 * libm::log2(MAX_POWER_FACTOR) * (p - 0.5))
+    }
+}
+
+/// Wraps a scheduler, recording the distance of each new corpus entry to the targets in a [`DistanceTestcaseMetadata`].
+///
+/// Use it together with a power schedule based on [`DirectedPowerTestcaseScore`] to direct fuzzing to the targets.
+/// The entries themselves are picked by the wrapped scheduler.
+#[derive(Debug, Clone)]
+pub struct DirectedScheduler<CS, C> {
+    base: CS,
+    distance_observer_handle: Handle<C>,
+    last_distance: Option<f64>,
+}
+
+impl<CS, C> UsesState for DirectedScheduler<CS, C>
+where
+    CS: UsesState,
+{
+    type State = CS::State;
+}
+
+impl<'a, CS, C> Scheduler for DirectedScheduler<CS, C>
+where
+    CS: Scheduler,
+    CS::State: HasCorpus + HasMetadata,
+    C: AsRef<DistanceObserver<'a>> + Named,
+{
+    fn on_add(&mut self, state: &mut Self::State, idx: CorpusId) -> Result<(), Error> {
+        self.base.on_add(state, idx)?;
+        if let Some(distance) = self.last_distance.take() {
+            state
+                .corpus()
+                .get(idx)?
+                .borrow_mut()
+                .add_metadata(DistanceTestcaseMetadata { distance });
+            state
+                .metadata_or_insert_with(|| DirectedMetadata::new(DEFAULT_TIME_TO_EXPLOITATION))
+                .update(distance);
+        }
+        Ok(())
+    }
+
+    fn on_evaluation<OT>(
+        &mut self,
+        state: &mut Self::State,
+        input: &<Self::State as UsesInput>::Input,
+        observers: &OT,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<Self::State>,
+    {
+        self.base.on_evaluation(state, input, observers)?;
+        let observer = observers
+            .get(&self.distance_observer_handle)
+            .ok_or_else(|| Error::key_not_found("DistanceObserver not found".to_string()))?
+            .as_ref();
+        self.last_distance = observer.distance();
+        Ok(())
+    }
+
+    fn next(&mut self, state: &mut Self::State) -> Result<CorpusId, Error> {
+        self.base.next(state)
+    }
+
+    fn set_current_scheduled(
+        &mut self,
+        _state: &mut Self::State,
+        _next_idx: Option<CorpusId>,
+    ) -> Result<(), Error> {
+        // We do nothing here, the inner scheduler will take care of it
+        Ok(())
+    }
+}
+
+impl<'a, CS, C> RemovableScheduler for DirectedScheduler<CS, C>
+where
+    CS: RemovableScheduler,
+    CS::State: HasCorpus + HasMetadata,
+    C: AsRef<DistanceObserver<'a>> + Named,
+{
+    fn on_remove(
+        &mut self,
+        state: &mut Self::State,
+        idx: CorpusId,
+        testcase: &Option<Testcase<<Self::State as UsesInput>::Input>>,
+    ) -> Result<(), Error> {
+        self.base.on_remove(state, idx, testcase)
+    }
+
+    fn on_replace(
+        &mut self,
+        state: &mut Self::State,
+        idx: CorpusId,
+        prev: &Testcase<<Self::State as UsesInput>::Input>,
+    ) -> Result<(), Error> {
+        self.base.on_replace(state, idx, prev)?;
+        if let Ok(meta) = prev.metadata::<DistanceTestcaseMetadata>() {
+            let meta = *meta;
+            let mut testcase = state.corpus().get(idx)?.borrow_mut();
+            if !testcase.has_metadata::<DistanceTestcaseMetadata>() {
+                testcase.add_metadata(meta);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<CS, C> DirectedScheduler<CS, C>
+where
+    C: Named,
+{
+    /// Creates a new [`DirectedScheduler`] wrapping `base`, with `AFLGo`'s exponential cooling over [`DEFAULT_TIME_TO_EXPLOITATION`]
+    #[must_use]
+    pub fn new<S>(state: &mut S, base: CS, distance_observer: &C) -> Self
+    where
+        S: HasMetadata,
+    {
+        Self::with_time_to_exploitation(
+            state,
+            base,
+            distance_observer,
+            DEFAULT_TIME_TO_EXPLOITATION,
+        )
+    }
+
+    /// Creates a new [`DirectedScheduler`] wrapping `base`, favoring the closest entries the most after `time_to_exploitation`
+    #[must_use]
+    pub fn with_time_to_exploitation<S>(
+        state: &mut S,
+        base: CS,
+        distance_observer: &C,
+        time_to_exploitation: Duration,
+    ) -> Self
+    where
+        S: HasMetadata,
+    {
+        if !state.has_metadata::<DirectedMetadata>() {
+            state.add_metadata(DirectedMetadata::new(time_to_exploitation));
+        }
+        Self {
+            base,
+            distance_observer_handle: distance_observer.handle(),
+            last_distance: None,
+        }
+    }
+
+    /// The wrapped scheduler
+    pub fn base(&self) -> &CS {
+        &self.base
+    }
+}
+
+/// `AFLGo`'s power schedule: the energy of [`CorpusPowerTestcaseScore`],
+/// multiplied by the annealing [`DirectedMetadata::power_factor`] of the entry's distance to the targets.
+///
+/// Entries without a [`DistanceTestcaseMetadata`] keep their energy.
+#[derive(Debug, Clone)]
+pub struct DirectedPowerTestcaseScore<S> {
+    phantom: PhantomData<S>,
+}
+
+impl<S> TestcaseScore<S> for DirectedPowerTestcaseScore<S>
+where
+    S: HasCorpus + HasMetadata + HasStartTime,
+{
+    fn compute(state: &S, entry: &mut Testcase<S::Input>) -> Result<f64, Error> {
+        let score = CorpusPowerTestcaseScore::<S>::compute(state, entry)?;
+        let (Ok(directed), Ok(meta)) = (
+            state.metadata::<DirectedMetadata>(),
+            entry.metadata::<DistanceTestcaseMetadata>(),
+        ) else {
+            return Ok(score);
+        };
+        let elapsed = current_time().saturating_sub(*state.start_time());
+        Ok(score * directed.power_factor(meta.distance, elapsed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::{DirectedMetadata, MAX_POWER_FACTOR};
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{actual} is not close to {expected}"
+        );
+    }
+
+    #[test]
+    fn test_annealing() {
+        let mut meta = DirectedMetadata::new(Duration::from_secs(100));
+        meta.update(2.0);
+        meta.update(10.0);
+        meta.update(4.0);
+        assert_close(meta.min_distance(), 2.0);
+        assert_close(meta.max_distance(), 10.0);
+        assert_close(meta.normalized_distance(4.0), 0.25);
+
+        // Hot: everyone gets the same energy
+        assert_close(meta.temperature(Duration::ZERO), 1.0);
+        assert_close(meta.power_factor(2.0, Duration::ZERO), 1.0);
+        assert_close(meta.power_factor(10.0, Duration::ZERO), 1.0);
+
+        // Cooling down, closer is better
+        let elapsed = Duration::from_secs(100);
+        assert_close(meta.temperature(elapsed), 0.05);
+        let close = meta.power_factor(2.0, elapsed);
+        let far = meta.power_factor(10.0, elapsed);
+        assert!(close > 1.0 && far < 1.0);
+        assert!(meta.power_factor(4.0, elapsed) < close);
+
+        // Cold: the extremes
+        let elapsed = Duration::from_secs(100_000);
+        assert_close(meta.power_factor(2.0, elapsed), MAX_POWER_FACTOR);
+        assert_close(meta.power_factor(10.0, elapsed), 1.0 / MAX_POWER_FACTOR);
+    }
+}
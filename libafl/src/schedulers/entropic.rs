@@ -0,0 +1,702 @@
+//! The [`EntropicScheduler`] is the information-theoretic scheduler of libFuzzer, see
+//! [Boosting fuzzer efficiency: an information theoretic perspective](https://dl.acm.org/doi/10.1145/3368089.3409748).
+//!
+//! It tracks the globally rarest features (here: map indexes), and for each corpus entry how often fuzzing it
+//! hit each of them. Entries whose mutants hit many different rare features, i.e., with a high entropy, get more energy.
+
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use hashbrown::HashMap;
+use libafl_bolts::{
+    rands::Rand,
+    tuples::{Handle, Handled, MatchNameRef},
+    Named,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{Corpus, CorpusId, HasTestcase, Testcase},
+    feedbacks::MapIndexesMetadata,
+    inputs::UsesInput,
+    observers::{MapObserver, ObserversTuple},
+    random_corpus_id,
+    schedulers::{RemovableScheduler, Scheduler},
+    state::{HasCorpus, HasRand, State, UsesState},
+    Error, HasMetadata,
+};
+
+/// How many of the rarest features are tracked by default, like libFuzzer's `-entropic_number_of_rarest_features`
+pub const DEFAULT_NUMBER_OF_RAREST_FEATURES: usize = 100;
+/// From which global frequency on a feature is abundant by default, like libFuzzer's `-entropic_feature_frequency_threshold`
+pub const DEFAULT_FEATURE_FREQUENCY_THRESHOLD: u16 = 0xff;
+
+/// The global state of the [`EntropicScheduler`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct EntropicMetadata {
+    number_of_rarest_features: usize,
+    feature_frequency_threshold: u16,
+    /// How often each known feature was hit while it was rare, saturating
+    global_feature_freqs: HashMap<usize, u16>,
+    /// How many corpus entries have each known feature
+    feature_entries: HashMap<usize, usize>,
+    rare_features: Vec<usize>,
+    freq_of_most_abundant_rare_feature: u16,
+    /// The state of each corpus entry, kept here so scheduling never needs to load a testcase
+    entries: HashMap<CorpusId, EntropicTestcaseMetadata>,
+}
+
+libafl_bolts::impl_serdeany!(EntropicMetadata);
+
+impl EntropicMetadata {
+    /// Creates a new [`struct@EntropicMetadata`]
+    #[must_use]
+    pub fn new(number_of_rarest_features: usize, feature_frequency_threshold: u16) -> Self {
+        Self {
+            number_of_rarest_features,
+            feature_frequency_threshold,
+            global_feature_freqs: HashMap::default(),
+            feature_entries: HashMap::default(),
+            rare_features: Vec::new(),
+            freq_of_most_abundant_rare_feature: 0,
+            entries: HashMap::default(),
+        }
+    }
+
+    /// The currently tracked rare features
+    #[must_use]
+    pub fn rare_features(&self) -> &[usize] {
+        &self.rare_features
+    }
+
+    /// How often a feature was hit while it was rare, or `None` if no corpus entry has it
+    #[must_use]
+    pub fn global_frequency(&self, feature: usize) -> Option<u16> {
+        self.global_feature_freqs.get(&feature).copied()
+    }
+
+    /// The state of the corpus entry `id`, if it was added to the corpus while the [`EntropicScheduler`] was in use
+    #[must_use]
+    pub fn entry(&self, id: CorpusId) -> Option<&EntropicTestcaseMetadata> {
+        self.entries.get(&id)
+    }
+
+    /// Counts a hit of `feature`. Returns whether it is one of the rare features,
+    /// and the entry being fuzzed should count it, too.
+    fn hit(&mut self, feature: usize) -> bool {
+        let Some(freq) = self.global_feature_freqs.get_mut(&feature) else {
+            return false;
+        };
+        if *freq == u16::MAX {
+            return false;
+        }
+        let old = *freq;
+        *freq += 1;
+        if old > self.freq_of_most_abundant_rare_feature || !self.rare_features.contains(&feature) {
+            return false;
+        }
+        if old == self.freq_of_most_abundant_rare_feature {
+            self.freq_of_most_abundant_rare_feature += 1;
+        }
+        true
+    }
+
+    /// Adds a newly discovered feature as rare one.
+    /// If there are too many rare features, the most abundant ones are dropped and pushed to `removed`.
+    fn add_rare_feature(&mut self, feature: usize, removed: &mut Vec<usize>) {
+        while self.rare_features.len() > self.number_of_rarest_features
+            && self.freq_of_most_abundant_rare_feature > self.feature_frequency_threshold
+        {
+            let freq = |feature: usize| {
+                self.global_feature_freqs
+                    .get(&feature)
+                    .copied()
+                    .unwrap_or(0)
+            };
+            // Like libFuzzer, the second most abundant feature is the last maximum before the most abundant one
+            let mut most_abundant = [self.rare_features[0]; 2];
+            let mut delete = 0;
+            for (i, &other) in self.rare_features.iter().enumerate() {
+                if freq(other) >= freq(most_abundant[0]) {
+                    most_abundant[1] = most_abundant[0];
+                    most_abundant[0] = other;
+                    delete = i;
+                }
+            }
+            self.freq_of_most_abundant_rare_feature = freq(most_abundant[1]);
+            self.rare_features.swap_remove(delete);
+            removed.push(most_abundant[0]);
+        }
+        self.rare_features.push(feature);
+        self.global_feature_freqs.insert(feature, 0);
+    }
+
+    /// Forgets the features of a corpus entry that went away, if no other entry has them
+    fn remove_features(&mut self, features: &[usize]) {
+        let mut gone = Vec::new();
+        for feature in features {
+            if let Some(count) = self.feature_entries.get_mut(feature) {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    self.feature_entries.remove(feature);
+                    self.global_feature_freqs.remove(feature);
+                    gone.push(*feature);
+                }
+            }
+        }
+        if gone.is_empty() {
+            return;
+        }
+
+        let num_rare_features = self.rare_features.len();
+        self.rare_features.retain(|feature| !gone.contains(feature));
+        if self.rare_features.len() != num_rare_features {
+            self.freq_of_most_abundant_rare_feature = self
+                .rare_features
+                .iter()
+                .filter_map(|feature| self.global_feature_freqs.get(feature))
+                .copied()
+                .max()
+                .unwrap_or(0);
+        }
+        for tcmeta in self.entries.values_mut() {
+            for feature in &gone {
+                if tcmeta.delete_feature_freq(*feature) {
+                    tcmeta.needs_energy_update = true;
+                }
+            }
+        }
+    }
+}
+
+/// The per-entry state of the [`EntropicScheduler`], see [`EntropicMetadata::entry`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntropicTestcaseMetadata {
+    /// How often the mutants of this entry hit each rare feature, sorted by feature
+    feature_freqs: Vec<(usize, u16)>,
+    energy: f64,
+    sum_incidence: f64,
+    executed_mutations: u64,
+    needs_energy_update: bool,
+    num_features: usize,
+}
+
+impl EntropicTestcaseMetadata {
+    #[allow(clippy::cast_precision_loss)]
+    fn new(num_features: usize, num_rare_features: usize) -> Self {
+        Self {
+            feature_freqs: Vec::new(),
+            energy: if num_rare_features == 0 {
+                1.0
+            } else {
+                libm::log(num_rare_features as f64)
+            },
+            sum_incidence: num_rare_features as f64,
+            executed_mutations: 0,
+            needs_energy_update: false,
+            num_features,
+        }
+    }
+
+    /// The energy of this entry, the estimated entropy of the rare features its mutants hit
+    #[must_use]
+    pub fn energy(&self) -> f64 {
+        self.energy
+    }
+
+    /// How often this entry was mutated and run
+    #[must_use]
+    pub fn executed_mutations(&self) -> u64 {
+        self.executed_mutations
+    }
+
+    fn update_feature_frequency(&mut self, feature: usize) {
+        self.needs_energy_update = true;
+        match self
+            .feature_freqs
+            .binary_search_by_key(&feature, |(feature, _)| *feature)
+        {
+            Ok(i) => self.feature_freqs[i].1 = self.feature_freqs[i].1.saturating_add(1),
+            Err(i) => self.feature_freqs.insert(i, (feature, 1)),
+        }
+    }
+
+    fn delete_feature_freq(&mut self, feature: usize) -> bool {
+        match self
+            .feature_freqs
+            .binary_search_by_key(&feature, |(feature, _)| *feature)
+        {
+            Ok(i) => {
+                self.feature_freqs.remove(i);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Estimates the entropy over the rare features from the local incidences, with add-one smoothing.
+    /// The number of mutations counts as an additional feature, so often fuzzed entries lose energy.
+    #[allow(clippy::cast_precision_loss)]
+    fn update_energy(&mut self, num_rare_features: usize) {
+        let mut energy = 0.0;
+        let mut sum_incidence = 0.0;
+        for (_, freq) in &self.feature_freqs {
+            let local_incidence = f64::from(*freq) + 1.0;
+            energy -= local_incidence * libm::log(local_incidence);
+            sum_incidence += local_incidence;
+        }
+        // Rare features this entry never hit have an incidence of one
+        sum_incidence += num_rare_features.saturating_sub(self.feature_freqs.len()) as f64;
+
+        let abd_incidence = self.executed_mutations as f64 + 1.0;
+        energy -= abd_incidence * libm::log(abd_incidence);
+        sum_incidence += abd_incidence;
+
+        if sum_incidence != 0.0 {
+            energy = energy / sum_incidence + libm::log(sum_incidence);
+        }
+        self.energy = energy;
+        self.sum_incidence = sum_incidence;
+        self.needs_energy_update = false;
+    }
+}
+
+/// libFuzzer's Entropic power schedule: picks corpus entries with a probability proportional to
+/// the entropy of the rare features their mutants hit.
+///
+/// The features of new entries are taken from their [`MapIndexesMetadata`], so the map feedback must track indexes.
+#[derive(Debug, Clone)]
+pub struct EntropicScheduler<C, O, S> {
+    map_observer_handle: Handle<C>,
+    rare_hits: Vec<usize>,
+    phantom: PhantomData<(O, S)>,
+}
+
+impl<C, O, S> EntropicScheduler<C, O, S>
+where
+    C: AsRef<O> + Named,
+    O: MapObserver,
+    S: HasMetadata,
+{
+    /// Creates a new [`EntropicScheduler`] with libFuzzer's default parameters
+    #[must_use]
+    pub fn new(state: &mut S, map_observer: &C) -> Self {
+        Self::with_parameters(
+            state,
+            map_observer,
+            DEFAULT_NUMBER_OF_RAREST_FEATURES,
+            DEFAULT_FEATURE_FREQUENCY_THRESHOLD,
+        )
+    }
+
+    /// Creates a new [`EntropicScheduler`], tracking up to `number_of_rarest_features` features
+    /// while they were hit less than `feature_frequency_threshold` times
+    #[must_use]
+    pub fn with_parameters(
+        state: &mut S,
+        map_observer: &C,
+        number_of_rarest_features: usize,
+        feature_frequency_threshold: u16,
+    ) -> Self {
+        let _ = state.metadata_or_insert_with(|| {
+            EntropicMetadata::new(number_of_rarest_features, feature_frequency_threshold)
+        });
+        Self {
+            map_observer_handle: map_observer.handle(),
+            rare_hits: Vec::new(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<C, O, S> UsesState for EntropicScheduler<C, O, S>
+where
+    S: State,
+{
+    type State = S;
+}
+
+impl<C, O, S> RemovableScheduler for EntropicScheduler<C, O, S>
+where
+    C: AsRef<O> + Named,
+    O: MapObserver,
+    S: HasCorpus + HasMetadata + HasRand + HasTestcase + State,
+{
+    fn on_remove(
+        &mut self,
+        state: &mut Self::State,
+        idx: CorpusId,
+        prev: &Option<Testcase<<Self::State as UsesInput>::Input>>,
+    ) -> Result<(), Error> {
+        let meta = state.metadata_mut::<EntropicMetadata>()?;
+        meta.entries.remove(&idx);
+        if let Some(indexes) = prev
+            .as_ref()
+            .and_then(|prev| prev.metadata::<MapIndexesMetadata>().ok())
+        {
+            meta.remove_features(&indexes.list);
+        }
+        Ok(())
+    }
+
+    /// The entry keeps its state, only the known features it has are updated
+    fn on_replace(
+        &mut self,
+        state: &mut Self::State,
+        idx: CorpusId,
+        prev: &Testcase<<Self::State as UsesInput>::Input>,
+    ) -> Result<(), Error> {
+        let features = state
+            .testcase(idx)?
+            .metadata::<MapIndexesMetadata>()
+            .map(|indexes| indexes.list.clone())
+            .unwrap_or_default();
+        let meta = state.metadata_mut::<EntropicMetadata>()?;
+        for feature in &features {
+            if let Some(count) = meta.feature_entries.get_mut(feature) {
+                *count += 1;
+            }
+        }
+        if let Ok(indexes) = prev.metadata::<MapIndexesMetadata>() {
+            meta.remove_features(&indexes.list);
+        }
+        Ok(())
+    }
+}
+
+impl<C, O, S> Scheduler for EntropicScheduler<C, O, S>
+where
+    C: AsRef<O> + Named,
+    O: MapObserver,
+    S: HasCorpus + HasMetadata + HasRand + HasTestcase + State,
+{
+    fn on_add(&mut self, state: &mut Self::State, idx: CorpusId) -> Result<(), Error> {
+        let current_idx = *state.corpus().current();
+        let features = {
+            let mut testcase = state.testcase_mut(idx)?;
+            testcase.set_parent_id_optional(current_idx);
+            testcase
+                .metadata::<MapIndexesMetadata>()
+                .map_err(|_| {
+                    Error::key_not_found(
+                        "MapIndexesMetadata needed by the EntropicScheduler not found, track the indexes in the map feedback",
+                    )
+                })?
+                .list
+                .clone()
+        };
+
+        let meta = state.metadata_mut::<EntropicMetadata>()?;
+        let mut new_features = Vec::new();
+        let mut removed = Vec::new();
+        for feature in &features {
+            if meta.global_frequency(*feature).is_none() {
+                meta.add_rare_feature(*feature, &mut removed);
+                new_features.push(*feature);
+            }
+            *meta.feature_entries.entry(*feature).or_insert(0) += 1;
+        }
+        // The execution that found the new features hit them once
+        let parent_hits: Vec<usize> = new_features
+            .iter()
+            .copied()
+            .filter(|feature| meta.hit(*feature))
+            .collect();
+        let num_rare_features = meta.rare_features().len();
+
+        if !new_features.is_empty() {
+            for tcmeta in meta.entries.values_mut() {
+                for feature in removed.iter().chain(&new_features) {
+                    if tcmeta.delete_feature_freq(*feature) {
+                        tcmeta.needs_energy_update = true;
+                    }
+                }
+                // Add-one smoothing for the features this entry did not discover.
+                // Entries without energy are never fuzzed and stay that way.
+                if tcmeta.energy > 0.0 {
+                    for _ in &new_features {
+                        tcmeta.sum_incidence += 1.0;
+                        tcmeta.energy += libm::log(tcmeta.sum_incidence) / tcmeta.sum_incidence;
+                    }
+                }
+            }
+        }
+        if let Some(tcmeta) = current_idx.and_then(|parent| meta.entries.get_mut(&parent)) {
+            for feature in parent_hits {
+                tcmeta.update_feature_frequency(feature);
+            }
+        }
+
+        meta.entries.insert(
+            idx,
+            EntropicTestcaseMetadata::new(features.len(), num_rare_features),
+        );
+        Ok(())
+    }
+
+    fn on_evaluation<OT>(
+        &mut self,
+        state: &mut Self::State,
+        _input: &<Self::State as UsesInput>::Input,
+        observers: &OT,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<Self::State>,
+    {
+        let observer = observers
+            .get(&self.map_observer_handle)
+            .ok_or_else(|| Error::key_not_found("MapObserver not found"))?
+            .as_ref();
+
+        let current_idx = *state.corpus().current();
+        let meta = state.metadata_mut::<EntropicMetadata>()?;
+        let initial = observer.initial();
+        let usable_count = observer.usable_count();
+        self.rare_hits.clear();
+        // Only the hits of rare features matter, so only those are looked up in the map
+        for i in 0..meta.rare_features.len() {
+            let feature = meta.rare_features[i];
+            if feature < usable_count && observer.get(feature) != initial && meta.hit(feature) {
+                self.rare_hits.push(feature);
+            }
+        }
+
+        if let Some(tcmeta) = current_idx.and_then(|idx| meta.entries.get_mut(&idx)) {
+            tcmeta.executed_mutations += 1;
+            for feature in &self.rare_hits {
+                tcmeta.update_feature_frequency(*feature);
+            }
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn next(&mut self, state: &mut Self::State) -> Result<CorpusId, Error> {
+        if state.corpus().count() == 0 {
+            return Err(Error::empty(
+                "No entries in corpus. This often implies the target is not properly instrumented.",
+            ));
+        }
+
+        let ids: Vec<CorpusId> = state.corpus().ids().collect();
+        let meta = state.metadata_mut::<EntropicMetadata>()?;
+        let num_rare_features = meta.rare_features().len();
+        let mut weights = Vec::with_capacity(ids.len());
+        let mut vanilla = true;
+        for (i, id) in ids.into_iter().enumerate() {
+            let (entropic, fallback) = match meta.entries.get_mut(&id) {
+                Some(tcmeta) if tcmeta.num_features > 0 => {
+                    if tcmeta.needs_energy_update && tcmeta.energy != 0.0 {
+                        tcmeta.update_energy(num_rare_features);
+                    }
+                    (tcmeta.energy.max(0.0), (i + 1) as f64)
+                }
+                _ => (0.0, 0.0),
+            };
+            if entropic > 0.0 {
+                vanilla = false;
+            }
+            weights.push((id, entropic, fallback));
+        }
+
+        // Like libFuzzer, fall back to preferring newer entries while no entry has energy
+        let weight = |(_, entropic, fallback): &(CorpusId, f64, f64)| {
+            if vanilla {
+                *fallback
+            } else {
+                *entropic
+            }
+        };
+        let total: f64 = weights.iter().map(weight).sum();
+        let id = if total > 0.0 {
+            let threshold = state.rand_mut().next_float() * total;
+            let mut sum = 0.0;
+            weights
+                .iter()
+                .find(|entry| {
+                    sum += weight(entry);
+                    sum >= threshold
+                })
+                .or(weights.last())
+                .map(|(id, _, _)| *id)
+                .unwrap()
+        } else {
+            random_corpus_id!(state.corpus(), state.rand_mut())
+        };
+        self.set_current_scheduled(state, Some(id))?;
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use libafl_bolts::{rands::StdRand, tuples::tuple_list};
+
+    use super::{EntropicMetadata, EntropicScheduler, EntropicTestcaseMetadata};
+    use crate::{
+        corpus::{Corpus, CorpusId, InMemoryCorpus, Testcase},
+        feedbacks::{ConstFeedback, MapIndexesMetadata},
+        inputs::BytesInput,
+        observers::{MapObserver, StdMapObserver},
+        schedulers::{RemovableScheduler, Scheduler},
+        state::{HasCorpus, StdState},
+        HasMetadata,
+    };
+
+    #[test]
+    fn test_entropic_rare_features() {
+        let mut meta = EntropicMetadata::new(2, 3);
+        let mut removed = vec![];
+        for feature in 0..3 {
+            meta.add_rare_feature(feature, &mut removed);
+        }
+        assert!(removed.is_empty());
+        // Make feature 1 abundant
+        for _ in 0..5 {
+            meta.hit(1);
+        }
+        assert!(!meta.hit(7));
+        meta.add_rare_feature(3, &mut removed);
+        assert_eq!(removed, [1]);
+        assert!(!meta.rare_features().contains(&1));
+        assert!(meta.rare_features().contains(&3));
+        assert_eq!(meta.global_frequency(3), Some(0));
+    }
+
+    #[test]
+    fn test_entropic_energy() {
+        // An entry whose mutants hit all rare features equally often is worth more
+        // than one whose mutants only ever hit the same feature
+        let mut diverse = EntropicTestcaseMetadata::new(10, 4);
+        let mut monotone = EntropicTestcaseMetadata::new(10, 4);
+        for feature in 0..4 {
+            for _ in 0..5 {
+                diverse.update_feature_frequency(feature);
+                monotone.update_feature_frequency(0);
+            }
+        }
+        diverse.executed_mutations = 20;
+        monotone.executed_mutations = 20;
+        diverse.update_energy(4);
+        monotone.update_energy(4);
+        assert!(diverse.energy() > monotone.energy());
+
+        assert!(monotone.delete_feature_freq(0));
+        assert!(!monotone.delete_feature_freq(0));
+    }
+
+    #[test]
+    fn test_entropic_remove_features() {
+        let mut meta = EntropicMetadata::new(10, 3);
+        let mut removed = vec![];
+        for feature in 0..3 {
+            meta.add_rare_feature(feature, &mut removed);
+        }
+        meta.feature_entries.extend([(0, 2), (1, 1), (2, 1)]);
+        let mut tcmeta = EntropicTestcaseMetadata::new(3, 3);
+        tcmeta.update_feature_frequency(1);
+        meta.entries.insert(CorpusId(0), tcmeta);
+
+        // Feature 0 is still in another entry, feature 1 is gone
+        meta.remove_features(&[0, 1]);
+        assert_eq!(meta.global_frequency(0), Some(0));
+        assert_eq!(meta.global_frequency(1), None);
+        assert_eq!(meta.rare_features(), [0, 2]);
+        let tcmeta = meta.entry(CorpusId(0)).unwrap();
+        assert!(tcmeta.feature_freqs.is_empty());
+        assert!(tcmeta.needs_energy_update);
+    }
+
+    type TestState =
+        StdState<BytesInput, InMemoryCorpus<BytesInput>, StdRand, InMemoryCorpus<BytesInput>>;
+
+    #[test]
+    fn test_entropic_scheduler() {
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state: TestState = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut observer = StdMapObserver::owned("map", vec![0_u8; 8]);
+        let mut scheduler = EntropicScheduler::new(&mut state, &observer);
+
+        let add = |scheduler: &mut EntropicScheduler<_, _, _>,
+                   state: &mut TestState,
+                   features: Vec<usize>| {
+            let mut testcase = Testcase::new(BytesInput::new(vec![0; features.len()]));
+            testcase.add_metadata(MapIndexesMetadata::new(features));
+            let idx = state.corpus_mut().add(testcase).unwrap();
+            scheduler.on_add(state, idx).unwrap();
+            idx
+        };
+        let picks = |scheduler: &mut EntropicScheduler<_, _, _>,
+                     state: &mut TestState,
+                     ids: &[CorpusId]| {
+            let mut picks = vec![0; ids.len()];
+            for _ in 0..1000 {
+                let idx = scheduler.next(state).unwrap();
+                picks[ids.iter().position(|id| *id == idx).unwrap()] += 1;
+            }
+            picks
+        };
+
+        // With a single rare feature no entry has energy, so newer entries are preferred
+        let first = add(&mut scheduler, &mut state, vec![0]);
+        let second = add(&mut scheduler, &mut state, vec![0]);
+        let fallback = picks(&mut scheduler, &mut state, &[first, second]);
+        assert!(fallback[0] > 0);
+        assert!(fallback[1] > fallback[0]);
+
+        // Only the entries that discovered rare features have energy
+        let rare = add(&mut scheduler, &mut state, vec![0, 1, 2]);
+        let rarer = add(&mut scheduler, &mut state, vec![3]);
+        let meta = state.metadata::<EntropicMetadata>().unwrap();
+        assert_eq!(meta.rare_features(), [0, 1, 2, 3]);
+        assert!(meta.entry(rare).unwrap().energy() > 0.0);
+        assert!(meta.entry(first).unwrap().energy().abs() < f64::EPSILON);
+
+        // Mutants of `rare` keep hitting the same rare feature, so it loses energy to `rarer`
+        observer.set(1, 1);
+        *state.corpus_mut().current_mut() = Some(rare);
+        let observers = tuple_list!(observer);
+        for _ in 0..50 {
+            scheduler
+                .on_evaluation(&mut state, &BytesInput::new(vec![]), &observers)
+                .unwrap();
+        }
+        let meta = state.metadata::<EntropicMetadata>().unwrap();
+        // The execution that discovered feature 1 hit it once, too
+        assert_eq!(meta.global_frequency(1), Some(51));
+        assert_eq!(meta.entry(rare).unwrap().executed_mutations(), 50);
+
+        let weighted = picks(&mut scheduler, &mut state, &[first, second, rare, rarer]);
+        assert_eq!(weighted[..2], [0, 0]);
+        assert!(weighted[2] > 0);
+        assert!(weighted[3] > weighted[2]);
+
+        // Removing `rarer` drops the feature only it had
+        let testcase = state.corpus_mut().remove(rarer).unwrap();
+        scheduler
+            .on_remove(&mut state, rarer, &Some(testcase))
+            .unwrap();
+        let meta = state.metadata::<EntropicMetadata>().unwrap();
+        assert_eq!(meta.rare_features(), [0, 1, 2]);
+        assert_eq!(meta.global_frequency(3), None);
+        assert!(meta.entry(rarer).is_none());
+        assert_eq!(
+            picks(&mut scheduler, &mut state, &[first, second, rare]),
+            [0, 0, 1000]
+        );
+    }
+}
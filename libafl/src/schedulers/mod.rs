@@ -26,6 +26,18 @@ pub use accounting::CoverageAccountingScheduler;
 pub mod weighted;
 pub use weighted::{StdWeightedScheduler, WeightedScheduler};
 
+pub mod entropic;
+pub use entropic::EntropicScheduler;
+
+pub mod directed;
+pub use directed::{DirectedPowerTestcaseScore, DirectedScheduler};
+
+pub mod bandit;
+pub use bandit::{BanditPowerScheduler, ThompsonSampling};
+
+pub mod protocol_state;
+pub use protocol_state::ProtocolStateScheduler;
+
 pub mod tuneable;
 use libafl_bolts::{
     rands::Rand,
@@ -0,0 +1,368 @@
+//! The [`ProtocolStateScheduler`] focuses on corpus entries that reach rarely exercised states of a stateful target,
+//! similar to the state selection of `AFLNet`.
+//! The states come from a [`ProtocolStateObserver`].
+
+use alloc::{borrow::ToOwned, collections::BTreeMap, vec::Vec};
+use core::marker::PhantomData;
+
+use libafl_bolts::{
+    impl_serdeany,
+    rands::Rand,
+    tuples::{Handle, Handled, MatchNameRef},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{Corpus, CorpusId, HasTestcase, Testcase},
+    inputs::UsesInput,
+    observers::{ObserversTuple, ProtocolStateObserver},
+    random_corpus_id,
+    schedulers::{RemovableScheduler, Scheduler},
+    state::{HasCorpus, HasRand, State, UsesState},
+    Error, HasMetadata,
+};
+
+/// What the [`ProtocolStateScheduler`] knows about a single protocol state
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProtocolStateInfo {
+    /// How many executions reached this state
+    pub hits: u64,
+    /// How often this state was picked to fuzz next
+    pub selected: u64,
+    /// The corpus entries reaching this state
+    pub entries: Vec<CorpusId>,
+}
+
+/// A state metadata holding the protocol states seen so far
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct ProtocolStatesMetadata {
+    /// state id -> info about this state
+    pub states: BTreeMap<u32, ProtocolStateInfo>,
+}
+
+impl_serdeany!(ProtocolStatesMetadata);
+
+impl ProtocolStatesMetadata {
+    /// Creates a new [`struct@ProtocolStatesMetadata`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// A testcase metadata holding the protocol states reached by this testcase, in the order they were first reached
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct ProtocolStatesTestcaseMetadata {
+    /// The states reached
+    pub states: Vec<u32>,
+}
+
+impl_serdeany!(ProtocolStatesTestcaseMetadata);
+
+/// Schedules corpus entries by the protocol states they reach.
+///
+/// Each time, it first picks a state, with a probability inversely proportional to how many executions reached it,
+/// then a random corpus entry reaching this state. Entries that did not reach any state are only scheduled
+/// as long as no state is known.
+#[derive(Debug, Clone)]
+pub struct ProtocolStateScheduler<S> {
+    observer_handle: Handle<ProtocolStateObserver>,
+    last_states: Vec<u32>,
+    phantom: PhantomData<S>,
+}
+
+impl<S> ProtocolStateScheduler<S> {
+    /// Creates a new [`ProtocolStateScheduler`], getting the states from the given observer
+    #[must_use]
+    pub fn new(observer: &ProtocolStateObserver) -> Self {
+        Self {
+            observer_handle: observer.handle(),
+            last_states: Vec::new(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<S> UsesState for ProtocolStateScheduler<S>
+where
+    S: State,
+{
+    type State = S;
+}
+
+impl<S> RemovableScheduler for ProtocolStateScheduler<S>
+where
+    S: HasCorpus + HasMetadata + HasRand + HasTestcase + State,
+{
+    fn on_remove(
+        &mut self,
+        state: &mut Self::State,
+        idx: CorpusId,
+        _testcase: &Option<Testcase<<Self::State as UsesInput>::Input>>,
+    ) -> Result<(), Error> {
+        if let Ok(meta) = state.metadata_mut::<ProtocolStatesMetadata>() {
+            for info in meta.states.values_mut() {
+                info.entries.retain(|entry| *entry != idx);
+            }
+        }
+        Ok(())
+    }
+
+    /// The new testcase is assumed to reach the same states as the one it replaces,
+    /// unless it already has a [`ProtocolStatesTestcaseMetadata`] of its own.
+    fn on_replace(
+        &mut self,
+        state: &mut Self::State,
+        idx: CorpusId,
+        prev: &Testcase<<Self::State as UsesInput>::Input>,
+    ) -> Result<(), Error> {
+        let states = {
+            let mut testcase = state.testcase_mut(idx)?;
+            if let Ok(meta) = testcase.metadata::<ProtocolStatesTestcaseMetadata>() {
+                meta.states.clone()
+            } else {
+                let states = prev
+                    .metadata::<ProtocolStatesTestcaseMetadata>()
+                    .map(|meta| meta.states.clone())
+                    .unwrap_or_default();
+                testcase.add_metadata(ProtocolStatesTestcaseMetadata {
+                    states: states.clone(),
+                });
+                states
+            }
+        };
+
+        let meta = state.metadata_or_insert_with(ProtocolStatesMetadata::new);
+        for info in meta.states.values_mut() {
+            info.entries.retain(|entry| *entry != idx);
+        }
+        for id in states {
+            meta.states.entry(id).or_default().entries.push(idx);
+        }
+        Ok(())
+    }
+}
+
+impl<S> Scheduler for ProtocolStateScheduler<S>
+where
+    S: HasCorpus + HasMetadata + HasRand + HasTestcase + State,
+{
+    fn on_add(&mut self, state: &mut Self::State, idx: CorpusId) -> Result<(), Error> {
+        let current_idx = *state.corpus().current();
+        let states = core::mem::take(&mut self.last_states);
+
+        {
+            let mut testcase = state.testcase_mut(idx)?;
+            testcase.set_parent_id_optional(current_idx);
+            testcase.add_metadata(ProtocolStatesTestcaseMetadata {
+                states: states.clone(),
+            });
+        }
+
+        let meta = state.metadata_or_insert_with(ProtocolStatesMetadata::new);
+        for id in states {
+            meta.states.entry(id).or_default().entries.push(idx);
+        }
+        Ok(())
+    }
+
+    fn on_evaluation<OT>(
+        &mut self,
+        state: &mut Self::State,
+        _input: &<Self::State as UsesInput>::Input,
+        observers: &OT,
+    ) -> Result<(), Error>
+    where
+        OT: ObserversTuple<Self::State>,
+    {
+        let observer = observers
+            .get(&self.observer_handle)
+            .ok_or_else(|| Error::key_not_found("ProtocolStateObserver not found".to_owned()))?;
+
+        self.last_states.clear();
+        for id in observer.states() {
+            if !self.last_states.contains(id) {
+                self.last_states.push(*id);
+            }
+        }
+
+        let meta = state.metadata_or_insert_with(ProtocolStatesMetadata::new);
+        for id in &self.last_states {
+            meta.states.entry(*id).or_default().hits += 1;
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn next(&mut self, state: &mut Self::State) -> Result<CorpusId, Error> {
+        if state.corpus().count() == 0 {
+            return Err(Error::empty(
+                "No entries in corpus. This often implies the target is not properly instrumented."
+                    .to_owned(),
+            ));
+        }
+
+        let rand_prob = state.rand_mut().next_float();
+        let rand_entry = state.rand_mut().next();
+        let picked = state
+            .metadata_map_mut()
+            .get_mut::<ProtocolStatesMetadata>()
+            .and_then(|meta| {
+                let weight = |info: &ProtocolStateInfo| 1.0 / (info.hits as f64 + 1.0);
+                let total: f64 = meta
+                    .states
+                    .values()
+                    .filter(|info| !info.entries.is_empty())
+                    .map(weight)
+                    .sum();
+                let threshold = total * rand_prob;
+
+                let mut k = 0.0;
+                let mut picked = None;
+                for info in meta
+                    .states
+                    .values_mut()
+                    .filter(|info| !info.entries.is_empty())
+                {
+                    k += weight(info);
+                    picked = Some(info);
+                    if k >= threshold {
+                        break;
+                    }
+                }
+                picked.map(|info| {
+                    info.selected += 1;
+                    info.entries[rand_entry as usize % info.entries.len()]
+                })
+            });
+
+        let id = if let Some(id) = picked {
+            id
+        } else {
+            random_corpus_id!(state.corpus(), state.rand_mut())
+        };
+        self.set_current_scheduled(state, Some(id))?;
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "std")]
+mod tests {
+    use libafl_bolts::rands::StdRand;
+
+    use super::{
+        ProtocolStateInfo, ProtocolStateScheduler, ProtocolStatesMetadata,
+        ProtocolStatesTestcaseMetadata,
+    };
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        feedbacks::ConstFeedback,
+        inputs::BytesInput,
+        observers::{ProtocolStateObserver, ResponseCodeParser},
+        schedulers::{RemovableScheduler, Scheduler},
+        state::{HasCorpus, StdState},
+        HasMetadata,
+    };
+
+    #[test]
+    fn test_protocol_state_scheduler_prefers_rare_states() {
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+
+        let common = state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(b"a".to_vec())))
+            .unwrap();
+        let rare = state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(b"b".to_vec())))
+            .unwrap();
+
+        let mut meta = ProtocolStatesMetadata::new();
+        meta.states.insert(
+            220,
+            ProtocolStateInfo {
+                hits: 10_000,
+                selected: 0,
+                entries: vec![common],
+            },
+        );
+        meta.states.insert(
+            530,
+            ProtocolStateInfo {
+                hits: 1,
+                selected: 0,
+                entries: vec![rare],
+            },
+        );
+        state.add_metadata(meta);
+
+        let observer = ProtocolStateObserver::new("state", ResponseCodeParser::TextStatusLine);
+        let mut scheduler = ProtocolStateScheduler::new(&observer);
+        let rare_count = (0..100)
+            .filter(|_| scheduler.next(&mut state).unwrap() == rare)
+            .count();
+        assert!(rare_count > 90);
+
+        let meta = state.metadata::<ProtocolStatesMetadata>().unwrap();
+        assert_eq!(meta.states[&220].selected + meta.states[&530].selected, 100);
+    }
+
+    #[test]
+    fn test_protocol_state_scheduler_replace() {
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(1337),
+            InMemoryCorpus::<BytesInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let observer = ProtocolStateObserver::new("state", ResponseCodeParser::TextStatusLine);
+        let mut scheduler = ProtocolStateScheduler::new(&observer);
+
+        let idx = state
+            .corpus_mut()
+            .add(Testcase::new(BytesInput::new(b"long".to_vec())))
+            .unwrap();
+        scheduler.last_states = vec![220, 530];
+        scheduler.on_add(&mut state, idx).unwrap();
+
+        let prev = state
+            .corpus_mut()
+            .replace(idx, Testcase::new(BytesInput::new(b"l".to_vec())))
+            .unwrap();
+        scheduler.on_replace(&mut state, idx, &prev).unwrap();
+
+        let meta = state.metadata::<ProtocolStatesMetadata>().unwrap();
+        assert_eq!(meta.states[&220].entries, [idx]);
+        assert_eq!(meta.states[&530].entries, [idx]);
+        let testcase = state.corpus().get(idx).unwrap().borrow();
+        assert_eq!(
+            testcase
+                .metadata::<ProtocolStatesTestcaseMetadata>()
+                .unwrap()
+                .states,
+            [220, 530]
+        );
+    }
+}
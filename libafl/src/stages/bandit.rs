@@ -0,0 +1,153 @@
+//! A stage picking one of several stages to run with a multi-armed bandit.
+
+use alloc::{boxed::Box, vec::Vec};
+use core::fmt::{self, Debug};
+
+use libafl_bolts::{impl_serdeany, tuples::IntoVec};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    schedulers::bandit::{BanditRound, ThompsonSampling},
+    stages::{HasCurrentStage, HasNestedStageStatus, NestedStageRestartHelper, Stage, StageId},
+    state::{HasCorpus, HasExecutions, HasRand, UsesState},
+    Error, HasMetadata,
+};
+
+/// The state of the [`BanditStage`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct StageBanditMetadata {
+    /// The posterior of each stage
+    pub bandit: ThompsonSampling,
+    /// The stage currently running
+    pub round: Option<BanditRound>,
+}
+
+impl_serdeany!(StageBanditMetadata);
+
+/// Runs one of its stages per corpus entry, picked with [`ThompsonSampling`].
+///
+/// A stage is rewarded for each of its executions that found a new corpus entry,
+/// so the stages finding the most per execution run the most.
+/// The posterior is kept in a [`StageBanditMetadata`] in the state.
+pub struct BanditStage<E, EM, Z>
+where
+    E: UsesState,
+{
+    #[allow(clippy::type_complexity)]
+    stages: Vec<Box<dyn Stage<E, EM, Z, State = E::State, Input = E::Input>>>,
+}
+
+impl<E, EM, Z> Debug for BanditStage<E, EM, Z>
+where
+    E: UsesState,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BanditStage with {} stages", self.stages.len())
+    }
+}
+
+impl<E, EM, Z> UsesState for BanditStage<E, EM, Z>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for BanditStage<E, EM, Z>
+where
+    E: UsesState,
+    EM: UsesState<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+    Self::State: HasNestedStageStatus + HasCorpus + HasExecutions + HasRand + HasMetadata,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let arm = if let Some(StageId(arm)) = state.current_stage_idx()? {
+            // resuming
+            arm
+        } else {
+            let mut meta = state
+                .metadata_map_mut()
+                .remove::<StageBanditMetadata>()
+                .ok_or_else(|| Error::key_not_found("StageBanditMetadata not found"))?;
+            let arm = meta.bandit.select(state.rand_mut())?;
+            meta.round = Some(BanditRound::start(state, arm));
+            state.metadata_map_mut().insert_boxed(meta);
+            state.set_current_stage_idx(StageId(arm))?;
+            arm
+        };
+
+        let stage = self
+            .stages
+            .get_mut(arm)
+            .ok_or_else(|| Error::illegal_state("BanditStage resumed an unknown stage"))?;
+        stage.perform_restartable(fuzzer, executor, state, manager)?;
+        state.clear_stage()?;
+
+        let finished = state.metadata::<StageBanditMetadata>()?.round;
+        if let Some(round) = finished {
+            let (found, executions) = round.finish(state);
+            let meta = state.metadata_mut::<StageBanditMetadata>()?;
+            meta.bandit.update(round.arm, found, executions)?;
+            meta.round = None;
+        }
+        Ok(())
+    }
+
+    fn restart_progress_should_run(&mut self, state: &mut Self::State) -> Result<bool, Error> {
+        NestedStageRestartHelper::restart_progress_should_run(state, self)
+    }
+
+    fn clear_restart_progress(&mut self, state: &mut Self::State) -> Result<(), Error> {
+        NestedStageRestartHelper::clear_restart_progress(state, self)
+    }
+}
+
+impl<E, EM, Z> BanditStage<E, EM, Z>
+where
+    E: UsesState,
+{
+    /// Creates a new [`BanditStage`] picking one of `stages` each time.
+    ///
+    /// If the state already holds a posterior for as many stages, e.g., after a restart, it is kept.
+    pub fn new<ST, S>(state: &mut S, stages: ST) -> Result<Self, Error>
+    where
+        ST: IntoVec<Box<dyn Stage<E, EM, Z, State = E::State, Input = E::Input>>>,
+        S: HasMetadata,
+    {
+        Self::with_discount(state, stages, 1.0)
+    }
+
+    /// Creates a new [`BanditStage`], discounting older observations, see [`ThompsonSampling::with_discount`]
+    pub fn with_discount<ST, S>(state: &mut S, stages: ST, discount: f64) -> Result<Self, Error>
+    where
+        ST: IntoVec<Box<dyn Stage<E, EM, Z, State = E::State, Input = E::Input>>>,
+        S: HasMetadata,
+    {
+        let stages = stages.into_vec();
+        if stages.is_empty() {
+            return Err(Error::illegal_argument(
+                "BanditStage needs at least one stage",
+            ));
+        }
+        let restored = state
+            .metadata::<StageBanditMetadata>()
+            .is_ok_and(|meta| meta.bandit.len() == stages.len());
+        if !restored {
+            state.add_metadata(StageBanditMetadata {
+                bandit: ThompsonSampling::with_discount(stages.len(), discount),
+                round: None,
+            });
+        }
+        Ok(Self { stages })
+    }
+}
@@ -0,0 +1,497 @@
+//! The [`CorpusDistillationStage`] periodically disables the corpus entries not needed to keep the coverage.
+//!
+//! The testcases disabled by one client are announced to all others with an [`Event::CustomBuf`],
+//! so that they disable the same inputs, as far as their own coverage allows,
+//! and the corpora of the whole cluster shrink consistently.
+
+use alloc::{boxed::Box, string::ToString, vec::Vec};
+use core::{marker::PhantomData, time::Duration};
+
+use hashbrown::HashSet;
+use libafl_bolts::{current_time, hash_std, impl_serdeany, AsIter, Named};
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{Corpus, CorpusId, GreedyCorpusMinimizer, HasCurrentCorpusId, HitcountMode},
+    events::{CustomBufEventResult, Event, EventFirer, HasCustomBufHandlers, LogSeverity},
+    executors::{Executor, HasObservers},
+    observers::MapObserver,
+    schedulers::{LenTimeMulTestcaseScore, RemovableScheduler, TestcaseScore},
+    stages::Stage,
+    state::{HasCorpus, HasExecutions, UsesState},
+    Error, HasMetadata, HasScheduler,
+};
+
+/// The tag of the [`Event::CustomBuf`] announcing the inputs disabled by a [`CorpusDistillationStage`]
+pub const DISTILLATION_EVENT_TAG: &str = "libafl_corpus_distillation";
+
+/// The progress of the [`CorpusDistillationStage`], kept in the state to survive restarts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct CorpusDistillationMetadata {
+    /// When the corpus was distilled last
+    pub last_time: Duration,
+    /// The number of corpus entries after the corpus was distilled last
+    pub last_corpus_size: usize,
+    /// The number of testcases disabled so far, here or because another client did
+    pub disabled: usize,
+    /// The hashes of the inputs disabled by other clients, still to be checked and disabled here
+    pub pending: Vec<u64>,
+}
+
+impl_serdeany!(CorpusDistillationMetadata);
+
+impl CorpusDistillationMetadata {
+    /// Creates a new [`CorpusDistillationMetadata`], as if the corpus was distilled at `last_time`
+    #[must_use]
+    pub fn new(last_time: Duration, last_corpus_size: usize) -> Self {
+        Self {
+            last_time,
+            last_corpus_size,
+            disabled: 0,
+            pending: Vec::new(),
+        }
+    }
+}
+
+/// The hash identifying an input across clients
+fn input_hash<I>(input: &I) -> Result<u64, Error>
+where
+    I: Serialize,
+{
+    Ok(hash_std(&postcard::to_allocvec(input)?))
+}
+
+/// A stage that, every so often, disables the corpus entries whose coverage is covered by others.
+///
+/// The coverage is re-evaluated by executing each enabled corpus entry with a [`GreedyCorpusMinimizer`].
+/// The redundant entries are moved to the disabled part of the [`Corpus`], so they are no longer scheduled,
+/// but can still be found by their id. The entry currently being fuzzed is never disabled.
+///
+/// The inputs disabled by other clients are only disabled here if the coverage of this corpus does not depend on them.
+///
+/// The stage runs once enough time passed or enough entries were added since it ran last, see
+/// [`CorpusDistillationStage::with_interval`] and [`CorpusDistillationStage::with_additions`].
+#[derive(Debug)]
+pub struct CorpusDistillationStage<C, E, EM, O, T, TS, Z> {
+    minimizer: GreedyCorpusMinimizer<C, E, O, T, TS>,
+    interval: Option<Duration>,
+    additions: Option<usize>,
+    phantom: PhantomData<(EM, Z)>,
+}
+
+/// A [`CorpusDistillationStage`] which prefers to keep small and fast inputs.
+pub type StdCorpusDistillationStage<C, E, EM, O, T, Z> =
+    CorpusDistillationStage<C, E, EM, O, T, LenTimeMulTestcaseScore<<E as UsesState>::State>, Z>;
+
+impl<C, E, EM, O, T, TS, Z> UsesState for CorpusDistillationStage<C, E, EM, O, T, TS, Z>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<C, E, EM, O, T, TS, Z> Stage<E, EM, Z> for CorpusDistillationStage<C, E, EM, O, T, TS, Z>
+where
+    E: Executor<EM, Z> + HasObservers,
+    EM: EventFirer<State = Self::State>,
+    Z: HasScheduler<State = Self::State>,
+    Z::Scheduler: RemovableScheduler,
+    for<'a> O: MapObserver<Entry = T> + AsIter<'a, Item = T>,
+    C: AsRef<O>,
+    T: Copy + PartialEq + ToPrimitive,
+    TS: TestcaseScore<Self::State>,
+    Self::State: HasCorpus + HasMetadata + HasExecutions,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let now = current_time();
+        let corpus_size = state.corpus().count();
+        state.metadata_or_insert_with(|| CorpusDistillationMetadata::new(now, corpus_size));
+
+        // Disabling the entries the stages are working on would pull the rug from under them
+        let protected = [state.current_corpus_id()?, *state.corpus().current()];
+
+        let pending =
+            core::mem::take(&mut state.metadata_mut::<CorpusDistillationMetadata>()?.pending);
+        if !pending.is_empty() {
+            let pending: HashSet<u64> = pending.into_iter().collect();
+            let mut candidates = Vec::new();
+            for id in state.corpus().ids() {
+                if protected.contains(&Some(id)) {
+                    continue;
+                }
+                let input = state.corpus().cloned_input_for_id(id)?;
+                if pending.contains(&input_hash(&input)?) {
+                    candidates.push(id);
+                }
+            }
+            if !candidates.is_empty() {
+                // The other client only checked its own coverage, this corpus may still need some of them
+                let redundant = self.minimizer.find_redundant_among(
+                    fuzzer,
+                    executor,
+                    manager,
+                    state,
+                    &candidates,
+                )?;
+                let disabled = disable_testcases(fuzzer, state, redundant)?;
+                state.metadata_mut::<CorpusDistillationMetadata>()?.disabled += disabled.len();
+            }
+        }
+
+        let meta = state.metadata_mut::<CorpusDistillationMetadata>()?;
+        let due = self
+            .interval
+            .is_some_and(|interval| now.saturating_sub(meta.last_time) >= interval)
+            || self
+                .additions
+                .is_some_and(|additions| corpus_size >= meta.last_corpus_size + additions);
+        if !due {
+            return Ok(());
+        }
+        // Mark the corpus as distilled before running the target, so a crash won't make us start over
+        meta.last_time = now;
+        meta.last_corpus_size = corpus_size;
+
+        let redundant: Vec<CorpusId> = self
+            .minimizer
+            .find_redundant(fuzzer, executor, manager, state)?
+            .into_iter()
+            .filter(|id| !protected.contains(&Some(*id)))
+            .collect();
+        let disabled = disable_testcases(fuzzer, state, redundant)?;
+
+        let corpus_size = state.corpus().count();
+        let meta = state.metadata_mut::<CorpusDistillationMetadata>()?;
+        meta.last_corpus_size = corpus_size;
+        meta.disabled += disabled.len();
+
+        manager.log(
+            state,
+            LogSeverity::Info,
+            format!(
+                "Distilled the corpus, disabled {} testcases, {corpus_size} left",
+                disabled.len()
+            ),
+        )?;
+        if !disabled.is_empty() {
+            manager.fire(
+                state,
+                Event::CustomBuf {
+                    buf: postcard::to_allocvec(&disabled)?,
+                    tag: DISTILLATION_EVENT_TAG.to_string(),
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn restart_progress_should_run(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // The corpus is marked as distilled before running the target, a restart won't distill it again
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_restart_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Disables the given testcases, keeping their ids, and returns the hashes of their inputs
+fn disable_testcases<S, Z>(
+    fuzzer: &mut Z,
+    state: &mut S,
+    ids: Vec<CorpusId>,
+) -> Result<Vec<u64>, Error>
+where
+    S: HasCorpus,
+    Z: HasScheduler<State = S>,
+    Z::Scheduler: RemovableScheduler,
+{
+    let mut hashes = Vec::with_capacity(ids.len());
+    for id in ids {
+        let input = state.corpus().cloned_input_for_id(id)?;
+        hashes.push(input_hash(&input)?);
+        state.corpus_mut().disable(id)?;
+        // the scheduler needs to know the testcase is gone, or it will continue to try to use it
+        let testcase = state.corpus().get_from_all(id)?.borrow().clone();
+        fuzzer
+            .scheduler_mut()
+            .on_remove(state, id, &Some(testcase))?;
+    }
+    Ok(hashes)
+}
+
+impl<C, E, EM, O, T, TS, Z> CorpusDistillationStage<C, E, EM, O, T, TS, Z>
+where
+    E: UsesState,
+    E::State: HasCorpus + HasMetadata + 'static,
+    EM: HasCustomBufHandlers<State = E::State>,
+    TS: TestcaseScore<E::State>,
+    C: Named,
+{
+    /// Creates a new [`CorpusDistillationStage`] keeping the coverage of the given map observer,
+    /// distilling the corpus every hour.
+    ///
+    /// Registers a handler with the `manager`, to disable the testcases other clients disabled, too.
+    pub fn new(manager: &mut EM, map_observer: &C) -> Self {
+        manager.add_custom_buf_handler(Box::new(|state: &mut E::State, tag, buf| {
+            if tag != DISTILLATION_EVENT_TAG {
+                return Ok(CustomBufEventResult::Next);
+            }
+            let hashes: Vec<u64> = postcard::from_bytes(buf)?;
+            let now = current_time();
+            let corpus_size = state.corpus().count();
+            state
+                .metadata_or_insert_with(|| CorpusDistillationMetadata::new(now, corpus_size))
+                .pending
+                .extend(hashes);
+            Ok(CustomBufEventResult::Handled)
+        }));
+        Self {
+            minimizer: GreedyCorpusMinimizer::new(map_observer),
+            interval: Some(Duration::from_secs(60 * 60)),
+            additions: None,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Distills the corpus once `interval` passed since it was distilled last, or never if `None`
+    #[must_use]
+    pub fn with_interval(mut self, interval: Option<Duration>) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Distills the corpus once `additions` entries were added since it was distilled last, or never if `None`
+    #[must_use]
+    pub fn with_additions(mut self, additions: Option<usize>) -> Self {
+        self.additions = additions;
+        self
+    }
+
+    /// Sets how hit counts are distinguished when comparing the coverage
+    #[must_use]
+    pub fn with_hitcount_mode(mut self, hitcount_mode: HitcountMode) -> Self {
+        self.minimizer = self.minimizer.with_hitcount_mode(hitcount_mode);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::ToString, vec, vec::Vec};
+    use core::ptr::addr_of_mut;
+
+    use libafl_bolts::{rands::StdRand, tuples::tuple_list};
+
+    use super::{
+        disable_testcases, input_hash, CorpusDistillationMetadata, StdCorpusDistillationStage,
+        DISTILLATION_EVENT_TAG,
+    };
+    use crate::{
+        corpus::{Corpus, CorpusId, InMemoryCorpus, Testcase},
+        events::{Event, EventFirer, EventProcessor, SimpleEventManager},
+        executors::{ExitKind, InProcessExecutor},
+        feedbacks::ConstFeedback,
+        inputs::{BytesInput, HasMutatorBytes},
+        monitors::NopMonitor,
+        observers::StdMapObserver,
+        schedulers::QueueScheduler,
+        stages::Stage,
+        state::{HasCorpus, StdState},
+        HasMetadata, StdFuzzer,
+    };
+
+    #[test]
+    fn test_disable_testcases() {
+        let mut corpus = InMemoryCorpus::<BytesInput>::new();
+        for i in 0..3 {
+            corpus
+                .add(Testcase::new(BytesInput::new(vec![i; 4])))
+                .unwrap();
+        }
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut fuzzer: StdFuzzer<_, _, _, ()> =
+            StdFuzzer::new(QueueScheduler::new(), feedback, objective);
+
+        let hashes =
+            disable_testcases(&mut fuzzer, &mut state, vec![CorpusId(0), CorpusId(2)]).unwrap();
+        assert_eq!(hashes.len(), 2);
+        assert_eq!(hashes[0], input_hash(&BytesInput::new(vec![0; 4])).unwrap());
+        assert_eq!(state.corpus().count(), 1);
+        assert_eq!(state.corpus().count_disabled(), 2);
+        assert!(state.corpus().get(CorpusId(1)).is_ok());
+        // the disabled testcases keep their ids
+        assert!(state.corpus().get(CorpusId(2)).is_err());
+        let disabled = state.corpus().get_from_all(CorpusId(2)).unwrap().borrow();
+        assert_eq!(
+            disabled.input().as_ref(),
+            Some(&BytesInput::new(vec![2; 4]))
+        );
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_distillation_stage() {
+        static mut MAP: [u8; 4] = [0; 4];
+
+        let mut corpus = InMemoryCorpus::<BytesInput>::new();
+        // 1 covers what the cheaper inputs 0 and 2 cover
+        for input in [&[0][..], &[0, 1], &[1], &[2]] {
+            corpus
+                .add(Testcase::new(BytesInput::new(input.to_vec())))
+                .unwrap();
+        }
+        // 1 is being fuzzed, it must not go away
+        *corpus.current_mut() = Some(CorpusId(1));
+
+        let mut feedback = ConstFeedback::new(false);
+        let mut objective = ConstFeedback::new(false);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            corpus,
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )
+        .unwrap();
+        let mut manager = SimpleEventManager::new(NopMonitor::new());
+        let mut fuzzer: StdFuzzer<_, _, _, ()> =
+            StdFuzzer::new(QueueScheduler::new(), feedback, objective);
+
+        // # Safety
+        // The map is only used by this test
+        let observer =
+            unsafe { StdMapObserver::from_mut_ptr("map", addr_of_mut!(MAP).cast::<u8>(), 4) };
+        let mut stage: StdCorpusDistillationStage<_, _, _, _, _, _> =
+            StdCorpusDistillationStage::new(&mut manager, &observer)
+                .with_interval(None)
+                .with_additions(Some(2));
+        let mut harness = |input: &BytesInput| {
+            for byte in input.bytes() {
+                // # Safety
+                // The map is only used by this test
+                unsafe {
+                    MAP[usize::from(*byte)] += 1;
+                }
+            }
+            ExitKind::Ok
+        };
+        let mut executor = InProcessExecutor::new(
+            &mut harness,
+            tuple_list!(observer),
+            &mut fuzzer,
+            &mut state,
+            &mut manager,
+        )
+        .unwrap();
+
+        // Nothing was added since the stage ran first, it is not due
+        stage
+            .perform(&mut fuzzer, &mut executor, &mut state, &mut manager)
+            .unwrap();
+        assert_eq!(state.corpus().count(), 4);
+        assert_eq!(
+            manager
+                .process(&mut fuzzer, &mut state, &mut executor)
+                .unwrap(),
+            0
+        );
+
+        // 4 covers what the cheaper inputs 3 and 5 cover
+        for input in [&[2, 3][..], &[3]] {
+            state
+                .corpus_mut()
+                .add(Testcase::new(BytesInput::new(input.to_vec())))
+                .unwrap();
+        }
+        stage
+            .perform(&mut fuzzer, &mut executor, &mut state, &mut manager)
+            .unwrap();
+        let kept: Vec<CorpusId> = state.corpus().ids().collect();
+        assert_eq!(
+            kept,
+            [
+                CorpusId(0),
+                CorpusId(1),
+                CorpusId(2),
+                CorpusId(3),
+                CorpusId(5)
+            ]
+        );
+        let meta = state.metadata::<CorpusDistillationMetadata>().unwrap();
+        assert_eq!(meta.disabled, 1);
+        assert_eq!(meta.last_corpus_size, 5);
+
+        // The announcement of the disabled input reaches the registered handler
+        assert_eq!(
+            manager
+                .process(&mut fuzzer, &mut state, &mut executor)
+                .unwrap(),
+            1
+        );
+        let disabled_hash = input_hash(&BytesInput::new(vec![2, 3])).unwrap();
+        assert_eq!(
+            state
+                .metadata::<CorpusDistillationMetadata>()
+                .unwrap()
+                .pending,
+            [disabled_hash]
+        );
+
+        // Another client disabled 1, 2 and 5, but only 1 is redundant here
+        *state.corpus_mut().current_mut() = None;
+        let hashes: Vec<u64> = [&[0, 1][..], &[1], &[3]]
+            .into_iter()
+            .map(|input| input_hash(&BytesInput::new(input.to_vec())).unwrap())
+            .collect();
+        manager
+            .fire(
+                &mut state,
+                Event::CustomBuf {
+                    buf: postcard::to_allocvec(&hashes).unwrap(),
+                    tag: DISTILLATION_EVENT_TAG.to_string(),
+                },
+            )
+            .unwrap();
+        manager
+            .process(&mut fuzzer, &mut state, &mut executor)
+            .unwrap();
+        stage
+            .perform(&mut fuzzer, &mut executor, &mut state, &mut manager)
+            .unwrap();
+        let kept: Vec<CorpusId> = state.corpus().ids().collect();
+        assert_eq!(kept, [CorpusId(0), CorpusId(2), CorpusId(3), CorpusId(5)]);
+        let meta = state.metadata::<CorpusDistillationMetadata>().unwrap();
+        assert_eq!(meta.disabled, 2);
+        assert!(meta.pending.is_empty());
+        // Not due, so nothing was announced
+        assert_eq!(
+            manager
+                .process(&mut fuzzer, &mut state, &mut executor)
+                .unwrap(),
+            0
+        );
+    }
+}
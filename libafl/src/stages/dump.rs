@@ -1,15 +1,19 @@
 //! The [`DumpToDiskStage`] is a stage that dumps the corpus and the solutions to disk to e.g. allow AFL to sync
+//!
+//! The [`DumpTokensStage`] periodically writes the learned [`Tokens`] to a dictionary file, to seed later campaigns.
 
 use alloc::{string::String, vec::Vec};
-use core::{clone::Clone, marker::PhantomData};
-use std::{fs, fs::File, io::Write, path::PathBuf};
+use core::{clone::Clone, marker::PhantomData, time::Duration};
+use std::{ffi::OsString, fs, fs::File, io::Write, path::PathBuf};
 
-use libafl_bolts::impl_serdeany;
+use hashbrown::HashMap;
+use libafl_bolts::{current_time, impl_serdeany};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     corpus::{Corpus, CorpusId},
     inputs::UsesInput,
+    mutators::Tokens,
     stages::Stage,
     state::{HasCorpus, HasRand, HasSolutions, UsesState},
     Error, HasMetadata,
@@ -165,3 +169,159 @@ where
         })
     }
 }
+
+/// The default interval between two dictionary dumps of the [`DumpTokensStage`]
+pub const DEFAULT_TOKENS_DUMP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The [`DumpTokensStage`] periodically writes the [`Tokens`] metadata, e.g., from autotokens, cmplog or the
+/// [`crate::stages::TokenMiningStage`], to an AFL/`libFuzzer` dictionary file, see [`Tokens::write_to_file`].
+///
+/// Tokens already in the file when the stage was created, e.g., from an earlier campaign, are kept in the file,
+/// with the higher of their old and new hit counts.
+/// The file is replaced atomically, so other processes can read it at any time.
+#[derive(Debug)]
+pub struct DumpTokensStage<EM, Z> {
+    path: PathBuf,
+    interval: Duration,
+    last_dump: Duration,
+    previous: Tokens,
+    phantom: PhantomData<(EM, Z)>,
+}
+
+impl<EM, Z> UsesState for DumpTokensStage<EM, Z>
+where
+    EM: UsesState,
+{
+    type State = EM::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for DumpTokensStage<EM, Z>
+where
+    EM: UsesState,
+    E: UsesState<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+    Self::State: HasMetadata,
+{
+    #[inline]
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut Self::State,
+        _manager: &mut EM,
+    ) -> Result<(), Error> {
+        let cur = current_time();
+        if cur.saturating_sub(self.last_dump) < self.interval {
+            return Ok(());
+        }
+        self.last_dump = cur;
+        match state.metadata::<Tokens>() {
+            Ok(tokens) => self.dump(tokens),
+            Err(_) => self.dump(&Tokens::new()),
+        }
+    }
+
+    #[inline]
+    fn restart_progress_should_run(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_restart_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        // Not executing the target, so restart safety is not needed
+        Ok(())
+    }
+}
+
+impl<EM, Z> DumpTokensStage<EM, Z> {
+    /// Create a new [`DumpTokensStage`] writing the dictionary to `path` every [`DEFAULT_TOKENS_DUMP_INTERVAL`]
+    pub fn new<P>(path: P) -> Result<Self, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        Self::with_interval(path, DEFAULT_TOKENS_DUMP_INTERVAL)
+    }
+
+    /// Create a new [`DumpTokensStage`] writing the dictionary to `path` every `interval`
+    pub fn with_interval<P>(path: P, interval: Duration) -> Result<Self, Error>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        let previous = if path.is_file() {
+            Tokens::from_file(&path)?
+        } else {
+            Tokens::new()
+        };
+        Ok(Self {
+            path,
+            interval,
+            last_dump: Duration::ZERO,
+            previous,
+            phantom: PhantomData,
+        })
+    }
+
+    /// Writes `tokens`, merged with the tokens previously in the file, to the dictionary file
+    fn dump(&self, tokens: &Tokens) -> Result<(), Error> {
+        let mut previous: HashMap<&[u8], u64> = self
+            .previous
+            .iter()
+            .map(Vec::as_slice)
+            .zip(self.previous.hits().iter().copied())
+            .collect();
+
+        let mut merged = Tokens::new();
+        for (token, hits) in tokens.iter().zip(tokens.hits()) {
+            let old = previous.remove(token.as_slice()).unwrap_or(0);
+            merged.add_token_with_hits(token, old.max(*hits));
+        }
+        for token in &self.previous {
+            if let Some(hits) = previous.get(token.as_slice()) {
+                merged.add_token_with_hits(token, *hits);
+            }
+        }
+
+        let mut tmp_name = OsString::from(".");
+        tmp_name.push(self.path.file_name().unwrap_or_default());
+        tmp_name.push(".tmp");
+        let tmp = self.path.with_file_name(tmp_name);
+        merged.write_to_file(&tmp)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::DumpTokensStage;
+    use crate::mutators::Tokens;
+
+    #[test]
+    fn test_dump_tokens() {
+        let path = "test_dump_tokens.dict";
+        let _res = fs::remove_file(path);
+
+        let mut old = Tokens::new();
+        old.add_token_with_hits(b"old", 5);
+        old.add_token_with_hits(b"both", 7);
+        old.write_to_file(path).unwrap();
+
+        let stage: DumpTokensStage<(), ()> = DumpTokensStage::new(path).unwrap();
+        let mut tokens = Tokens::new();
+        tokens.add_token_with_hits(b"both", 2);
+        tokens.add_token_with_hits(b"new", 9);
+        stage.dump(&tokens).unwrap();
+
+        let read = Tokens::from_file(path).unwrap();
+        assert_eq!(
+            read.tokens(),
+            [b"new".to_vec(), b"both".to_vec(), b"old".to_vec()]
+        );
+        assert_eq!(read.hits(), [9, 7, 5]);
+        let _res = fs::remove_file(path);
+    }
+}
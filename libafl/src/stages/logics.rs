@@ -13,7 +13,10 @@ use crate::{
 pub struct NestedStageRestartHelper;
 
 impl NestedStageRestartHelper {
-    fn restart_progress_should_run<S, ST>(state: &mut S, _stage: &ST) -> Result<bool, Error>
+    pub(crate) fn restart_progress_should_run<S, ST>(
+        state: &mut S,
+        _stage: &ST,
+    ) -> Result<bool, Error>
     where
         S: HasNestedStageStatus,
     {
@@ -21,7 +24,7 @@ impl NestedStageRestartHelper {
         Ok(true)
     }
 
-    fn clear_restart_progress<S, ST>(state: &mut S, _stage: &ST) -> Result<(), Error>
+    pub(crate) fn clear_restart_progress<S, ST>(state: &mut S, _stage: &ST) -> Result<(), Error>
     where
         S: HasNestedStageStatus,
     {
@@ -7,12 +7,14 @@ Other stages may enrich [`crate::corpus::Testcase`]s with metadata.
 use alloc::{borrow::Cow, boxed::Box, vec::Vec};
 use core::{fmt, marker::PhantomData};
 
+pub use bandit::BanditStage;
 pub use calibrate::CalibrationStage;
 pub use colorization::*;
 #[cfg(feature = "std")]
 pub use concolic::ConcolicTracingStage;
 #[cfg(all(feature = "std", feature = "concolic_mutation"))]
 pub use concolic::SimpleConcolicMutationalStage;
+pub use distillation::{CorpusDistillationStage, StdCorpusDistillationStage};
 #[cfg(feature = "std")]
 pub use dump::*;
 pub use generalization::GeneralizationStage;
@@ -26,7 +28,7 @@ pub use logics::*;
 pub use mutational::{MutationalStage, StdMutationalStage};
 pub use power::{PowerMutationalStage, StdPowerMutationalStage};
 use serde::{Deserialize, Serialize};
-pub use stats::AflStatsStage;
+pub use stats::{AflStatsStage, MutatorStatsStage};
 #[cfg(feature = "unicode")]
 pub use string::*;
 #[cfg(feature = "std")]
@@ -34,9 +36,11 @@ pub use sync::*;
 pub use tmin::{
     MapEqualityFactory, MapEqualityFeedback, StdTMinMutationalStage, TMinMutationalStage,
 };
+pub use token_mining::TokenMiningStage;
 pub use tracing::{ShadowTracingStage, TracingStage};
 pub use tuneable::*;
 use tuple_list::NonEmptyTuple;
+pub use type_inference::TypeInferenceStage;
 
 use crate::{
     corpus::{CorpusId, HasCurrentCorpusId},
@@ -56,10 +60,12 @@ pub mod mutational;
 pub mod push;
 pub mod tmin;
 
+pub mod bandit;
 pub mod calibrate;
 pub mod colorization;
 #[cfg(feature = "std")]
 pub mod concolic;
+pub mod distillation;
 #[cfg(feature = "std")]
 pub mod dump;
 pub mod generalization;
@@ -72,8 +78,10 @@ pub mod stats;
 pub mod string;
 #[cfg(feature = "std")]
 pub mod sync;
+pub mod token_mining;
 pub mod tracing;
 pub mod tuneable;
+pub mod type_inference;
 
 /// A stage is one step in the fuzzing process.
 /// Multiple stages will be scheduled one by one for each input.
@@ -11,7 +11,7 @@ use crate::{
     fuzzer::Evaluator,
     inputs::Input,
     mark_feature_time,
-    mutators::{MultiMutator, MutationResult, Mutator},
+    mutators::{MultiMutator, MutationResult, Mutator, UndoLog},
     stages::{ExecutionCountRestartHelper, RetryRestartHelper, Stage},
     start_timer,
     state::{HasCorpus, HasCurrentTestcase, HasExecutions, HasRand, UsesState},
@@ -51,6 +51,15 @@ where
 
     /// Transform this instance back into the original input type
     fn try_transform_into(self, state: &S) -> Result<(I, Self::Post), Error>;
+
+    /// Borrow this instance as the original input type, if no transformation is needed.
+    ///
+    /// If this returns `Some`, [`MutationalStage::perform_mutational`] may mutate this instance in place,
+    /// execute it, and undo the mutations, without calling [`MutatedTransform::try_transform_into`].
+    #[inline]
+    fn as_input(&self) -> Option<&I> {
+        None
+    }
 }
 
 // reflexive definition
@@ -71,6 +80,11 @@ where
     fn try_transform_into(self, _state: &S) -> Result<(I, Self::Post), Error> {
         Ok((self, ()))
     }
+
+    #[inline]
+    fn as_input(&self) -> Option<&I> {
+        Some(self)
+    }
 }
 
 /// A Mutational stage is the stage in a fuzzing run that mutates inputs.
@@ -97,7 +111,10 @@ where
     /// Gets the number of executions this mutator already did since it got first called in this fuzz round.
     fn execs_since_progress_start(&mut self, state: &mut Self::State) -> Result<u64, Error>;
 
-    /// Runs this (mutational) stage for the given testcase
+    /// Runs this (mutational) stage for the given testcase.
+    ///
+    /// If the mutator can undo its mutations, see [`Mutator::can_undo`], and the input needs no transformation,
+    /// the input is mutated in place and restored after each execution, instead of cloned for each iteration.
     #[allow(clippy::cast_possible_wrap)] // more than i32 stages on 32 bit system - highly unlikely...
     fn perform_mutational(
         &mut self,
@@ -117,12 +134,42 @@ where
         let num = self.iterations(state)?;
         let mut testcase = state.current_testcase_mut()?;
 
-        let Ok(input) = I::try_transform_from(&mut testcase, state) else {
+        let Ok(mut input) = I::try_transform_from(&mut testcase, state) else {
             return Ok(());
         };
         drop(testcase);
         mark_feature_time!(state, PerfFeature::GetInputFromCorpus);
 
+        if self.mutator().can_undo() && input.as_input().is_some() {
+            let mut undo = UndoLog::new();
+            for _ in 0..num {
+                start_timer!(state);
+                let mutated = self
+                    .mutator_mut()
+                    .mutate_undoable(state, &mut input, &mut undo)?;
+                mark_feature_time!(state, PerfFeature::Mutate);
+
+                if mutated == MutationResult::Skipped {
+                    undo.revert(&mut input);
+                    continue;
+                }
+
+                // Time is measured directly the `evaluate_input_ref` function
+                let (_, corpus_idx) = fuzzer.evaluate_input_ref(
+                    state,
+                    executor,
+                    manager,
+                    input.as_input().unwrap(),
+                )?;
+                undo.revert(&mut input);
+
+                start_timer!(state);
+                self.mutator_mut().post_exec(state, corpus_idx)?;
+                mark_feature_time!(state, PerfFeature::MutatePostExec);
+            }
+            return Ok(());
+        }
+
         for _ in 0..num {
             let mut input = input.clone();
 
@@ -1,34 +1,31 @@
 //! Stage to compute/report AFL stats
 
 #[cfg(feature = "std")]
-use alloc::{borrow::Cow, string::ToString};
+use alloc::string::ToString;
+use alloc::{borrow::Cow, collections::BTreeMap, string::String, vec::Vec};
 use core::{marker::PhantomData, time::Duration};
 
+use hashbrown::HashSet;
 use libafl_bolts::current_time;
 #[cfg(feature = "std")]
 use serde_json::json;
 
 use crate::{
-    corpus::{Corpus, HasCurrentCorpusId},
-    events::EventFirer,
-    schedulers::minimizer::IsFavoredMetadata,
+    corpus::{Corpus, CorpusId, HasCurrentCorpusId},
+    events::{Event, EventFirer},
+    monitors::{AggregatorOps, UserStats, UserStatsValue},
+    mutators::{learning::MutationWeightsMetadata, lineage::MutatorStatsMetadata},
+    schedulers::{minimizer::TopRatedsMetadata, powersched::SchedulerMetadata},
     stages::Stage,
     state::{HasCorpus, HasImported, UsesState},
     Error, HasMetadata,
 };
-#[cfg(feature = "std")]
-use crate::{
-    events::Event,
-    monitors::{AggregatorOps, UserStats, UserStatsValue},
-};
 
 /// The [`AflStatsStage`] is a simple stage that computes and reports some stats.
 #[derive(Debug, Clone)]
 pub struct AflStatsStage<E, EM, Z> {
-    // the number of testcases that have been fuzzed
-    has_fuzzed_size: usize,
-    // the number of "favored" testcases
-    is_favored_size: usize,
+    // the testcases that have been fuzzed
+    fuzzed: HashSet<CorpusId>,
     // the number of testcases found by itself
     own_finds_size: usize,
     // the number of testcases imported by other fuzzers
@@ -69,35 +66,47 @@ where
         };
 
         // Report your stats every `STATS_REPORT_INTERVAL`
-        // compute pending, pending_favored, imported, own_finds
+        // compute pending, imported, own_finds
         {
             let testcase = state.corpus().get(corpus_idx)?.borrow();
             if testcase.scheduled_count() == 0 {
-                self.has_fuzzed_size += 1;
-                if testcase.has_metadata::<IsFavoredMetadata>() {
-                    self.is_favored_size += 1;
-                }
+                self.fuzzed.insert(corpus_idx);
             } else {
                 return Ok(());
             }
         }
 
         let corpus_size = state.corpus().count();
-        let pending_size = corpus_size - self.has_fuzzed_size;
-        let pend_favored_size = corpus_size - self.is_favored_size;
+        let pending_size = corpus_size.saturating_sub(self.fuzzed.len());
         self.imported_size = *state.imported();
         self.own_finds_size = corpus_size - self.imported_size;
 
         let cur = current_time();
 
         if cur.checked_sub(self.last_report_time).unwrap_or_default() > self.stats_report_interval {
+            // like in AFL++, the favored testcases are the top rated ones, which change with every new entry.
+            // Read them from the scheduler metadata, so the testcases are not loaded from the corpus.
+            let favored: HashSet<CorpusId> = state
+                .metadata_map()
+                .get::<TopRatedsMetadata>()
+                .map(|meta| meta.map().values().copied().collect())
+                .unwrap_or_default();
+            let favored_size = favored.len();
+            let pend_favored_size = favored.difference(&self.fuzzed).count();
+            let cycles_done = state
+                .metadata_map()
+                .get::<SchedulerMetadata>()
+                .map_or(0, SchedulerMetadata::queue_cycles);
+
             #[cfg(feature = "std")]
             {
                 let json = json!({
                         "pending":pending_size,
                         "pend_fav":pend_favored_size,
+                        "favored":favored_size,
                         "own_finds":self.own_finds_size,
                         "imported":self.imported_size,
+                        "cycles_done":cycles_done,
                 });
                 _manager.fire(
                     state,
@@ -113,11 +122,13 @@ where
             }
             #[cfg(not(feature = "std"))]
             log::info!(
-                "pending: {}, pend_favored: {}, own_finds: {}, imported: {}",
+                "pending: {}, pend_favored: {}, favored: {}, own_finds: {}, imported: {}, cycles_done: {}",
                 pending_size,
                 pend_favored_size,
+                favored_size,
                 self.own_finds_size,
-                self.imported_size
+                self.imported_size,
+                cycles_done
             );
             self.last_report_time = cur;
         }
@@ -154,8 +165,7 @@ impl<E, EM, Z> Default for AflStatsStage<E, EM, Z> {
     #[must_use]
     fn default() -> Self {
         Self {
-            has_fuzzed_size: 0,
-            is_favored_size: 0,
+            fuzzed: HashSet::new(),
             own_finds_size: 0,
             imported_size: 0,
             last_report_time: current_time(),
@@ -164,3 +174,134 @@ impl<E, EM, Z> Default for AflStatsStage<E, EM, Z> {
         }
     }
 }
+
+/// The [`MutatorStatsStage`] periodically reports how many testcases each mutation found, as recorded by a
+/// [`crate::mutators::LineageScheduledMutator`] in the [`MutatorStatsMetadata`].
+///
+/// Each mutation gets its own user stat, `mutator_finds_<name>`, summed up over all clients by the monitor.
+/// If a [`crate::mutators::LearningScheduledMutator`] is in use, its learned weights are reported as `mutator_weight_<name>`,
+/// averaged over all clients.
+#[derive(Debug, Clone)]
+pub struct MutatorStatsStage<E, EM, Z> {
+    // the found counts we reported last, to only report changes
+    reported: BTreeMap<String, u64>,
+    // the last time that we report all stats
+    last_report_time: Duration,
+    // the interval that we report all stats
+    stats_report_interval: Duration,
+
+    phantom: PhantomData<(E, EM, Z)>,
+}
+
+impl<E, EM, Z> UsesState for MutatorStatsStage<E, EM, Z>
+where
+    E: UsesState,
+{
+    type State = E::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for MutatorStatsStage<E, EM, Z>
+where
+    E: UsesState,
+    EM: EventFirer<State = Self::State>,
+    Z: UsesState<State = Self::State>,
+    Self::State: HasMetadata,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let cur = current_time();
+        if cur.checked_sub(self.last_report_time).unwrap_or_default() <= self.stats_report_interval
+        {
+            return Ok(());
+        }
+        self.last_report_time = cur;
+
+        let changed: Vec<(String, u64)> = state
+            .metadata_map()
+            .get::<MutatorStatsMetadata>()
+            .map(|meta| {
+                meta.stats
+                    .iter()
+                    .filter(|(name, stats)| self.reported.get(*name) != Some(&stats.found))
+                    .map(|(name, stats)| (name.clone(), stats.found))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        for (name, found) in changed {
+            manager.fire(
+                state,
+                Event::UpdateUserStats {
+                    name: Cow::from(format!("mutator_finds_{name}")),
+                    value: UserStats::new(UserStatsValue::Number(found), AggregatorOps::Sum),
+                    phantom: PhantomData,
+                },
+            )?;
+            self.reported.insert(name, found);
+        }
+
+        let weights: Vec<(String, f64)> = state
+            .metadata_map()
+            .get::<MutationWeightsMetadata>()
+            .map(|meta| {
+                meta.names
+                    .iter()
+                    .cloned()
+                    .zip(meta.weights.iter().copied())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        for (name, weight) in weights {
+            manager.fire(
+                state,
+                Event::UpdateUserStats {
+                    name: Cow::from(format!("mutator_weight_{name}")),
+                    value: UserStats::new(UserStatsValue::Float(weight), AggregatorOps::Avg),
+                    phantom: PhantomData,
+                },
+            )?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn restart_progress_should_run(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // Not running the target so we wont't crash/timeout and, hence, don't need to restore anything
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_restart_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        // Not running the target so we wont't crash/timeout and, hence, don't need to restore anything
+        Ok(())
+    }
+}
+
+impl<E, EM, Z> MutatorStatsStage<E, EM, Z> {
+    /// create a new instance of the [`MutatorStatsStage`], reporting every `interval`
+    #[must_use]
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            stats_report_interval: interval,
+            ..Default::default()
+        }
+    }
+}
+
+impl<E, EM, Z> Default for MutatorStatsStage<E, EM, Z> {
+    /// the default instance of the [`MutatorStatsStage`], reporting every 15 seconds
+    fn default() -> Self {
+        Self {
+            reported: BTreeMap::new(),
+            last_report_time: current_time(),
+            stats_report_interval: Duration::from_secs(15),
+            phantom: PhantomData,
+        }
+    }
+}
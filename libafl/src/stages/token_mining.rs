@@ -0,0 +1,512 @@
+//! The [`TokenMiningStage`] learns a dictionary from the corpus and the solutions.
+//!
+//! Candidates are byte strings recurring across the inputs, found with a suffix array,
+//! and words delimited by whitespace or punctuation in the printable parts of the inputs.
+//! A candidate becomes a token in the [`Tokens`] metadata once inserting it into corpus entries yields new corpus entries.
+//! The tokens can be exported in AFL dictionary format with [`Tokens::write_to_file`].
+
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use hashbrown::HashMap;
+use libafl_bolts::{impl_serdeany, rands::Rand};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    corpus::{Corpus, CorpusId},
+    inputs::{HasMutatorBytes, UsesInput},
+    mutators::Tokens,
+    random_corpus_id,
+    stages::Stage,
+    state::{HasCorpus, HasMaxSize, HasRand, HasSolutions, UsesState},
+    Error, Evaluator, HasMetadata,
+};
+
+/// The default minimum length of a mined token
+pub const DEFAULT_MIN_TOKEN_LEN: usize = 3;
+/// The default maximum length of a mined token
+pub const DEFAULT_MAX_TOKEN_LEN: usize = 32;
+
+/// A byte string recurring in the mined inputs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenCandidate {
+    /// The candidate token
+    pub token: Vec<u8>,
+    /// How often it occurs in all inputs
+    pub occurrences: usize,
+    /// How many inputs it occurs in
+    pub entries: usize,
+}
+
+/// Returns `true` for bytes that delimit words in printable input
+fn is_delimiter(byte: u8) -> bool {
+    byte.is_ascii_whitespace() || b"\"'`<>=,;:&?/\\()[]{}|".contains(&byte)
+}
+
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+/// Mines candidate tokens from `inputs`, between `min_len` and `max_len` bytes, occurring at least `min_occurrences` times.
+///
+/// Byte strings recurring anywhere are found with a suffix array, truncated to `max_len`.
+/// Only the longest of nested repeats that always occur together is kept, e.g., not `TTP/` next to `HTTP/`.
+/// Words in the printable parts of the inputs, delimited by whitespace or punctuation, are candidates as well.
+/// The candidates are sorted by how many bytes of the inputs they cover, most first.
+#[must_use]
+pub fn mine_tokens<'a, IT>(
+    inputs: IT,
+    min_len: usize,
+    max_len: usize,
+    min_occurrences: usize,
+) -> Vec<TokenCandidate>
+where
+    IT: IntoIterator<Item = &'a [u8]>,
+{
+    let min_len = min_len.max(1);
+    let max_len = max_len.max(min_len);
+    let inputs: Vec<&[u8]> = inputs.into_iter().collect();
+
+    // All suffixes long enough for a token, truncated to max_len, as (entry, start, end)
+    let mut suffixes: Vec<(usize, usize, usize)> = Vec::new();
+    for (entry, input) in inputs.iter().enumerate() {
+        for start in 0..(input.len() + 1).saturating_sub(min_len) {
+            suffixes.push((entry, start, (start + max_len).min(input.len())));
+        }
+    }
+    let suffix = |(entry, start, end): (usize, usize, usize)| &inputs[entry][start..end];
+    suffixes.sort_unstable_by(|a, b| suffix(*a).cmp(suffix(*b)));
+    // lcp[i] is the longest common prefix of the suffixes i - 1 and i
+    let lcp: Vec<usize> = (0..suffixes.len())
+        .map(|i| {
+            if i == 0 {
+                0
+            } else {
+                common_prefix_len(suffix(suffixes[i - 1]), suffix(suffixes[i]))
+            }
+        })
+        .collect();
+
+    // token -> (occurrences, entries)
+    let mut candidates: HashMap<&[u8], (usize, usize)> = HashMap::new();
+    for i in 1..suffixes.len() {
+        let len = lcp[i];
+        if len < min_len {
+            continue;
+        }
+        let (entry, start, _) = suffixes[i];
+        let token = &inputs[entry][start..start + len];
+        if candidates.contains_key(token) {
+            continue;
+        }
+        // The suffixes starting with the token are next to each other
+        let mut first = i - 1;
+        while first > 0 && lcp[first] >= len {
+            first -= 1;
+        }
+        let mut last = i;
+        while last + 1 < suffixes.len() && lcp[last + 1] >= len {
+            last += 1;
+        }
+        let mut entries: Vec<usize> = suffixes[first..=last].iter().map(|s| s.0).collect();
+        entries.sort_unstable();
+        entries.dedup();
+        candidates.insert(token, (last - first + 1, entries.len()));
+    }
+
+    // Drop the repeats that only occur as part of a longer one
+    let nested: Vec<&[u8]> = candidates
+        .iter()
+        .filter(|(token, _)| token.len() > min_len)
+        .filter_map(|(&token, counts)| {
+            let inner = &token[1..];
+            (candidates.get(inner) == Some(counts)).then_some(inner)
+        })
+        .collect();
+    for token in nested {
+        candidates.remove(token);
+    }
+
+    // token -> (occurrences, entries, last entry)
+    let mut words: HashMap<&[u8], (usize, usize, usize)> = HashMap::new();
+    for (entry, input) in inputs.iter().enumerate() {
+        for text in input.split(|byte| !(byte.is_ascii_graphic() || *byte == b' ')) {
+            for word in text.split(|byte| is_delimiter(*byte)) {
+                if word.len() < min_len
+                    || word.len() > max_len
+                    || !word.iter().any(u8::is_ascii_alphanumeric)
+                {
+                    continue;
+                }
+                let counts = words.entry(word).or_insert((0, 0, usize::MAX));
+                counts.0 += 1;
+                if counts.2 != entry {
+                    counts.1 += 1;
+                    counts.2 = entry;
+                }
+            }
+        }
+    }
+    for (word, (occurrences, entries, _)) in words {
+        candidates.entry(word).or_insert((occurrences, entries));
+    }
+
+    let mut mined: Vec<TokenCandidate> = candidates
+        .into_iter()
+        .filter(|(token, (occurrences, _))| {
+            *occurrences >= min_occurrences && token.iter().any(|byte| *byte != token[0])
+        })
+        .map(|(token, (occurrences, entries))| TokenCandidate {
+            token: token.to_vec(),
+            occurrences,
+            entries,
+        })
+        .collect();
+    mined.sort_unstable_by(|a, b| {
+        (b.occurrences * b.token.len())
+            .cmp(&(a.occurrences * a.token.len()))
+            .then_with(|| a.token.cmp(&b.token))
+    });
+    mined
+}
+
+/// The progress of the [`TokenMiningStage`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(
+    any(not(feature = "serdeany_autoreg"), miri),
+    allow(clippy::unsafe_derive_deserialize)
+)] // for SerdeAny
+pub struct TokenMiningMetadata {
+    /// The number of corpus entries and solutions when they were mined last
+    pub mined_at: usize,
+    /// The newest corpus entry when the corpus was mined last
+    pub newest_mined: Option<CorpusId>,
+    /// The next older corpus entry to mine, where mining the older entries stopped last
+    pub cursor: Option<CorpusId>,
+    /// Each candidate tried so far, with the number of its trial insertions that were added to the corpus
+    pub scores: HashMap<Vec<u8>, u64>,
+}
+
+impl_serdeany!(TokenMiningMetadata);
+
+/// Clones the input of the corpus entry `id` into `inputs`, returning its length
+fn push_input<C>(corpus: &C, id: CorpusId, inputs: &mut Vec<C::Input>) -> Result<usize, Error>
+where
+    C: Corpus,
+    C::Input: HasMutatorBytes,
+{
+    let input = corpus.cloned_input_for_id(id)?;
+    let len = input.bytes().len();
+    inputs.push(input);
+    Ok(len)
+}
+
+/// Picks about `max_bytes` of corpus entries to mine, returning their inputs and the cursor for the next time.
+///
+/// The entries newer than `newest_mined` come first, newest first.
+/// The remaining budget goes to the older entries, continuing at the `cursor`,
+/// and starting over from the newest once all of them were mined.
+fn corpus_inputs_to_mine<C>(
+    corpus: &C,
+    newest_mined: Option<CorpusId>,
+    cursor: Option<CorpusId>,
+    max_bytes: usize,
+) -> Result<(Vec<C::Input>, Option<CorpusId>), Error>
+where
+    C: Corpus,
+    C::Input: HasMutatorBytes,
+{
+    let mut inputs = Vec::new();
+    let mut bytes = 0;
+
+    let mut id = corpus.last();
+    while let Some(current) = id {
+        if bytes >= max_bytes || newest_mined.is_some_and(|newest| current <= newest) {
+            break;
+        }
+        bytes += push_input(corpus, current, &mut inputs)?;
+        id = corpus.prev(current);
+    }
+
+    if bytes < max_bytes {
+        // The cursor may be gone, e.g. if the entry was removed in the meantime
+        id = cursor.filter(|cursor| corpus.get(*cursor).is_ok()).or(id);
+        while let Some(current) = id {
+            if bytes >= max_bytes {
+                break;
+            }
+            bytes += push_input(corpus, current, &mut inputs)?;
+            id = corpus.prev(current);
+        }
+    }
+
+    Ok((inputs, id))
+}
+
+/// A stage growing the [`Tokens`] with tokens mined from the corpus and the solutions, see [`mine_tokens`].
+///
+/// It mines whenever enough new corpus entries or solutions were found since it mined last,
+/// at most about `max_bytes` of inputs at once: the new corpus entries first, then older ones where it stopped the last time,
+/// and the newest solutions with what is left.
+/// Each new candidate is inserted into a few random corpus entries,
+/// and added to the [`Tokens`] if any of those is interesting enough for the corpus.
+#[derive(Debug, Clone)]
+pub struct TokenMiningStage<Z> {
+    min_len: usize,
+    max_len: usize,
+    min_occurrences: usize,
+    max_candidates: usize,
+    trials: usize,
+    mine_every: usize,
+    max_bytes: usize,
+    phantom: PhantomData<Z>,
+}
+
+impl<Z> UsesState for TokenMiningStage<Z>
+where
+    Z: UsesState,
+{
+    type State = Z::State;
+}
+
+impl<E, EM, Z> Stage<E, EM, Z> for TokenMiningStage<Z>
+where
+    E: UsesState<State = Self::State>,
+    EM: UsesState<State = Self::State>,
+    Z: Evaluator<E, EM>,
+    <Self::State as UsesInput>::Input: HasMutatorBytes,
+    Self::State: HasCorpus + HasSolutions + HasRand + HasMaxSize + HasMetadata,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut Self::State,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let found = state.corpus().count() + state.solutions().count();
+        let mined_at = state
+            .metadata_or_insert_with(TokenMiningMetadata::default)
+            .mined_at;
+        if found < mined_at + self.mine_every || state.corpus().count() == 0 {
+            return Ok(());
+        }
+
+        let meta = state.metadata::<TokenMiningMetadata>()?;
+        let (mut inputs, cursor) = corpus_inputs_to_mine(
+            state.corpus(),
+            meta.newest_mined,
+            meta.cursor,
+            self.max_bytes,
+        )?;
+        let newest = state.corpus().last();
+        let meta = state.metadata_mut::<TokenMiningMetadata>()?;
+        meta.newest_mined = newest;
+        meta.cursor = cursor;
+
+        let mut bytes: usize = inputs.iter().map(|input| input.bytes().len()).sum();
+        let mut id = state.solutions().last();
+        while let Some(current) = id {
+            if bytes >= self.max_bytes {
+                break;
+            }
+            bytes += push_input(state.solutions(), current, &mut inputs)?;
+            id = state.solutions().prev(current);
+        }
+        let mined = mine_tokens(
+            inputs.iter().map(HasMutatorBytes::bytes),
+            self.min_len,
+            self.max_len,
+            self.min_occurrences,
+        );
+        drop(inputs);
+
+        let candidates: Vec<Vec<u8>> = {
+            let known = state.metadata::<Tokens>().ok();
+            let meta = state.metadata::<TokenMiningMetadata>()?;
+            mined
+                .into_iter()
+                .map(|candidate| candidate.token)
+                .filter(|token| {
+                    !meta.scores.contains_key(token)
+                        && !known.is_some_and(|tokens| tokens.contains_token(token))
+                })
+                .take(self.max_candidates)
+                .collect()
+        };
+
+        // Mark all candidates as tried before running the target, so we don't try them again after a crash
+        let meta = state.metadata_mut::<TokenMiningMetadata>()?;
+        meta.mined_at = found;
+        for token in &candidates {
+            meta.scores.insert(token.clone(), 0);
+        }
+
+        for token in candidates {
+            let mut score = 0;
+            for _ in 0..self.trials {
+                let id = random_corpus_id!(state.corpus(), state.rand_mut());
+                let mut input = state.corpus().cloned_input_for_id(id)?;
+                if input.bytes().len() + token.len() > state.max_size() {
+                    continue;
+                }
+                let pos = state.rand_mut().below(input.bytes().len() + 1);
+                input.splice(pos..pos, token.iter().copied());
+                let (_, corpus_idx) = fuzzer.evaluate_input(state, executor, manager, input)?;
+                if corpus_idx.is_some() {
+                    score += 1;
+                }
+            }
+            if score > 0 {
+                state
+                    .metadata_or_insert_with(Tokens::default)
+                    .add_token(&token);
+            }
+            state
+                .metadata_mut::<TokenMiningMetadata>()?
+                .scores
+                .insert(token, score);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn restart_progress_should_run(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // The candidates are marked as tried before running the target, a restart won't try them again
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_restart_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<Z> TokenMiningStage<Z> {
+    /// Creates a new [`TokenMiningStage`], mining after every 100 new corpus entries or solutions,
+    /// trying up to 16 new candidates with 4 insertions each
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            min_len: DEFAULT_MIN_TOKEN_LEN,
+            max_len: DEFAULT_MAX_TOKEN_LEN,
+            min_occurrences: 2,
+            max_candidates: 16,
+            trials: 4,
+            mine_every: 100,
+            max_bytes: 1 << 20,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Mines after every `mine_every` new corpus entries or solutions
+    #[must_use]
+    pub fn with_mine_every(mut self, mine_every: usize) -> Self {
+        self.mine_every = mine_every;
+        self
+    }
+
+    /// Mines tokens between `min_len` and `max_len` bytes, occurring at least `min_occurrences` times
+    #[must_use]
+    pub fn with_token_len(
+        mut self,
+        min_len: usize,
+        max_len: usize,
+        min_occurrences: usize,
+    ) -> Self {
+        self.min_len = min_len;
+        self.max_len = max_len;
+        self.min_occurrences = min_occurrences;
+        self
+    }
+
+    /// Tries up to `max_candidates` new candidates each time, inserting each into `trials` random corpus entries
+    #[must_use]
+    pub fn with_candidates(mut self, max_candidates: usize, trials: usize) -> Self {
+        self.max_candidates = max_candidates;
+        self.trials = trials;
+        self
+    }
+
+    /// Mines at most about `max_bytes` of inputs at once
+    #[must_use]
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+}
+
+impl<Z> Default for TokenMiningStage<Z> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{vec, vec::Vec};
+
+    use super::{corpus_inputs_to_mine, mine_tokens};
+    use crate::{
+        corpus::{Corpus, InMemoryCorpus, Testcase},
+        inputs::{BytesInput, HasMutatorBytes},
+    };
+
+    #[test]
+    fn test_corpus_inputs_to_mine() {
+        let mut corpus = InMemoryCorpus::<BytesInput>::new();
+        for i in 0..4 {
+            corpus
+                .add(Testcase::new(BytesInput::new(vec![i; 4])))
+                .unwrap();
+        }
+        let (mut newest_mined, mut cursor) = (None, None);
+        let mut mine = |corpus: &InMemoryCorpus<BytesInput>| -> Vec<u8> {
+            let (inputs, next_cursor) =
+                corpus_inputs_to_mine(corpus, newest_mined, cursor, 8).unwrap();
+            newest_mined = corpus.last();
+            cursor = next_cursor;
+            inputs.iter().map(|input| input.bytes()[0]).collect()
+        };
+
+        // Newest first
+        assert_eq!(mine(&corpus), [3, 2]);
+        // New entries first, then the older ones where it stopped
+        corpus
+            .add(Testcase::new(BytesInput::new(vec![4; 4])))
+            .unwrap();
+        assert_eq!(mine(&corpus), [4, 1]);
+        assert_eq!(mine(&corpus), [0]);
+        // All were mined, start over from the newest
+        assert_eq!(mine(&corpus), [4, 3]);
+    }
+
+    #[test]
+    fn test_mine_tokens() {
+        let inputs: [&[u8]; 3] = [
+            b"GET /index.html HTTP/1.1\r\nHost: a\r\n",
+            b"POST /form HTTP/1.1\r\nHost: b\r\n\x00\x00\x00\x00",
+            b"\x7fELF\xde\xad\xbe\xefGET \x00\x00\x00\x00",
+        ];
+        let mined = mine_tokens(inputs, 3, 32, 2);
+        let tokens: Vec<&[u8]> = mined.iter().map(|c| c.token.as_slice()).collect();
+
+        // Recurring n-grams, only the longest of nested ones
+        assert!(tokens.contains(&&b" HTTP/1.1\r\nHost: "[..]));
+        assert!(!tokens.contains(&&b"TTP/1.1\r\nHost: "[..]));
+        // Delimited words
+        assert!(tokens.contains(&&b"HTTP"[..]));
+        assert!(tokens.contains(&&b"Host"[..]));
+        // Occurring once, or no information
+        assert!(!tokens.contains(&&b"index"[..]));
+        assert!(!tokens.iter().any(|token| token.iter().all(|b| *b == 0)));
+
+        let http = mined
+            .iter()
+            .find(|c| c.token == b" HTTP/1.1\r\nHost: ")
+            .unwrap();
+        assert_eq!(http.occurrences, 2);
+        assert_eq!(http.entries, 2);
+        assert_eq!(mined[0].token, b" HTTP/1.1\r\nHost: ");
+    }
+}
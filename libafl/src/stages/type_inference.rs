@@ -0,0 +1,336 @@
+//! A stage inferring typed fields of the current testcase, for the mutators in [`crate::mutators::typed`]
+
+use alloc::vec::Vec;
+use core::{marker::PhantomData, ops::Range};
+
+use libafl_bolts::Error;
+
+use crate::{
+    inputs::{schema::Endian, HasMutatorBytes},
+    mutators::{
+        typed::{read_decimal, read_uint},
+        FieldKind, TypedField, TypedFieldsMetadata,
+    },
+    observers::{AFLppCmpValuesMetadata, CmpValues, CmpValuesMetadata},
+    stages::{Stage, TaintMetadata},
+    state::{HasCorpus, HasCurrentTestcase, State, UsesState},
+    HasMetadata,
+};
+
+/// The longest ASCII decimal number considered, the length of `i64::MIN`
+const MAX_DECIMAL_LEN: usize = 20;
+
+/// Returns if `val` looks like a float written by a human or a program, rather than random bytes:
+/// a normal magnitude, and at most four significant decimal digits.
+fn is_plausible_float(val: f64, tolerance: f64) -> bool {
+    let abs = libm::fabs(val);
+    if !(1e-6..=1e9).contains(&abs) {
+        return false;
+    }
+    let digits = abs / libm::pow(10.0, libm::floor(libm::log10(abs)) - 3.0);
+    libm::fabs(digits - libm::round(digits)) <= tolerance * digits
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn is_plausible_f32(bits: u64) -> bool {
+    is_plausible_float(f64::from(f32::from_bits(bits as u32)), 1e-6)
+}
+
+fn is_plausible_f64(bits: u64) -> bool {
+    is_plausible_float(f64::from_bits(bits), 1e-12)
+}
+
+/// Encodes the lower `len` bytes of `val`
+fn encode(val: u64, len: usize, endian: Endian) -> Vec<u8> {
+    match endian {
+        Endian::Little => val.to_le_bytes()[..len].to_vec(),
+        Endian::Big => val.to_be_bytes()[8 - len..].to_vec(),
+    }
+}
+
+/// Infers the typed fields of `input`.
+///
+/// - Integers are the operands of the numeric comparisons in `cmps`, found verbatim in the input, in either byte order.
+///   Operands that look like floats, see [`FieldKind::F32`], are taken as floats instead.
+/// - Decimals are ASCII numbers in the input equal to an operand.
+///
+/// If colorization told which bytes of the input change the coverage, `influencing`,
+/// fields outside of them are dropped. Then decimals and plausible floats anywhere in those bytes are fields, too.
+#[must_use]
+#[allow(clippy::cast_sign_loss)]
+pub fn infer_typed_fields(
+    input: &[u8],
+    cmps: &[CmpValues],
+    influencing: Option<&[Range<usize>]>,
+) -> Vec<TypedField> {
+    let overlaps = |range: Range<usize>| {
+        influencing.map_or(true, |ranges| {
+            ranges
+                .iter()
+                .any(|r| r.start < range.end && range.start < r.end)
+        })
+    };
+    let mut fields = Vec::new();
+
+    // the operand values, masked to their width
+    let mut operands: Vec<(u64, u64)> = Vec::new();
+    for cmp in cmps {
+        let (len, vals) = match cmp {
+            CmpValues::U8((a, b)) => {
+                operands.push((u64::from(*a), 0xff));
+                operands.push((u64::from(*b), 0xff));
+                continue;
+            }
+            CmpValues::U16((a, b)) => (2, [u64::from(*a), u64::from(*b)]),
+            CmpValues::U32((a, b)) => (4, [u64::from(*a), u64::from(*b)]),
+            CmpValues::U64((a, b)) => (8, [*a, *b]),
+            CmpValues::Bytes(_) => continue,
+        };
+        for val in vals {
+            operands.push((val, u64::MAX >> (64 - len * 8)));
+            let bytes = val.to_le_bytes();
+            // 0, -1 and the like are everywhere
+            if bytes[..len].iter().all(|b| *b == bytes[0]) {
+                continue;
+            }
+            let kind = match len {
+                2 => FieldKind::U16,
+                4 if is_plausible_f32(val) => FieldKind::F32,
+                4 => FieldKind::U32,
+                8 if is_plausible_f64(val) => FieldKind::F64,
+                _ => FieldKind::U64,
+            };
+            for endian in [Endian::Little, Endian::Big] {
+                let pattern = encode(val, len, endian);
+                for (offset, window) in input.windows(len).enumerate() {
+                    if window == pattern && overlaps(offset..offset + len) {
+                        fields.push(TypedField::new(offset, kind, endian));
+                    }
+                }
+            }
+        }
+    }
+
+    // ASCII decimals, with an optional leading minus
+    let mut idx = 0;
+    while idx < input.len() {
+        let start = idx;
+        if input[idx] == b'-' {
+            idx += 1;
+        }
+        let digits_start = idx;
+        while idx < input.len() && input[idx].is_ascii_digit() {
+            idx += 1;
+        }
+        if idx == digits_start {
+            idx = start + 1;
+            continue;
+        }
+        let len = idx - start;
+        if len > MAX_DECIMAL_LEN {
+            continue;
+        }
+        let Some(val) = read_decimal(&input[start..idx]) else {
+            continue;
+        };
+        let compared = operands.iter().any(|(op, mask)| val as u64 & mask == *op);
+        if (compared || influencing.is_some()) && overlaps(start..idx) {
+            fields.push(TypedField::decimal(start, len));
+        }
+    }
+
+    // floats among the bytes changing the coverage
+    for range in influencing.unwrap_or_default() {
+        let end = range.end.min(input.len());
+        let mut offset = range.start;
+        'windows: while offset < end {
+            for (kind, len) in [(FieldKind::F64, 8), (FieldKind::F32, 4)] {
+                if offset + len > end {
+                    continue;
+                }
+                for endian in [Endian::Little, Endian::Big] {
+                    let bits = read_uint(&input[offset..offset + len], endian);
+                    let plausible = if len == 8 {
+                        is_plausible_f64(bits)
+                    } else {
+                        is_plausible_f32(bits)
+                    };
+                    if plausible {
+                        fields.push(TypedField::new(offset, kind, endian));
+                        offset += len;
+                        continue 'windows;
+                    }
+                }
+            }
+            offset += 1;
+        }
+    }
+
+    fields.sort_unstable();
+    fields.dedup();
+    fields
+}
+
+/// The bytes of an input of `len` bytes that are not in the `ranges` colorization was free to change
+fn complement(len: usize, ranges: &[Range<usize>]) -> Vec<Range<usize>> {
+    let mut sorted = ranges.to_vec();
+    sorted.sort_unstable_by_key(|range| range.start);
+    let mut res = Vec::new();
+    let mut pos = 0;
+    for range in sorted {
+        if range.start > pos {
+            res.push(pos..range.start.min(len));
+        }
+        pos = pos.max(range.end);
+    }
+    if pos < len {
+        res.push(pos..len);
+    }
+    res
+}
+
+/// Infers the typed fields of the current testcase and stores them in a [`TypedFieldsMetadata`] of the testcase,
+/// see [`infer_typed_fields`].
+///
+/// Put it after a cmplog tracing stage, e.g., [`crate::stages::TracingStage`], so that the [`CmpValuesMetadata`]
+/// or [`AFLppCmpValuesMetadata`] belong to the current testcase, and optionally after a
+/// [`crate::stages::ColorizationStage`]. The fields are inferred once per testcase.
+#[derive(Debug)]
+pub struct TypeInferenceStage<S> {
+    phantom: PhantomData<S>,
+}
+
+impl<S> Default for TypeInferenceStage<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> TypeInferenceStage<S> {
+    /// Create a new [`TypeInferenceStage`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<S> UsesState for TypeInferenceStage<S>
+where
+    S: State,
+{
+    type State = S;
+}
+
+impl<S, E, EM, Z> Stage<E, EM, Z> for TypeInferenceStage<S>
+where
+    S: HasCorpus + HasMetadata + State,
+    S::Input: HasMutatorBytes,
+    E: UsesState<State = S>,
+    EM: UsesState<State = S>,
+    Z: UsesState<State = S>,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut Self::State,
+        _manager: &mut EM,
+    ) -> Result<(), Error> {
+        if state
+            .current_testcase()?
+            .has_metadata::<TypedFieldsMetadata>()
+        {
+            return Ok(()); // skip recompute
+        }
+
+        let input = state.current_input_cloned()?;
+        let bytes = input.bytes();
+
+        let mut cmps: Vec<CmpValues> = Vec::new();
+        if let Ok(meta) = state.metadata::<CmpValuesMetadata>() {
+            cmps.extend_from_slice(&meta.list);
+        }
+        if let Ok(meta) = state.metadata::<AFLppCmpValuesMetadata>() {
+            cmps.extend(meta.orig_cmpvals().values().flatten().cloned());
+        }
+        // the taint only applies if it was computed for this input
+        let influencing = state
+            .metadata::<TaintMetadata>()
+            .ok()
+            .filter(|meta| meta.input_vec().len() == bytes.len())
+            .map(|meta| complement(bytes.len(), meta.ranges()));
+
+        let fields = infer_typed_fields(bytes, &cmps, influencing.as_deref());
+        state
+            .current_testcase_mut()?
+            .add_metadata(TypedFieldsMetadata::new(fields));
+        Ok(())
+    }
+
+    #[inline]
+    fn restart_progress_should_run(&mut self, _state: &mut Self::State) -> Result<bool, Error> {
+        // Stage does not run the target. No reset helper needed.
+        Ok(true)
+    }
+
+    #[inline]
+    fn clear_restart_progress(&mut self, _state: &mut Self::State) -> Result<(), Error> {
+        // Stage does not run the target. No reset helper needed.
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::{complement, infer_typed_fields};
+    use crate::{
+        inputs::schema::Endian,
+        mutators::{FieldKind, TypedField},
+        observers::CmpValues,
+    };
+
+    #[test]
+    fn test_infer_typed_fields() {
+        let mut input = Vec::new();
+        input.extend_from_slice(b"HDR");
+        input.extend_from_slice(&0x1234_u16.to_be_bytes());
+        input.extend_from_slice(&0xdead_beef_u32.to_le_bytes());
+        input.extend_from_slice(&1.5_f32.to_le_bytes());
+        input.extend_from_slice(b"w=640&h=480");
+        let cmps = [
+            CmpValues::U16((0x1234, 0x1000)),
+            CmpValues::U32((0xdead_beef, 0)),
+            CmpValues::U32((1.5_f32.to_bits(), 0)),
+            CmpValues::U32((640, 1024)),
+            CmpValues::U8((0, 0xff)),
+        ];
+
+        let fields = infer_typed_fields(&input, &cmps, None);
+        assert_eq!(
+            fields,
+            [
+                TypedField::new(3, FieldKind::U16, Endian::Big),
+                TypedField::new(5, FieldKind::U32, Endian::Little),
+                TypedField::new(9, FieldKind::F32, Endian::Little),
+                TypedField::decimal(15, 3),
+            ]
+        );
+
+        // With colorization, only the bytes changing the coverage count, but all decimals and floats in them
+        let influencing = complement(input.len(), &[0..5, 13..15]);
+        assert_eq!(influencing, [5..13, 15..input.len()]);
+        let fields = infer_typed_fields(&input, &[], Some(&influencing));
+        assert_eq!(
+            fields,
+            [
+                TypedField::new(9, FieldKind::F32, Endian::Little),
+                TypedField::decimal(15, 3),
+                TypedField::decimal(21, 3),
+            ]
+        );
+    }
+}
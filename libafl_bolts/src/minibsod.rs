@@ -797,6 +797,64 @@ fn write_crash<W: Write>(
     Ok(())
 }
 
+/// Returns the program counter at which the crash occurred, if it can be read from this `ucontext` on this platform.
+///
+/// This is the same address [`generate_minibsod`] prints as `Received signal .. at ..`,
+/// and can be used to group crashes by their faulting instruction.
+#[cfg(unix)]
+#[must_use]
+#[allow(clippy::cast_sign_loss, clippy::unnecessary_cast)]
+pub fn fault_pc(ucontext: &ucontext_t) -> Option<u64> {
+    #[cfg(all(
+        any(
+            target_os = "linux",
+            target_os = "android",
+            target_os = "solaris",
+            target_os = "illumos"
+        ),
+        target_arch = "x86_64"
+    ))]
+    return Some(ucontext.uc_mcontext.gregs[libc::REG_RIP as usize] as u64);
+
+    #[cfg(all(any(target_os = "linux", target_os = "android"), target_arch = "x86"))]
+    return Some(u64::from(
+        ucontext.uc_mcontext.gregs[libc::REG_EIP as usize] as u32,
+    ));
+
+    #[cfg(all(
+        any(target_os = "linux", target_os = "android"),
+        target_arch = "aarch64"
+    ))]
+    return Some(ucontext.uc_mcontext.pc);
+
+    #[cfg(all(target_os = "linux", target_arch = "arm"))]
+    return Some(u64::from(ucontext.uc_mcontext.arm_pc as u32));
+
+    #[cfg(all(target_os = "freebsd", target_arch = "aarch64"))]
+    return Some(ucontext.uc_mcontext.mc_gpregs.gp_elr as u64);
+
+    #[cfg(all(target_vendor = "apple", target_arch = "aarch64"))]
+    return Some(unsafe { (*ucontext.uc_mcontext).__ss.__pc });
+
+    #[cfg(all(target_vendor = "apple", target_arch = "x86_64"))]
+    return Some(unsafe { (*ucontext.uc_mcontext).__ss.__rip });
+
+    #[cfg(all(
+        any(target_os = "freebsd", target_os = "dragonfly"),
+        target_arch = "x86_64"
+    ))]
+    return Some(ucontext.uc_mcontext.mc_rip as u64);
+
+    #[cfg(all(target_os = "haiku", target_arch = "x86_64"))]
+    return Some(ucontext.uc_mcontext.rip as u64);
+
+    #[allow(unreachable_code)]
+    {
+        let _ = ucontext;
+        None
+    }
+}
+
 #[cfg(windows)]
 fn write_crash<W: Write>(
     writer: &mut BufWriter<W>,
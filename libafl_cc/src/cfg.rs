@@ -290,6 +290,11 @@ where
         self.func_to_entry_bb.get_mut(func_name)
     }
 
+    /// Iterates over all edges in the control flow graph.
+    pub fn edges(&self) -> impl Iterator<Item = &CfgEdge<T>> {
+        self.edges.iter().flatten()
+    }
+
     /// Calculate shortest distance from start edge to all other edges
     /// in the function containing such ``start``.
     ///
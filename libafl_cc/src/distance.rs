@@ -0,0 +1,505 @@
+//! Distances of coverage map edges to target locations, for `AFLGo`-style directed fuzzing.
+//!
+//! Distances are computed at compile time, in two levels:
+//! - A function level distance on the call graph, collected by the [`crate::LLVMPasses::DumpCfg`] pass.
+//! - A basic block level distance on the [`ControlFlowGraph`] dumped by the ``AFLCoverage`` pass.
+//!   Blocks containing a target have distance 0, blocks calling a function that reaches a target get
+//!   [`FUNCTION_DISTANCE_FACTOR`] times the function level distance of the callee, and all other blocks
+//!   the harmonic mean of their distances over the paths to these blocks, as in `AFLGo`.
+//!
+//! Targets are edges of the coverage map, use [`resolve_source_locations`] to find them from `file:line` locations.
+//! An edge gets the distance of the basic block it enters.
+//! With multiple targets, the distances to each of them are combined by their harmonic mean, as in `AFLGo`.
+//!
+//! The result is written with [`write_distances`] and loaded at runtime by the `DistanceObserver` of `libafl`.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+    fmt::Write as _,
+    fs,
+    hash::BuildHasher,
+    path::Path,
+};
+
+use serde::Deserialize;
+
+use crate::{cfg::ControlFlowGraph, Error, HasWeight};
+
+/// The factor between a function level distance and a basic block level distance, `c` in the `AFLGo` paper.
+pub const FUNCTION_DISTANCE_FACTOR: f64 = 10.0;
+
+/// The parts of the JSON dumped by the [`crate::LLVMPasses::DumpCfg`] pass we are interested in.
+#[derive(Debug, Deserialize)]
+struct CfgDump {
+    /// function -> basic block -> called functions
+    #[serde(default)]
+    calls: HashMap<String, HashMap<String, Vec<String>>>,
+    /// function -> basic block -> successor basic blocks
+    #[serde(default)]
+    edges: HashMap<String, Vec<Option<Vec<usize>>>>,
+    /// function -> entry basic block
+    #[serde(default)]
+    entries: HashMap<String, usize>,
+    /// function -> basic block -> `file:line` source locations
+    #[serde(default)]
+    locations: HashMap<String, HashMap<String, Vec<String>>>,
+}
+
+/// The basic blocks of a function, numbered like the [`crate::LLVMPasses::DumpCfg`] pass does.
+#[derive(Debug, Clone, Default)]
+struct FunctionBlocks {
+    entry: usize,
+    /// basic block -> successor basic blocks
+    successors: Vec<Vec<usize>>,
+    /// basic block -> called functions
+    calls: HashMap<usize, Vec<String>>,
+    /// basic block -> `file:line` source locations
+    locations: HashMap<usize, Vec<String>>,
+}
+
+/// The call graph of a program and the basic blocks of its functions,
+/// built from the dumps of the [`crate::LLVMPasses::DumpCfg`] pass.
+#[derive(Debug, Clone, Default)]
+pub struct CallGraph {
+    /// caller -> callees
+    calls: HashMap<String, HashSet<String>>,
+    /// function -> its basic blocks
+    functions: HashMap<String, FunctionBlocks>,
+}
+
+impl CallGraph {
+    /// Creates an empty [`CallGraph`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a call from `func` to `callee`
+    pub fn add_call(&mut self, func: &str, callee: &str) {
+        self.calls
+            .entry(func.to_string())
+            .or_default()
+            .insert(callee.to_string());
+    }
+
+    /// Adds the calls of one module dump of the [`crate::LLVMPasses::DumpCfg`] pass
+    pub fn add_cfg_dump(&mut self, content: &str) -> Result<(), Error> {
+        let dump: CfgDump = serde_json::from_str(content)
+            .map_err(|e| Error::Unknown(format!("Invalid cfg dump: {e}")))?;
+        for (caller, blocks) in &dump.calls {
+            for callee in blocks.values().flatten() {
+                self.add_call(caller, callee);
+            }
+        }
+
+        let parse_block = |block: &str| {
+            block.parse::<usize>().map_err(|e| {
+                Error::Unknown(format!("Invalid basic block {block} in cfg dump: {e}"))
+            })
+        };
+        for (func, successors) in dump.edges {
+            let mut blocks = FunctionBlocks {
+                entry: dump.entries.get(&func).copied().unwrap_or_default(),
+                successors: successors
+                    .into_iter()
+                    .map(Option::unwrap_or_default)
+                    .collect(),
+                ..FunctionBlocks::default()
+            };
+            for (block, callees) in dump.calls.get(&func).into_iter().flatten() {
+                blocks.calls.insert(parse_block(block)?, callees.clone());
+            }
+            for (block, locations) in dump.locations.get(&func).into_iter().flatten() {
+                blocks
+                    .locations
+                    .insert(parse_block(block)?, locations.clone());
+            }
+            self.functions.insert(func, blocks);
+        }
+        Ok(())
+    }
+
+    /// Loads all `.cfg` module dumps the [`crate::LLVMPasses::DumpCfg`] pass wrote to `dir`, i.e., to `CFG_OUTPUT_PATH`
+    pub fn from_cfg_dir<P>(dir: P) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut call_graph = Self::new();
+        for entry in fs::read_dir(dir).map_err(Error::Io)? {
+            let path = entry.map_err(Error::Io)?.path();
+            if path.extension().is_some_and(|ext| ext == "cfg") {
+                call_graph.add_cfg_dump(&fs::read_to_string(&path).map_err(Error::Io)?)?;
+            }
+        }
+        Ok(call_graph)
+    }
+
+    /// The functions called by `caller`
+    pub fn callees(&self, caller: &str) -> impl Iterator<Item = &String> {
+        self.calls.get(caller).into_iter().flatten()
+    }
+
+    /// Calculates the harmonic mean of the call graph distances from each function to the `targets`.
+    ///
+    /// Target functions have distance 0, functions that can't reach any target are not in the returned map.
+    #[must_use]
+    pub fn function_distances(&self, targets: &[&str]) -> HashMap<String, f64> {
+        let mut callers: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (caller, callees) in &self.calls {
+            for callee in callees {
+                callers.entry(callee).or_default().push(caller);
+            }
+        }
+
+        let mut per_target = Vec::with_capacity(targets.len());
+        for target in targets {
+            // Unweighted, so a breadth first search backwards from the target will do
+            let mut distances: HashMap<&str, u32> = HashMap::new();
+            let mut to_visit = VecDeque::new();
+            distances.insert(target, 0);
+            to_visit.push_back(*target);
+            while let Some(func) = to_visit.pop_front() {
+                let distance = distances[func] + 1;
+                for caller in callers.get(func).into_iter().flatten() {
+                    if !distances.contains_key(caller) {
+                        distances.insert(caller, distance);
+                        to_visit.push_back(caller);
+                    }
+                }
+            }
+            per_target.push(distances);
+        }
+
+        let mut funcs: HashSet<&str> = HashSet::new();
+        for distances in &per_target {
+            funcs.extend(distances.keys());
+        }
+        funcs
+            .into_iter()
+            .map(|func| {
+                let distance = harmonic_mean(
+                    per_target
+                        .iter()
+                        .filter_map(|distances| distances.get(func))
+                        .map(|d| f64::from(*d)),
+                );
+                (func.to_string(), distance)
+            })
+            .collect()
+    }
+}
+
+/// Combines the distances to several targets like `AFLGo`, `(sum of d^-1)^-1`, which is 0 if any distance is 0.
+fn harmonic_mean<I>(distances: I) -> f64
+where
+    I: IntoIterator<Item = f64>,
+{
+    let mut sum = 0.0;
+    for distance in distances {
+        if distance == 0.0 {
+            return 0.0;
+        }
+        sum += 1.0 / distance;
+    }
+    1.0 / sum
+}
+
+/// Matches the basic blocks of the [`crate::LLVMPasses::DumpCfg`] dumps to the basic blocks of the `cfg`.
+///
+/// Both graphs are walked from the entry of each function, pairing the successors of matched blocks in order.
+/// Where the graphs differ, e.g. because the coverage pass split or skipped blocks, the walk does not go further.
+/// Returns the AFL location of each matched block, with its function and its number in the dump.
+fn match_blocks<'a, T>(
+    cfg: &ControlFlowGraph<T>,
+    call_graph: &'a CallGraph,
+) -> HashMap<usize, (&'a FunctionBlocks, usize)>
+where
+    T: HasWeight<T>,
+{
+    // Every block, including the entry blocks, is entered by an edge that knows its successors
+    let mut cfg_successors: HashMap<usize, &[usize]> = HashMap::new();
+    for edge in cfg.edges() {
+        cfg_successors.insert(edge.bottom_node_loc, &edge.successor_basic_blocks);
+    }
+
+    let mut matched = HashMap::new();
+    for (func, blocks) in &call_graph.functions {
+        let Some(entry) = cfg.get_entry(func) else {
+            continue;
+        };
+        let mut to_visit = vec![(blocks.entry, entry.node_loc)];
+        while let Some((block, loc)) = to_visit.pop() {
+            if matched.contains_key(&loc) {
+                continue;
+            }
+            matched.insert(loc, (blocks, block));
+            let (Some(successors), Some(cfg_successors)) =
+                (blocks.successors.get(block), cfg_successors.get(&loc))
+            else {
+                continue;
+            };
+            if successors.len() == cfg_successors.len() {
+                to_visit.extend(
+                    successors
+                        .iter()
+                        .copied()
+                        .zip(cfg_successors.iter().copied()),
+                );
+            }
+        }
+    }
+    matched
+}
+
+/// Splits a `file:line` location into the file name, without directories, and the line
+fn split_location(location: &str) -> Option<(&str, &str)> {
+    let (file, line) = location.rsplit_once(':')?;
+    let file = file.rsplit_once('/').map_or(file, |(_, name)| name);
+    Some((file, line))
+}
+
+/// Resolves source locations, given as `file:line`, to the edges entering the basic blocks with code from these lines.
+///
+/// Like `AFLGo`, only the file name is compared, not its directory.
+/// Lines in basic blocks that could not be matched to the `cfg` are not found.
+#[must_use]
+pub fn resolve_source_locations<T>(
+    cfg: &ControlFlowGraph<T>,
+    call_graph: &CallGraph,
+    locations: &[&str],
+) -> Vec<usize>
+where
+    T: HasWeight<T>,
+{
+    let wanted: HashSet<(&str, &str)> = locations
+        .iter()
+        .filter_map(|location| split_location(location))
+        .collect();
+    let matched = match_blocks(cfg, call_graph);
+
+    let mut edges: Vec<usize> = cfg
+        .edges()
+        .filter(|edge| {
+            matched
+                .get(&edge.bottom_node_loc)
+                .and_then(|(blocks, block)| blocks.locations.get(block))
+                .is_some_and(|block_locations| {
+                    block_locations
+                        .iter()
+                        .filter_map(|location| split_location(location))
+                        .any(|location| wanted.contains(&location))
+                })
+        })
+        .map(|edge| edge.xored_loc)
+        .collect();
+    edges.sort_unstable();
+    edges
+}
+
+/// The shortest distances from each basic block to `target`, going backwards over the `predecessors`
+fn block_distances_to(
+    predecessors: &HashMap<usize, Vec<(usize, u32)>>,
+    target: usize,
+) -> HashMap<usize, u32> {
+    let mut distances: HashMap<usize, u32> = HashMap::new();
+    let mut to_visit = BinaryHeap::new();
+    distances.insert(target, 0);
+    to_visit.push(Reverse((0, target)));
+    while let Some(Reverse((distance, block))) = to_visit.pop() {
+        if distances
+            .get(&block)
+            .is_some_and(|&current| distance > current)
+        {
+            continue;
+        }
+        for (predecessor, weight) in predecessors.get(&block).into_iter().flatten() {
+            let new_distance = distance + weight;
+            let is_shorter = distances
+                .get(predecessor)
+                .map_or(true, |&current| new_distance < current);
+            if is_shorter {
+                distances.insert(*predecessor, new_distance);
+                to_visit.push(Reverse((new_distance, *predecessor)));
+            }
+        }
+    }
+    distances
+}
+
+/// Calculates the distance of each edge of the `cfg` to the `targets`, given as indexes in the coverage map,
+/// e.g. from [`resolve_source_locations`].
+///
+/// Edges that can't reach any target are not in the returned map.
+#[must_use]
+pub fn calculate_target_distances<T>(
+    cfg: &ControlFlowGraph<T>,
+    call_graph: &CallGraph,
+    targets: &[usize],
+) -> HashMap<usize, f64>
+where
+    T: HasWeight<T>,
+{
+    // The basic blocks inside each function, leaving out the artificial block 0 all functions are entered from
+    let mut predecessors: HashMap<usize, Vec<(usize, u32)>> = HashMap::new();
+    for edge in cfg.edges() {
+        if edge.top_node_loc != 0 {
+            predecessors
+                .entry(edge.bottom_node_loc)
+                .or_default()
+                .push((edge.top_node_loc, edge.get_weight()));
+        }
+    }
+
+    // The blocks the distances are measured to: the target blocks, and the blocks calling towards a target
+    let mut anchors: HashMap<usize, f64> = HashMap::new();
+    let mut target_funcs = Vec::new();
+    for target in targets {
+        let Some(target_edge) = cfg.get_edge(*target) else {
+            continue;
+        };
+        if !target_funcs.contains(&target_edge.calling_func.as_str()) {
+            target_funcs.push(target_edge.calling_func.as_str());
+        }
+        anchors.insert(target_edge.bottom_node_loc, 0.0);
+    }
+
+    let func_distances = call_graph.function_distances(&target_funcs);
+    for (loc, (blocks, block)) in match_blocks(cfg, call_graph) {
+        if anchors.contains_key(&loc) {
+            continue;
+        }
+        let nearest_callee = blocks
+            .calls
+            .get(&block)
+            .into_iter()
+            .flatten()
+            .filter_map(|callee| func_distances.get(callee).copied())
+            .reduce(f64::min);
+        if let Some(func_distance) = nearest_callee {
+            // The call itself is one step, so calling a target function is not as good as reaching the target
+            anchors.insert(loc, FUNCTION_DISTANCE_FACTOR * (func_distance + 1.0));
+        }
+    }
+
+    let mut block_distances: HashMap<usize, Vec<f64>> = HashMap::new();
+    for (anchor, anchor_distance) in &anchors {
+        for (block, distance) in block_distances_to(&predecessors, *anchor) {
+            block_distances
+                .entry(block)
+                .or_default()
+                .push(f64::from(distance) + anchor_distance);
+        }
+    }
+
+    let mut result = HashMap::new();
+    for edge in cfg.edges() {
+        if let Some(distances) = block_distances.get(&edge.bottom_node_loc) {
+            result.insert(edge.xored_loc, harmonic_mean(distances.iter().copied()));
+        }
+    }
+    result
+}
+
+/// Writes the `distances` to `path`, one `index,distance` line per edge, sorted by index.
+pub fn write_distances<P, S>(path: P, distances: &HashMap<usize, f64, S>) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+    S: BuildHasher,
+{
+    let mut sorted: Vec<(&usize, &f64)> = distances.iter().collect();
+    sorted.sort_by_key(|(idx, _)| **idx);
+    let mut content = String::new();
+    for (idx, distance) in sorted {
+        writeln!(content, "{idx},{distance}").unwrap();
+    }
+    fs::write(path, content).map_err(Error::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cfg::{ControlFlowGraph, HasWeight},
+        distance::{
+            calculate_target_distances, resolve_source_locations, CallGraph,
+            FUNCTION_DISTANCE_FACTOR,
+        },
+    };
+
+    struct TestMetadata {}
+
+    fn assert_distance(distance: f64, expected: f64) {
+        assert!(
+            (distance - expected).abs() < f64::EPSILON,
+            "distance {distance}, expected {expected}"
+        );
+    }
+
+    impl HasWeight<TestMetadata> for TestMetadata {
+        fn compute(_metadata: Option<&TestMetadata>) -> u32 {
+            1
+        }
+    }
+
+    // main: 41864 -> 26911 -> 41925, 41864 -> 52706, 26911 -> 52706; helper: 50306 -> 19123
+    const TEST_GRAPH_STR: &str = "$$main+41864\n$$helper+50306\n%%helper+50306\n->19123\n%%main+41864\n->52706\n->26911\n%%main+52706\n%%main+26911\n->52706\n->41925\n";
+
+    // The same functions, numbered by the DumpCfg pass: 41864 is 0, 52706 is 1, 26911 is 2, 41925 is 3.
+    // main calls caller in 26911, which calls helper.
+    const TEST_BLOCKS_DUMP: &str = r#"{"calls":{"main":{"2":["caller"]},"caller":{"0":["helper"]}},"edges":{"main":[[1,2],[],[1,3],[]],"caller":[[]],"helper":[[1],[]]},"entries":{"main":0,"caller":0,"helper":0},"locations":{"main":{"2":["main.c:5"],"3":["main.c:7"]},"helper":{"1":["helper.c:3"]}}}"#;
+
+    const TEST_CFG_DUMP: &str = r#"{"calls":{"caller":{"0":["helper"]},"main":{"1":["caller","printf"]}},"entries":{"main":0}}"#;
+
+    #[test]
+    fn test_function_distances() {
+        let mut call_graph = CallGraph::new();
+        call_graph.add_cfg_dump(TEST_CFG_DUMP).unwrap();
+        assert!(call_graph.callees("main").any(|callee| callee == "printf"));
+
+        let distances = call_graph.function_distances(&["helper"]);
+        assert_distance(distances["helper"], 0.0);
+        assert_distance(distances["caller"], 1.0);
+        assert_distance(distances["main"], 2.0);
+        assert!(!distances.contains_key("printf"));
+
+        // Harmonic mean of 1 and 2
+        let distances = call_graph.function_distances(&["helper", "caller"]);
+        assert_distance(distances["main"], 1.0 / (1.0 + 1.0 / 2.0));
+    }
+
+    #[test]
+    fn test_target_distances() {
+        let cfg: ControlFlowGraph<TestMetadata> = ControlFlowGraph::from_content(TEST_GRAPH_STR);
+        let mut call_graph = CallGraph::new();
+        call_graph.add_cfg_dump(TEST_BLOCKS_DUMP).unwrap();
+
+        let target = (26911 >> 1) ^ 41925;
+        assert_eq!(
+            resolve_source_locations(&cfg, &call_graph, &["src/main.c:7"]),
+            vec![target]
+        );
+        let distances = calculate_target_distances(&cfg, &call_graph, &[target]);
+        assert_distance(distances[&target], 0.0);
+        assert_distance(distances[&((41864 >> 1) ^ 26911)], 1.0);
+        // The entry edge of main
+        assert_distance(distances[&41864], 2.0);
+        // Can't reach the target
+        assert!(!distances.contains_key(&((26911 >> 1) ^ 52706)));
+        assert!(!distances.contains_key(&((50306 >> 1) ^ 19123)));
+
+        // helper is two calls away from main, the call is in block 26911
+        let target = (50306 >> 1) ^ 19123;
+        assert_eq!(
+            resolve_source_locations(&cfg, &call_graph, &["helper.c:3"]),
+            vec![target]
+        );
+        let distances = calculate_target_distances(&cfg, &call_graph, &[target]);
+        assert_distance(distances[&target], 0.0);
+        let call_distance = FUNCTION_DISTANCE_FACTOR * 2.0;
+        assert_distance(distances[&((41864 >> 1) ^ 26911)], call_distance);
+        // The blocks of main are farther from the target the farther they are from the call
+        assert_distance(distances[&41864], call_distance + 1.0);
+        assert!(!distances.contains_key(&((26911 >> 1) ^ 41925)));
+        assert!(!distances.contains_key(&((41864 >> 1) ^ 52706)));
+    }
+}
@@ -52,6 +52,7 @@
 #include "llvm/IR/CFG.h"
 #include "llvm/IR/Verifier.h"
 #include "llvm/Support/Debug.h"
+#include "llvm/Support/Path.h"
 #include "llvm/Support/raw_ostream.h"
 #include "llvm/Transforms/Utils/BasicBlockUtils.h"
 #include "llvm/Analysis/LoopInfo.h"
@@ -97,6 +98,7 @@ class DumpCfgPass : public ModulePass {
   DenseMap<BasicBlock *, uint32_t>               bb_to_cur_loc;
   DenseMap<StringRef, BasicBlock *>              entry_bb;
   DenseMap<BasicBlock *, std::vector<StringRef>> calls_in_bb;
+  DenseMap<BasicBlock *, std::set<std::string>>  locations_in_bb;
 
  private:
   bool isLLVMIntrinsicFn(StringRef &n) {
@@ -153,6 +155,16 @@ bool DumpCfgPass::runOnModule(Module &M) {
       bb_to_cur_loc[&BB] = bb_cnt;
       bb_cnt++;
       for (auto &IN : BB) {
+        // The source lines of the block, as "file:line" like AFLGo, to resolve
+        // target locations
+        if (DILocation *Loc = IN.getDebugLoc()) {
+          if (Loc->getLine()) {
+            locations_in_bb[&BB].insert(
+                std::string(sys::path::filename(Loc->getFilename())) + ":" +
+                std::to_string(Loc->getLine()));
+          }
+        }
+
         CallBase *callBase = nullptr;
         if ((callBase = dyn_cast<CallBase>(&IN))) {
           auto F = callBase->getCalledFunction();
@@ -211,6 +223,19 @@ bool DumpCfgPass::runOnModule(Module &M) {
     }
   }
 
+  for (auto record = locations_in_bb.begin(); record != locations_in_bb.end();
+       record++) {
+    auto        current_bb = record->getFirst();
+    auto        loc = bb_to_cur_loc[current_bb];
+    Function   *calling_func = current_bb->getParent();
+    std::string func_name = std::string("");
+
+    if (calling_func) { func_name = std::string(calling_func->getName()); }
+
+    cfg["locations"][func_name][std::to_string(loc)] = std::vector<std::string>(
+        record->getSecond().begin(), record->getSecond().end());
+  }
+
   for (auto record = entry_bb.begin(); record != entry_bb.end(); record++) {
     cfg["entries"][std::string(record->getFirst())] =
         bb_to_cur_loc[record->getSecond()];
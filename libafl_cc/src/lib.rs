@@ -64,6 +64,10 @@ pub mod cfg;
 pub use cfg::{CfgEdge, ControlFlowGraph, EntryBasicBlockInfo, HasWeight};
 pub mod clang;
 pub use clang::{ClangWrapper, LLVMPasses};
+pub mod distance;
+pub use distance::{
+    calculate_target_distances, resolve_source_locations, write_distances, CallGraph,
+};
 pub mod libtool;
 pub use libtool::LibtoolWrapper;
 
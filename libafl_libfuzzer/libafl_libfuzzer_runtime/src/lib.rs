@@ -160,7 +160,7 @@ macro_rules! fuzz_with {
             },
             observers::{stacktrace::BacktraceObserver, TimeObserver, CanTrack},
             schedulers::{
-                IndexesLenTimeMinimizerScheduler, powersched::PowerSchedule, PowerQueueScheduler,
+                EntropicScheduler, SchedulerMetadata,
             },
             stages::{
                 CalibrationStage, GeneralizationStage, IfStage, StdMutationalStage,
@@ -401,8 +401,12 @@ macro_rules! fuzz_with {
             );
             let grimoire = IfStage::new(|_, _, _, _| Ok(grimoire.into()), (StdMutationalStage::transforming(grimoire_mutator), ()));
 
-            // A minimization+queue policy to get testcasess from the corpus
-            let scheduler = IndexesLenTimeMinimizerScheduler::new(&edges_observer, PowerQueueScheduler::new(&mut state, &edges_observer, PowerSchedule::FAST));
+            // libFuzzer's default entropic policy to get testcases from the corpus, based on the indexes tracked by the edges observer
+            let scheduler = EntropicScheduler::new(&mut state, &edges_observer);
+            // The power stages compute their energy from the scheduler metadata, which the calibration stage fills in
+            if !state.has_metadata::<SchedulerMetadata>() {
+                state.add_metadata(SchedulerMetadata::new(None));
+            }
 
             // A fuzzer with feedbacks and a corpus scheduler
             let mut fuzzer = StdFuzzer::new(scheduler, feedback, objective);
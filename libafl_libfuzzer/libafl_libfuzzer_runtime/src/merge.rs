@@ -9,7 +9,7 @@ use std::{
 };
 
 use libafl::{
-    corpus::Corpus,
+    corpus::{Corpus, CorpusMinimizer, HitcountMode, StdGreedyCorpusMinimizer},
     events::{EventRestarter, SimpleRestartingEventManager},
     executors::{ExitKind, InProcessExecutor},
     feedback_and_fast, feedback_or_fast,
@@ -105,6 +105,10 @@ pub fn merge(
     let edges_observer =
         MappedEdgeMapObserver::new(edges_observer, SizeTimeValueObserver::new(time));
 
+    // the mapped entries are size/time values instead of hit counts, so only keep one input per edge
+    let minimizer =
+        StdGreedyCorpusMinimizer::new(&edges_observer).with_hitcount_mode(HitcountMode::Ignore);
+
     let map_feedback = MinMapFeedback::new(&edges_observer);
 
     // Create an OOM observer to monitor if an OOM has occurred
@@ -213,6 +217,9 @@ pub fn merge(
             .on_remove(&mut state, idx, &Some(testcase))?;
     }
 
+    // the scheduler only drops inputs that are no one's best; also drop those whose edges are all covered by others
+    minimizer.minimize(&mut fuzzer, &mut executor, &mut mgr, &mut state)?;
+
     for idx in fuzzer.scheduler().current().clone() {
         let mut testcase = state.corpus_mut().get(idx)?.borrow_mut();
         let file_path = testcase
@@ -0,0 +1,728 @@
+//! Source-level coverage reports for a corpus, in [lcov](https://github.com/linux-test-project/lcov) `.info` format and as static HTML.
+//!
+//! The corpus is replayed against the instrumented target with [`replay_corpus`], counting the inputs that reach each edge.
+//! With `-fsanitize-coverage=pc-table`, the edges map one-to-one to the entries of the `pc_table`,
+//! which are symbolized to source lines with the DWARF debug info of the target by a [`DwarfSymbolizer`].
+//! Two reports can be compared with [`SourceCoverage::difference`] and [`write_html_diff`].
+
+use alloc::{
+    borrow::Cow,
+    collections::BTreeMap,
+    rc::Rc,
+    string::{String, ToString},
+    vec::Vec,
+};
+use std::{
+    fmt::Write as _,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use addr2line::Context;
+use gimli::{Dwarf, EndianRcSlice, RunTimeEndian, SectionId};
+use libafl::Error;
+#[cfg(all(
+    target_os = "linux",
+    any(feature = "sancov_pcguard_edges", feature = "sancov_pcguard_hitcounts")
+))]
+use libafl_bolts::AsSliceMut;
+use object::{Object, ObjectSection};
+
+#[cfg(any(
+    feature = "sancov_pcguard_edges",
+    feature = "sancov_pcguard_hitcounts",
+    feature = "sancov_ngram4",
+    feature = "sancov_ctx"
+))]
+use crate::sancov_pcguard::PcTableEntry;
+#[cfg(all(
+    target_os = "linux",
+    any(feature = "sancov_pcguard_edges", feature = "sancov_pcguard_hitcounts")
+))]
+use crate::{coverage::edges_map_mut_slice, sancov_pcguard::sanitizer_cov_pc_table};
+
+/// A source location of an instrumented PC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// The path of the source file
+    pub file: String,
+    /// The line in the source file
+    pub line: u32,
+    /// The (demangled) name of the function, if known
+    pub function: Option<String>,
+}
+
+/// Maps PCs of a loaded module to source locations, using its DWARF debug info
+pub struct DwarfSymbolizer {
+    context: Context<EndianRcSlice<RunTimeEndian>>,
+    bias: usize,
+}
+
+impl core::fmt::Debug for DwarfSymbolizer {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DwarfSymbolizer")
+            .field("bias", &self.bias)
+            .finish_non_exhaustive()
+    }
+}
+
+impl DwarfSymbolizer {
+    /// Loads the debug info of the given binary, loaded into memory at `bias` (0 for non-PIE executables)
+    pub fn new<P>(binary: P, bias: usize) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        let binary = binary.as_ref();
+        let data = fs::read(binary)?;
+        let object = object::File::parse(&*data).map_err(|e| {
+            Error::illegal_argument(format!("Failed to parse {}: {e}", binary.display()))
+        })?;
+        let endian = if object.is_little_endian() {
+            RunTimeEndian::Little
+        } else {
+            RunTimeEndian::Big
+        };
+        let dwarf = Dwarf::load(|id: SectionId| -> Result<_, Error> {
+            let data = match object.section_by_name(id.name()) {
+                Some(section) => section.data().map_err(|e| {
+                    Error::illegal_argument(format!(
+                        "Failed to read section {} of {}: {e}",
+                        id.name(),
+                        binary.display()
+                    ))
+                })?,
+                None => &[],
+            };
+            Ok(EndianRcSlice::new(Rc::from(data), endian))
+        })?;
+        let context = Context::from_dwarf(dwarf).map_err(|e| {
+            Error::illegal_argument(format!(
+                "Failed to parse the debug info of {}: {e}",
+                binary.display()
+            ))
+        })?;
+        Ok(Self { context, bias })
+    }
+
+    /// Loads the debug info of the module of the current process containing `addr`,
+    /// such as the address of an entry of the `pc_table`.
+    #[cfg(target_os = "linux")]
+    pub fn for_address(addr: usize) -> Result<Self, Error> {
+        let (path, bias) = loaded_module(addr).ok_or_else(|| {
+            Error::key_not_found(format!("No loaded module contains the address {addr:#x}"))
+        })?;
+        Self::new(path, bias)
+    }
+
+    /// The source location of the given PC, if the debug info knows it
+    pub fn locate(&self, pc: usize) -> Result<Option<SourceLocation>, Error> {
+        let probe = pc.wrapping_sub(self.bias) as u64;
+        let mut frames = self
+            .context
+            .find_frames(probe)
+            .skip_all_loads()
+            .map_err(|e| Error::unknown(format!("Failed to symbolize {pc:#x}: {e}")))?;
+        // The first frame is the innermost one, i.e., the code that was actually inlined here
+        let Some(frame) = frames
+            .next()
+            .map_err(|e| Error::unknown(format!("Failed to symbolize {pc:#x}: {e}")))?
+        else {
+            return Ok(None);
+        };
+        let Some((Some(file), Some(line))) = frame.location.map(|l| (l.file, l.line)) else {
+            return Ok(None);
+        };
+        let function = frame
+            .function
+            .and_then(|f| f.demangle().ok().map(Cow::into_owned));
+        Ok(Some(SourceLocation {
+            file: file.to_string(),
+            line,
+            function,
+        }))
+    }
+}
+
+/// Finds the path and the load bias of the loaded module containing `addr`
+#[cfg(target_os = "linux")]
+fn loaded_module(addr: usize) -> Option<(PathBuf, usize)> {
+    use core::ffi::{c_int, c_void, CStr};
+
+    struct Search {
+        addr: usize,
+        found: Option<(PathBuf, usize)>,
+    }
+
+    unsafe extern "C" fn callback(
+        info: *mut libc::dl_phdr_info,
+        _size: libc::size_t,
+        data: *mut c_void,
+    ) -> c_int {
+        let search = &mut *(data as *mut Search);
+        let info = &*info;
+        let bias = info.dlpi_addr as usize;
+        let headers = core::slice::from_raw_parts(info.dlpi_phdr, info.dlpi_phnum as usize);
+        let contains = headers.iter().any(|header| {
+            let start = bias + header.p_vaddr as usize;
+            header.p_type == libc::PT_LOAD
+                && (start..start + header.p_memsz as usize).contains(&search.addr)
+        });
+        if !contains {
+            return 0;
+        }
+        // The main executable has no name
+        let name = if info.dlpi_name.is_null() {
+            ""
+        } else {
+            CStr::from_ptr(info.dlpi_name).to_str().unwrap_or_default()
+        };
+        let path = if name.is_empty() {
+            PathBuf::from("/proc/self/exe")
+        } else {
+            PathBuf::from(name)
+        };
+        search.found = Some((path, bias));
+        1
+    }
+
+    let mut search = Search { addr, found: None };
+    unsafe {
+        libc::dl_iterate_phdr(
+            Some(callback),
+            core::ptr::addr_of_mut!(search) as *mut c_void,
+        );
+    }
+    search.found
+}
+
+/// Runs `harness` on each file in the `dirs`, and counts the inputs reaching each entry of the coverage `map`.
+///
+/// The map is cleared before each run. Subdirectories are replayed as well, hidden files are skipped.
+/// As the inputs run in this process, crashing inputs should not be part of the replayed corpora.
+pub fn replay_corpus<H>(dirs: &[PathBuf], map: &mut [u8], mut harness: H) -> Result<Vec<u64>, Error>
+where
+    H: FnMut(&[u8]),
+{
+    let mut hits = vec![0; map.len()];
+    let mut todo: Vec<PathBuf> = dirs.to_vec();
+    while let Some(dir) = todo.pop() {
+        let mut entries: Vec<PathBuf> = fs::read_dir(&dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<_, _>>()?;
+        entries.sort();
+        for path in entries {
+            if path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+            {
+                continue;
+            }
+            if path.is_dir() {
+                todo.push(path);
+                continue;
+            }
+            let input = fs::read(&path)?;
+            map.fill(0);
+            harness(&input);
+            for (hit, entry) in hits.iter_mut().zip(map.iter()) {
+                if *entry != 0 {
+                    *hit += 1;
+                }
+            }
+        }
+    }
+    Ok(hits)
+}
+
+/// Replays the corpora in `dirs` with the in-process `harness`, and maps the edges they reach to source lines,
+/// using the `pc_table` and the debug info of the instrumented module.
+///
+/// This requires the target to be built with `-fsanitize-coverage=trace-pc-guard,pc-table`,
+/// without context or n-gram coverage, so that each edge is the index of its guard.
+#[cfg(all(
+    target_os = "linux",
+    any(feature = "sancov_pcguard_edges", feature = "sancov_pcguard_hitcounts")
+))]
+pub fn pc_table_coverage<H>(dirs: &[PathBuf], harness: H) -> Result<SourceCoverage, Error>
+where
+    H: FnMut(&[u8]),
+{
+    let pc_table = sanitizer_cov_pc_table().ok_or_else(|| {
+        Error::illegal_state("No pc_table, was the target built with -fsanitize-coverage=pc-table?")
+    })?;
+    let first = pc_table
+        .first()
+        .ok_or_else(|| Error::illegal_state("The pc_table is empty"))?;
+    let symbolizer = DwarfSymbolizer::for_address(first.addr())?;
+    let mut map = unsafe { edges_map_mut_slice() };
+    let hits = replay_corpus(dirs, map.as_slice_mut(), harness)?;
+    SourceCoverage::from_pc_table(&symbolizer, pc_table, &hits)
+}
+
+/// The source lines and functions reached by a corpus
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceCoverage {
+    /// file -> line -> number of inputs reaching it, 0 for instrumented lines never reached
+    lines: BTreeMap<String, BTreeMap<u32, u64>>,
+    /// file -> function -> (first line, number of inputs calling it)
+    functions: BTreeMap<String, BTreeMap<String, (u32, u64)>>,
+}
+
+impl SourceCoverage {
+    /// Creates an empty [`SourceCoverage`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Symbolizes the given instrumented PCs, as `(pc, is_function_entry, hits)`, into a [`SourceCoverage`].
+    /// PCs without debug info are skipped.
+    pub fn from_pcs<I>(symbolizer: &DwarfSymbolizer, pcs: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = (usize, bool, u64)>,
+    {
+        let mut coverage = Self::new();
+        for (pc, is_function_entry, hits) in pcs {
+            if let Some(location) = symbolizer.locate(pc)? {
+                coverage.add(&location, is_function_entry, hits);
+            }
+        }
+        Ok(coverage)
+    }
+
+    /// Symbolizes the `pc_table` of the target, with the `hits` of each entry as returned by [`replay_corpus`]
+    #[cfg(any(
+        feature = "sancov_pcguard_edges",
+        feature = "sancov_pcguard_hitcounts",
+        feature = "sancov_ngram4",
+        feature = "sancov_ctx"
+    ))]
+    pub fn from_pc_table(
+        symbolizer: &DwarfSymbolizer,
+        pc_table: &[PcTableEntry],
+        hits: &[u64],
+    ) -> Result<Self, Error> {
+        Self::from_pcs(
+            symbolizer,
+            pc_table
+                .iter()
+                .zip(hits)
+                .map(|(entry, hits)| (entry.addr(), entry.is_function_entry(), *hits)),
+        )
+    }
+
+    /// Records an instrumented PC at `location`, reached by `hits` inputs.
+    /// A line with several PCs counts the most inputs reaching one of them.
+    pub fn add(&mut self, location: &SourceLocation, is_function_entry: bool, hits: u64) {
+        let line = self
+            .lines
+            .entry(location.file.clone())
+            .or_default()
+            .entry(location.line)
+            .or_default();
+        *line = (*line).max(hits);
+        if let (true, Some(function)) = (is_function_entry, &location.function) {
+            let (first_line, calls) = self
+                .functions
+                .entry(location.file.clone())
+                .or_default()
+                .entry(function.clone())
+                .or_insert((location.line, 0));
+            *first_line = (*first_line).min(location.line);
+            *calls = (*calls).max(hits);
+        }
+    }
+
+    /// The number of inputs reaching each instrumented line of the given source file
+    #[must_use]
+    pub fn lines(&self, file: &str) -> Option<&BTreeMap<u32, u64>> {
+        self.lines.get(file)
+    }
+
+    /// The source files with instrumented lines
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.lines.keys().map(String::as_str)
+    }
+
+    /// The number of instrumented lines
+    #[must_use]
+    pub fn lines_found(&self) -> usize {
+        self.lines.values().map(BTreeMap::len).sum()
+    }
+
+    /// The number of lines reached by at least one input
+    #[must_use]
+    pub fn lines_hit(&self) -> usize {
+        self.lines
+            .values()
+            .map(|lines| lines.values().filter(|hits| **hits > 0).count())
+            .sum()
+    }
+
+    /// The coverage reached here but not in `other`: all instrumented lines and functions are kept,
+    /// but the ones `other` reaches as well count as not reached.
+    #[must_use]
+    pub fn difference(&self, other: &Self) -> Self {
+        let reached_by_other = |file: &str, line: u32| {
+            other
+                .lines
+                .get(file)
+                .and_then(|lines| lines.get(&line))
+                .is_some_and(|hits| *hits > 0)
+        };
+        let lines = self
+            .lines
+            .iter()
+            .map(|(file, lines)| {
+                let lines = lines
+                    .iter()
+                    .map(|(line, hits)| {
+                        let hits = if reached_by_other(file, *line) {
+                            0
+                        } else {
+                            *hits
+                        };
+                        (*line, hits)
+                    })
+                    .collect();
+                (file.clone(), lines)
+            })
+            .collect();
+        let functions = self
+            .functions
+            .iter()
+            .map(|(file, functions)| {
+                let functions = functions
+                    .iter()
+                    .map(|(name, (line, calls))| {
+                        let calls = if reached_by_other(file, *line) {
+                            0
+                        } else {
+                            *calls
+                        };
+                        (name.clone(), (*line, calls))
+                    })
+                    .collect();
+                (file.clone(), functions)
+            })
+            .collect();
+        Self { lines, functions }
+    }
+
+    /// Writes the coverage in lcov `.info` format, as test `test_name`
+    pub fn write_lcov<P>(&self, path: P, test_name: &str) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(self.to_lcov(test_name).as_bytes())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// The coverage in lcov `.info` format, as test `test_name`
+    #[must_use]
+    pub fn to_lcov(&self, test_name: &str) -> String {
+        let mut lcov = String::new();
+        for (file, lines) in &self.lines {
+            let _ = writeln!(lcov, "TN:{test_name}");
+            let _ = writeln!(lcov, "SF:{file}");
+            let functions = self.functions.get(file);
+            for (name, (line, _)) in functions.into_iter().flatten() {
+                let _ = writeln!(lcov, "FN:{line},{name}");
+            }
+            for (name, (_, calls)) in functions.into_iter().flatten() {
+                let _ = writeln!(lcov, "FNDA:{calls},{name}");
+            }
+            if let Some(functions) = functions {
+                let _ = writeln!(lcov, "FNF:{}", functions.len());
+                let _ = writeln!(
+                    lcov,
+                    "FNH:{}",
+                    functions.values().filter(|(_, calls)| *calls > 0).count()
+                );
+            }
+            for (line, hits) in lines {
+                let _ = writeln!(lcov, "DA:{line},{hits}");
+            }
+            let _ = writeln!(lcov, "LF:{}", lines.len());
+            let _ = writeln!(
+                lcov,
+                "LH:{}",
+                lines.values().filter(|hits| **hits > 0).count()
+            );
+            lcov.push_str("end_of_record\n");
+        }
+        lcov
+    }
+
+    /// Writes a static HTML report into `dir`, starting at `index.html`, with a page per source file.
+    /// The sources are read from their original paths, if they exist.
+    pub fn write_html<P>(&self, dir: P) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let files = self
+            .lines
+            .iter()
+            .map(|(file, lines)| {
+                let lines = lines
+                    .iter()
+                    .map(|(line, hits)| {
+                        let status = if *hits > 0 {
+                            LineStatus::Hit(*hits)
+                        } else {
+                            LineStatus::Missed
+                        };
+                        (*line, status)
+                    })
+                    .collect();
+                (file.as_str(), lines)
+            })
+            .collect();
+        write_html_report(dir.as_ref(), "Coverage report", &files, false)
+    }
+}
+
+/// Writes a static HTML report comparing the coverage of two corpora into `dir`, starting at `index.html`.
+///
+/// Lines only `new` reaches are marked as gained, lines only `base` reaches as lost.
+pub fn write_html_diff<P>(dir: P, base: &SourceCoverage, new: &SourceCoverage) -> Result<(), Error>
+where
+    P: AsRef<Path>,
+{
+    let mut files: BTreeMap<&str, BTreeMap<u32, LineStatus>> = BTreeMap::new();
+    for (file, lines) in base.lines.iter().chain(&new.lines) {
+        let file_lines = files.entry(file.as_str()).or_default();
+        for line in lines.keys() {
+            let hits = |coverage: &SourceCoverage| {
+                coverage
+                    .lines
+                    .get(file)
+                    .and_then(|lines| lines.get(line))
+                    .copied()
+                    .unwrap_or_default()
+            };
+            let status = match (hits(base), hits(new)) {
+                (0, 0) => LineStatus::Missed,
+                (0, hits) => LineStatus::Gained(hits),
+                (hits, 0) => LineStatus::Lost(hits),
+                (_, hits) => LineStatus::Hit(hits),
+            };
+            file_lines.insert(*line, status);
+        }
+    }
+    write_html_report(dir.as_ref(), "Coverage difference", &files, true)
+}
+
+/// How an instrumented line shows up in an HTML report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineStatus {
+    /// Not reached
+    Missed,
+    /// Reached by the given number of inputs (by both corpora, in a diff)
+    Hit(u64),
+    /// Only reached by the new corpus
+    Gained(u64),
+    /// Only reached by the base corpus
+    Lost(u64),
+}
+
+impl LineStatus {
+    fn class(self) -> &'static str {
+        match self {
+            LineStatus::Missed => "missed",
+            LineStatus::Hit(_) => "hit",
+            LineStatus::Gained(_) => "gained",
+            LineStatus::Lost(_) => "lost",
+        }
+    }
+
+    fn hits(self) -> u64 {
+        match self {
+            LineStatus::Missed => 0,
+            LineStatus::Hit(hits) | LineStatus::Gained(hits) | LineStatus::Lost(hits) => hits,
+        }
+    }
+}
+
+const HTML_STYLE: &str = "body{font-family:sans-serif;margin:2em}\
+table{border-collapse:collapse}td,th{padding:2px 8px;text-align:left}\
+tr:nth-child(even){background:#f4f4f4}\
+.src td{font-family:monospace;white-space:pre;padding:0 8px}\
+.hit{background:#c8f0c8}.missed{background:#f8c8c8}\
+.gained{background:#8fe08f}.lost{background:#f09090}\
+.bar{background:#f8c8c8;width:100px;height:10px}.bar div{background:#5c5;height:10px}";
+
+/// Escapes text for HTML
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn percent(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 * 100.0 / total as f64
+    }
+}
+
+fn write_html_report(
+    dir: &Path,
+    title: &str,
+    files: &BTreeMap<&str, BTreeMap<u32, LineStatus>>,
+    diff: bool,
+) -> Result<(), Error> {
+    fs::create_dir_all(dir.join("files"))?;
+
+    let mut index = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{title}</title>\
+         <style>{HTML_STYLE}</style></head><body><h1>{title}</h1>\n"
+    );
+    let mut rows = String::new();
+    let (mut total_found, mut total_hit, mut total_gained, mut total_lost) = (0, 0, 0, 0);
+    for (n, (file, lines)) in files.iter().enumerate() {
+        let count = |f: fn(&LineStatus) -> bool| lines.values().filter(|s| f(s)).count();
+        let found = lines.len();
+        let hit = count(|s| matches!(s, LineStatus::Hit(_) | LineStatus::Gained(_)));
+        let gained = count(|s| matches!(s, LineStatus::Gained(_)));
+        let lost = count(|s| matches!(s, LineStatus::Lost(_)));
+        total_found += found;
+        total_hit += hit;
+        total_gained += gained;
+        total_lost += lost;
+
+        let cover = percent(hit, found);
+        let _ = write!(
+            rows,
+            "<tr><td><a href=\"files/{n}.html\">{}</a></td><td>{hit}/{found}</td><td>{cover:.1}%</td>\
+             <td><div class=\"bar\"><div style=\"width:{cover:.0}px\"></div></div></td>",
+            html_escape(file)
+        );
+        if diff {
+            let _ = write!(rows, "<td>+{gained}</td><td>-{lost}</td>");
+        }
+        rows.push_str("</tr>\n");
+
+        write_html_source(&dir.join("files").join(format!("{n}.html")), file, lines)?;
+    }
+
+    let _ = write!(
+        index,
+        "<p>Lines: {total_hit}/{total_found} ({:.1}%)",
+        percent(total_hit, total_found)
+    );
+    if diff {
+        let _ = write!(
+            index,
+            ", <span class=\"gained\">{total_gained} gained</span>, <span class=\"lost\">{total_lost} lost</span>"
+        );
+    }
+    index.push_str("</p>\n<table><tr><th>File</th><th>Lines</th><th>Coverage</th><th></th>");
+    if diff {
+        index.push_str("<th>Gained</th><th>Lost</th>");
+    }
+    index.push_str("</tr>\n");
+    index.push_str(&rows);
+    index.push_str("</table></body></html>\n");
+    fs::write(dir.join("index.html"), index)?;
+    Ok(())
+}
+
+fn write_html_source(
+    path: &Path,
+    file: &str,
+    lines: &BTreeMap<u32, LineStatus>,
+) -> Result<(), Error> {
+    let mut page = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{0}</title>\
+         <style>{HTML_STYLE}</style></head><body><h1>{0}</h1><p><a href=\"../index.html\">Back</a></p>\n\
+         <table class=\"src\">\n",
+        html_escape(file)
+    );
+    let mut write_line = |number: u32, source: &str| {
+        let (class, hits) = lines.get(&number).map_or(("", String::new()), |status| {
+            (status.class(), status.hits().to_string())
+        });
+        let _ = writeln!(
+            page,
+            "<tr class=\"{class}\"><td>{number}</td><td>{hits}</td><td>{}</td></tr>",
+            html_escape(source)
+        );
+    };
+    if let Ok(source) = fs::read(file) {
+        for (number, source) in (1..).zip(String::from_utf8_lossy(&source).lines()) {
+            write_line(number, source);
+        }
+    } else {
+        // Without the source, list the instrumented lines only
+        for number in lines.keys() {
+            write_line(*number, "");
+        }
+    }
+    page.push_str("</table></body></html>\n");
+    fs::write(path, page)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::{SourceCoverage, SourceLocation};
+
+    fn location(line: u32, function: Option<&str>) -> SourceLocation {
+        SourceLocation {
+            file: "/src/target.c".to_string(),
+            line,
+            function: function.map(ToString::to_string),
+        }
+    }
+
+    #[test]
+    fn test_lcov() {
+        let mut coverage = SourceCoverage::new();
+        coverage.add(&location(3, Some("main")), true, 5);
+        coverage.add(&location(4, Some("main")), false, 5);
+        coverage.add(&location(4, Some("main")), false, 2);
+        coverage.add(&location(7, Some("main")), false, 0);
+        assert_eq!(coverage.lines_found(), 3);
+        assert_eq!(coverage.lines_hit(), 2);
+        assert_eq!(
+            coverage.to_lcov("corpus"),
+            "TN:corpus\nSF:/src/target.c\nFN:3,main\nFNDA:5,main\nFNF:1\nFNH:1\n\
+             DA:3,5\nDA:4,5\nDA:7,0\nLF:3\nLH:2\nend_of_record\n"
+        );
+    }
+
+    #[test]
+    fn test_difference() {
+        let mut base = SourceCoverage::new();
+        base.add(&location(3, None), false, 1);
+        base.add(&location(4, None), false, 0);
+        let mut new = SourceCoverage::new();
+        new.add(&location(3, None), false, 2);
+        new.add(&location(4, None), false, 1);
+
+        let gained = new.difference(&base);
+        assert_eq!(gained.lines_found(), 2);
+        assert_eq!(
+            gained.lines("/src/target.c").unwrap().get(&4).copied(),
+            Some(1)
+        );
+        assert_eq!(gained.lines_hit(), 1);
+        assert_eq!(base.difference(&new).lines_hit(), 0);
+    }
+}